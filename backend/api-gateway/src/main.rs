@@ -9,7 +9,7 @@
 use axum::{
     body::Body,
     extract::State,
-    http::{header, HeaderMap, Method, Request, StatusCode, Uri},
+    http::{header, HeaderMap, HeaderName, Method, Request, StatusCode, Uri},
     middleware::{self, Next},
     response::{IntoResponse, Response},
     routing::{any, get, post},
@@ -17,8 +17,8 @@ use axum::{
 };
 use governor::{clock::DefaultClock, middleware::NoOpMiddleware, state::keyed::DashMapStateStore, Quota, RateLimiter};
 use std::num::NonZeroU32;
-use guardrail_shared::{crypto, ApiResponse, GuardRailError, Result};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use guardrail_shared::{crypto, ApiResponse, AuthenticatedUser, Claims, GuardRailError, Result};
+use jsonwebtoken::{encode, EncodingKey, Header};
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
@@ -60,31 +60,6 @@ pub struct GatewayConfig {
 // Authentication Types
 // ============================================================================
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: String,        // User ID
-    pub email: String,
-    pub role: String,
-    pub org_id: Option<String>,
-    pub exp: usize,         // Expiry timestamp
-    pub iat: usize,         // Issued at
-}
-
-#[derive(Debug, Clone)]
-pub struct AuthenticatedUser {
-    pub user_id: Uuid,
-    pub email: String,
-    pub role: String,
-    pub org_id: Option<Uuid>,
-    pub auth_method: AuthMethod,
-}
-
-#[derive(Debug, Clone)]
-pub enum AuthMethod {
-    Jwt,
-    ApiKey,
-}
-
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct LoginRequest {
     pub email: String,
@@ -375,79 +350,13 @@ async fn create_api_key_impl(
 // ============================================================================
 
 async fn authenticate(state: &AppState, headers: &HeaderMap) -> Result<AuthenticatedUser> {
-    // Check for API key first
-    if let Some(api_key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
-        return authenticate_api_key(state, api_key).await;
-    }
-    
-    // Check for JWT
-    if let Some(auth_header) = headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()) {
-        if let Some(token) = auth_header.strip_prefix("Bearer ") {
-            return authenticate_jwt(state, token).await;
-        }
-    }
-    
-    Err(GuardRailError::Unauthorized("No valid authentication provided".to_string()))
-}
-
-async fn authenticate_jwt(state: &AppState, token: &str) -> Result<AuthenticatedUser> {
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(state.config.jwt_secret.as_bytes()),
-        &Validation::default(),
-    )
-    .map_err(|e| GuardRailError::Unauthorized(format!("Invalid token: {}", e)))?;
-    
-    let claims = token_data.claims;
-    
-    Ok(AuthenticatedUser {
-        user_id: Uuid::parse_str(&claims.sub)
-            .map_err(|_| GuardRailError::Unauthorized("Invalid user ID in token".to_string()))?,
-        email: claims.email,
-        role: claims.role,
-        org_id: claims.org_id.and_then(|s| Uuid::parse_str(&s).ok()),
-        auth_method: AuthMethod::Jwt,
-    })
-}
-
-async fn authenticate_api_key(state: &AppState, api_key: &str) -> Result<AuthenticatedUser> {
-    let key_hash = crypto::sha256_hex(api_key.as_bytes());
-    
-    let key_record = sqlx::query!(
-        r#"
-        SELECT ak.id, ak.scopes, ak.organization_id, ak.expires_at, u.id as user_id, u.email, u.role
-        FROM api_keys ak
-        JOIN users u ON ak.created_by = u.id
-        WHERE ak.key_hash = $1 AND ak.is_active = true
-        "#,
-        key_hash,
+    guardrail_shared::authenticate(
+        &state.db,
+        Some(&state.config.jwt_secret),
+        headers.get("x-api-key").and_then(|v| v.to_str().ok()),
+        headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()),
     )
-    .fetch_optional(&state.db)
-    .await?
-    .ok_or_else(|| GuardRailError::Unauthorized("Invalid API key".to_string()))?;
-    
-    // Check expiry
-    if let Some(expires_at) = key_record.expires_at {
-        if expires_at < chrono::Utc::now() {
-            return Err(GuardRailError::Unauthorized("API key expired".to_string()));
-        }
-    }
-    
-    // Update last used
-    sqlx::query!(
-        "UPDATE api_keys SET last_used_at = NOW() WHERE id = $1",
-        key_record.id,
-    )
-    .execute(&state.db)
-    .await?;
-    
-    Ok(AuthenticatedUser {
-        user_id: key_record.user_id,
-        email: key_record.email,
-        role: key_record.role,
-        org_id: key_record.organization_id,
-        auth_method: AuthMethod::ApiKey,
-    })
+    .await
 }
 
 /// Auth middleware that rejects unauthenticated requests
@@ -492,9 +401,12 @@ async fn proxy_to_service(
     
     let mut request = state.http_client.request(method.clone(), &url);
     
-    // Forward relevant headers
-    if let Some(content_type) = headers.get(header::CONTENT_TYPE) {
-        request = request.header(header::CONTENT_TYPE, content_type);
+    // Forward relevant headers, including the caller's credentials for services
+    // that act as the caller, such as approval votes
+    for name in [header::CONTENT_TYPE, header::AUTHORIZATION, HeaderName::from_static("x-api-key")] {
+        if let Some(value) = headers.get(&name) {
+            request = request.header(name, value);
+        }
     }
     
     // Add body if present
@@ -795,6 +707,16 @@ fn create_router(state: Arc<AppState>) -> Router {
         .route("/api/v1/policies", any(handle_policy))
         .route("/api/v1/policies/*path", any(handle_policy))
         .route("/api/v1/check", any(handle_policy))
+        .route("/api/v1/check/batch", any(handle_policy))
+        .route("/api/v1/approvals", any(handle_policy))
+        .route("/api/v1/approvals/*path", any(handle_policy))
+        .route("/api/v1/decisions", any(handle_policy))
+        .route("/api/v1/decisions/*path", any(handle_policy))
+        .route("/api/v1/data", any(handle_policy))
+        .route("/api/v1/data/*path", any(handle_policy))
+        .route("/api/v1/bundles/*path", any(handle_policy))
+        .route("/api/v1/controls", any(handle_policy))
+        .route("/api/v1/controls/*path", any(handle_policy))
         // Movement ledger routes
         .route("/api/v1/events", any(handle_ledger))
         .route("/api/v1/events/*path", any(handle_ledger))
@@ -9,7 +9,6 @@ use axum::{
     routing::{delete, get, patch, post},
     Json, Router,
 };
-use base64;
 use guardrail_shared::{
     ApiResponse, CreateIdentityRequest, Credential, CredentialType, GuardRailError,
     Identity, IdentityKey, IdentityType, KeyType, PaginatedResponse, Result,
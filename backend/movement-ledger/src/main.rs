@@ -50,6 +50,7 @@ pub struct ListEventsQuery {
     pub per_page: Option<i32>,
     pub event_type: Option<String>,
     pub actor_id: Option<Uuid>,
+    pub policy_decision_id: Option<Uuid>,
     pub from_date: Option<chrono::DateTime<chrono::Utc>>,
     pub to_date: Option<chrono::DateTime<chrono::Utc>>,
     pub anchored_only: Option<bool>,
@@ -155,15 +156,19 @@ fn compute_event_hash(
     hex::encode(hasher.finalize())
 }
 
-/// Verify the hash chain integrity for a sequence of events
-fn verify_hash_chain(events: &[MovementEvent]) -> bool {
+/// Check the hash chain integrity for a sequence of events, describing each break
+fn hash_chain_errors(events: &[MovementEvent]) -> Vec<String> {
+    let mut errors = Vec::new();
     for i in 1..events.len() {
         let prev = &events[i - 1];
         let curr = &events[i];
         
         // Current event's previous_hash should match previous event's event_hash
         if curr.previous_hash != prev.event_hash {
-            return false;
+            errors.push(format!(
+                "Chain break at sequence {}: previous_hash mismatch",
+                curr.sequence_number
+            ));
         }
         
         // Verify the event's own hash is correct
@@ -177,10 +182,13 @@ fn verify_hash_chain(events: &[MovementEvent]) -> bool {
         );
         
         if computed != curr.event_hash {
-            return false;
+            errors.push(format!(
+                "Hash mismatch at sequence {}: computed {} != stored {}",
+                curr.sequence_number, computed, curr.event_hash
+            ));
         }
     }
-    true
+    errors
 }
 
 // ============================================================================
@@ -327,6 +335,13 @@ async fn create_event(
 }
 
 async fn create_event_impl(state: &AppState, req: CreateEventRequest) -> Result<MovementEvent> {
+    // Decisions are delivered at least once; a redelivery gets the recorded event back
+    if let (EventType::PolicyDecision, Some(decision_id)) = (req.event_type, req.policy_decision_id) {
+        if let Some(event) = find_decision_event(&state.db, decision_id).await? {
+            return Ok(event);
+        }
+    }
+
     let id = Uuid::new_v4();
     let now = chrono::Utc::now();
     
@@ -384,6 +399,22 @@ async fn create_event_impl(state: &AppState, req: CreateEventRequest) -> Result<
     Ok(event)
 }
 
+async fn find_decision_event(db: &PgPool, decision_id: Uuid) -> Result<Option<MovementEvent>> {
+    let event = sqlx::query_as!(
+        MovementEvent,
+        r#"
+        SELECT id, sequence_number, event_type as "event_type: EventType", actor_id, policy_decision_id, payload, previous_hash, event_hash, anchor_batch_id, created_at as "created_at!"
+        FROM movement_events
+        WHERE policy_decision_id = $1 AND event_type = 'POLICY_DECISION'
+        "#,
+        decision_id,
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(event)
+}
+
 async fn list_events(
     State(state): State<Arc<AppState>>,
     Query(query): Query<ListEventsQuery>,
@@ -420,6 +451,7 @@ async fn list_events_impl(
         AND ($4::timestamptz IS NULL OR created_at >= $4)
         AND ($5::timestamptz IS NULL OR created_at <= $5)
         AND ($6::boolean IS NULL OR ($6 = true AND anchor_batch_id IS NOT NULL) OR $6 = false)
+        AND ($7::uuid IS NULL OR policy_decision_id = $7)
         ORDER BY sequence_number DESC
         LIMIT $1 OFFSET $2
         "#,
@@ -429,6 +461,7 @@ async fn list_events_impl(
         query.from_date,
         query.to_date,
         query.anchored_only,
+        query.policy_decision_id,
     )
     .fetch_all(db)
     .await?;
@@ -441,11 +474,13 @@ async fn list_events_impl(
         AND ($2::timestamptz IS NULL OR created_at >= $2)
         AND ($3::timestamptz IS NULL OR created_at <= $3)
         AND ($4::boolean IS NULL OR ($4 = true AND anchor_batch_id IS NOT NULL) OR $4 = false)
+        AND ($5::uuid IS NULL OR policy_decision_id = $5)
         "#,
         query.actor_id,
         query.from_date,
         query.to_date,
         query.anchored_only,
+        query.policy_decision_id,
     )
     .fetch_one(db)
     .await?;
@@ -597,36 +632,7 @@ async fn verify_chain_impl(db: &PgPool, query: VerifyChainQuery) -> Result<Chain
     .fetch_all(db)
     .await?;
 
-    let mut errors = Vec::new();
-    
-    // Verify hash chain
-    for i in 1..events.len() {
-        let prev = &events[i - 1];
-        let curr = &events[i];
-        
-        if curr.previous_hash != prev.event_hash {
-            errors.push(format!(
-                "Chain break at sequence {}: previous_hash mismatch",
-                curr.sequence_number
-            ));
-        }
-        
-        let computed = compute_event_hash(
-            curr.sequence_number,
-            &curr.event_type,
-            &curr.actor_id,
-            &curr.payload,
-            &curr.previous_hash,
-            &curr.created_at,
-        );
-        
-        if computed != curr.event_hash {
-            errors.push(format!(
-                "Hash mismatch at sequence {}: computed {} != stored {}",
-                curr.sequence_number, computed, curr.event_hash
-            ));
-        }
-    }
+    let errors = hash_chain_errors(&events);
     
     let actual_to = events.last().map(|e| e.sequence_number).unwrap_or(from);
     
@@ -1004,7 +1010,7 @@ mod tests {
     }
 
     #[test]
-    fn test_verify_hash_chain() {
+    fn test_hash_chain_errors() {
         use chrono::Utc;
         use guardrail_shared::{EventType, MovementEvent, GENESIS_HASH};
         use uuid::Uuid;
@@ -1045,17 +1051,17 @@ mod tests {
         };
         
         let events = vec![e1, e2];
-        assert!(verify_hash_chain(&events));
+        assert!(hash_chain_errors(&events).is_empty());
         
         // Test broken chain (wrong previous hash)
         let mut broken_events = events.clone();
         broken_events[1].previous_hash = "broken".to_string();
-        assert!(!verify_hash_chain(&broken_events));
+        assert!(!hash_chain_errors(&broken_events).is_empty());
 
         // Test broken chain (tampered payload)
         let mut tampered_events = events.clone();
         tampered_events[1].payload = json!({"tampered": true});
         // The hash won't match the payload anymore
-        assert!(!verify_hash_chain(&tampered_events));
+        assert!(!hash_chain_errors(&tampered_events).is_empty());
     }
 }
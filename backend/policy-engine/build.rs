@@ -0,0 +1,16 @@
+//! Generates the gRPC messages and server from `proto/guardrail/v1/check.proto`.
+//!
+//! `protoc` comes from `protoc-bin-vendored`, so the build doesn't need one on
+//! the path.
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=proto");
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+
+    tonic_build::configure()
+        .build_client(false)
+        // A decision is several times the size of an error
+        .boxed(".guardrail.v1.BatchCheckResult.outcome.decision")
+        .compile_protos(&["proto/guardrail/v1/check.proto"], &["proto"])?;
+    Ok(())
+}
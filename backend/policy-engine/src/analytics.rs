@@ -0,0 +1,459 @@
+//! Decision analytics
+//!
+//! Aggregates over `policy_decisions` for dashboards: decision counts over time,
+//! deny rates per policy and per identity, and the most frequent deny reasons.
+//! Every endpoint takes the decision log filters and a time window, which
+//! defaults to the last 24 hours.
+//!
+//! A policy counts as having evaluated a decision when its targets matched the
+//! action, as recorded on the decision, and as having denied it when it gave a
+//! reason for a `DENY`. Libraries never count.
+
+use crate::decisions::DecisionFilter;
+use crate::AppState;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use guardrail_shared::{ApiResponse, Decision, GuardRailError, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Most buckets a time series may span
+const MAX_BUCKETS: i64 = 2000;
+
+// ============================================================================
+// Queries
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    #[default]
+    Hour,
+    Day,
+}
+
+impl Bucket {
+    /// `date_trunc` unit
+    fn as_str(self) -> &'static str {
+        match self {
+            Bucket::Hour => "hour",
+            Bucket::Day => "day",
+        }
+    }
+
+    fn duration(self) -> Duration {
+        match self {
+            Bucket::Hour => Duration::hours(1),
+            Bucket::Day => Duration::days(1),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct StatsQuery {
+    /// Time series bucket width
+    pub bucket: Option<Bucket>,
+    /// Rows returned by the ranked endpoints
+    pub limit: Option<i64>,
+    /// Leave out identities with fewer decisions in the window
+    pub min_decisions: Option<i64>,
+}
+
+impl StatsQuery {
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(20).clamp(1, 100)
+    }
+}
+
+/// Window the filter covers, defaulting to the 24 hours before `until`
+fn window(filter: &DecisionFilter, now: DateTime<Utc>) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    filter.validate()?;
+    let until = filter.until.unwrap_or(now);
+    let from = filter.from.unwrap_or(until - Duration::hours(24));
+    if from >= until {
+        return Err(GuardRailError::Validation("from must be before until".to_string()));
+    }
+    Ok((from, until))
+}
+
+fn deny_rate(denied: i64, total: i64) -> Option<f64> {
+    (total > 0).then(|| denied as f64 / total as f64)
+}
+
+// ============================================================================
+// Stats
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+pub struct DecisionBucket {
+    pub bucket_start: DateTime<Utc>,
+    pub total: i64,
+    pub allowed: i64,
+    pub denied: i64,
+    pub approval_required: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DecisionTimeSeries {
+    pub from: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub buckets: Vec<DecisionBucket>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PolicyDenyStats {
+    pub policy_id: Uuid,
+    pub policy_name: String,
+    /// Decisions made with the policy enforced
+    pub evaluated: i64,
+    /// `DENY` decisions the policy gave a reason for
+    pub denied: i64,
+    /// `REQUIRE_APPROVAL` decisions the policy required an approver for
+    pub approval_required: i64,
+    /// `denied / evaluated`, absent when the policy evaluated nothing
+    pub deny_rate: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdentityDenyStats {
+    pub identity_id: Uuid,
+    pub display_name: String,
+    pub total: i64,
+    pub denied: i64,
+    pub approval_required: i64,
+    pub deny_rate: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DenyReasonStats {
+    pub code: Option<String>,
+    /// Message of one occurrence; coded reasons may vary in wording
+    pub message: String,
+    pub count: i64,
+    pub identities: i64,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Ranked<T> {
+    pub from: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub items: Vec<T>,
+}
+
+// ============================================================================
+// Aggregation
+// ============================================================================
+
+async fn timeseries_impl(db: &PgPool, filter: &DecisionFilter, bucket: Bucket) -> Result<DecisionTimeSeries> {
+    let (from, until) = window(filter, Utc::now())?;
+    if (until - from).num_seconds() / bucket.duration().num_seconds() >= MAX_BUCKETS {
+        return Err(GuardRailError::Validation(format!(
+            "Window spans more than {} {} buckets",
+            MAX_BUCKETS,
+            bucket.as_str()
+        )));
+    }
+    let action_type = filter.stored_action_type();
+
+    let buckets = sqlx::query_as!(
+        DecisionBucket,
+        r#"
+        WITH counts AS (
+            SELECT date_trunc($1, d.created_at, 'UTC') as bucket,
+                   COUNT(*) as total,
+                   COUNT(*) FILTER (WHERE d.decision = 'ALLOW') as allowed,
+                   COUNT(*) FILTER (WHERE d.decision = 'DENY') as denied,
+                   COUNT(*) FILTER (WHERE d.decision = 'REQUIRE_APPROVAL') as approval_required
+            FROM policy_decisions d
+            WHERE d.created_at >= $2 AND d.created_at < $3
+            AND ($4::uuid IS NULL OR d.identity_id = $4)
+            AND ($5::uuid IS NULL OR EXISTS (
+                SELECT 1 FROM policy_decision_attributions a WHERE a.decision_id = d.id AND a.policy_id = $5
+            ))
+            AND ($6::decision IS NULL OR d.decision = $6)
+            AND ($7::text IS NULL OR d.action_type = $7)
+            AND ($8::text IS NULL OR d.reason_details @> jsonb_build_array(jsonb_build_object('code', $8::text)))
+            GROUP BY 1
+        )
+        SELECT s.bucket as "bucket_start!",
+               COALESCE(c.total, 0) as "total!",
+               COALESCE(c.allowed, 0) as "allowed!",
+               COALESCE(c.denied, 0) as "denied!",
+               COALESCE(c.approval_required, 0) as "approval_required!"
+        FROM generate_series(
+            date_trunc($1, $2::timestamptz, 'UTC'),
+            $3::timestamptz - INTERVAL '1 microsecond',
+            ('1 ' || $1)::interval
+        ) s(bucket)
+        LEFT JOIN counts c ON c.bucket = s.bucket
+        ORDER BY s.bucket
+        "#,
+        bucket.as_str(),
+        from,
+        until,
+        filter.identity_id,
+        filter.policy_id,
+        filter.decision as Option<Decision>,
+        action_type,
+        filter.reason_code,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(DecisionTimeSeries { from, until, buckets })
+}
+
+/// Per-policy stats. `policy_id` selects the policy to report rather than the
+/// decisions counted.
+async fn policy_stats_impl(db: &PgPool, filter: &DecisionFilter, limit: i64) -> Result<Ranked<PolicyDenyStats>> {
+    let (from, until) = window(filter, Utc::now())?;
+    let action_type = filter.stored_action_type();
+
+    let rows = sqlx::query!(
+        r#"
+        WITH scoped AS (
+            SELECT d.id, d.decision, d.evaluated_policy_ids
+            FROM policy_decisions d
+            WHERE d.created_at >= $1 AND d.created_at < $2
+            AND ($3::uuid IS NULL OR d.identity_id = $3)
+            AND ($4::decision IS NULL OR d.decision = $4)
+            AND ($5::text IS NULL OR d.action_type = $5)
+            AND ($6::text IS NULL OR d.reason_details @> jsonb_build_array(jsonb_build_object('code', $6::text)))
+        ),
+        evaluated AS (
+            SELECT e.policy_id, COUNT(*) as evaluated
+            FROM scoped s
+            CROSS JOIN unnest(s.evaluated_policy_ids) as e(policy_id)
+            GROUP BY 1
+        ),
+        contributed AS (
+            SELECT a.policy_id,
+                   COUNT(*) FILTER (WHERE s.decision = 'DENY' AND cardinality(a.reasons) > 0) as denied,
+                   COUNT(*) FILTER (WHERE s.decision = 'REQUIRE_APPROVAL' AND cardinality(a.required_approvers) > 0) as approval_required
+            FROM scoped s
+            JOIN policy_decision_attributions a ON a.decision_id = s.id
+            GROUP BY 1
+        )
+        SELECT p.id, p.name,
+               COALESCE(e.evaluated, 0) as "evaluated!",
+               COALESCE(c.denied, 0) as "denied!",
+               COALESCE(c.approval_required, 0) as "approval_required!"
+        FROM policies p
+        LEFT JOIN evaluated e ON e.policy_id = p.id
+        LEFT JOIN contributed c ON c.policy_id = p.id
+        WHERE (e.policy_id IS NOT NULL OR c.policy_id IS NOT NULL)
+        AND ($7::uuid IS NULL OR p.id = $7)
+        ORDER BY 4 DESC, 3 DESC, p.name
+        LIMIT $8
+        "#,
+        from,
+        until,
+        filter.identity_id,
+        filter.decision as Option<Decision>,
+        action_type,
+        filter.reason_code,
+        filter.policy_id,
+        limit,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let items = rows
+        .into_iter()
+        .map(|row| PolicyDenyStats {
+            policy_id: row.id,
+            policy_name: row.name,
+            evaluated: row.evaluated,
+            denied: row.denied,
+            approval_required: row.approval_required,
+            deny_rate: deny_rate(row.denied, row.evaluated),
+        })
+        .collect();
+
+    Ok(Ranked { from, until, items })
+}
+
+async fn identity_stats_impl(
+    db: &PgPool,
+    filter: &DecisionFilter,
+    limit: i64,
+    min_decisions: i64,
+) -> Result<Ranked<IdentityDenyStats>> {
+    let (from, until) = window(filter, Utc::now())?;
+    let action_type = filter.stored_action_type();
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT d.identity_id, i.display_name,
+               COUNT(*) as "total!",
+               COUNT(*) FILTER (WHERE d.decision = 'DENY') as "denied!",
+               COUNT(*) FILTER (WHERE d.decision = 'REQUIRE_APPROVAL') as "approval_required!"
+        FROM policy_decisions d
+        JOIN identities i ON i.id = d.identity_id
+        WHERE d.created_at >= $1 AND d.created_at < $2
+        AND ($3::uuid IS NULL OR d.identity_id = $3)
+        AND ($4::uuid IS NULL OR EXISTS (
+            SELECT 1 FROM policy_decision_attributions a WHERE a.decision_id = d.id AND a.policy_id = $4
+        ))
+        AND ($5::decision IS NULL OR d.decision = $5)
+        AND ($6::text IS NULL OR d.action_type = $6)
+        AND ($7::text IS NULL OR d.reason_details @> jsonb_build_array(jsonb_build_object('code', $7::text)))
+        GROUP BY d.identity_id, i.display_name
+        HAVING COUNT(*) >= $8
+        ORDER BY 4 DESC, 3 DESC, d.identity_id
+        LIMIT $9
+        "#,
+        from,
+        until,
+        filter.identity_id,
+        filter.policy_id,
+        filter.decision as Option<Decision>,
+        action_type,
+        filter.reason_code,
+        min_decisions,
+        limit,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let items = rows
+        .into_iter()
+        .map(|row| IdentityDenyStats {
+            identity_id: row.identity_id,
+            display_name: row.display_name,
+            total: row.total,
+            denied: row.denied,
+            approval_required: row.approval_required,
+            deny_rate: deny_rate(row.denied, row.total),
+        })
+        .collect();
+
+    Ok(Ranked { from, until, items })
+}
+
+/// Deny reasons grouped by code, or by message for reasons without one
+async fn reason_stats_impl(db: &PgPool, filter: &DecisionFilter, limit: i64) -> Result<Ranked<DenyReasonStats>> {
+    let (from, until) = window(filter, Utc::now())?;
+    let action_type = filter.stored_action_type();
+
+    let items = sqlx::query_as!(
+        DenyReasonStats,
+        r#"
+        SELECT MAX(r->>'code') as code,
+               MIN(r->>'message') as "message!",
+               COUNT(*) as "count!",
+               COUNT(DISTINCT d.identity_id) as "identities!",
+               MAX(d.created_at) as "last_seen!"
+        FROM policy_decisions d
+        CROSS JOIN jsonb_array_elements(d.reason_details) r
+        WHERE d.decision = 'DENY'
+        AND d.created_at >= $1 AND d.created_at < $2
+        AND ($3::uuid IS NULL OR d.identity_id = $3)
+        AND ($4::uuid IS NULL OR EXISTS (
+            SELECT 1 FROM policy_decision_attributions a WHERE a.decision_id = d.id AND a.policy_id = $4
+        ))
+        AND ($5::text IS NULL OR d.action_type = $5)
+        AND ($6::text IS NULL OR r->>'code' = $6)
+        GROUP BY COALESCE(r->>'code', r->>'message')
+        ORDER BY 3 DESC, 1, 2
+        LIMIT $7
+        "#,
+        from,
+        until,
+        filter.identity_id,
+        filter.policy_id,
+        action_type,
+        filter.reason_code,
+        limit,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(Ranked { from, until, items })
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+fn respond<T: Serialize>(result: Result<T>) -> (StatusCode, Json<ApiResponse<T>>) {
+    match result {
+        Ok(stats) => (StatusCode::OK, Json(ApiResponse::success(stats))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<T>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+pub async fn decision_timeseries(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<DecisionFilter>,
+    Query(query): Query<StatsQuery>,
+) -> impl IntoResponse {
+    respond(timeseries_impl(&state.db, &filter, query.bucket.unwrap_or_default()).await)
+}
+
+pub async fn policy_stats(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<DecisionFilter>,
+    Query(query): Query<StatsQuery>,
+) -> impl IntoResponse {
+    respond(policy_stats_impl(&state.db, &filter, query.limit()).await)
+}
+
+pub async fn identity_stats(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<DecisionFilter>,
+    Query(query): Query<StatsQuery>,
+) -> impl IntoResponse {
+    let min_decisions = query.min_decisions.unwrap_or(1).max(1);
+    respond(identity_stats_impl(&state.db, &filter, query.limit(), min_decisions).await)
+}
+
+pub async fn reason_stats(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<DecisionFilter>,
+    Query(query): Query<StatsQuery>,
+) -> impl IntoResponse {
+    respond(reason_stats_impl(&state.db, &filter, query.limit()).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window() {
+        let now = Utc::now();
+        assert_eq!(window(&DecisionFilter::default(), now).unwrap(), (now - Duration::hours(24), now));
+
+        let until = now - Duration::days(3);
+        let filter = DecisionFilter {
+            until: Some(until),
+            ..Default::default()
+        };
+        assert_eq!(window(&filter, now).unwrap(), (until - Duration::hours(24), until));
+
+        // An explicit start after the default end
+        let filter = DecisionFilter {
+            from: Some(now + Duration::hours(1)),
+            ..Default::default()
+        };
+        assert!(matches!(window(&filter, now), Err(GuardRailError::Validation(_))));
+    }
+
+    #[test]
+    fn test_deny_rate() {
+        assert_eq!(deny_rate(0, 0), None);
+        assert_eq!(deny_rate(1, 4), Some(0.25));
+    }
+}
@@ -0,0 +1,627 @@
+//! Approval workflow for REQUIRE_APPROVAL decisions
+//!
+//! Every role returned in `required_approvers` gets its own approval row with an
+//! m-of-n quorum. The action is cleared once every role for the decision has
+//! reached its quorum; a single rejection rejects the whole decision.
+//!
+//! Votes are cast as the authenticated caller (`x-api-key` or a bearer token,
+//! forwarded by the gateway), never as an identity named in the request body.
+
+use crate::{outbox, AppState};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use guardrail_shared::{
+    ApiResponse, Approval, ApprovalStatus, ApprovalVote, ApprovalVoteRequest, EventType,
+    GuardRailError, PaginatedResponse, Result,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+#[derive(Debug, Clone)]
+pub struct ApprovalConfig {
+    /// How long an approval stays pending before it expires
+    pub ttl: chrono::Duration,
+    /// Number of approvers required for roles without an explicit quorum
+    pub default_quorum: i32,
+    /// Per-role quorum overrides, keyed by lowercase role name
+    pub role_quorums: HashMap<String, i32>,
+    /// How often the expiry sweeper runs
+    pub sweep_interval: Duration,
+    /// Secret for verifying voters' bearer tokens; only API keys are accepted without it
+    pub jwt_secret: Option<String>,
+}
+
+impl ApprovalConfig {
+    pub fn from_env() -> Self {
+        Self {
+            ttl: chrono::Duration::hours(
+                std::env::var("APPROVAL_TTL_HOURS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(24),
+            ),
+            default_quorum: std::env::var("APPROVAL_DEFAULT_QUORUM")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(1),
+            role_quorums: std::env::var("APPROVAL_QUORUMS")
+                .map(|s| parse_quorums(&s))
+                .unwrap_or_default(),
+            sweep_interval: Duration::from_secs(
+                std::env::var("APPROVAL_SWEEP_INTERVAL_SECS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(60),
+            ),
+            jwt_secret: std::env::var("JWT_SECRET").ok().filter(|s| !s.is_empty()),
+        }
+    }
+
+    /// Number of distinct approvers needed for a role
+    pub fn quorum_for(&self, role: &str) -> i32 {
+        self.role_quorums
+            .get(&role.to_lowercase())
+            .copied()
+            .unwrap_or(self.default_quorum)
+            .max(1)
+    }
+}
+
+/// Parse a quorum spec like `risk_officer=2,compliance=1`
+pub fn parse_quorums(spec: &str) -> HashMap<String, i32> {
+    spec.split(',')
+        .filter_map(|entry| {
+            let (role, count) = entry.split_once('=')?;
+            let count = count.trim().parse().ok()?;
+            Some((role.trim().to_lowercase(), count))
+        })
+        .collect()
+}
+
+/// Whether a console user role may vote on an approval for `required_role`
+fn role_satisfies(user_role: &str, required_role: &str) -> bool {
+    user_role.eq_ignore_ascii_case(required_role) || user_role.eq_ignore_ascii_case("super_admin")
+}
+
+// ============================================================================
+// Request/Response Types
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct ListApprovalsQuery {
+    pub page: Option<i32>,
+    pub per_page: Option<i32>,
+    pub status: Option<ApprovalStatus>,
+    pub identity_id: Option<Uuid>,
+    pub decision_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApprovalWithVotes {
+    pub approval: Approval,
+    pub votes: Vec<ApprovalVote>,
+}
+
+// ============================================================================
+// Creation (called from check_action)
+// ============================================================================
+
+/// Create one pending approval per required role for a decision
+pub async fn create_approvals(
+    tx: &mut Transaction<'_, Postgres>,
+    config: &ApprovalConfig,
+    decision_id: Uuid,
+    identity_id: Uuid,
+    action: &serde_json::Value,
+    required_roles: &[String],
+) -> Result<Vec<Approval>> {
+    let now = chrono::Utc::now();
+    let expires_at = now + config.ttl;
+
+    let mut roles: Vec<&String> = required_roles.iter().collect();
+    roles.sort();
+    roles.dedup();
+
+    let mut approvals = Vec::with_capacity(roles.len());
+    for role in roles {
+        let approval = sqlx::query_as!(
+            Approval,
+            r#"
+            INSERT INTO approvals (id, decision_id, identity_id, action, required_role, required_count, approval_count, status, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, 0, 'PENDING', $7, $8)
+            RETURNING id, decision_id, identity_id, action, required_role, required_count, approval_count, status as "status!: ApprovalStatus", approved_by, approved_at, rejection_reason, expires_at, created_at as "created_at!"
+            "#,
+            Uuid::new_v4(),
+            decision_id,
+            identity_id,
+            action,
+            role,
+            config.quorum_for(role),
+            expires_at,
+            now,
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+        outbox::enqueue_event(
+            tx,
+            EventType::ApprovalRequested,
+            identity_id,
+            Some(decision_id),
+            approval_payload(&approval, None),
+        )
+        .await?;
+
+        approvals.push(approval);
+    }
+
+    Ok(approvals)
+}
+
+fn approval_payload(approval: &Approval, comment: Option<&str>) -> serde_json::Value {
+    serde_json::json!({
+        "approval_id": approval.id,
+        "decision_id": approval.decision_id,
+        "identity_id": approval.identity_id,
+        "required_role": approval.required_role,
+        "required_count": approval.required_count,
+        "approval_count": approval.approval_count,
+        "status": approval.status,
+        "expires_at": approval.expires_at,
+        "comment": comment,
+    })
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+pub async fn list_approvals(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListApprovalsQuery>,
+) -> impl IntoResponse {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(20).min(100);
+    let offset = (page - 1) * per_page;
+
+    match list_approvals_impl(&state, offset, per_page, &query).await {
+        Ok((approvals, total)) => {
+            let response = PaginatedResponse::new(approvals, total, page, per_page);
+            (StatusCode::OK, Json(ApiResponse::success(response)))
+        }
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<PaginatedResponse<Approval>>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn list_approvals_impl(
+    state: &AppState,
+    offset: i32,
+    limit: i32,
+    query: &ListApprovalsQuery,
+) -> Result<(Vec<Approval>, i64)> {
+    let approvals = sqlx::query_as!(
+        Approval,
+        r#"
+        SELECT id, decision_id, identity_id, action, required_role, required_count, approval_count, status as "status!: ApprovalStatus", approved_by, approved_at, rejection_reason, expires_at, created_at as "created_at!"
+        FROM approvals
+        WHERE ($3::approval_status IS NULL OR status = $3)
+        AND ($4::uuid IS NULL OR identity_id = $4)
+        AND ($5::uuid IS NULL OR decision_id = $5)
+        ORDER BY created_at DESC
+        LIMIT $1 OFFSET $2
+        "#,
+        limit as i64,
+        offset as i64,
+        query.status as Option<ApprovalStatus>,
+        query.identity_id,
+        query.decision_id,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let total: i64 = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM approvals
+        WHERE ($1::approval_status IS NULL OR status = $1)
+        AND ($2::uuid IS NULL OR identity_id = $2)
+        AND ($3::uuid IS NULL OR decision_id = $3)
+        "#,
+        query.status as Option<ApprovalStatus>,
+        query.identity_id,
+        query.decision_id,
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok((approvals, total))
+}
+
+pub async fn get_approval(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match get_approval_impl(&state, id).await {
+        Ok(approval) => (StatusCode::OK, Json(ApiResponse::success(approval))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<ApprovalWithVotes>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn get_approval_impl(state: &AppState, id: Uuid) -> Result<ApprovalWithVotes> {
+    let approval = sqlx::query_as!(
+        Approval,
+        r#"
+        SELECT id, decision_id, identity_id, action, required_role, required_count, approval_count, status as "status!: ApprovalStatus", approved_by, approved_at, rejection_reason, expires_at, created_at as "created_at!"
+        FROM approvals
+        WHERE id = $1
+        "#,
+        id,
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| GuardRailError::ApprovalNotFound(id.to_string()))?;
+
+    let votes = sqlx::query_as!(
+        ApprovalVote,
+        r#"
+        SELECT id, approval_id, approver_id, approved, comment, created_at as "created_at!"
+        FROM approval_votes
+        WHERE approval_id = $1
+        ORDER BY created_at ASC
+        "#,
+        id,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(ApprovalWithVotes { approval, votes })
+}
+
+pub async fn approve_approval(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+    Json(req): Json<ApprovalVoteRequest>,
+) -> impl IntoResponse {
+    match vote_impl(&state, id, &headers, req, true).await {
+        Ok(approval) => (StatusCode::OK, Json(ApiResponse::success(approval))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Approval>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+pub async fn reject_approval(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+    Json(req): Json<ApprovalVoteRequest>,
+) -> impl IntoResponse {
+    match vote_impl(&state, id, &headers, req, false).await {
+        Ok(approval) => (StatusCode::OK, Json(ApiResponse::success(approval))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Approval>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn vote_impl(
+    state: &AppState,
+    id: Uuid,
+    headers: &HeaderMap,
+    req: ApprovalVoteRequest,
+    approved: bool,
+) -> Result<Approval> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let caller = guardrail_shared::authenticate(
+        &state.db,
+        state.approval_config.jwt_secret.as_deref(),
+        header("x-api-key"),
+        header("authorization"),
+    )
+    .await?;
+
+    let now = chrono::Utc::now();
+    let mut tx = state.db.begin().await?;
+
+    // Lock the row so concurrent votes cannot overshoot the quorum
+    let approval = sqlx::query_as!(
+        Approval,
+        r#"
+        SELECT id, decision_id, identity_id, action, required_role, required_count, approval_count, status as "status!: ApprovalStatus", approved_by, approved_at, rejection_reason, expires_at, created_at as "created_at!"
+        FROM approvals
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        id,
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| GuardRailError::ApprovalNotFound(id.to_string()))?;
+
+    match approval.status {
+        ApprovalStatus::Pending => {}
+        ApprovalStatus::Expired => return Err(GuardRailError::ApprovalExpired),
+        ApprovalStatus::Approved | ApprovalStatus::Rejected => {
+            return Err(GuardRailError::ApprovalAlreadyProcessed)
+        }
+    }
+
+    if approval.expires_at <= now {
+        // The sweeper has not caught this one yet
+        let expired = sqlx::query_as!(
+            Approval,
+            r#"
+            UPDATE approvals
+            SET status = 'EXPIRED'
+            WHERE id = $1
+            RETURNING id, decision_id, identity_id, action, required_role, required_count, approval_count, status as "status!: ApprovalStatus", approved_by, approved_at, rejection_reason, expires_at, created_at as "created_at!"
+            "#,
+            id,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        enqueue_expired(&mut tx, &expired).await?;
+        tx.commit().await?;
+        state.outbox_ready.notify_one();
+
+        return Err(GuardRailError::ApprovalExpired);
+    }
+
+    let approver_id = verify_approver(&mut tx, &approval, caller.user_id).await?;
+
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO approval_votes (id, approval_id, approver_id, approved, comment, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (approval_id, approver_id) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        id,
+        approver_id,
+        approved,
+        req.comment,
+        now,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if inserted.rows_affected() == 0 {
+        return Err(GuardRailError::Conflict(format!(
+            "Approver {} has already voted on approval {}",
+            approver_id, id
+        )));
+    }
+
+    let updated = if approved {
+        let approval_count = approval.approval_count + 1;
+        let status = if approval_count >= approval.required_count {
+            ApprovalStatus::Approved
+        } else {
+            ApprovalStatus::Pending
+        };
+        let quorum_reached = status == ApprovalStatus::Approved;
+
+        sqlx::query_as!(
+            Approval,
+            r#"
+            UPDATE approvals
+            SET approval_count = $2,
+                status = $3,
+                approved_by = CASE WHEN $4 THEN $5 ELSE approved_by END,
+                approved_at = CASE WHEN $4 THEN $6 ELSE approved_at END
+            WHERE id = $1
+            RETURNING id, decision_id, identity_id, action, required_role, required_count, approval_count, status as "status!: ApprovalStatus", approved_by, approved_at, rejection_reason, expires_at, created_at as "created_at!"
+            "#,
+            id,
+            approval_count,
+            status as ApprovalStatus,
+            quorum_reached,
+            approver_id,
+            now,
+        )
+        .fetch_one(&mut *tx)
+        .await?
+    } else {
+        let reason = req
+            .comment
+            .clone()
+            .unwrap_or_else(|| format!("Rejected by {}", approval.required_role));
+
+        let rejected = sqlx::query_as!(
+            Approval,
+            r#"
+            UPDATE approvals
+            SET status = 'REJECTED', rejection_reason = $2
+            WHERE id = $1
+            RETURNING id, decision_id, identity_id, action, required_role, required_count, approval_count, status as "status!: ApprovalStatus", approved_by, approved_at, rejection_reason, expires_at, created_at as "created_at!"
+            "#,
+            id,
+            reason,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // One rejection is final for the whole decision
+        sqlx::query!(
+            r#"
+            UPDATE approvals
+            SET status = 'REJECTED', rejection_reason = $3
+            WHERE decision_id = $1 AND id <> $2 AND status = 'PENDING'
+            "#,
+            approval.decision_id,
+            id,
+            format!("Rejected by {} approver", approval.required_role),
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        rejected
+    };
+
+    let event_type = if approved {
+        EventType::ApprovalGranted
+    } else {
+        EventType::ApprovalRejected
+    };
+    outbox::enqueue_event(
+        &mut tx,
+        event_type,
+        approver_id,
+        Some(updated.decision_id),
+        approval_payload(&updated, req.comment.as_deref()),
+    )
+    .await?;
+
+    tx.commit().await?;
+    state.outbox_ready.notify_one();
+
+    Ok(updated)
+}
+
+/// Ensure the calling console user is active, linked to an identity and holds the
+/// required role, and return the identity votes are recorded against
+async fn verify_approver(
+    tx: &mut Transaction<'_, Postgres>,
+    approval: &Approval,
+    user_id: Uuid,
+) -> Result<Uuid> {
+    let user = sqlx::query!(
+        r#"
+        SELECT u.identity_id as "identity_id!", u.role
+        FROM users u
+        JOIN identities i ON i.id = u.identity_id
+        WHERE u.id = $1 AND u.is_active = true AND i.is_active = true
+        "#,
+        user_id,
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| {
+        GuardRailError::Authorization(format!("User {} is not an active approver with an identity", user_id))
+    })?;
+
+    if user.identity_id == approval.identity_id {
+        return Err(GuardRailError::Authorization(
+            "Requesters cannot approve their own actions".to_string(),
+        ));
+    }
+
+    if !role_satisfies(&user.role, &approval.required_role) {
+        return Err(GuardRailError::Authorization(format!(
+            "Approver {} does not hold role {}",
+            user.identity_id, approval.required_role
+        )));
+    }
+
+    Ok(user.identity_id)
+}
+
+// ============================================================================
+// Expiry Sweeper
+// ============================================================================
+
+async fn enqueue_expired(tx: &mut Transaction<'_, Postgres>, approval: &Approval) -> Result<()> {
+    outbox::enqueue_event(
+        tx,
+        EventType::ApprovalExpired,
+        approval.identity_id,
+        Some(approval.decision_id),
+        approval_payload(approval, None),
+    )
+    .await
+}
+
+/// Move pending approvals past their deadline to EXPIRED
+pub async fn expire_stale_approvals(state: &AppState) -> Result<usize> {
+    let mut tx = state.db.begin().await?;
+    let expired = sqlx::query_as!(
+        Approval,
+        r#"
+        UPDATE approvals
+        SET status = 'EXPIRED'
+        WHERE status = 'PENDING' AND expires_at <= $1
+        RETURNING id, decision_id, identity_id, action, required_role, required_count, approval_count, status as "status!: ApprovalStatus", approved_by, approved_at, rejection_reason, expires_at, created_at as "created_at!"
+        "#,
+        chrono::Utc::now(),
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for approval in &expired {
+        enqueue_expired(&mut tx, approval).await?;
+    }
+    tx.commit().await?;
+    if !expired.is_empty() {
+        state.outbox_ready.notify_one();
+    }
+
+    Ok(expired.len())
+}
+
+/// Periodically expire stale approvals in the background
+pub fn spawn_expiry_sweeper(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(state.approval_config.sweep_interval);
+        loop {
+            interval.tick().await;
+            match expire_stale_approvals(&state).await {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("Expired {} stale approvals", n),
+                Err(e) => tracing::error!("Approval expiry sweep failed: {}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quorums() {
+        let quorums = parse_quorums("risk_officer=2, Compliance=3,bogus,admin=x");
+        assert_eq!(quorums.get("risk_officer"), Some(&2));
+        assert_eq!(quorums.get("compliance"), Some(&3));
+        assert_eq!(quorums.len(), 2);
+    }
+
+    #[test]
+    fn test_quorum_for_defaults() {
+        let config = ApprovalConfig {
+            ttl: chrono::Duration::hours(24),
+            default_quorum: 1,
+            role_quorums: parse_quorums("risk_officer=2"),
+            sweep_interval: Duration::from_secs(60),
+            jwt_secret: None,
+        };
+        assert_eq!(config.quorum_for("RISK_OFFICER"), 2);
+        assert_eq!(config.quorum_for("compliance"), 1);
+    }
+
+    #[test]
+    fn test_role_satisfies() {
+        assert!(role_satisfies("RISK_OFFICER", "risk_officer"));
+        assert!(role_satisfies("SUPER_ADMIN", "compliance"));
+        assert!(!role_satisfies("VIEWER", "risk_officer"));
+    }
+}
@@ -0,0 +1,330 @@
+//! Historical backtesting of candidate policies
+//!
+//! Replays recorded `policy_decisions` through a draft policy and reports which
+//! decisions would change. Inputs are rebuilt the same way as in `check_action`,
+//! using each identity's current credentials. `input.history` is rebuilt from the
+//! identity's recorded decisions as of each replayed decision.
+
+use crate::{history, targeting};
+use crate::{build_input, load_identity_input, validate_rego, AppState};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use guardrail_shared::{ApiResponse, Decision, GuardRailError, PolicyTargets, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Upper bound on the number of decisions replayed in one request
+const MAX_BACKTEST_DECISIONS: i64 = 50_000;
+
+// ============================================================================
+// Request/Response Types
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct BacktestRequest {
+    /// Draft Rego source to evaluate
+    pub rego_source: String,
+    /// Actions the draft is evaluated for
+    #[serde(default)]
+    pub targets: PolicyTargets,
+    /// Existing policy the draft would replace, excluded from the candidate set
+    pub replaces_policy_id: Option<Uuid>,
+    /// Evaluate the draft alongside the other active policies (default true)
+    pub include_active: Option<bool>,
+    pub from_date: DateTime<Utc>,
+    pub to_date: DateTime<Utc>,
+    pub limit: Option<i64>,
+    /// Number of sample records kept per transition (default 10)
+    pub sample_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BacktestSample {
+    pub decision_id: Uuid,
+    pub identity_id: Uuid,
+    pub action_type: String,
+    pub created_at: DateTime<Utc>,
+    pub recorded_reasons: Vec<String>,
+    pub candidate_reasons: Vec<String>,
+    pub candidate_approvers: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DecisionTransition {
+    pub from: Decision,
+    pub to: Decision,
+    pub count: usize,
+    pub samples: Vec<BacktestSample>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BacktestError {
+    pub decision_id: Uuid,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BacktestReport {
+    pub from_date: DateTime<Utc>,
+    pub to_date: DateTime<Utc>,
+    pub evaluated: usize,
+    pub unchanged: usize,
+    pub changed: usize,
+    pub recorded_totals: BTreeMap<String, usize>,
+    pub candidate_totals: BTreeMap<String, usize>,
+    pub transitions: Vec<DecisionTransition>,
+    pub errors: Vec<BacktestError>,
+}
+
+// ============================================================================
+// Aggregation
+// ============================================================================
+
+fn decision_key(decision: Decision) -> String {
+    match decision {
+        Decision::Allow => "ALLOW",
+        Decision::Deny => "DENY",
+        Decision::RequireApproval => "REQUIRE_APPROVAL",
+    }
+    .to_string()
+}
+
+/// Collects per-decision outcomes into a report
+struct BacktestAccumulator {
+    sample_size: usize,
+    evaluated: usize,
+    unchanged: usize,
+    recorded_totals: BTreeMap<String, usize>,
+    candidate_totals: BTreeMap<String, usize>,
+    transitions: BTreeMap<(String, String), DecisionTransition>,
+    errors: Vec<BacktestError>,
+}
+
+impl BacktestAccumulator {
+    fn new(sample_size: usize) -> Self {
+        Self {
+            sample_size,
+            evaluated: 0,
+            unchanged: 0,
+            recorded_totals: BTreeMap::new(),
+            candidate_totals: BTreeMap::new(),
+            transitions: BTreeMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, recorded: Decision, candidate: Decision, sample: BacktestSample) {
+        self.evaluated += 1;
+        *self.recorded_totals.entry(decision_key(recorded)).or_default() += 1;
+        *self.candidate_totals.entry(decision_key(candidate)).or_default() += 1;
+
+        if recorded == candidate {
+            self.unchanged += 1;
+            return;
+        }
+
+        let transition = self
+            .transitions
+            .entry((decision_key(recorded), decision_key(candidate)))
+            .or_insert_with(|| DecisionTransition {
+                from: recorded,
+                to: candidate,
+                count: 0,
+                samples: Vec::new(),
+            });
+        transition.count += 1;
+        if transition.samples.len() < self.sample_size {
+            transition.samples.push(sample);
+        }
+    }
+
+    fn record_error(&mut self, decision_id: Uuid, message: String) {
+        self.errors.push(BacktestError { decision_id, message });
+    }
+
+    fn finish(self, from_date: DateTime<Utc>, to_date: DateTime<Utc>) -> BacktestReport {
+        let mut transitions: Vec<DecisionTransition> = self.transitions.into_values().collect();
+        transitions.sort_by_key(|t| std::cmp::Reverse(t.count));
+
+        BacktestReport {
+            from_date,
+            to_date,
+            evaluated: self.evaluated,
+            unchanged: self.unchanged,
+            changed: self.evaluated - self.unchanged,
+            recorded_totals: self.recorded_totals,
+            candidate_totals: self.candidate_totals,
+            transitions,
+            errors: self.errors,
+        }
+    }
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+pub async fn backtest_policy(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<BacktestRequest>,
+) -> impl IntoResponse {
+    match backtest_policy_impl(&state, req).await {
+        Ok(report) => (StatusCode::OK, Json(ApiResponse::success(report))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<BacktestReport>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn backtest_policy_impl(state: &AppState, req: BacktestRequest) -> Result<BacktestReport> {
+    if req.from_date >= req.to_date {
+        return Err(GuardRailError::Validation("from_date must be before to_date".to_string()));
+    }
+
+    validate_rego("candidate", &req.rego_source)?;
+    targeting::validate(&req.targets)?;
+
+    // Build the candidate engine: the draft and the loaded libraries, plus (optionally) the rest of the active set
+    let mut engine = state.engine.load().scratch()?;
+    if req.include_active.unwrap_or(true) {
+        let active = sqlx::query!(
+            r#"
+            SELECT id, name, version, rego_source, targets as "targets: PolicyTargets"
+            FROM policies
+            WHERE is_active = true AND kind = 'DECISION' AND ($1::uuid IS NULL OR id <> $1)
+            ORDER BY created_at ASC
+            "#,
+            req.replaces_policy_id,
+        )
+        .fetch_all(&state.db)
+        .await?;
+
+        for policy in active {
+            engine.load_targeted_policy(policy.id, &policy.name, &policy.version, &policy.rego_source, &policy.targets)?;
+        }
+    }
+    engine.load_targeted_policy(Uuid::nil(), "backtest/candidate", "draft", &req.rego_source, &req.targets)?;
+    engine.prepare()?;
+
+    let limit = req.limit.unwrap_or(MAX_BACKTEST_DECISIONS).clamp(1, MAX_BACKTEST_DECISIONS);
+    let decisions = sqlx::query!(
+        r#"
+        SELECT id, identity_id, action_type, action_payload, context, decision as "decision: Decision", reasons, created_at as "created_at!"
+        FROM policy_decisions
+        WHERE created_at >= $1 AND created_at < $2
+        ORDER BY created_at ASC
+        LIMIT $3
+        "#,
+        req.from_date,
+        req.to_date,
+        limit,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut identity_ids: Vec<Uuid> = decisions.iter().map(|row| row.identity_id).collect();
+    identity_ids.sort();
+    identity_ids.dedup();
+    let replay = history::replay(&state.db, &state.history_config, &identity_ids, req.from_date, req.to_date).await?;
+
+    // Rebuild inputs, loading each identity once
+    let mut identities: HashMap<Uuid, Option<serde_json::Value>> = HashMap::new();
+    let mut inputs = Vec::with_capacity(decisions.len());
+    for row in &decisions {
+        let identity = match identities.entry(row.identity_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let identity = match load_identity_input(&state.db, row.identity_id, true).await {
+                    Ok(identity) => Some(identity),
+                    Err(GuardRailError::IdentityNotFound(_)) => None,
+                    Err(e) => return Err(e),
+                };
+                entry.insert(identity)
+            }
+        };
+        let input = identity.clone().map(|identity| {
+            let mut input = build_input(identity, &row.action_payload, &row.context);
+            let asset = row.action_payload["asset"].as_str().unwrap_or_default();
+            input["history"] = replay.at(row.identity_id, &row.action_type, asset, row.created_at);
+            input
+        });
+        inputs.push(input);
+    }
+
+    // Evaluation is CPU-bound; keep it off the async workers
+    let sample_size = req.sample_size.unwrap_or(10);
+    let (from_date, to_date) = (req.from_date, req.to_date);
+    tokio::task::spawn_blocking(move || {
+        let mut acc = BacktestAccumulator::new(sample_size);
+
+        for (row, input) in decisions.into_iter().zip(inputs) {
+            let Some(input) = input else {
+                acc.record_error(row.id, format!("Identity {} no longer exists", row.identity_id));
+                continue;
+            };
+
+            match engine.evaluate(&input) {
+                Ok(result) => acc.record(
+                    row.decision,
+                    result.decision,
+                    BacktestSample {
+                        decision_id: row.id,
+                        identity_id: row.identity_id,
+                        action_type: row.action_type,
+                        created_at: row.created_at,
+                        recorded_reasons: row.reasons.unwrap_or_default(),
+                        candidate_reasons: result.reasons,
+                        candidate_approvers: result.required_approvers,
+                    },
+                ),
+                Err(e) => acc.record_error(row.id, e.to_string()),
+            }
+        }
+
+        acc.finish(from_date, to_date)
+    })
+    .await
+    .map_err(|e| GuardRailError::Internal(format!("Backtest task failed: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> BacktestSample {
+        BacktestSample {
+            decision_id: Uuid::new_v4(),
+            identity_id: Uuid::new_v4(),
+            action_type: "Withdrawal".to_string(),
+            created_at: Utc::now(),
+            recorded_reasons: Vec::new(),
+            candidate_reasons: Vec::new(),
+            candidate_approvers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_accumulator_counts_transitions() {
+        let mut acc = BacktestAccumulator::new(1);
+        acc.record(Decision::Allow, Decision::Allow, sample());
+        acc.record(Decision::Allow, Decision::Deny, sample());
+        acc.record(Decision::Allow, Decision::Deny, sample());
+        acc.record(Decision::Deny, Decision::RequireApproval, sample());
+        acc.record_error(Uuid::new_v4(), "boom".to_string());
+
+        let report = acc.finish(Utc::now(), Utc::now());
+        assert_eq!(report.evaluated, 4);
+        assert_eq!(report.unchanged, 1);
+        assert_eq!(report.changed, 3);
+        assert_eq!(report.recorded_totals["ALLOW"], 3);
+        assert_eq!(report.candidate_totals["DENY"], 2);
+        assert_eq!(report.errors.len(), 1);
+
+        let first = &report.transitions[0];
+        assert_eq!((first.from, first.to, first.count), (Decision::Allow, Decision::Deny, 2));
+        assert_eq!(first.samples.len(), 1);
+    }
+}
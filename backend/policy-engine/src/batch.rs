@@ -0,0 +1,211 @@
+//! Batch action checks
+//!
+//! `POST /api/v1/check/batch` evaluates many checks in one request. Each identity
+//! is loaded once, every check is evaluated against the same policy snapshot, and
+//! all decisions are written in one transaction in request order, so velocity
+//! rules see the earlier checks of the same batch.
+//!
+//! A check for an unknown identity gets an error entry and the rest of the batch
+//! carries on. A check whose policies fail or run past the deadline gets its
+//! fallback decision, stored with the others. A database failure rolls back the
+//! whole batch, and every check gets its fallback decision instead.
+
+use crate::{
+    after_commit, build_input, decision_response, fallback, history, load_identity_input,
+    record_decision, AppState,
+};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use guardrail_shared::{
+    ApiError, ApiResponse, BatchCheckRequest, BatchCheckResponse, BatchCheckResult, GuardRailError, Result,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Most checks accepted in one batch
+pub const MAX_BATCH_CHECKS: usize = 100;
+
+pub(crate) fn api_error(e: &GuardRailError) -> ApiError {
+    ApiError {
+        code: e.error_code().to_string(),
+        message: e.to_string(),
+        details: None,
+    }
+}
+
+fn validate(req: &BatchCheckRequest) -> Result<()> {
+    if req.checks.is_empty() {
+        return Err(GuardRailError::Validation("Batch contains no checks".to_string()));
+    }
+    if req.checks.len() > MAX_BATCH_CHECKS {
+        return Err(GuardRailError::Validation(format!(
+            "Batch has {} checks; at most {} are allowed",
+            req.checks.len(),
+            MAX_BATCH_CHECKS
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) async fn check_batch_impl(state: &AppState, req: BatchCheckRequest) -> Result<BatchCheckResponse> {
+    validate(&req)?;
+
+    let results = match decide_batch(state, &req).await {
+        Ok(results) => results,
+        Err(e) => {
+            let Some(cause) = fallback::cause(&e) else {
+                return Err(e);
+            };
+            let mut results = Vec::with_capacity(req.checks.len());
+            for (index, check) in req.checks.iter().enumerate() {
+                results.push(BatchCheckResult {
+                    index,
+                    decision: Some(fallback::fall_back(state, check, cause, &e).await),
+                    error: None,
+                });
+            }
+            results
+        }
+    };
+
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    Ok(BatchCheckResponse {
+        succeeded: results.len() - failed,
+        failed,
+        results,
+    })
+}
+
+async fn decide_batch(state: &AppState, req: &BatchCheckRequest) -> Result<Vec<BatchCheckResult>> {
+    // Load each identity once; an unknown identity only fails its own checks
+    let mut identities: HashMap<Uuid, std::result::Result<serde_json::Value, ApiError>> = HashMap::new();
+    for check in &req.checks {
+        if identities.contains_key(&check.identity_id) {
+            continue;
+        }
+        let identity = match load_identity_input(&state.db, check.identity_id, false).await {
+            Ok(identity) => Ok(identity),
+            Err(e @ GuardRailError::IdentityNotFound(_)) => Err(api_error(&e)),
+            Err(e) => return Err(e),
+        };
+        identities.insert(check.identity_id, identity);
+    }
+
+    let engine = state.engine.load();
+    let now = chrono::Utc::now();
+    let mut tx = state.db.begin().await?;
+
+    if !state.history_config.windows.is_empty() {
+        // Lock in a fixed order so overlapping batches can't deadlock
+        let mut locked: Vec<Uuid> = identities
+            .iter()
+            .filter(|(_, identity)| identity.is_ok())
+            .map(|(id, _)| *id)
+            .collect();
+        locked.sort();
+        for identity_id in locked {
+            history::lock_identity(&mut tx, identity_id).await?;
+        }
+    }
+
+    let mut outcomes = Vec::with_capacity(req.checks.len());
+    for check in &req.checks {
+        let identity = match &identities[&check.identity_id] {
+            Ok(identity) => identity.clone(),
+            Err(e) => {
+                outcomes.push(Err(e.clone()));
+                continue;
+            }
+        };
+        let mut input = build_input(identity, &check.action, &check.context);
+        input["history"] = history::load(&mut tx, &state.history_config, check.identity_id, &check.action, now).await?;
+
+        let evaluated = fallback::evaluate(engine.clone(), input.clone(), check.explain, state.fallback_config.deadline).await;
+        let (eval_result, degraded) = match evaluated {
+            Ok(eval_result) => (eval_result, None),
+            Err(e) => match fallback::cause(&e) {
+                Some(cause) => {
+                    let eval_result = fallback::fallback_result(&state.fallback_config, check.action.action_type, cause);
+                    fallback::warn(check, &eval_result, cause, &e);
+                    (eval_result, Some(cause))
+                }
+                None => {
+                    outcomes.push(Err(api_error(&e)));
+                    continue;
+                }
+            },
+        };
+
+        let decision_id = Uuid::new_v4();
+        record_decision(&mut tx, state, decision_id, check, &eval_result, degraded, now).await?;
+        outcomes.push(Ok((decision_id, input, eval_result, degraded)));
+    }
+
+    tx.commit().await?;
+    state.outbox_ready.notify_one();
+
+    let mut results = Vec::with_capacity(outcomes.len());
+    for (index, outcome) in outcomes.into_iter().enumerate() {
+        results.push(match outcome {
+            Ok((decision_id, _, eval_result, Some(cause))) => BatchCheckResult {
+                index,
+                decision: Some(fallback::degraded_response(state, decision_id, eval_result, cause, true, now)),
+                error: None,
+            },
+            Ok((decision_id, input, eval_result, None)) => {
+                after_commit(state, decision_id, input, &eval_result).await;
+                BatchCheckResult {
+                    index,
+                    decision: Some(decision_response(decision_id, eval_result, now)),
+                    error: None,
+                }
+            }
+            Err(error) => BatchCheckResult {
+                index,
+                decision: None,
+                error: Some(error),
+            },
+        });
+    }
+
+    Ok(results)
+}
+
+pub async fn check_batch(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<BatchCheckRequest>,
+) -> impl IntoResponse {
+    match check_batch_impl(&state, req).await {
+        Ok(response) => (StatusCode::OK, Json(ApiResponse::success(response))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<BatchCheckResponse>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_size_limits() {
+        let check: guardrail_shared::CheckActionRequest = serde_json::from_value(serde_json::json!({
+            "identity_id": Uuid::nil(),
+            "action": {"action_type": "WITHDRAWAL", "amount": "1", "asset": "BTC", "metadata": {}},
+            "context": {"timestamp": "2024-01-01T00:00:00Z", "metadata": {}},
+        }))
+        .unwrap();
+
+        let batch = |n| BatchCheckRequest {
+            checks: vec![check.clone(); n],
+        };
+        assert!(validate(&batch(1)).is_ok());
+        assert!(validate(&batch(MAX_BATCH_CHECKS)).is_ok());
+        assert!(matches!(validate(&batch(0)), Err(GuardRailError::Validation(_))));
+        assert!(matches!(validate(&batch(MAX_BATCH_CHECKS + 1)), Err(GuardRailError::Validation(_))));
+
+        let error = api_error(&GuardRailError::IdentityNotFound(Uuid::nil().to_string()));
+        assert_eq!(error.code, "IDENTITY_NOT_FOUND");
+    }
+}
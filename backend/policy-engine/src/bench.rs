@@ -0,0 +1,148 @@
+//! Evaluation throughput benchmark
+//!
+//! Compares a single engine behind a lock (how checks used to run) with shared
+//! snapshots, for 1..N threads. Ignored by default; run with:
+//!
+//! ```text
+//! cargo test -p policy-engine --release -- --ignored --nocapture bench_concurrent_evaluation
+//! ```
+
+use crate::snapshot::Snapshot;
+use crate::PolicyEngine;
+use serde_json::json;
+use std::sync::{Arc, Barrier, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const RUN_FOR: Duration = Duration::from_secs(2);
+
+fn policy_set() -> PolicyEngine {
+    let policies = [
+        ("limits", r#"
+            package guardrail
+            deny["Withdrawal over daily limit"] {
+                input.action.action_type == "WITHDRAWAL"
+                to_number(input.action.amount) > 10000
+            }
+            required_approvers["treasury"] {
+                to_number(input.action.amount) > 5000
+            }
+        "#),
+        ("kyc", r#"
+            package guardrail
+            has_kyc {
+                some i
+                input.identity.credentials[i].type == "KYC"
+            }
+            deny["KYC required"] {
+                input.action.action_type == "WITHDRAWAL"
+                not has_kyc
+            }
+        "#),
+        ("geo", r#"
+            package guardrail
+            blocked := {"KP", "IR", "SY"}
+            deny["Blocked jurisdiction"] {
+                blocked[input.context.geo_location]
+            }
+        "#),
+        ("agents", r#"
+            package guardrail
+            deny["Agents cannot change config"] {
+                input.identity.type == "AGENT"
+                input.action.action_type == "CONFIG_CHANGE"
+            }
+        "#),
+    ];
+
+    let mut engine = PolicyEngine::new();
+    for (name, source) in policies {
+        engine.load_policy(Uuid::new_v4(), name, "1.0.0", source).unwrap();
+    }
+    engine.prepare().unwrap();
+    engine
+}
+
+fn inputs() -> Vec<serde_json::Value> {
+    [("100", "US"), ("7000", "US"), ("20000", "US"), ("50", "KP")]
+        .iter()
+        .map(|(amount, geo)| {
+            json!({
+                "identity": {
+                    "type": "HUMAN",
+                    "credentials": [{"type": "KYC", "provider": "test", "value": {}}],
+                },
+                "action": {"action_type": "WITHDRAWAL", "amount": amount},
+                "context": {"geo_location": geo},
+            })
+        })
+        .collect()
+}
+
+/// Evaluations per second across `threads` threads
+fn measure(threads: usize, evaluate: Arc<dyn Fn(&serde_json::Value) + Send + Sync>) -> f64 {
+    let inputs = Arc::new(inputs());
+    let barrier = Arc::new(Barrier::new(threads));
+
+    let handles: Vec<_> = (0..threads)
+        .map(|t| {
+            let (evaluate, inputs, barrier) = (evaluate.clone(), inputs.clone(), barrier.clone());
+            std::thread::spawn(move || {
+                barrier.wait();
+                let start = Instant::now();
+                let mut count = 0u64;
+                while start.elapsed() < RUN_FOR {
+                    evaluate(&inputs[(t + count as usize) % inputs.len()]);
+                    count += 1;
+                }
+                count
+            })
+        })
+        .collect();
+
+    let total: u64 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+    total as f64 / RUN_FOR.as_secs_f64()
+}
+
+#[test]
+#[ignore]
+fn bench_concurrent_evaluation() {
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut thread_counts = vec![1];
+    while thread_counts.last().unwrap() * 2 <= cores {
+        thread_counts.push(thread_counts.last().unwrap() * 2);
+    }
+    if *thread_counts.last().unwrap() != cores {
+        thread_counts.push(cores);
+    }
+
+    let locked = Arc::new(Mutex::new(policy_set()));
+    let snapshot = Arc::new(Snapshot::new(policy_set()));
+
+    println!("{:>8} {:>14} {:>14} {:>8}", "threads", "locked/s", "snapshot/s", "speedup");
+    for threads in thread_counts {
+        let engine = locked.clone();
+        let locked_rate = measure(
+            threads,
+            Arc::new(move |input| {
+                engine.lock().unwrap().evaluate(input).unwrap();
+            }),
+        );
+
+        let engine = snapshot.clone();
+        let snapshot_rate = measure(
+            threads,
+            Arc::new(move |input| {
+                engine.load().evaluate(input).unwrap();
+            }),
+        );
+
+        println!(
+            "{:>8} {:>14.0} {:>14.0} {:>7.2}x",
+            threads,
+            locked_rate,
+            snapshot_rate,
+            snapshot_rate / locked_rate
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! GuardRail builtins for Rego
+//!
+//! Native functions registered on every engine so policies don't have to
+//! re-implement common checks:
+//!
+//! | Builtin | Returns |
+//! |---------|---------|
+//! | `guardrail.credential(identity, type)` | First credential of `type` on `identity` (case-insensitive), undefined if none |
+//! | `guardrail.address_equal(a, b, chain)` | Whether two addresses are the same on `chain`, ignoring case where the chain does |
+//! | `guardrail.decimal_gt(a, b)` | `a > b` for decimal strings or numbers (exponent forms too), without float rounding |
+//! | `guardrail.in_list(list_name, value)` | Whether `value` is in the active data document at `list_name` |
+//!
+//! Builtins only see their arguments, so `credential` takes the identity
+//! (usually `input.identity`) rather than reading `input` itself. `in_list` reads
+//! the data documents loaded into the engine; `with data... as` does not affect it.
+//!
+//! Invalid arguments raise an evaluation error rather than returning false, so a
+//! malformed amount fails the check instead of silently skipping a deny rule.
+
+use anyhow::{anyhow, bail};
+use regorus::{Engine, Value};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+/// Array-valued data documents indexed for `in_list`, keyed by document path
+pub type ListIndex = HashMap<String, HashSet<String>>;
+
+/// Lists shared by every engine of a `PolicyEngine`, filled in when data is set
+pub type SharedLists = Arc<RwLock<Arc<ListIndex>>>;
+
+/// Chains whose addresses are case-insensitive hex
+const EVM_CHAINS: &[&str] = &[
+    "ethereum", "eth", "polygon", "matic", "arbitrum", "optimism", "base", "bsc", "avalanche", "avax",
+];
+
+/// Register the GuardRail builtins on an engine
+pub fn register(engine: &mut Engine, lists: &SharedLists) -> anyhow::Result<()> {
+    engine.add_extension("guardrail.credential".to_string(), 2, Box::new(credential))?;
+    engine.add_extension("guardrail.address_equal".to_string(), 3, Box::new(address_equal))?;
+    engine.add_extension("guardrail.decimal_gt".to_string(), 2, Box::new(decimal_gt))?;
+
+    let lists = lists.clone();
+    engine.add_extension(
+        "guardrail.in_list".to_string(),
+        2,
+        Box::new(move |args: Vec<Value>| {
+            let name = args[0].as_string().map_err(|_| anyhow!("in_list: list name must be a string"))?;
+            let lists = lists.read().unwrap_or_else(|e| e.into_inner()).clone();
+            let list = lists
+                .get(name.as_ref())
+                .ok_or_else(|| anyhow!("in_list: no active list at data.{}", name))?;
+            Ok(Value::from(list.contains(&list_key(&args[1])?)))
+        }),
+    )?;
+
+    Ok(())
+}
+
+/// Membership key for a list entry: strings as-is, anything else as JSON
+pub fn list_key(value: &Value) -> anyhow::Result<String> {
+    match value {
+        Value::String(s) => Ok(s.to_string()),
+        other => Ok(serde_json::to_string(other)?),
+    }
+}
+
+fn credential(args: Vec<Value>) -> anyhow::Result<Value> {
+    let wanted = args[1].as_string().map_err(|_| anyhow!("credential: type must be a string"))?;
+    let credentials = &args[0]["credentials"];
+    let Ok(credentials) = credentials.as_array() else {
+        return Ok(Value::Undefined);
+    };
+
+    Ok(credentials
+        .iter()
+        .find(|c| matches!(&c["type"], Value::String(t) if t.eq_ignore_ascii_case(wanted)))
+        .cloned()
+        .unwrap_or(Value::Undefined))
+}
+
+fn address_equal(args: Vec<Value>) -> anyhow::Result<Value> {
+    let mut strings = Vec::with_capacity(3);
+    for arg in &args {
+        strings.push(arg.as_string().map_err(|_| anyhow!("address_equal: arguments must be strings"))?);
+    }
+    let chain = strings[2].to_ascii_lowercase();
+    Ok(Value::from(
+        normalize_address(strings[0], &chain) == normalize_address(strings[1], &chain),
+    ))
+}
+
+/// Canonical form of an address for comparison on `chain` (lowercase chain name)
+fn normalize_address(address: &str, chain: &str) -> String {
+    let address = address.trim();
+    if EVM_CHAINS.contains(&chain) {
+        let hex = address
+            .strip_prefix("0x")
+            .or_else(|| address.strip_prefix("0X"))
+            .unwrap_or(address);
+        return format!("0x{}", hex.to_ascii_lowercase());
+    }
+    if matches!(chain, "bitcoin" | "btc") {
+        // Bech32 is case-insensitive; base58 addresses are not
+        let lower = address.to_ascii_lowercase();
+        if ["bc1", "tb1", "bcrt1"].iter().any(|hrp| lower.starts_with(hrp)) {
+            return lower;
+        }
+    }
+    address.to_string()
+}
+
+fn decimal_gt(args: Vec<Value>) -> anyhow::Result<Value> {
+    let a = Decimal::parse(&decimal_text(&args[0])?)?;
+    let b = Decimal::parse(&decimal_text(&args[1])?)?;
+    Ok(Value::from(a.cmp(&b) == Ordering::Greater))
+}
+
+fn decimal_text(value: &Value) -> anyhow::Result<String> {
+    match value {
+        Value::String(s) => Ok(s.trim().to_string()),
+        Value::Number(_) => Ok(serde_json::to_string(value)?),
+        _ => bail!("decimal_gt: arguments must be strings or numbers"),
+    }
+}
+
+/// Largest exponent `Decimal::parse` expands; JSON numbers stay well within it
+const MAX_EXPONENT: i64 = 1000;
+
+/// A decimal split into digits, compared exactly
+#[derive(Debug, PartialEq, Eq)]
+struct Decimal {
+    negative: bool,
+    /// Integer digits without leading zeros
+    integer: String,
+    /// Fraction digits without trailing zeros
+    fraction: String,
+}
+
+impl Decimal {
+    /// Parse a plain or exponent form decimal, such as `12.5` or `1.25e1` (what
+    /// large JSON numbers serialise to)
+    fn parse(text: &str) -> anyhow::Result<Self> {
+        let invalid = || anyhow!("decimal_gt: {:?} is not a decimal number", text);
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (digits, exponent) = match unsigned.split_once(['e', 'E']) {
+            Some((digits, exponent)) => {
+                let exponent: i64 = exponent.parse().map_err(|_| invalid())?;
+                if exponent.abs() > MAX_EXPONENT {
+                    bail!("decimal_gt: the exponent of {:?} is out of range", text);
+                }
+                (digits, exponent)
+            }
+            None => (unsigned, 0),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if integer.is_empty() && fraction.is_empty()
+            || !integer.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+
+        // Move the decimal point by the exponent, padding with zeros
+        let all = format!("{}{}", integer, fraction);
+        let point = integer.len() as i64 + exponent;
+        let (integer, fraction) = if point <= 0 {
+            (String::new(), "0".repeat(point.unsigned_abs() as usize) + &all)
+        } else if point as usize >= all.len() {
+            (all.clone() + &"0".repeat(point as usize - all.len()), String::new())
+        } else {
+            let (integer, fraction) = all.split_at(point as usize);
+            (integer.to_string(), fraction.to_string())
+        };
+
+        let integer = integer.trim_start_matches('0').to_string();
+        let fraction = fraction.trim_end_matches('0').to_string();
+        // -0 and 0 are the same number
+        let negative = negative && !(integer.is_empty() && fraction.is_empty());
+        Ok(Self {
+            negative,
+            integer,
+            fraction,
+        })
+    }
+
+    fn cmp_magnitude(&self, other: &Self) -> Ordering {
+        self.integer
+            .len()
+            .cmp(&other.integer.len())
+            .then_with(|| self.integer.cmp(&other.integer))
+            .then_with(|| self.fraction.cmp(&other.fraction))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => self.cmp_magnitude(other),
+            (true, true) => other.cmp_magnitude(self),
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gt(a: &str, b: &str) -> bool {
+        Decimal::parse(a).unwrap() > Decimal::parse(b).unwrap()
+    }
+
+    #[test]
+    fn test_decimal_and_address_helpers() {
+        assert!(gt("10000.000000000000000001", "10000"));
+        assert!(!gt("10000.0", "10000"));
+        assert!(gt("9", "-10"));
+        assert!(gt("-1.5", "-2"));
+        assert!(gt("0012.3", "12.29"));
+        assert_eq!(Decimal::parse("-0.00").unwrap(), Decimal::parse("0").unwrap());
+        assert!(Decimal::parse(".").is_err());
+
+        // Exponent forms, as large and small JSON numbers serialise
+        assert_eq!(Decimal::parse("1e5").unwrap(), Decimal::parse("100000").unwrap());
+        assert_eq!(Decimal::parse("1.25E-3").unwrap(), Decimal::parse("0.00125").unwrap());
+        assert_eq!(Decimal::parse("-12.5e1").unwrap(), Decimal::parse("-125").unwrap());
+        let big = serde_json::to_string(&serde_json::json!(1e21)).unwrap();
+        assert!(gt(&big, "999999999999999999999.99"));
+        assert!(Decimal::parse("1e").is_err());
+        assert!(Decimal::parse("1e5000").is_err());
+
+        assert_eq!(normalize_address(" 0xAbC ", "ethereum"), "0xabc");
+        assert_eq!(normalize_address("BC1QXY", "bitcoin"), "bc1qxy");
+        assert_ne!(normalize_address("1BoatSLRHt", "bitcoin"), normalize_address("1boatslrht", "bitcoin"));
+    }
+
+    #[test]
+    fn test_builtins_in_rego() {
+        let lists: SharedLists = Arc::new(RwLock::new(Arc::new(HashMap::from([(
+            "sanctions.addresses".to_string(),
+            HashSet::from(["0xbad".to_string()]),
+        )]))));
+        let mut engine = Engine::new();
+        register(&mut engine, &lists).unwrap();
+        engine
+            .add_policy(
+                "policy/builtins".to_string(),
+                r#"
+                package guardrail
+                kyc := guardrail.credential(input.identity, "kyc_level")
+                big := guardrail.decimal_gt(input.amount, "1000.5")
+                same := guardrail.address_equal(input.to, "0xABCDEF", "ethereum")
+                sanctioned := guardrail.in_list("sanctions.addresses", input.to_lower)
+                "#
+                .to_string(),
+            )
+            .unwrap();
+        engine.set_input(
+            serde_json::json!({
+                "identity": {"credentials": [{"type": "KYC_LEVEL", "value": {"level": 2}}]},
+                "amount": "1000.51",
+                "to": "0xabcdef",
+                "to_lower": "0xbad",
+            })
+            .into(),
+        );
+
+        let value = |rule: &str| engine.clone().eval_rule(format!("data.guardrail.{}", rule)).unwrap();
+        assert_eq!(value("kyc")["value"]["level"], Value::from(2i64));
+        assert_eq!(value("big"), Value::from(true));
+        assert_eq!(value("same"), Value::from(true));
+        assert_eq!(value("sanctioned"), Value::from(true));
+
+        // Bad arguments fail evaluation rather than returning false
+        engine.set_input(serde_json::json!({"amount": "lots"}).into());
+        assert!(engine.eval_rule("data.guardrail.big".to_string()).is_err());
+    }
+}
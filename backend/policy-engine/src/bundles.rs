@@ -0,0 +1,1297 @@
+//! Signed policy bundles
+//!
+//! Exports the active policies and data documents as an OPA-style bundle and
+//! imports such bundles into another environment. A bundle is a gzipped tarball:
+//!
+//! ```text
+//! .manifest                     revision, roots, and the policies and documents it carries
+//! .signatures.json              compact JWS (EdDSA) listing the SHA-256 of every other file
+//! policies/<name>.rego          policy module
+//! policies/<name>_test.rego     its test module, if any
+//! sanctions/addresses/data.json data document at `sanctions.addresses`
+//! ```
+//!
+//! Imports are only applied once the signature verifies against a trusted key and
+//! every file matches its signed hash. Every policy in the bundle is then linted
+//! and tested against the data and libraries the import leaves loaded, and only
+//! once all of them pass are the documents and policies written, in one
+//! transaction. Bundles travel base64-encoded in the JSON body.
+
+use crate::data_documents;
+use crate::ledger::SYSTEM_ACTOR_ID;
+use crate::rego_tests::{self, TestTrigger};
+use crate::versions::{self, bump_version, Revision};
+use crate::{
+    insert_policy, libraries, lint, record_policy_created, reload_policies, set_active, targeting, validate_rego,
+    AppState, PolicyEngine,
+};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine as _;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use chrono::{DateTime, Utc};
+use guardrail_shared::{
+    sha256_hex, ApiResponse, CreatePolicyRequest, EventType, GuardRailError, Policy, PolicyKind, PolicyTargets,
+    PolicyTestReport, Result, UploadDataDocumentRequest, VersionBump,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::Read;
+use std::sync::Arc;
+use uuid::Uuid;
+
+const MANIFEST: &str = ".manifest";
+const SIGNATURES: &str = ".signatures.json";
+const POLICY_DIR: &str = "policies";
+/// Package every policy is loaded under
+const POLICY_ROOT: &str = "guardrail";
+
+const JWS_ALGORITHM: &str = "EdDSA";
+const HASH_ALGORITHM: &str = "SHA-256";
+
+/// Limits on an unpacked bundle
+const MAX_BUNDLE_FILES: usize = 1_000;
+const MAX_BUNDLE_BYTES: u64 = 32 * 1024 * 1024;
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+#[derive(Clone, Default)]
+pub struct BundleConfig {
+    /// Key id and key used to sign exports
+    pub signing_key: Option<(String, SigningKey)>,
+    /// Keys whose signatures are accepted on import, by key id
+    pub trusted_keys: HashMap<String, VerifyingKey>,
+}
+
+impl BundleConfig {
+    pub fn from_env() -> Self {
+        let key_id = std::env::var("BUNDLE_SIGNING_KEY_ID").unwrap_or_else(|_| "default".to_string());
+        let signing_key = std::env::var("BUNDLE_SIGNING_KEY")
+            .ok()
+            .and_then(|seed| match parse_key_bytes(&seed) {
+                Ok(bytes) => Some((key_id, SigningKey::from_bytes(&bytes))),
+                Err(e) => {
+                    tracing::warn!("Ignoring BUNDLE_SIGNING_KEY: {}", e);
+                    None
+                }
+            });
+
+        Self {
+            signing_key,
+            trusted_keys: parse_trusted_keys(&std::env::var("BUNDLE_TRUSTED_KEYS").unwrap_or_default()),
+        }
+    }
+}
+
+/// Decode a hex-encoded 32-byte key or seed
+fn parse_key_bytes(hex_key: &str) -> Result<[u8; 32]> {
+    hex::decode(hex_key.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| GuardRailError::CryptoError("expected 32 hex-encoded bytes".to_string()))
+}
+
+/// Parse a trusted key list like `prod=<hex public key>,ci=<hex public key>`
+pub fn parse_trusted_keys(spec: &str) -> HashMap<String, VerifyingKey> {
+    spec.split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .filter_map(|entry| {
+            let parsed = entry
+                .split_once('=')
+                .ok_or_else(|| GuardRailError::CryptoError("expected key_id=public_key".to_string()))
+                .and_then(|(key_id, key)| {
+                    let key = VerifyingKey::from_bytes(&parse_key_bytes(key)?)
+                        .map_err(|e| GuardRailError::CryptoError(e.to_string()))?;
+                    Ok((key_id.trim().to_string(), key))
+                });
+            match parsed {
+                Ok(key) => Some(key),
+                Err(e) => {
+                    tracing::warn!("Ignoring trusted bundle key {:?}: {}", entry.trim(), e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+// ============================================================================
+// Request/Response Types
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+pub struct ExportedBundle {
+    pub revision: String,
+    pub key_id: String,
+    pub policies: usize,
+    pub data_documents: usize,
+    /// The `.tar.gz`, base64-encoded
+    pub bundle: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportBundleRequest {
+    /// The `.tar.gz`, base64-encoded
+    pub bundle: String,
+    /// Activate imported policies and documents (default true); when false, new
+    /// policies are created in shadow mode and documents are uploaded inactive
+    pub activate: Option<bool>,
+    #[serde(default)]
+    pub created_by: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ImportOutcome {
+    Created,
+    Updated,
+    /// Content already matched; only activated
+    Activated,
+    Unchanged,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportedPolicy {
+    pub policy_id: Uuid,
+    pub name: String,
+    pub version: String,
+    pub outcome: ImportOutcome,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportedDocument {
+    pub document_id: Uuid,
+    pub path: String,
+    pub version: i32,
+    pub outcome: ImportOutcome,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BundleImport {
+    pub revision: String,
+    pub key_id: String,
+    pub policies: Vec<ImportedPolicy>,
+    pub data_documents: Vec<ImportedDocument>,
+}
+
+// ============================================================================
+// Bundle format
+// ============================================================================
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    revision: String,
+    #[serde(default)]
+    roots: Vec<String>,
+    #[serde(default)]
+    metadata: ManifestMetadata,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ManifestMetadata {
+    #[serde(default)]
+    policies: Vec<ManifestPolicy>,
+    #[serde(default)]
+    data: Vec<ManifestDocument>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestPolicy {
+    name: String,
+    description: Option<String>,
+    /// Version in the exporting environment; the importer assigns its own
+    version: String,
+    file: String,
+    test_file: Option<String>,
+    rego_sha256: String,
+    #[serde(default)]
+    kind: PolicyKind,
+    #[serde(default, skip_serializing_if = "PolicyTargets::is_empty")]
+    targets: PolicyTargets,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestDocument {
+    path: String,
+    version: i32,
+    sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Signatures {
+    signatures: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JwsHeader {
+    alg: String,
+    kid: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SignedFiles {
+    files: Vec<SignedFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SignedFile {
+    name: String,
+    hash: String,
+    algorithm: String,
+}
+
+/// An active data document to export
+struct BundleDocument {
+    path: String,
+    version: i32,
+    content: serde_json::Value,
+}
+
+/// A policy read from a bundle
+#[derive(Debug, PartialEq)]
+struct BundlePolicy {
+    name: String,
+    description: Option<String>,
+    kind: PolicyKind,
+    rego_source: String,
+    test_source: Option<String>,
+    targets: PolicyTargets,
+}
+
+/// The verified contents of a bundle
+#[derive(Debug)]
+struct ParsedBundle {
+    revision: String,
+    policies: Vec<BundlePolicy>,
+    documents: Vec<(String, serde_json::Value)>,
+}
+
+/// File name stem for a policy, unique within the bundle
+fn policy_stem(name: &str, files: &BTreeMap<String, Vec<u8>>) -> String {
+    let slug: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    let slug = match slug.trim_matches('_') {
+        "" => "policy".to_string(),
+        s => s.to_string(),
+    };
+
+    let mut stem = slug.clone();
+    let mut n = 2;
+    while files.contains_key(&format!("{}/{}.rego", POLICY_DIR, stem)) {
+        stem = format!("{}_{}", slug, n);
+        n += 1;
+    }
+    stem
+}
+
+/// Revision of a bundle: a hash over its content files, independent of the manifest
+fn revision_of(files: &BTreeMap<String, Vec<u8>>) -> String {
+    let lines: Vec<String> = files
+        .iter()
+        .filter(|(name, _)| name.as_str() != MANIFEST && name.as_str() != SIGNATURES)
+        .map(|(name, content)| format!("{}:{}", name, sha256_hex(content)))
+        .collect();
+    sha256_hex(lines.join("\n").as_bytes())
+}
+
+/// Lay out policies and documents as bundle files, returning the revision
+fn build(policies: &[Policy], documents: &[BundleDocument]) -> Result<(String, BTreeMap<String, Vec<u8>>)> {
+    let mut files = BTreeMap::new();
+    let mut roots = BTreeSet::new();
+    let mut metadata = ManifestMetadata::default();
+
+    for policy in policies {
+        let stem = policy_stem(&policy.name, &files);
+        let file = format!("{}/{}.rego", POLICY_DIR, stem);
+        files.insert(file.clone(), policy.rego_source.clone().into_bytes());
+
+        let test_file = policy.test_source.as_ref().map(|test_source| {
+            let test_file = format!("{}/{}_test.rego", POLICY_DIR, stem);
+            files.insert(test_file.clone(), test_source.clone().into_bytes());
+            test_file
+        });
+
+        roots.insert(POLICY_ROOT.to_string());
+        metadata.policies.push(ManifestPolicy {
+            name: policy.name.clone(),
+            description: policy.description.clone(),
+            version: policy.version.clone(),
+            file,
+            test_file,
+            rego_sha256: sha256_hex(policy.rego_source.as_bytes()),
+            kind: policy.kind,
+            targets: policy.targets.clone(),
+        });
+    }
+
+    for document in documents {
+        // Compact JSON, so the file hash is the document's sha256
+        let content = serde_json::to_vec(&document.content)?;
+        roots.extend(document.path.split('.').next().map(str::to_string));
+        metadata.data.push(ManifestDocument {
+            path: document.path.clone(),
+            version: document.version,
+            sha256: sha256_hex(&content),
+        });
+        files.insert(format!("{}/data.json", document.path.replace('.', "/")), content);
+    }
+
+    let revision = revision_of(&files);
+    let manifest = Manifest {
+        revision: revision.clone(),
+        roots: roots.into_iter().collect(),
+        metadata,
+    };
+    files.insert(MANIFEST.to_string(), serde_json::to_vec_pretty(&manifest)?);
+
+    Ok((revision, files))
+}
+
+/// Read policies and documents from verified bundle files
+fn read(files: &BTreeMap<String, Vec<u8>>) -> Result<ParsedBundle> {
+    let invalid = GuardRailError::InvalidRego;
+    let manifest: Manifest = serde_json::from_slice(
+        files
+            .get(MANIFEST)
+            .ok_or_else(|| invalid("Bundle has no .manifest".to_string()))?,
+    )
+    .map_err(|e| invalid(format!("Bundle .manifest is malformed: {}", e)))?;
+
+    let text = |name: &str| -> Result<String> {
+        let bytes = files
+            .get(name)
+            .ok_or_else(|| invalid(format!("Bundle is missing {}", name)))?;
+        String::from_utf8(bytes.clone()).map_err(|_| invalid(format!("Bundle file {} is not UTF-8", name)))
+    };
+
+    let mut claimed = HashSet::new();
+    let mut policies = Vec::new();
+    for entry in &manifest.metadata.policies {
+        let rego_source = text(&entry.file)?;
+        if sha256_hex(rego_source.as_bytes()) != entry.rego_sha256 {
+            return Err(invalid(format!("Bundle file {} does not match its manifest entry", entry.file)));
+        }
+        claimed.insert(entry.file.as_str());
+        claimed.extend(entry.test_file.as_deref());
+        policies.push(BundlePolicy {
+            name: entry.name.clone(),
+            description: entry.description.clone(),
+            kind: entry.kind,
+            rego_source,
+            test_source: entry.test_file.as_deref().map(text).transpose()?,
+            targets: entry.targets.clone(),
+        });
+    }
+
+    // Modules the manifest doesn't describe are named after their file
+    for name in files.keys().filter(|n| n.ends_with(".rego") && !n.ends_with("_test.rego")) {
+        if claimed.contains(name.as_str()) {
+            continue;
+        }
+        let base = name.trim_end_matches(".rego");
+        let test_file = format!("{}_test.rego", base);
+        let test_source = match files.contains_key(&test_file) {
+            true => Some(text(&test_file)?),
+            false => None,
+        };
+        claimed.insert(name.as_str());
+        let rego_source = text(name)?;
+        policies.push(BundlePolicy {
+            name: base.rsplit('/').next().unwrap_or(base).to_string(),
+            description: None,
+            kind: libraries::kind_of(&rego_source),
+            rego_source,
+            test_source,
+            targets: PolicyTargets::default(),
+        });
+        if let Some((test_file, _)) = files.get_key_value(&test_file) {
+            claimed.insert(test_file.as_str());
+        }
+    }
+
+    if let Some(orphan) = files.keys().find(|n| n.ends_with("_test.rego") && !claimed.contains(n.as_str())) {
+        return Err(invalid(format!("Bundle test module {} has no policy module", orphan)));
+    }
+    let mut names = HashSet::new();
+    if let Some(duplicate) = policies.iter().find(|p| !names.insert(p.name.as_str())) {
+        return Err(invalid(format!("Bundle contains policy {} more than once", duplicate.name)));
+    }
+
+    let mut documents = Vec::new();
+    for (name, bytes) in files {
+        if name != "data.json" && !name.ends_with("/data.json") {
+            continue;
+        }
+        let path = name.trim_end_matches("data.json").trim_end_matches('/').replace('/', ".");
+        data_documents::validate_path(&path)
+            .map_err(|e| invalid(format!("Bundle data file {}: {}", name, e)))?;
+        let content = serde_json::from_slice(bytes)
+            .map_err(|e| invalid(format!("Bundle data file {} is not valid JSON: {}", name, e)))?;
+        documents.push((path, content));
+    }
+
+    Ok(ParsedBundle {
+        revision: manifest.revision,
+        policies,
+        documents,
+    })
+}
+
+// ============================================================================
+// Archive and signatures
+// ============================================================================
+
+/// Gzipped tarball of `files`, with fixed metadata so equal contents give equal bytes
+fn pack(files: &BTreeMap<String, Vec<u8>>) -> Result<Vec<u8>> {
+    let io_error = |e: std::io::Error| GuardRailError::Internal(format!("Failed to write bundle: {}", e));
+
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (name, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        builder.append_data(&mut header, name, content.as_slice()).map_err(io_error)?;
+    }
+    builder.into_inner().and_then(|gz| gz.finish()).map_err(io_error)
+}
+
+/// Bundle path with `./` and leading `/` removed; anything escaping the bundle is rejected
+fn entry_name(path: &std::path::Path) -> Result<String> {
+    use std::path::Component;
+
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(
+                part.to_str()
+                    .ok_or_else(|| GuardRailError::InvalidRego("Bundle file names must be UTF-8".to_string()))?,
+            ),
+            Component::CurDir | Component::RootDir => {}
+            _ => return Err(GuardRailError::InvalidRego(format!("Bundle file {} escapes the bundle", path.display()))),
+        }
+    }
+    Ok(parts.join("/"))
+}
+
+/// Unpack a `.tar.gz` into its files
+fn unpack(bytes: &[u8]) -> Result<BTreeMap<String, Vec<u8>>> {
+    let invalid = |e: std::io::Error| GuardRailError::InvalidRego(format!("Bundle is not a valid tar.gz: {}", e));
+
+    let mut archive = tar::Archive::new(GzDecoder::new(bytes));
+    let mut files = BTreeMap::new();
+    let mut total = 0;
+    for entry in archive.entries().map_err(invalid)? {
+        let mut entry = entry.map_err(invalid)?;
+        match entry.header().entry_type() {
+            tar::EntryType::Directory => continue,
+            tar::EntryType::Regular => {}
+            other => {
+                return Err(GuardRailError::InvalidRego(format!(
+                    "Bundle entries must be regular files, found {:?}",
+                    other
+                )))
+            }
+        }
+
+        let name = entry_name(&entry.path().map_err(invalid)?)?;
+        total += entry.size();
+        if total > MAX_BUNDLE_BYTES || files.len() >= MAX_BUNDLE_FILES {
+            return Err(GuardRailError::InvalidRego(format!(
+                "Bundle exceeds {} files or {} bytes",
+                MAX_BUNDLE_FILES, MAX_BUNDLE_BYTES
+            )));
+        }
+
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content).map_err(invalid)?;
+        if files.insert(name.clone(), content).is_some() {
+            return Err(GuardRailError::InvalidRego(format!("Bundle contains {} more than once", name)));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Hashes of every file except the signatures themselves
+fn signed_files(files: &BTreeMap<String, Vec<u8>>) -> Vec<SignedFile> {
+    files
+        .iter()
+        .filter(|(name, _)| name.as_str() != SIGNATURES)
+        .map(|(name, content)| SignedFile {
+            name: name.clone(),
+            hash: sha256_hex(content),
+            algorithm: HASH_ALGORITHM.to_string(),
+        })
+        .collect()
+}
+
+/// `.signatures.json` for `files`: one compact JWS over their hashes
+fn sign(files: &BTreeMap<String, Vec<u8>>, key_id: &str, key: &SigningKey) -> Result<Vec<u8>> {
+    let header = JwsHeader {
+        alg: JWS_ALGORITHM.to_string(),
+        kid: key_id.to_string(),
+    };
+    let payload = SignedFiles {
+        files: signed_files(files),
+    };
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&payload)?)
+    );
+    let signature = URL_SAFE_NO_PAD.encode(key.sign(signing_input.as_bytes()).to_bytes());
+
+    Ok(serde_json::to_vec_pretty(&Signatures {
+        signatures: vec![format!("{}.{}", signing_input, signature)],
+    })?)
+}
+
+/// Check the signature and every file hash, returning the signing key id
+fn verify(files: &BTreeMap<String, Vec<u8>>, trusted_keys: &HashMap<String, VerifyingKey>) -> Result<String> {
+    let crypto = |message: String| GuardRailError::CryptoError(message);
+    let malformed = || crypto("Bundle signature is malformed".to_string());
+
+    let signatures: Signatures = serde_json::from_slice(
+        files
+            .get(SIGNATURES)
+            .ok_or_else(|| crypto("Bundle is not signed".to_string()))?,
+    )
+    .map_err(|_| malformed())?;
+    let [token] = signatures.signatures.as_slice() else {
+        return Err(crypto("Bundle must carry exactly one signature".to_string()));
+    };
+
+    let parts: Vec<&str> = token.split('.').collect();
+    let [header_b64, payload_b64, signature_b64] = parts[..] else {
+        return Err(malformed());
+    };
+    let decode = |part: &str| URL_SAFE_NO_PAD.decode(part).map_err(|_| malformed());
+
+    let header: JwsHeader = serde_json::from_slice(&decode(header_b64)?).map_err(|_| malformed())?;
+    if header.alg != JWS_ALGORITHM {
+        return Err(crypto(format!("Unsupported bundle signature algorithm {}", header.alg)));
+    }
+    let key = trusted_keys
+        .get(&header.kid)
+        .ok_or_else(|| crypto(format!("Bundle is signed by untrusted key {:?}", header.kid)))?;
+    let signature = Signature::from_slice(&decode(signature_b64)?).map_err(|_| malformed())?;
+    key.verify_strict(format!("{}.{}", header_b64, payload_b64).as_bytes(), &signature)
+        .map_err(|_| crypto("Bundle signature is invalid".to_string()))?;
+
+    // Every file must be signed, and every signed file present and unchanged
+    let payload: SignedFiles = serde_json::from_slice(&decode(payload_b64)?).map_err(|_| malformed())?;
+    let mut signed: HashMap<&str, &SignedFile> = HashMap::new();
+    for file in &payload.files {
+        if file.algorithm != HASH_ALGORITHM || signed.insert(&file.name, file).is_some() {
+            return Err(malformed());
+        }
+    }
+    for file in signed_files(files) {
+        match signed.remove(file.name.as_str()) {
+            Some(expected) if expected.hash == file.hash => {}
+            Some(_) => return Err(crypto(format!("Bundle file {} does not match its signed hash", file.name))),
+            None => return Err(crypto(format!("Bundle file {} is not signed", file.name))),
+        }
+    }
+    if let Some(missing) = signed.keys().next() {
+        return Err(crypto(format!("Signed file {} is missing from the bundle", missing)));
+    }
+
+    Ok(header.kid)
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+pub async fn export_bundle(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match export_bundle_impl(&state).await {
+        Ok(bundle) => (StatusCode::OK, Json(ApiResponse::success(bundle))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<ExportedBundle>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn export_bundle_impl(state: &AppState) -> Result<ExportedBundle> {
+    let (key_id, key) = state.bundle_config.signing_key.as_ref().ok_or_else(|| {
+        GuardRailError::CryptoError("No bundle signing key configured; set BUNDLE_SIGNING_KEY".to_string())
+    })?;
+
+    let policies = sqlx::query_as!(
+        Policy,
+        r#"
+        SELECT id, name, description, version, kind as "kind: PolicyKind", rego_source, test_source, is_active as "is_active!", is_shadow, effective_from, effective_until, targets as "targets: PolicyTargets", created_by, created_at as "created_at!", updated_at as "updated_at!"
+        FROM policies
+        WHERE is_active = true
+        ORDER BY name ASC, created_at ASC
+        "#,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let documents = sqlx::query_as!(
+        BundleDocument,
+        r#"
+        SELECT path, version, content
+        FROM data_documents
+        WHERE is_active = true
+        ORDER BY path ASC
+        "#,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let (revision, mut files) = build(&policies, &documents)?;
+    files.insert(SIGNATURES.to_string(), sign(&files, key_id, key)?);
+
+    Ok(ExportedBundle {
+        revision,
+        key_id: key_id.clone(),
+        policies: policies.len(),
+        data_documents: documents.len(),
+        bundle: STANDARD.encode(pack(&files)?),
+    })
+}
+
+pub async fn import_bundle(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ImportBundleRequest>,
+) -> impl IntoResponse {
+    match import_bundle_impl(&state, req).await {
+        Ok(import) => (StatusCode::OK, Json(ApiResponse::success(import))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<BundleImport>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+/// Verify a bundle and check all of it, then apply its data documents and policies
+/// in one transaction
+async fn import_bundle_impl(state: &AppState, req: ImportBundleRequest) -> Result<BundleImport> {
+    let bytes = STANDARD
+        .decode(req.bundle.trim())
+        .map_err(|_| GuardRailError::InvalidField {
+            field: "bundle".to_string(),
+            message: "must be a base64-encoded tar.gz".to_string(),
+        })?;
+    let files = unpack(&bytes)?;
+    let key_id = verify(&files, &state.bundle_config.trusted_keys)?;
+    let bundle = read(&files)?;
+
+    for policy in &bundle.policies {
+        validate_rego(&policy.name, &policy.rego_source)?;
+    }
+
+    let activate = req.activate.unwrap_or(true);
+    let summary = format!("Imported from bundle {}", bundle.revision);
+
+    // Check everything before writing anything, with tests seeing the bundle's own data and libraries
+    let documents = plan_documents(state, bundle.documents, activate).await?;
+    let staged_data: Vec<(String, serde_json::Value)> = documents
+        .iter()
+        .filter(|d| activate && d.outcome != ImportOutcome::Unchanged)
+        .map(|d| (d.path.clone(), d.content.clone()))
+        .collect();
+    let policies = plan_policies(state, bundle.policies, activate, &staged_data).await?;
+
+    let now = Utc::now();
+    let mut tx = state.db.begin().await?;
+
+    let mut imported_documents = Vec::with_capacity(documents.len());
+    let mut activated_documents = Vec::new();
+    if !documents.is_empty() {
+        data_documents::lock(&mut tx).await?;
+    }
+    for planned in documents {
+        let (document_id, version) = match (planned.outcome, planned.latest) {
+            (ImportOutcome::Unchanged, Some((id, version))) => (id, version),
+            (ImportOutcome::Activated, Some((id, _))) => {
+                let document = data_documents::activate_in(&mut tx, id).await?;
+                activated_documents.push(document.clone());
+                (document.id, document.version)
+            }
+            _ => {
+                let upload = UploadDataDocumentRequest {
+                    path: planned.path.clone(),
+                    content: planned.content,
+                    description: Some(summary.clone()),
+                    activate,
+                    created_by: req.created_by,
+                };
+                let mut document = data_documents::insert_document(&mut tx, &upload).await?;
+                if activate {
+                    document = data_documents::activate_in(&mut tx, document.id).await?;
+                    activated_documents.push(document.clone());
+                }
+                (document.id, document.version)
+            }
+        };
+        imported_documents.push(ImportedDocument {
+            document_id,
+            path: planned.path,
+            version,
+            outcome: planned.outcome,
+        });
+    }
+
+    let mut stored = Vec::with_capacity(policies.len());
+    for planned in &policies {
+        let policy = store_policy(&mut tx, planned, &summary, req.created_by, now).await?;
+        stored.push(policy);
+    }
+
+    tx.commit().await?;
+
+    let changed = !activated_documents.is_empty()
+        || imported_documents.iter().any(|d| d.outcome != ImportOutcome::Unchanged)
+        || policies.iter().any(|p| p.outcome != ImportOutcome::Unchanged);
+    if changed {
+        reload_policies(state).await?;
+    }
+
+    for document in &activated_documents {
+        data_documents::record_activated(state, document).await;
+    }
+    let mut imported_policies = Vec::with_capacity(policies.len());
+    for (planned, policy) in policies.iter().zip(stored) {
+        match (planned.outcome, &planned.current) {
+            (ImportOutcome::Created, _) => record_policy_created(state, &policy).await,
+            (ImportOutcome::Updated, Some(current)) => {
+                versions::record_policy_updated(state, current, &policy, Some(&summary), req.created_by).await
+            }
+            _ => {}
+        }
+        imported_policies.push(ImportedPolicy {
+            policy_id: policy.id,
+            name: policy.name,
+            version: policy.version,
+            outcome: planned.outcome,
+        });
+    }
+
+    state
+        .ledger
+        .record_event_logged(
+            EventType::SystemEvent,
+            req.created_by.unwrap_or(SYSTEM_ACTOR_ID),
+            None,
+            serde_json::json!({
+                "event": "POLICY_BUNDLE_IMPORTED",
+                "revision": bundle.revision,
+                "key_id": key_id,
+                "policies": imported_policies,
+                "data_documents": imported_documents,
+            }),
+        )
+        .await;
+
+    Ok(BundleImport {
+        revision: bundle.revision,
+        key_id,
+        policies: imported_policies,
+        data_documents: imported_documents,
+    })
+}
+
+/// A bundle document matched against the stored versions of its path
+struct PlannedDocument {
+    path: String,
+    content: serde_json::Value,
+    /// Id and version of the latest stored version
+    latest: Option<(Uuid, i32)>,
+    outcome: ImportOutcome,
+}
+
+/// A bundle policy matched against the stored policy of its name, with the state
+/// the import leaves it in
+struct PlannedPolicy {
+    id: Uuid,
+    policy: BundlePolicy,
+    current: Option<Policy>,
+    description: Option<String>,
+    outcome: ImportOutcome,
+    version: String,
+    is_active: bool,
+    is_shadow: bool,
+    /// Passing tests of a policy the import creates, updates or activates
+    test_report: Option<PolicyTestReport>,
+}
+
+/// A live policy as it would be loaded once the import is applied
+struct StagedPolicy {
+    id: Uuid,
+    name: String,
+    version: String,
+    kind: PolicyKind,
+    rego_source: String,
+    is_active: bool,
+}
+
+async fn plan_documents(
+    state: &AppState,
+    documents: Vec<(String, serde_json::Value)>,
+    activate: bool,
+) -> Result<Vec<PlannedDocument>> {
+    let mut planned = Vec::with_capacity(documents.len());
+    for (path, content) in documents {
+        data_documents::validate_path(&path)?;
+        let sha256 = sha256_hex(&serde_json::to_vec(&content)?);
+        let latest = sqlx::query!(
+            r#"
+            SELECT id, version, sha256, is_active
+            FROM data_documents
+            WHERE path = $1
+            ORDER BY version DESC
+            LIMIT 1
+            "#,
+            path,
+        )
+        .fetch_optional(&state.db)
+        .await?;
+
+        let outcome = match &latest {
+            Some(latest) if latest.sha256 == sha256 && (latest.is_active || !activate) => ImportOutcome::Unchanged,
+            Some(latest) if latest.sha256 == sha256 => ImportOutcome::Activated,
+            Some(_) => ImportOutcome::Updated,
+            None => ImportOutcome::Created,
+        };
+        planned.push(PlannedDocument {
+            path,
+            content,
+            latest: latest.map(|l| (l.id, l.version)),
+            outcome,
+        });
+    }
+    Ok(planned)
+}
+
+/// Match each policy to the most recently updated stored policy of its name, then
+/// validate, lint and test every change against the policies, libraries and data
+/// the import leaves loaded
+async fn plan_policies(
+    state: &AppState,
+    mut policies: Vec<BundlePolicy>,
+    activate: bool,
+    staged_data: &[(String, serde_json::Value)],
+) -> Result<Vec<PlannedPolicy>> {
+    // Libraries first, so the policies importing them are stored after them
+    policies.sort_by_key(|p| p.kind != PolicyKind::Library);
+
+    let mut planned = Vec::with_capacity(policies.len());
+    for policy in policies {
+        let current = sqlx::query_as!(
+            Policy,
+            r#"
+            SELECT id, name, description, version, kind as "kind: PolicyKind", rego_source, test_source, is_active as "is_active!", is_shadow, effective_from, effective_until, targets as "targets: PolicyTargets", created_by, created_at as "created_at!", updated_at as "updated_at!"
+            FROM policies
+            WHERE name = $1
+            ORDER BY updated_at DESC
+            LIMIT 1
+            "#,
+            policy.name,
+        )
+        .fetch_optional(&state.db)
+        .await?;
+        planned.push(plan_policy(policy, current, activate)?);
+    }
+
+    let live = sqlx::query!(
+        r#"
+        SELECT id, name, version, kind as "kind: PolicyKind", rego_source, is_active as "is_active!"
+        FROM policies
+        WHERE is_active = true OR is_shadow = true
+        ORDER BY kind = 'DECISION', created_at ASC
+        "#,
+    )
+    .fetch_all(&state.db)
+    .await?;
+    let replaced: HashSet<Uuid> = planned.iter().map(|p| p.id).collect();
+    let mut staged: Vec<StagedPolicy> = live
+        .into_iter()
+        .filter(|p| !replaced.contains(&p.id))
+        .map(|p| StagedPolicy {
+            id: p.id,
+            name: p.name,
+            version: p.version,
+            kind: p.kind,
+            rego_source: p.rego_source,
+            is_active: p.is_active,
+        })
+        .collect();
+    staged.extend(planned.iter().filter(|p| p.is_active || p.is_shadow).map(|p| StagedPolicy {
+        id: p.id,
+        name: p.policy.name.clone(),
+        version: p.version.clone(),
+        kind: p.policy.kind,
+        rego_source: p.policy.rego_source.clone(),
+        is_active: p.is_active,
+    }));
+    staged.sort_by_key(|p| p.kind != PolicyKind::Library);
+
+    for p in planned.iter().filter(|p| p.outcome != ImportOutcome::Unchanged) {
+        let source_changed = p.current.as_ref().is_none_or(|c| c.rego_source != p.policy.rego_source);
+        if source_changed {
+            let others: Vec<(String, String)> = staged
+                .iter()
+                .filter(|s| s.id != p.id)
+                .map(|s| (s.name.clone(), s.rego_source.clone()))
+                .collect();
+            lint::ensure_clean(&p.policy.name, &lint::lint(&p.policy.rego_source, p.policy.kind, &others))?;
+        }
+    }
+    ensure_imports_provided(&planned, &staged)?;
+
+    let mut engine = PolicyEngine::new();
+    if let Some(data) = data_documents::load_staged(&state.db, staged_data).await? {
+        engine.set_data(Arc::new(data))?;
+    }
+    for library in staged.iter().filter(|s| s.kind == PolicyKind::Library && s.is_active) {
+        engine
+            .load_library(library.id, &library.name, &library.version, &library.rego_source)
+            .map_err(|e| GuardRailError::InvalidRego(format!("Library {}: {}", library.name, e)))?;
+    }
+    for p in planned.iter_mut().filter(|p| p.outcome != ImportOutcome::Unchanged) {
+        let report = rego_tests::run_tests(
+            &engine,
+            Some(p.id),
+            &p.policy.name,
+            &p.policy.rego_source,
+            p.policy.test_source.as_deref(),
+        )?;
+        rego_tests::ensure_passed(&report)?;
+        p.test_report = Some(report);
+    }
+
+    Ok(planned)
+}
+
+/// What importing `policy` over `current` does, after the checks that need no other policies
+fn plan_policy(mut policy: BundlePolicy, current: Option<Policy>, activate: bool) -> Result<PlannedPolicy> {
+    let Some(current) = current else {
+        // Libraries can't be shadowed, and decide nothing while active
+        let is_shadow = !activate && policy.kind == PolicyKind::Decision;
+        targeting::validate(&policy.targets)?;
+        libraries::validate(policy.kind, &policy.targets, is_shadow, None, None)?;
+        policy.test_source = policy.test_source.filter(|t| !t.trim().is_empty());
+        return Ok(PlannedPolicy {
+            id: Uuid::new_v4(),
+            description: policy.description.clone(),
+            policy,
+            current: None,
+            outcome: ImportOutcome::Created,
+            version: versions::INITIAL_VERSION.to_string(),
+            is_active: !is_shadow,
+            is_shadow,
+            test_report: None,
+        });
+    };
+
+    if current.kind != policy.kind {
+        return Err(GuardRailError::Conflict(format!(
+            "Policy {} is a {:?} policy here but a {:?} policy in the bundle",
+            current.name, current.kind, policy.kind
+        )));
+    }
+
+    let description = policy.description.clone().or_else(|| current.description.clone());
+    let unchanged = policy.rego_source == current.rego_source
+        && policy.test_source == current.test_source
+        && policy.targets == current.targets
+        && description == current.description;
+
+    let (outcome, version, is_active, is_shadow) = if unchanged && activate && !current.is_active {
+        (ImportOutcome::Activated, current.version.clone(), true, false)
+    } else if unchanged {
+        (ImportOutcome::Unchanged, current.version.clone(), current.is_active, current.is_shadow)
+    } else {
+        targeting::validate(&policy.targets)?;
+        libraries::validate(current.kind, &policy.targets, false, None, None)?;
+        (
+            ImportOutcome::Updated,
+            bump_version(&current.version, VersionBump::Patch)?,
+            current.is_active || activate,
+            current.is_shadow && !activate,
+        )
+    };
+
+    Ok(PlannedPolicy {
+        id: current.id,
+        policy,
+        current: Some(current),
+        description,
+        outcome,
+        version,
+        is_active,
+        is_shadow,
+        test_report: None,
+    })
+}
+
+/// Reject an import that leaves a live policy importing a package no active
+/// library provides. Checks the policies the import changes, and those importing
+/// the previous package of a library it changes.
+fn ensure_imports_provided(planned: &[PlannedPolicy], staged: &[StagedPolicy]) -> Result<()> {
+    let changed: HashSet<Uuid> = planned
+        .iter()
+        .filter(|p| p.outcome != ImportOutcome::Unchanged)
+        .map(|p| p.id)
+        .collect();
+    let replaced_packages: Vec<String> = planned
+        .iter()
+        .filter(|p| p.outcome == ImportOutcome::Updated && p.policy.kind == PolicyKind::Library)
+        .filter_map(|p| p.current.as_ref())
+        .filter_map(|c| lint::module_imports(&c.rego_source))
+        .map(|(package, _)| package)
+        .collect();
+    let provided: Vec<String> = staged
+        .iter()
+        .filter(|s| s.kind == PolicyKind::Library && s.is_active)
+        .filter_map(|s| lint::module_imports(&s.rego_source))
+        .map(|(package, _)| package)
+        .collect();
+
+    for policy in staged {
+        let Some((_, imports)) = lint::module_imports(&policy.rego_source) else {
+            continue;
+        };
+        let affected = changed.contains(&policy.id)
+            || imports
+                .iter()
+                .any(|path| replaced_packages.iter().any(|package| lint::provides(package, path)));
+        if !affected {
+            continue;
+        }
+        let missing: Vec<&str> = imports
+            .iter()
+            .filter(|path| !provided.iter().any(|package| lint::provides(package, path)))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            return Err(GuardRailError::Conflict(format!(
+                "Policy {} imports `data.{}`, which no active library provides",
+                policy.name,
+                missing.join("`, `data.")
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Write one planned policy inside the import transaction
+async fn store_policy(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    planned: &PlannedPolicy,
+    summary: &str,
+    created_by: Option<Uuid>,
+    now: DateTime<Utc>,
+) -> Result<Policy> {
+    let report = planned.test_report.as_ref();
+    match (&planned.current, planned.outcome, report) {
+        (None, ImportOutcome::Created, Some(report)) => {
+            let req = CreatePolicyRequest {
+                name: planned.policy.name.clone(),
+                description: planned.description.clone(),
+                kind: planned.policy.kind,
+                rego_source: planned.policy.rego_source.clone(),
+                test_source: planned.policy.test_source.clone(),
+                shadow: planned.is_shadow,
+                effective_from: None,
+                effective_until: None,
+                targets: planned.policy.targets.clone(),
+                created_by,
+            };
+            let policy = insert_policy(tx, planned.id, &req, None, now).await?;
+            rego_tests::record_run(&mut **tx, policy.id, &policy.version, TestTrigger::Create, report).await?;
+            Ok(policy)
+        }
+        (Some(current), ImportOutcome::Updated, Some(report)) => {
+            let revision = Revision {
+                description: planned.description.clone(),
+                rego_source: &planned.policy.rego_source,
+                test_source: planned.policy.test_source.as_deref(),
+                targets: &planned.policy.targets,
+                bump: VersionBump::Patch,
+                change_summary: Some(summary),
+                created_by,
+                activate: planned.is_active && !current.is_active,
+                template: None,
+            };
+            versions::store_revision(tx, current, &revision, &planned.version, report, now).await
+        }
+        (Some(current), ImportOutcome::Activated, Some(report)) => {
+            let policy = set_active(&mut **tx, current.id, true, now).await?;
+            rego_tests::record_run(&mut **tx, policy.id, &policy.version, TestTrigger::Activate, report).await?;
+            Ok(policy)
+        }
+        (Some(current), _, _) => Ok(current.clone()),
+        (None, _, _) => Err(GuardRailError::Internal(format!(
+            "Bundle policy {} was planned without a stored policy",
+            planned.policy.name
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(name: &str, rego_source: &str, test_source: Option<&str>) -> Policy {
+        Policy {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            description: Some(format!("{} policy", name)),
+            version: "1.0.3".to_string(),
+            kind: PolicyKind::Decision,
+            rego_source: rego_source.to_string(),
+            test_source: test_source.map(str::to_string),
+            is_active: true,
+            is_shadow: false,
+            effective_from: None,
+            effective_until: None,
+            targets: PolicyTargets::default(),
+            created_by: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn signed_bundle(key: &SigningKey) -> BTreeMap<String, Vec<u8>> {
+        let mut limits = policy("limits", "package guardrail\ndeny contains \"too big\" if false", None);
+        limits.targets.assets = vec!["BTC".to_string()];
+        let policies = [
+            policy("Sanctions Screen", "package guardrail\ndeny contains \"sanctioned\" if false", Some("package guardrail\ntest_ok if true")),
+            limits,
+        ];
+        let documents = [BundleDocument {
+            path: "sanctions.addresses".to_string(),
+            version: 4,
+            content: serde_json::json!(["0xbad"]),
+        }];
+        let (_, mut files) = build(&policies, &documents).unwrap();
+        files.insert(SIGNATURES.to_string(), sign(&files, "ci", key).unwrap());
+        files
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let trusted = HashMap::from([("ci".to_string(), key.verifying_key())]);
+
+        let packed = pack(&signed_bundle(&key)).unwrap();
+        assert_eq!(packed, pack(&signed_bundle(&key)).unwrap(), "export is deterministic");
+
+        let files = unpack(&packed).unwrap();
+        assert!(files.contains_key("policies/sanctions_screen_test.rego"));
+        assert!(files.contains_key("sanctions/addresses/data.json"));
+        assert_eq!(verify(&files, &trusted).unwrap(), "ci");
+
+        let bundle = read(&files).unwrap();
+        assert_eq!(bundle.revision, revision_of(&files));
+        assert_eq!(bundle.policies.len(), 2);
+        assert_eq!(bundle.policies[0].name, "Sanctions Screen");
+        assert_eq!(bundle.policies[0].test_source.as_deref(), Some("package guardrail\ntest_ok if true"));
+        assert_eq!(bundle.policies[1].targets.assets, vec!["BTC".to_string()]);
+        assert_eq!(bundle.documents, vec![("sanctions.addresses".to_string(), serde_json::json!(["0xbad"]))]);
+
+        let manifest: Manifest = serde_json::from_slice(&files[MANIFEST]).unwrap();
+        assert_eq!(manifest.roots, vec!["guardrail", "sanctions"]);
+    }
+
+    #[test]
+    fn test_rejects_unsigned_and_tampered_bundles() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let trusted = HashMap::from([("ci".to_string(), key.verifying_key())]);
+        let rejected = |files: &BTreeMap<String, Vec<u8>>| {
+            matches!(verify(files, &trusted), Err(GuardRailError::CryptoError(_)))
+        };
+
+        let mut unsigned = signed_bundle(&key);
+        unsigned.remove(SIGNATURES);
+        assert!(rejected(&unsigned));
+
+        let mut tampered = signed_bundle(&key);
+        tampered.insert("policies/limits.rego".to_string(), b"package guardrail".to_vec());
+        assert!(rejected(&tampered));
+
+        let mut extra = signed_bundle(&key);
+        extra.insert("policies/backdoor.rego".to_string(), b"package guardrail".to_vec());
+        assert!(rejected(&extra));
+
+        let mut missing = signed_bundle(&key);
+        missing.remove("sanctions/addresses/data.json");
+        assert!(rejected(&missing));
+
+        let untrusted = signed_bundle(&SigningKey::from_bytes(&[9; 32]));
+        assert!(rejected(&untrusted));
+
+        assert!(matches!(unpack(b"not a tarball"), Err(GuardRailError::InvalidRego(_))));
+    }
+
+    #[test]
+    fn test_import_plan() {
+        let bundled = |rego_source: &str| BundlePolicy {
+            name: "limits".to_string(),
+            description: Some("limits policy".to_string()),
+            kind: PolicyKind::Library,
+            rego_source: rego_source.to_string(),
+            test_source: None,
+            targets: PolicyTargets::default(),
+        };
+        let old_source = "package guardrail.lib.limits\nmax := 10";
+        let mut current = policy("limits", old_source, None);
+        current.kind = PolicyKind::Library;
+
+        let plan = plan_policy(bundled(old_source), Some(current.clone()), true).unwrap();
+        assert_eq!(plan.outcome, ImportOutcome::Unchanged);
+        assert_eq!(plan.version, "1.0.3");
+
+        // Moving the library's package breaks a live policy that the bundle leaves alone
+        let moved = plan_policy(bundled("package guardrail.lib.caps\nmax := 10"), Some(current.clone()), true).unwrap();
+        assert_eq!(moved.outcome, ImportOutcome::Updated);
+        assert_eq!(moved.version, "1.0.4");
+        let staged = |library_source: &str| {
+            vec![
+                StagedPolicy {
+                    id: current.id,
+                    name: "limits".to_string(),
+                    version: "1.0.4".to_string(),
+                    kind: PolicyKind::Library,
+                    rego_source: library_source.to_string(),
+                    is_active: true,
+                },
+                StagedPolicy {
+                    id: Uuid::new_v4(),
+                    name: "withdrawals".to_string(),
+                    version: "1.0.0".to_string(),
+                    kind: PolicyKind::Decision,
+                    rego_source: "package guardrail\nimport rego.v1\nimport data.guardrail.lib.limits\ndeny contains \"x\" if limits.max < 1"
+                        .to_string(),
+                    is_active: true,
+                },
+            ]
+        };
+        assert!(matches!(
+            ensure_imports_provided(&[moved], &staged("package guardrail.lib.caps\nmax := 10")),
+            Err(GuardRailError::Conflict(_))
+        ));
+
+        let kept = plan_policy(bundled("package guardrail.lib.limits\nmax := 20"), Some(current.clone()), true).unwrap();
+        assert!(ensure_imports_provided(&[kept], &staged("package guardrail.lib.limits\nmax := 20")).is_ok());
+
+        current.kind = PolicyKind::Decision;
+        assert!(matches!(
+            plan_policy(bundled(old_source), Some(current), true),
+            Err(GuardRailError::Conflict(_))
+        ));
+    }
+}
@@ -0,0 +1,1086 @@
+//! Continuous control monitoring
+//!
+//! Some controls concern state rather than actions: "every human with a recent
+//! withdrawal holds unexpired KYC", "no agent holds more than 3 signing keys". A
+//! control is a Rego module in `package guardrail.control` producing a `violation`
+//! set, in the same shapes as `deny` reasons. It isn't evaluated on checks; the
+//! sweeper evaluates it every `interval_secs` against each active identity in its
+//! scope, with the libraries and data documents decision policies see. Identities
+//! are loaded and evaluated in batches, each batch on a blocking thread.
+//!
+//! Each violation opens a finding, identified by its code or else its message. A
+//! sweep that finds it again updates it, and one that doesn't resolves it. Opened
+//! and resolved findings are queued for the ledger through the decision outbox,
+//! in the transaction that records them. A sweep that fails records the error on
+//! its run and leaves the findings as they were.
+
+use crate::ledger::SYSTEM_ACTOR_ID;
+use crate::{libraries, lint, outbox, AppState, PolicyEngine};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use guardrail_shared::{
+    ApiResponse, Control, ControlFinding, ControlRun, CreateControlRequest, DenyReason, EventType, FindingStatus,
+    GuardRailError, IdentityType, PaginatedResponse, Result, UpdateControlRequest,
+};
+use regorus::Engine;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Package a control module declares
+pub const CONTROL_PACKAGE: &str = "guardrail.control";
+
+const DEFAULT_INTERVAL_SECS: i32 = 3600;
+const DEFAULT_ACTIVITY_DAYS: i32 = 30;
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+#[derive(Debug, Clone)]
+pub struct ControlConfig {
+    /// Longest the sweeper sleeps between looking for due controls
+    pub poll_interval: Duration,
+    /// Identities loaded and evaluated at a time during a sweep
+    pub batch_size: i64,
+}
+
+impl ControlConfig {
+    pub fn from_env() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(
+                std::env::var("CONTROL_POLL_SECS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(30),
+            ),
+            batch_size: std::env::var("CONTROL_SWEEP_BATCH")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|&n: &i64| n > 0)
+                .unwrap_or(500),
+        }
+    }
+}
+
+// ============================================================================
+// Request/Response Types
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct ListControlsQuery {
+    pub page: Option<i32>,
+    pub per_page: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListFindingsQuery {
+    pub page: Option<i32>,
+    pub per_page: Option<i32>,
+    pub status: Option<FindingStatus>,
+    pub control_id: Option<Uuid>,
+    pub identity_id: Option<Uuid>,
+}
+
+/// An identity as swept, before its credentials, keys and activity are attached
+struct SweptIdentity {
+    id: Uuid,
+    identity_type: String,
+    display_name: String,
+    metadata: Option<serde_json::Value>,
+    organization_id: Option<Uuid>,
+    created_at: Option<DateTime<Utc>>,
+}
+
+struct CredentialRow {
+    identity_id: Uuid,
+    credential_type: String,
+    provider: String,
+    value: serde_json::Value,
+    expires_at: Option<DateTime<Utc>>,
+    verified_at: Option<DateTime<Utc>>,
+}
+
+struct KeyRow {
+    identity_id: Uuid,
+    key_type: String,
+    chain: Option<String>,
+    is_primary: Option<bool>,
+    verified_at: Option<DateTime<Utc>>,
+    created_at: Option<DateTime<Utc>>,
+}
+
+/// Decisions for one identity and action type within a control's activity window
+struct ActivityRow {
+    identity_id: Uuid,
+    /// As stored on the decision row, e.g. `Withdrawal`
+    action_type: String,
+    count: i64,
+    denied: i64,
+    last_at: Option<DateTime<Utc>>,
+}
+
+// ============================================================================
+// Evaluation
+// ============================================================================
+
+/// Module path of a control, apart from the policies' `policy/...` modules
+fn module_name(control: &Control) -> String {
+    format!("control/{}/{}", control.id, control.name)
+}
+
+/// Reject a control module that doesn't compile, declares another package or
+/// imports a library that isn't active
+async fn validate_source(state: &AppState, name: &str, rego_source: &str) -> Result<()> {
+    let engine = state.engine.load();
+    engine
+        .engine_with(&format!("control/new/{}", name), rego_source)
+        .map_err(|e| match e {
+            GuardRailError::PolicyEvaluation(msg) => GuardRailError::InvalidRego(msg),
+            other => other,
+        })?;
+
+    match lint::module_imports(rego_source) {
+        Some((package, _)) if package == CONTROL_PACKAGE => {}
+        Some((package, _)) => {
+            return Err(GuardRailError::InvalidRego(format!(
+                "Control {} declares package {}; controls use {}",
+                name, package, CONTROL_PACKAGE
+            )))
+        }
+        None => return Err(GuardRailError::InvalidRego(format!("Control {} has no package", name))),
+    }
+    libraries::ensure_imports_active(&state.db, name, rego_source).await
+}
+
+/// Key of an action type in `input.activity`: its API name, e.g. `WITHDRAWAL` or
+/// `API_CALL`, rather than the `Withdrawal` stored on decision rows
+fn activity_key(stored: &str) -> String {
+    let mut key = String::with_capacity(stored.len() + 4);
+    for (i, c) in stored.char_indices() {
+        if i > 0 && c.is_ascii_uppercase() {
+            key.push('_');
+        }
+        key.push(c.to_ascii_uppercase());
+    }
+    key
+}
+
+/// Input document a control sees for one identity
+fn control_input(
+    identity: &SweptIdentity,
+    credentials: &[&CredentialRow],
+    keys: &[&KeyRow],
+    activity: &[&ActivityRow],
+    activity_days: i32,
+    now: DateTime<Utc>,
+) -> serde_json::Value {
+    let activity: serde_json::Map<String, serde_json::Value> = activity
+        .iter()
+        .map(|a| {
+            (
+                activity_key(&a.action_type),
+                serde_json::json!({
+                    "count": a.count,
+                    "denied": a.denied,
+                    "last_at": a.last_at,
+                }),
+            )
+        })
+        .collect();
+
+    serde_json::json!({
+        "identity": {
+            "id": identity.id.to_string(),
+            "type": identity.identity_type,
+            "display_name": identity.display_name,
+            "metadata": identity.metadata,
+            "organization_id": identity.organization_id,
+            "created_at": identity.created_at,
+            "credentials": credentials.iter().map(|c| serde_json::json!({
+                "type": c.credential_type,
+                "provider": c.provider,
+                "value": c.value,
+                "expires_at": c.expires_at,
+                "verified_at": c.verified_at,
+                "expired": c.expires_at.is_some_and(|at| at <= now),
+            })).collect::<Vec<_>>(),
+        },
+        "keys": keys.iter().map(|k| serde_json::json!({
+            "type": k.key_type,
+            "chain": k.chain,
+            "is_primary": k.is_primary.unwrap_or(false),
+            "verified": k.verified_at.is_some(),
+            "created_at": k.created_at,
+        })).collect::<Vec<_>>(),
+        "activity": activity,
+        "activity_days": activity_days,
+        "now": now,
+    })
+}
+
+/// Violations a compiled control finds in `input`, one per code or message
+fn violations(engine: &Engine, input: &serde_json::Value) -> Result<Vec<DenyReason>> {
+    let mut engine = engine.clone();
+    engine.set_input(input.clone().into());
+    let results = engine
+        .eval_query(format!("x = data.{}", CONTROL_PACKAGE), false)
+        .map_err(|e| GuardRailError::PolicyEvaluation(format!("Failed to evaluate control: {}", e)))?;
+
+    let package = results
+        .result
+        .iter()
+        .filter_map(|result| result.bindings.as_object().ok())
+        .filter_map(|bindings| bindings.get(&"x".into()))
+        .find_map(|value| serde_json::to_value(value).ok())
+        .unwrap_or_default();
+
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    for item in package.get("violation").and_then(|v| v.as_array()).into_iter().flatten() {
+        let reason = PolicyEngine::parse_reason(item)?;
+        if seen.insert(finding_key(&reason).to_string()) {
+            found.push(reason);
+        }
+    }
+    Ok(found)
+}
+
+/// What identifies a violation across sweeps
+fn finding_key(reason: &DenyReason) -> &str {
+    reason.code.as_deref().unwrap_or(&reason.message)
+}
+
+// ============================================================================
+// Sweeps
+// ============================================================================
+
+/// Evaluate a control against every active identity in its scope
+async fn sweep(state: &AppState, control: &Control, now: DateTime<Utc>) -> Result<(i32, Vec<(Uuid, DenyReason)>)> {
+    let engine = Arc::new(state.engine.load().engine_with(&module_name(control), &control.rego_source)?);
+    let since = now - ChronoDuration::days(control.activity_days as i64);
+
+    let mut checked = 0;
+    let mut found = Vec::new();
+    let mut after: Option<Uuid> = None;
+    loop {
+        let identities = sqlx::query_as!(
+            SweptIdentity,
+            r#"
+            SELECT id, identity_type as "identity_type: String", display_name, metadata, organization_id, created_at
+            FROM identities
+            WHERE is_active = true
+            AND (cardinality($1::identity_type[]) = 0 OR identity_type = ANY($1))
+            AND ($2::uuid IS NULL OR id > $2)
+            ORDER BY id
+            LIMIT $3
+            "#,
+            &control.identity_types as &[IdentityType],
+            after,
+            state.control_config.batch_size,
+        )
+        .fetch_all(&state.db)
+        .await?;
+        let Some(last) = identities.last() else {
+            break;
+        };
+        after = Some(last.id);
+        let ids: Vec<Uuid> = identities.iter().map(|i| i.id).collect();
+
+        let credentials = sqlx::query_as!(
+            CredentialRow,
+            r#"
+            SELECT identity_id, credential_type as "credential_type: String", provider, value, expires_at, verified_at
+            FROM credentials
+            WHERE identity_id = ANY($1)
+            "#,
+            &ids,
+        )
+        .fetch_all(&state.db)
+        .await?;
+        let keys = sqlx::query_as!(
+            KeyRow,
+            r#"
+            SELECT identity_id, key_type as "key_type: String", chain, is_primary, verified_at, created_at
+            FROM identity_keys
+            WHERE identity_id = ANY($1)
+            "#,
+            &ids,
+        )
+        .fetch_all(&state.db)
+        .await?;
+        let activity = sqlx::query_as!(
+            ActivityRow,
+            r#"
+            SELECT identity_id, action_type,
+                COUNT(*) FILTER (WHERE decision <> 'DENY') as "count!",
+                COUNT(*) FILTER (WHERE decision = 'DENY') as "denied!",
+                MAX(created_at) FILTER (WHERE decision <> 'DENY') as last_at
+            FROM policy_decisions
+            WHERE identity_id = ANY($1) AND created_at >= $2
+            GROUP BY identity_id, action_type
+            "#,
+            &ids,
+            since,
+        )
+        .fetch_all(&state.db)
+        .await?;
+
+        let mut credentials_of: HashMap<Uuid, Vec<&CredentialRow>> = HashMap::new();
+        for c in &credentials {
+            credentials_of.entry(c.identity_id).or_default().push(c);
+        }
+        let mut keys_of: HashMap<Uuid, Vec<&KeyRow>> = HashMap::new();
+        for k in &keys {
+            keys_of.entry(k.identity_id).or_default().push(k);
+        }
+        let mut activity_of: HashMap<Uuid, Vec<&ActivityRow>> = HashMap::new();
+        for a in &activity {
+            activity_of.entry(a.identity_id).or_default().push(a);
+        }
+
+        let inputs: Vec<(Uuid, serde_json::Value)> = identities
+            .iter()
+            .map(|identity| {
+                let input = control_input(
+                    identity,
+                    credentials_of.get(&identity.id).map_or(&[][..], Vec::as_slice),
+                    keys_of.get(&identity.id).map_or(&[][..], Vec::as_slice),
+                    activity_of.get(&identity.id).map_or(&[][..], Vec::as_slice),
+                    control.activity_days,
+                    now,
+                );
+                (identity.id, input)
+            })
+            .collect();
+
+        // Evaluate the batch on a blocking thread, away from the request workers
+        let engine = engine.clone();
+        let batch = tokio::task::spawn_blocking(move || -> Result<Vec<(Uuid, DenyReason)>> {
+            let mut found = Vec::new();
+            for (identity_id, input) in &inputs {
+                for reason in violations(&engine, input)? {
+                    found.push((*identity_id, reason));
+                }
+            }
+            Ok(found)
+        })
+        .await
+        .map_err(|e| GuardRailError::PolicyEvaluation(format!("Control evaluation task failed: {}", e)))??;
+        found.extend(batch);
+        checked += identities.len() as i32;
+    }
+
+    Ok((checked, found))
+}
+
+/// Open, update and resolve a control's findings to match a sweep, queue their ledger
+/// events and record the run
+async fn reconcile(
+    state: &AppState,
+    control: &Control,
+    started_at: DateTime<Utc>,
+    checked: i32,
+    found: &[(Uuid, DenyReason)],
+) -> Result<ControlRun> {
+    let mut tx = state.db.begin().await?;
+
+    // Serialise sweeps of the same control, e.g. a manual run during a scheduled one
+    sqlx::query!("SELECT id FROM controls WHERE id = $1 FOR UPDATE", control.id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| GuardRailError::NotFound(format!("Control {}", control.id)))?;
+
+    let ids: Vec<Uuid> = found.iter().map(|_| Uuid::new_v4()).collect();
+    let identity_ids: Vec<Uuid> = found.iter().map(|(identity_id, _)| *identity_id).collect();
+    let codes: Vec<Option<String>> = found.iter().map(|(_, r)| r.code.clone()).collect();
+    let messages: Vec<String> = found.iter().map(|(_, r)| r.message.clone()).collect();
+    let severities: Vec<Option<String>> = found
+        .iter()
+        .map(|(_, r)| {
+            r.severity
+                .and_then(|s| serde_json::to_value(s).ok())
+                .and_then(|s| s.as_str().map(str::to_string))
+        })
+        .collect();
+    let params: Vec<serde_json::Value> = found.iter().map(|(_, r)| r.params.clone()).collect();
+
+    // New findings have first_seen_at == last_seen_at; ones seen before keep their first_seen_at
+    let seen = sqlx::query_as!(
+        ControlFinding,
+        r#"
+        INSERT INTO control_findings (id, control_id, identity_id, code, message, severity, params, status, first_seen_at, last_seen_at)
+        SELECT f.id, $1, f.identity_id, f.code, f.message, f.severity, f.params, 'OPEN', $8, $8
+        FROM UNNEST($2::uuid[], $3::uuid[], $4::varchar[], $5::text[], $6::varchar[], $7::jsonb[])
+            AS f(id, identity_id, code, message, severity, params)
+        ON CONFLICT (control_id, identity_id, COALESCE(code, message)) WHERE status = 'OPEN'
+        DO UPDATE SET message = EXCLUDED.message, severity = EXCLUDED.severity, params = EXCLUDED.params,
+            last_seen_at = GREATEST(control_findings.last_seen_at, EXCLUDED.last_seen_at)
+        RETURNING id, control_id, identity_id, code, message, severity, params, status as "status: FindingStatus", first_seen_at, last_seen_at, resolved_at
+        "#,
+        control.id,
+        &ids,
+        &identity_ids,
+        &codes as &[Option<String>],
+        &messages,
+        &severities as &[Option<String>],
+        &params,
+        started_at,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let opened: Vec<ControlFinding> = seen
+        .into_iter()
+        .filter(|f| f.first_seen_at == f.last_seen_at)
+        .collect();
+
+    let resolved = sqlx::query_as!(
+        ControlFinding,
+        r#"
+        UPDATE control_findings
+        SET status = 'RESOLVED', resolved_at = NOW()
+        WHERE control_id = $1 AND status = 'OPEN' AND last_seen_at < $2
+        RETURNING id, control_id, identity_id, code, message, severity, params, status as "status: FindingStatus", first_seen_at, last_seen_at, resolved_at
+        "#,
+        control.id,
+        started_at,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for finding in &opened {
+        enqueue_finding_event(&mut tx, control, "CONTROL_FINDING_OPENED", finding).await?;
+    }
+    for finding in &resolved {
+        enqueue_finding_event(&mut tx, control, "CONTROL_FINDING_RESOLVED", finding).await?;
+    }
+
+    let run = record_run(
+        &mut tx,
+        control.id,
+        started_at,
+        checked,
+        found.len() as i32,
+        opened.len() as i32,
+        resolved.len() as i32,
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    state.outbox_ready.notify_one();
+
+    Ok(run)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn record_run(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    control_id: Uuid,
+    started_at: DateTime<Utc>,
+    checked: i32,
+    violations: i32,
+    opened: i32,
+    resolved: i32,
+    error: Option<String>,
+) -> Result<ControlRun> {
+    sqlx::query!("UPDATE controls SET last_run_at = $2 WHERE id = $1", control_id, started_at)
+        .execute(&mut **tx)
+        .await?;
+    let run = sqlx::query_as!(
+        ControlRun,
+        r#"
+        INSERT INTO control_runs (id, control_id, started_at, finished_at, identities_checked, violations, opened, resolved, error)
+        VALUES ($1, $2, $3, NOW(), $4, $5, $6, $7, $8)
+        RETURNING id, control_id, started_at, finished_at, identities_checked, violations, opened, resolved, error
+        "#,
+        Uuid::new_v4(),
+        control_id,
+        started_at,
+        checked,
+        violations,
+        opened,
+        resolved,
+        error,
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(run)
+}
+
+/// Queue a finding's ledger event in the transaction that opens or resolves it
+async fn enqueue_finding_event(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    control: &Control,
+    event: &str,
+    finding: &ControlFinding,
+) -> Result<()> {
+    outbox::enqueue_event(
+        tx,
+        EventType::SystemEvent,
+        SYSTEM_ACTOR_ID,
+        None,
+        serde_json::json!({
+            "event": event,
+            "control_id": control.id,
+            "control": control.name,
+            "finding_id": finding.id,
+            "identity_id": finding.identity_id,
+            "code": finding.code,
+            "message": finding.message,
+            "severity": finding.severity,
+        }),
+    )
+    .await
+}
+
+/// Sweep a control and record the run and its findings
+pub async fn run_control(state: &AppState, control: &Control) -> Result<ControlRun> {
+    let started_at = Utc::now();
+    let swept = sweep(state, control, started_at).await;
+    let outcome = match swept {
+        Ok((checked, found)) => reconcile(state, control, started_at, checked, &found).await,
+        Err(e) => Err(e),
+    };
+
+    match outcome {
+        Ok(run) => {
+            tracing::info!(
+                "Control {} checked {} identities: {} violations, {} opened, {} resolved",
+                control.name,
+                run.identities_checked,
+                run.violations,
+                run.opened,
+                run.resolved
+            );
+            Ok(run)
+        }
+        Err(e) => {
+            tracing::error!("Control {} sweep failed: {}", control.name, e);
+            let mut tx = state.db.begin().await?;
+            let run = record_run(&mut tx, control.id, started_at, 0, 0, 0, 0, Some(e.to_string())).await?;
+            tx.commit().await?;
+            state
+                .ledger
+                .record_event_logged(
+                    EventType::SystemEvent,
+                    SYSTEM_ACTOR_ID,
+                    None,
+                    serde_json::json!({
+                        "event": "CONTROL_RUN_FAILED",
+                        "control_id": control.id,
+                        "control": control.name,
+                        "run_id": run.id,
+                        "error": run.error,
+                    }),
+                )
+                .await;
+            Ok(run)
+        }
+    }
+}
+
+/// Claim the active controls that are due, moving each one's next run on by its interval
+async fn claim_due(state: &AppState, now: DateTime<Utc>) -> Result<Vec<Control>> {
+    let controls = sqlx::query_as!(
+        Control,
+        r#"
+        UPDATE controls
+        SET next_run_at = $1 + make_interval(secs => interval_secs)
+        WHERE id IN (
+            SELECT id FROM controls
+            WHERE is_active = true AND next_run_at <= $1
+            ORDER BY next_run_at
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, name, description, rego_source, identity_types as "identity_types: Vec<IdentityType>", interval_secs, activity_days, is_active, next_run_at, last_run_at, created_by, created_at, updated_at
+        "#,
+        now,
+    )
+    .fetch_all(&state.db)
+    .await?;
+    Ok(controls)
+}
+
+/// Run the sweeper, waking for the next due control, a control change on this
+/// instance, or at most every poll interval
+pub fn spawn_sweeper(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let poll_interval = state.control_config.poll_interval;
+        loop {
+            match claim_due(&state, Utc::now()).await {
+                Ok(controls) => {
+                    for control in &controls {
+                        if let Err(e) = run_control(&state, control).await {
+                            tracing::error!("Failed to record control {} run: {}", control.name, e);
+                        }
+                    }
+                }
+                Err(e) => tracing::error!("Control lookup failed: {}", e),
+            }
+
+            let next = sqlx::query_scalar!("SELECT MIN(next_run_at) FROM controls WHERE is_active = true")
+                .fetch_one(&state.db)
+                .await;
+            let wait = match next {
+                Ok(Some(at)) => (at - Utc::now()).to_std().unwrap_or_default().min(poll_interval),
+                Ok(None) => poll_interval,
+                Err(e) => {
+                    tracing::error!("Control lookup failed: {}", e);
+                    poll_interval
+                }
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = state.controls_changed.notified() => {}
+            }
+        }
+    })
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+fn validate_settings(interval_secs: i32, activity_days: i32) -> Result<()> {
+    if interval_secs <= 0 {
+        return Err(GuardRailError::Validation("interval_secs must be positive".to_string()));
+    }
+    if activity_days <= 0 {
+        return Err(GuardRailError::Validation("activity_days must be positive".to_string()));
+    }
+    Ok(())
+}
+
+async fn get_control_impl(state: &AppState, id: Uuid) -> Result<Control> {
+    sqlx::query_as!(
+        Control,
+        r#"
+        SELECT id, name, description, rego_source, identity_types as "identity_types: Vec<IdentityType>", interval_secs, activity_days, is_active, next_run_at, last_run_at, created_by, created_at, updated_at
+        FROM controls
+        WHERE id = $1
+        "#,
+        id,
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| GuardRailError::NotFound(format!("Control {}", id)))
+}
+
+async fn create_control_impl(state: &AppState, req: CreateControlRequest) -> Result<Control> {
+    if req.name.trim().is_empty() {
+        return Err(GuardRailError::Validation("Control name must not be blank".to_string()));
+    }
+    let interval_secs = req.interval_secs.unwrap_or(DEFAULT_INTERVAL_SECS);
+    let activity_days = req.activity_days.unwrap_or(DEFAULT_ACTIVITY_DAYS);
+    validate_settings(interval_secs, activity_days)?;
+    validate_source(state, &req.name, &req.rego_source).await?;
+
+    let exists = sqlx::query_scalar!(r#"SELECT EXISTS(SELECT 1 FROM controls WHERE name = $1) as "exists!""#, req.name)
+        .fetch_one(&state.db)
+        .await?;
+    if exists {
+        return Err(GuardRailError::Conflict(format!("Control {} already exists", req.name)));
+    }
+
+    // The first sweep is due straight away
+    let now = Utc::now();
+    let control = sqlx::query_as!(
+        Control,
+        r#"
+        INSERT INTO controls (id, name, description, rego_source, identity_types, interval_secs, activity_days, is_active, next_run_at, created_by, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, true, $8, $9, $8, $8)
+        RETURNING id, name, description, rego_source, identity_types as "identity_types: Vec<IdentityType>", interval_secs, activity_days, is_active, next_run_at, last_run_at, created_by, created_at, updated_at
+        "#,
+        Uuid::new_v4(),
+        req.name,
+        req.description,
+        req.rego_source,
+        &req.identity_types as &[IdentityType],
+        interval_secs,
+        activity_days,
+        now,
+        req.created_by,
+    )
+    .fetch_one(&state.db)
+    .await?;
+    state.controls_changed.notify_one();
+
+    state
+        .ledger
+        .record_event_logged(
+            EventType::SystemEvent,
+            control.created_by.unwrap_or(SYSTEM_ACTOR_ID),
+            None,
+            serde_json::json!({
+                "event": "CONTROL_CREATED",
+                "control_id": control.id,
+                "control": control.name,
+                "rego_sha256": guardrail_shared::sha256_hex(control.rego_source.as_bytes()),
+            }),
+        )
+        .await;
+
+    Ok(control)
+}
+
+async fn update_control_impl(state: &AppState, id: Uuid, req: UpdateControlRequest) -> Result<Control> {
+    let current = get_control_impl(state, id).await?;
+    validate_settings(
+        req.interval_secs.unwrap_or(current.interval_secs),
+        req.activity_days.unwrap_or(current.activity_days),
+    )?;
+    if let Some(rego_source) = &req.rego_source {
+        validate_source(state, &current.name, rego_source).await?;
+    }
+
+    // A changed control, or one switched back on, is swept straight away
+    let now = Utc::now();
+    let control = sqlx::query_as!(
+        Control,
+        r#"
+        UPDATE controls
+        SET description = COALESCE($2, description),
+            rego_source = COALESCE($3, rego_source),
+            identity_types = COALESCE($4, identity_types),
+            interval_secs = COALESCE($5, interval_secs),
+            activity_days = COALESCE($6, activity_days),
+            is_active = COALESCE($7, is_active),
+            next_run_at = $8
+        WHERE id = $1
+        RETURNING id, name, description, rego_source, identity_types as "identity_types: Vec<IdentityType>", interval_secs, activity_days, is_active, next_run_at, last_run_at, created_by, created_at, updated_at
+        "#,
+        id,
+        req.description,
+        req.rego_source,
+        req.identity_types.as_deref() as Option<&[IdentityType]>,
+        req.interval_secs,
+        req.activity_days,
+        req.is_active,
+        now,
+    )
+    .fetch_one(&state.db)
+    .await?;
+    state.controls_changed.notify_one();
+
+    state
+        .ledger
+        .record_event_logged(
+            EventType::SystemEvent,
+            SYSTEM_ACTOR_ID,
+            None,
+            serde_json::json!({
+                "event": "CONTROL_UPDATED",
+                "control_id": control.id,
+                "control": control.name,
+                "is_active": control.is_active,
+                "rego_sha256": guardrail_shared::sha256_hex(control.rego_source.as_bytes()),
+            }),
+        )
+        .await;
+
+    Ok(control)
+}
+
+async fn list_controls_impl(state: &AppState, offset: i32, limit: i32) -> Result<(Vec<Control>, i64)> {
+    let controls = sqlx::query_as!(
+        Control,
+        r#"
+        SELECT id, name, description, rego_source, identity_types as "identity_types: Vec<IdentityType>", interval_secs, activity_days, is_active, next_run_at, last_run_at, created_by, created_at, updated_at
+        FROM controls
+        ORDER BY name
+        LIMIT $1 OFFSET $2
+        "#,
+        limit as i64,
+        offset as i64,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let total = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM controls"#)
+        .fetch_one(&state.db)
+        .await?;
+
+    Ok((controls, total))
+}
+
+async fn list_control_runs_impl(state: &AppState, id: Uuid) -> Result<Vec<ControlRun>> {
+    get_control_impl(state, id).await?;
+    let runs = sqlx::query_as!(
+        ControlRun,
+        r#"
+        SELECT id, control_id, started_at, finished_at, identities_checked, violations, opened, resolved, error
+        FROM control_runs
+        WHERE control_id = $1
+        ORDER BY started_at DESC
+        LIMIT 50
+        "#,
+        id,
+    )
+    .fetch_all(&state.db)
+    .await?;
+    Ok(runs)
+}
+
+async fn list_findings_impl(
+    state: &AppState,
+    offset: i32,
+    limit: i32,
+    query: &ListFindingsQuery,
+) -> Result<(Vec<ControlFinding>, i64)> {
+    let findings = sqlx::query_as!(
+        ControlFinding,
+        r#"
+        SELECT id, control_id, identity_id, code, message, severity, params, status as "status: FindingStatus", first_seen_at, last_seen_at, resolved_at
+        FROM control_findings
+        WHERE ($3::finding_status IS NULL OR status = $3)
+        AND ($4::uuid IS NULL OR control_id = $4)
+        AND ($5::uuid IS NULL OR identity_id = $5)
+        ORDER BY last_seen_at DESC, id
+        LIMIT $1 OFFSET $2
+        "#,
+        limit as i64,
+        offset as i64,
+        query.status as Option<FindingStatus>,
+        query.control_id,
+        query.identity_id,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let total: i64 = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM control_findings
+        WHERE ($1::finding_status IS NULL OR status = $1)
+        AND ($2::uuid IS NULL OR control_id = $2)
+        AND ($3::uuid IS NULL OR identity_id = $3)
+        "#,
+        query.status as Option<FindingStatus>,
+        query.control_id,
+        query.identity_id,
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok((findings, total))
+}
+
+pub async fn create_control(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateControlRequest>,
+) -> impl IntoResponse {
+    match create_control_impl(&state, req).await {
+        Ok(control) => (StatusCode::CREATED, Json(ApiResponse::success(control))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Control>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+pub async fn list_controls(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListControlsQuery>,
+) -> impl IntoResponse {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(20).min(100);
+    let offset = (page - 1) * per_page;
+
+    match list_controls_impl(&state, offset, per_page).await {
+        Ok((controls, total)) => {
+            let response = PaginatedResponse::new(controls, total, page, per_page);
+            (StatusCode::OK, Json(ApiResponse::success(response)))
+        }
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<PaginatedResponse<Control>>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+pub async fn get_control(State(state): State<Arc<AppState>>, Path(id): Path<Uuid>) -> impl IntoResponse {
+    match get_control_impl(&state, id).await {
+        Ok(control) => (StatusCode::OK, Json(ApiResponse::success(control))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Control>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+pub async fn update_control(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateControlRequest>,
+) -> impl IntoResponse {
+    match update_control_impl(&state, id, req).await {
+        Ok(control) => (StatusCode::OK, Json(ApiResponse::success(control))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Control>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+/// Sweep a control now, outside its schedule
+pub async fn run_control_now(State(state): State<Arc<AppState>>, Path(id): Path<Uuid>) -> impl IntoResponse {
+    let outcome = match get_control_impl(&state, id).await {
+        Ok(control) => run_control(&state, &control).await,
+        Err(e) => Err(e),
+    };
+    match outcome {
+        Ok(run) => (StatusCode::OK, Json(ApiResponse::success(run))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<ControlRun>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+pub async fn list_control_runs(State(state): State<Arc<AppState>>, Path(id): Path<Uuid>) -> impl IntoResponse {
+    match list_control_runs_impl(&state, id).await {
+        Ok(runs) => (StatusCode::OK, Json(ApiResponse::success(runs))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Vec<ControlRun>>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+pub async fn list_findings(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListFindingsQuery>,
+) -> impl IntoResponse {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(20).min(100);
+    let offset = (page - 1) * per_page;
+
+    match list_findings_impl(&state, offset, per_page, &query).await {
+        Ok((findings, total)) => {
+            let response = PaginatedResponse::new(findings, total, page, per_page);
+            (StatusCode::OK, Json(ApiResponse::success(response)))
+        }
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<PaginatedResponse<ControlFinding>>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use guardrail_shared::ActionType;
+    use serde_json::json;
+
+    const KYC_CONTROL: &str = r#"
+package guardrail.control
+
+import future.keywords.contains
+import future.keywords.if
+import future.keywords.in
+
+violation contains {"code": "KYC_REQUIRED", "message": "Withdrawing without KYC level 2", "severity": "high"} if {
+    input.identity.type == "HUMAN"
+    input.activity.WITHDRAWAL.count > 0
+    not kyc_verified
+}
+
+kyc_verified if {
+    some credential in input.identity.credentials
+    credential.type == "KYC_LEVEL"
+    not credential.expired
+    credential.value.level >= 2
+}
+
+violation contains "Agent holds more than 3 signing keys" if {
+    input.identity.type == "AGENT"
+    count([key | some key in input.keys; key.type == "SIGNING_KEY"]) > 3
+}
+"#;
+
+    fn identity(identity_type: &str) -> SweptIdentity {
+        SweptIdentity {
+            id: Uuid::new_v4(),
+            identity_type: identity_type.to_string(),
+            display_name: "test".to_string(),
+            metadata: Some(json!({})),
+            organization_id: None,
+            created_at: None,
+        }
+    }
+
+    fn signing_key(identity_id: Uuid) -> KeyRow {
+        KeyRow {
+            identity_id,
+            key_type: "SIGNING_KEY".to_string(),
+            chain: None,
+            is_primary: None,
+            verified_at: None,
+            created_at: None,
+        }
+    }
+
+    #[test]
+    fn test_control_violations() {
+        let engine = PolicyEngine::new().engine_with("control/test", KYC_CONTROL).unwrap();
+        let now = Utc::now();
+
+        let human = identity("HUMAN");
+        let withdrawal = ActivityRow {
+            identity_id: human.id,
+            // As record_decision stores it
+            action_type: format!("{:?}", ActionType::Withdrawal),
+            count: 2,
+            denied: 0,
+            last_at: Some(now),
+        };
+        let kyc = |expires_at| CredentialRow {
+            identity_id: human.id,
+            credential_type: "KYC_LEVEL".to_string(),
+            provider: "test".to_string(),
+            value: json!({"level": 2}),
+            expires_at: Some(expires_at),
+            verified_at: None,
+        };
+
+        // Withdrawing with unexpired KYC passes; expired KYC is a finding
+        let valid = kyc(now + ChronoDuration::days(1));
+        let input = control_input(&human, &[&valid], &[], &[&withdrawal], 30, now);
+        assert!(violations(&engine, &input).unwrap().is_empty());
+
+        let expired = kyc(now - ChronoDuration::days(1));
+        let input = control_input(&human, &[&expired], &[], &[&withdrawal], 30, now);
+        let found = violations(&engine, &input).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].code.as_deref(), Some("KYC_REQUIRED"));
+        assert_eq!(finding_key(&found[0]), "KYC_REQUIRED");
+
+        // Without recent withdrawals there's nothing to find
+        let input = control_input(&human, &[&expired], &[], &[], 30, now);
+        assert!(violations(&engine, &input).unwrap().is_empty());
+
+        let agent = identity("AGENT");
+        let keys: Vec<KeyRow> = (0..4).map(|_| signing_key(agent.id)).collect();
+        let refs: Vec<&KeyRow> = keys.iter().collect();
+        let found = violations(&engine, &control_input(&agent, &[], &refs, &[], 30, now)).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(finding_key(&found[0]), "Agent holds more than 3 signing keys");
+        assert!(violations(&engine, &control_input(&agent, &[], &refs[..3], &[], 30, now))
+            .unwrap()
+            .is_empty());
+        for action_type in [ActionType::Withdrawal, ActionType::ApiCall, ActionType::ConfigChange] {
+            assert_eq!(
+                json!(activity_key(&format!("{:?}", action_type))),
+                serde_json::to_value(action_type).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,613 @@
+//! External data documents
+//!
+//! JSON documents uploaded under a dotted path (e.g. `sanctions.addresses`) and
+//! exposed to policies as `data.<path>`. Each upload creates a new version; at most
+//! one version per path is active. The active set is loaded into every engine on
+//! reload and identified by a revision hash, which is recorded on each decision.
+
+use crate::builtins::{self, ListIndex};
+use crate::ledger::SYSTEM_ACTOR_ID;
+use crate::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use guardrail_shared::{
+    sha256_hex, ApiResponse, DataDocument, DataDocumentWithContent, EventType, GuardRailError, Result,
+    UploadDataDocumentRequest,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Top-level names reserved for policy packages
+const RESERVED_ROOTS: &[&str] = &["guardrail"];
+
+/// The active data documents merged into one `data` tree
+#[derive(Debug)]
+pub struct DataSet {
+    pub revision: String,
+    pub document: serde_json::Value,
+    /// Array-valued documents, for `guardrail.in_list`
+    pub lists: Arc<ListIndex>,
+}
+
+// ============================================================================
+// Request/Response Types
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct ListDataQuery {
+    pub path: Option<String>,
+    pub active_only: Option<bool>,
+}
+
+/// A document version that is part of a data revision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevisionEntry {
+    pub id: Uuid,
+    pub path: String,
+    pub version: i32,
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DataRevision {
+    pub revision: String,
+    pub documents: Vec<RevisionEntry>,
+    pub created_at: DateTime<Utc>,
+}
+
+// ============================================================================
+// Paths and revisions
+// ============================================================================
+
+/// Check that a path is a dotted list of identifiers outside the reserved roots
+pub(crate) fn validate_path(path: &str) -> Result<()> {
+    let invalid = |message: &str| GuardRailError::InvalidField {
+        field: "path".to_string(),
+        message: message.to_string(),
+    };
+
+    if path.is_empty() || path.len() > 255 {
+        return Err(invalid("must be between 1 and 255 characters"));
+    }
+    for segment in path.split('.') {
+        let mut chars = segment.chars();
+        let valid = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(invalid("must be dot-separated identifiers, e.g. sanctions.addresses"));
+        }
+    }
+    if RESERVED_ROOTS.iter().any(|root| path.split('.').next() == Some(root)) {
+        return Err(invalid("is reserved for policy packages"));
+    }
+    Ok(())
+}
+
+/// Whether one path is the other or nested inside it (`limits` and `limits.btc`)
+fn paths_overlap(a: &str, b: &str) -> bool {
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    long == short || long.strip_prefix(short).is_some_and(|rest| rest.starts_with('.'))
+}
+
+/// Revision hash of a set of document versions, independent of their order
+fn revision_of(entries: &[RevisionEntry]) -> String {
+    let mut lines: Vec<String> = entries
+        .iter()
+        .map(|e| format!("{}@{}:{}", e.path, e.version, e.sha256))
+        .collect();
+    lines.sort();
+    sha256_hex(lines.join("\n").as_bytes())
+}
+
+/// Place `content` at `path` inside `root`, creating intermediate objects
+fn insert_at(root: &mut serde_json::Value, path: &str, content: serde_json::Value) -> Result<()> {
+    let segments: Vec<&str> = path.split('.').collect();
+    let (last, parents) = segments.split_last().expect("paths have at least one segment");
+
+    let mut node = root;
+    for segment in parents {
+        node = node
+            .as_object_mut()
+            .ok_or_else(|| GuardRailError::Conflict(format!("Data path {} overlaps another document", path)))?
+            .entry(segment.to_string())
+            .or_insert_with(|| serde_json::json!({}));
+    }
+
+    let parent = node
+        .as_object_mut()
+        .ok_or_else(|| GuardRailError::Conflict(format!("Data path {} overlaps another document", path)))?;
+    if parent.contains_key(*last) {
+        return Err(GuardRailError::Conflict(format!("Data path {} overlaps another document", path)));
+    }
+    parent.insert(last.to_string(), content);
+    Ok(())
+}
+
+// ============================================================================
+// Loading
+// ============================================================================
+
+/// Load the active documents as one data set and record its revision.
+///
+/// Returns `None` when no documents are active.
+pub async fn load_active(db: &PgPool) -> Result<Option<DataSet>> {
+    let rows = active_documents(db).await?;
+    if rows.is_empty() {
+        return Ok(None);
+    }
+
+    let data = merge(rows)?;
+    sqlx::query!(
+        r#"
+        INSERT INTO data_revisions (revision, documents, created_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (revision) DO NOTHING
+        "#,
+        data.set.revision,
+        serde_json::to_value(&data.entries)?,
+        Utc::now(),
+    )
+    .execute(db)
+    .await?;
+
+    Ok(Some(data.set))
+}
+
+/// The data set the active documents would form with `documents` activated over
+/// them, without recording its revision. Rejects documents whose paths overlap.
+pub(crate) async fn load_staged(db: &PgPool, documents: &[(String, serde_json::Value)]) -> Result<Option<DataSet>> {
+    let mut rows: Vec<ActiveDocument> = active_documents(db)
+        .await?
+        .into_iter()
+        .filter(|row| !documents.iter().any(|(path, _)| *path == row.path))
+        .collect();
+
+    for (path, content) in documents {
+        validate_path(path)?;
+        if let Some(other) = rows.iter().find(|row| paths_overlap(path, &row.path)) {
+            return Err(GuardRailError::Conflict(format!(
+                "Data path {} overlaps active document {}",
+                path, other.path
+            )));
+        }
+        rows.push(ActiveDocument {
+            id: Uuid::nil(),
+            path: path.clone(),
+            version: 0,
+            sha256: sha256_hex(&serde_json::to_vec(content)?),
+            content: content.clone(),
+        });
+    }
+
+    if rows.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(merge(rows)?.set))
+}
+
+/// An active document version
+struct ActiveDocument {
+    id: Uuid,
+    path: String,
+    version: i32,
+    sha256: String,
+    content: serde_json::Value,
+}
+
+/// A data set and the document versions it was merged from
+struct Merged {
+    set: DataSet,
+    entries: Vec<RevisionEntry>,
+}
+
+async fn active_documents(db: &PgPool) -> Result<Vec<ActiveDocument>> {
+    let rows = sqlx::query_as!(
+        ActiveDocument,
+        r#"
+        SELECT id, path, version, sha256, content
+        FROM data_documents
+        WHERE is_active = true
+        ORDER BY path ASC
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+fn merge(rows: Vec<ActiveDocument>) -> Result<Merged> {
+    let mut document = serde_json::json!({});
+    let mut lists = ListIndex::new();
+    let mut entries = Vec::with_capacity(rows.len());
+    for row in rows {
+        if let Some(items) = row.content.as_array() {
+            let members = items
+                .iter()
+                .map(|item| builtins::list_key(&item.clone().into()))
+                .collect::<anyhow::Result<_>>()
+                .map_err(|e| GuardRailError::Internal(e.to_string()))?;
+            lists.insert(row.path.clone(), members);
+        }
+        // Activation rejects overlapping paths, so this only skips rows written around it
+        if let Err(e) = insert_at(&mut document, &row.path, row.content) {
+            lists.remove(&row.path);
+            tracing::error!("Skipping data document {} v{}: {}", row.path, row.version, e);
+            continue;
+        }
+        entries.push(RevisionEntry {
+            id: row.id,
+            path: row.path,
+            version: row.version,
+            sha256: row.sha256,
+        });
+    }
+
+    Ok(Merged {
+        set: DataSet {
+            revision: revision_of(&entries),
+            document,
+            lists: Arc::new(lists),
+        },
+        entries,
+    })
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+pub async fn upload_document(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<UploadDataDocumentRequest>,
+) -> impl IntoResponse {
+    match upload_document_impl(&state, req).await {
+        Ok(document) => (StatusCode::CREATED, Json(ApiResponse::success(document))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<DataDocument>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+/// Store a new version of the document at `req.path`, optionally activating it
+pub(crate) async fn upload_document_impl(state: &AppState, req: UploadDataDocumentRequest) -> Result<DataDocument> {
+    validate_path(&req.path)?;
+
+    let mut tx = state.db.begin().await?;
+    lock(&mut tx).await?;
+    let document = insert_document(&mut tx, &req).await?;
+
+    if !req.activate {
+        tx.commit().await?;
+        return Ok(document);
+    }
+
+    let document = activate_in(&mut tx, document.id).await?;
+    tx.commit().await?;
+    after_activation(state, &document).await?;
+
+    Ok(document)
+}
+
+/// Uploads and activations are rare; serialise them so versions and overlap checks don't race
+pub(crate) async fn lock(tx: &mut sqlx::Transaction<'_, sqlx::Postgres>) -> Result<()> {
+    sqlx::query!("LOCK TABLE data_documents IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Store `req.content` as the next, inactive version of its path
+pub(crate) async fn insert_document(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    req: &UploadDataDocumentRequest,
+) -> Result<DataDocument> {
+    let bytes = serde_json::to_vec(&req.content)?;
+    let size_bytes = i32::try_from(bytes.len())
+        .map_err(|_| GuardRailError::Validation("Data document is too large".to_string()))?;
+
+    let document = sqlx::query_as!(
+        DataDocument,
+        r#"
+        INSERT INTO data_documents (id, path, version, description, content, sha256, size_bytes, is_active, created_by, created_at)
+        VALUES ($1, $2::varchar, (SELECT COALESCE(MAX(version), 0) + 1 FROM data_documents WHERE path = $2::varchar), $3, $4, $5, $6, false, $7, $8)
+        RETURNING id, path, version, description, sha256, size_bytes, is_active, created_by, created_at as "created_at!"
+        "#,
+        Uuid::new_v4(),
+        req.path,
+        req.description,
+        req.content,
+        sha256_hex(&bytes),
+        size_bytes,
+        req.created_by,
+        Utc::now(),
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(document)
+}
+
+pub async fn list_documents(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListDataQuery>,
+) -> impl IntoResponse {
+    match list_documents_impl(&state.db, query).await {
+        Ok(documents) => (StatusCode::OK, Json(ApiResponse::success(documents))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Vec<DataDocument>>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn list_documents_impl(db: &PgPool, query: ListDataQuery) -> Result<Vec<DataDocument>> {
+    let documents = sqlx::query_as!(
+        DataDocument,
+        r#"
+        SELECT id, path, version, description, sha256, size_bytes, is_active, created_by, created_at as "created_at!"
+        FROM data_documents
+        WHERE ($1::text IS NULL OR path = $1) AND ($2::boolean = false OR is_active = true)
+        ORDER BY path ASC, version DESC
+        "#,
+        query.path,
+        query.active_only.unwrap_or(false),
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(documents)
+}
+
+pub async fn get_document(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match get_document_impl(&state.db, id).await {
+        Ok(document) => (StatusCode::OK, Json(ApiResponse::success(document))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<DataDocumentWithContent>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn get_document_impl(db: &PgPool, id: Uuid) -> Result<DataDocumentWithContent> {
+    let row = sqlx::query!(
+        r#"
+        SELECT id, path, version, description, sha256, size_bytes, is_active, created_by, created_at as "created_at!", content
+        FROM data_documents
+        WHERE id = $1
+        "#,
+        id,
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| GuardRailError::NotFound(format!("Data document {}", id)))?;
+
+    Ok(DataDocumentWithContent {
+        document: DataDocument {
+            id: row.id,
+            path: row.path,
+            version: row.version,
+            description: row.description,
+            sha256: row.sha256,
+            size_bytes: row.size_bytes,
+            is_active: row.is_active,
+            created_by: row.created_by,
+            created_at: row.created_at,
+        },
+        content: row.content,
+    })
+}
+
+pub async fn activate_document(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match activate_document_impl(&state, id).await {
+        Ok(document) => (StatusCode::OK, Json(ApiResponse::success(document))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<DataDocument>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+pub(crate) async fn activate_document_impl(state: &AppState, id: Uuid) -> Result<DataDocument> {
+    let mut tx = state.db.begin().await?;
+    lock(&mut tx).await?;
+    let document = activate_in(&mut tx, id).await?;
+    tx.commit().await?;
+
+    after_activation(state, &document).await?;
+
+    Ok(document)
+}
+
+/// Make `id` the active version of its path, replacing the previous one
+pub(crate) async fn activate_in(tx: &mut sqlx::Transaction<'_, sqlx::Postgres>, id: Uuid) -> Result<DataDocument> {
+    let path = sqlx::query_scalar!("SELECT path FROM data_documents WHERE id = $1", id)
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or_else(|| GuardRailError::NotFound(format!("Data document {}", id)))?;
+
+    let active_paths = sqlx::query_scalar!(
+        "SELECT path FROM data_documents WHERE is_active = true AND path <> $1",
+        path,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    if let Some(other) = active_paths.iter().find(|other| paths_overlap(&path, other)) {
+        return Err(GuardRailError::Conflict(format!(
+            "Data path {} overlaps active document {}",
+            path, other
+        )));
+    }
+
+    sqlx::query!(
+        "UPDATE data_documents SET is_active = false WHERE path = $1 AND is_active = true AND id <> $2",
+        path,
+        id,
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    let document = sqlx::query_as!(
+        DataDocument,
+        r#"
+        UPDATE data_documents
+        SET is_active = true
+        WHERE id = $1
+        RETURNING id, path, version, description, sha256, size_bytes, is_active, created_by, created_at as "created_at!"
+        "#,
+        id,
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(document)
+}
+
+/// Reload the engines with the new data set and record the change in the ledger
+async fn after_activation(state: &AppState, document: &DataDocument) -> Result<()> {
+    crate::reload_policies(state).await?;
+    record_activated(state, document).await;
+    Ok(())
+}
+
+/// Record an activation in the ledger, with the data revision now in use
+pub(crate) async fn record_activated(state: &AppState, document: &DataDocument) {
+    let revision = state.engine.load().data_revision().map(str::to_string);
+    state
+        .ledger
+        .record_event_logged(
+            EventType::SystemEvent,
+            document.created_by.unwrap_or(SYSTEM_ACTOR_ID),
+            None,
+            serde_json::json!({
+                "event": "DATA_DOCUMENT_ACTIVATED",
+                "document_id": document.id,
+                "path": document.path,
+                "version": document.version,
+                "sha256": document.sha256,
+                "data_revision": revision,
+            }),
+        )
+        .await;
+}
+
+pub async fn deactivate_document(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match deactivate_document_impl(&state, id).await {
+        Ok(document) => (StatusCode::OK, Json(ApiResponse::success(document))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<DataDocument>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn deactivate_document_impl(state: &AppState, id: Uuid) -> Result<DataDocument> {
+    let document = sqlx::query_as!(
+        DataDocument,
+        r#"
+        UPDATE data_documents
+        SET is_active = false
+        WHERE id = $1
+        RETURNING id, path, version, description, sha256, size_bytes, is_active, created_by, created_at as "created_at!"
+        "#,
+        id,
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| GuardRailError::NotFound(format!("Data document {}", id)))?;
+
+    crate::reload_policies(state).await?;
+
+    Ok(document)
+}
+
+pub async fn get_revision(
+    State(state): State<Arc<AppState>>,
+    Path(revision): Path<String>,
+) -> impl IntoResponse {
+    match get_revision_impl(&state.db, &revision).await {
+        Ok(revision) => (StatusCode::OK, Json(ApiResponse::success(revision))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<DataRevision>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn get_revision_impl(db: &PgPool, revision: &str) -> Result<DataRevision> {
+    let row = sqlx::query!(
+        r#"
+        SELECT revision, documents, created_at as "created_at!"
+        FROM data_revisions
+        WHERE revision = $1
+        "#,
+        revision,
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| GuardRailError::NotFound(format!("Data revision {}", revision)))?;
+
+    Ok(DataRevision {
+        revision: row.revision,
+        documents: serde_json::from_value(row.documents)?,
+        created_at: row.created_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_path_and_overlap() {
+        assert!(validate_path("sanctions.addresses").is_ok());
+        assert!(validate_path("limits_v2").is_ok());
+        assert!(validate_path("").is_err());
+        assert!(validate_path("sanctions..addresses").is_err());
+        assert!(validate_path("2fa.providers").is_err());
+        assert!(validate_path("guardrail.limits").is_err());
+
+        assert!(paths_overlap("limits", "limits.btc"));
+        assert!(paths_overlap("limits.btc", "limits"));
+        assert!(!paths_overlap("limits", "limits_eth"));
+        assert!(!paths_overlap("limits.btc", "limits.eth"));
+    }
+
+    #[test]
+    fn test_merge_and_revision() {
+        let mut root = json!({});
+        insert_at(&mut root, "limits.btc", json!({"daily": 5})).unwrap();
+        insert_at(&mut root, "limits.eth", json!({"daily": 50})).unwrap();
+        insert_at(&mut root, "sanctions", json!(["0xabc"])).unwrap();
+        assert_eq!(root["limits"]["eth"]["daily"], json!(50));
+        assert!(insert_at(&mut root, "limits.btc", json!({})).is_err());
+        assert!(insert_at(&mut root, "sanctions.extra", json!([])).is_err());
+
+        let entry = |path: &str, version| RevisionEntry {
+            id: Uuid::nil(),
+            path: path.to_string(),
+            version,
+            sha256: "00".to_string(),
+        };
+        let a = revision_of(&[entry("a", 1), entry("b", 2)]);
+        assert_eq!(a, revision_of(&[entry("b", 2), entry("a", 1)]));
+        assert_ne!(a, revision_of(&[entry("a", 1), entry("b", 3)]));
+    }
+}
@@ -0,0 +1,271 @@
+//! Recorded policy decisions
+//!
+//! Read access to `policy_decisions`, including per-policy attributions and any
+//! explanation captured when the check ran in `explain` mode, and a filtered,
+//! newest-first decision log.
+
+use crate::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use guardrail_shared::{
+    ActionType, ApiResponse, Decision, DecisionRecord, DegradedCause, GuardRailError, Obligation, PaginatedResponse,
+    PolicyAttribution, PolicyDecision, Result,
+};
+use serde::Deserialize;
+use sqlx::types::Json as SqlJson;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Filters shared by the decision log and the decision analytics
+#[derive(Debug, Default, Deserialize)]
+pub struct DecisionFilter {
+    pub identity_id: Option<Uuid>,
+    /// Decisions this policy contributed a reason, approver or obligation to
+    pub policy_id: Option<Uuid>,
+    pub decision: Option<Decision>,
+    pub action_type: Option<ActionType>,
+    /// Deny reason code, e.g. `KYC_REQUIRED`
+    pub reason_code: Option<String>,
+    /// Inclusive lower bound on the decision time
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on the decision time
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl DecisionFilter {
+    pub fn validate(&self) -> Result<()> {
+        if let (Some(from), Some(until)) = (self.from, self.until) {
+            if from >= until {
+                return Err(GuardRailError::Validation("from must be before until".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// `action_type` as stored on the decision row
+    pub fn stored_action_type(&self) -> Option<String> {
+        self.action_type.map(|t| format!("{:?}", t))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PageQuery {
+    pub page: Option<i32>,
+    pub per_page: Option<i32>,
+}
+
+pub async fn get_decision(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match get_decision_impl(&state.db, id).await {
+        Ok(decision) => (StatusCode::OK, Json(ApiResponse::success(decision))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<PolicyDecision>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+pub(crate) async fn get_decision_impl(db: &PgPool, id: Uuid) -> Result<PolicyDecision> {
+    let row = sqlx::query!(
+        r#"
+        SELECT id, decision as "decision: Decision", reasons, reason_details, required_approvers, obligations, policy_id, policy_version, explanation, data_revision, policy_set_revision, degraded_cause, created_at as "created_at!"
+        FROM policy_decisions
+        WHERE id = $1
+        "#,
+        id,
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| GuardRailError::NotFound(format!("Decision {}", id)))?;
+
+    let contributing_policies = sqlx::query!(
+        r#"
+        SELECT a.policy_id, p.name as policy_name, a.policy_version,
+               a.reasons as "reasons!", a.required_approvers as "required_approvers!",
+               a.obligations as "obligations: SqlJson<Vec<Obligation>>"
+        FROM policy_decision_attributions a
+        JOIN policies p ON p.id = a.policy_id
+        WHERE a.decision_id = $1
+        ORDER BY p.created_at ASC
+        "#,
+        id,
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|a| PolicyAttribution {
+        policy_id: a.policy_id,
+        policy_name: a.policy_name,
+        policy_version: a.policy_version,
+        reasons: a.reasons,
+        required_approvers: a.required_approvers,
+        obligations: a.obligations.0,
+    })
+    .collect();
+
+    Ok(PolicyDecision {
+        decision_id: row.id,
+        decision: row.decision,
+        reasons: row.reasons.unwrap_or_default(),
+        reason_details: serde_json::from_value(row.reason_details)?,
+        required_approvers: row.required_approvers.unwrap_or_default(),
+        policy_id: row.policy_id,
+        policy_version: row.policy_version,
+        contributing_policies,
+        obligations: serde_json::from_value(row.obligations)?,
+        explanation: row.explanation.map(serde_json::from_value).transpose()?,
+        data_revision: row.data_revision,
+        policy_set_revision: row.policy_set_revision,
+        degraded: row.degraded_cause.is_some(),
+        degraded_cause: row.degraded_cause.as_deref().and_then(DegradedCause::parse),
+        evaluated_at: row.created_at,
+    })
+}
+
+pub async fn list_decisions(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<DecisionFilter>,
+    Query(page): Query<PageQuery>,
+) -> impl IntoResponse {
+    let per_page = page.per_page.unwrap_or(20).min(100);
+    let page = page.page.unwrap_or(1).max(1);
+    let offset = (page - 1) * per_page;
+
+    match list_decisions_impl(&state.db, &filter, offset, per_page).await {
+        Ok((decisions, total)) => {
+            let response = PaginatedResponse::new(decisions, total, page, per_page);
+            (StatusCode::OK, Json(ApiResponse::success(response)))
+        }
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<PaginatedResponse<DecisionRecord>>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn list_decisions_impl(
+    db: &PgPool,
+    filter: &DecisionFilter,
+    offset: i32,
+    limit: i32,
+) -> Result<(Vec<DecisionRecord>, i64)> {
+    filter.validate()?;
+    let action_type = filter.stored_action_type();
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT d.id, d.identity_id, d.decision as "decision: Decision", d.action_payload, d.context,
+               d.reasons, d.reason_details, d.required_approvers, d.policy_id, d.policy_version,
+               ARRAY(SELECT a.policy_id FROM policy_decision_attributions a WHERE a.decision_id = d.id) as "contributing_policy_ids!",
+               d.data_revision, d.policy_set_revision, d.degraded_cause, d.created_at as "created_at!"
+        FROM policy_decisions d
+        WHERE ($1::uuid IS NULL OR d.identity_id = $1)
+        AND ($2::uuid IS NULL OR EXISTS (
+            SELECT 1 FROM policy_decision_attributions a WHERE a.decision_id = d.id AND a.policy_id = $2
+        ))
+        AND ($3::decision IS NULL OR d.decision = $3)
+        AND ($4::text IS NULL OR d.action_type = $4)
+        AND ($5::text IS NULL OR d.reason_details @> jsonb_build_array(jsonb_build_object('code', $5::text)))
+        AND ($6::timestamptz IS NULL OR d.created_at >= $6)
+        AND ($7::timestamptz IS NULL OR d.created_at < $7)
+        ORDER BY d.created_at DESC, d.id
+        LIMIT $8 OFFSET $9
+        "#,
+        filter.identity_id,
+        filter.policy_id,
+        filter.decision as Option<Decision>,
+        action_type,
+        filter.reason_code,
+        filter.from,
+        filter.until,
+        limit as i64,
+        offset as i64,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let total: i64 = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM policy_decisions d
+        WHERE ($1::uuid IS NULL OR d.identity_id = $1)
+        AND ($2::uuid IS NULL OR EXISTS (
+            SELECT 1 FROM policy_decision_attributions a WHERE a.decision_id = d.id AND a.policy_id = $2
+        ))
+        AND ($3::decision IS NULL OR d.decision = $3)
+        AND ($4::text IS NULL OR d.action_type = $4)
+        AND ($5::text IS NULL OR d.reason_details @> jsonb_build_array(jsonb_build_object('code', $5::text)))
+        AND ($6::timestamptz IS NULL OR d.created_at >= $6)
+        AND ($7::timestamptz IS NULL OR d.created_at < $7)
+        "#,
+        filter.identity_id,
+        filter.policy_id,
+        filter.decision as Option<Decision>,
+        action_type,
+        filter.reason_code,
+        filter.from,
+        filter.until,
+    )
+    .fetch_one(db)
+    .await?;
+
+    let decisions = rows
+        .into_iter()
+        .map(|row| {
+            Ok(DecisionRecord {
+                decision_id: row.id,
+                identity_id: row.identity_id,
+                decision: row.decision,
+                action: serde_json::from_value(row.action_payload)?,
+                context: serde_json::from_value(row.context)?,
+                reasons: row.reasons.unwrap_or_default(),
+                reason_details: serde_json::from_value(row.reason_details)?,
+                required_approvers: row.required_approvers.unwrap_or_default(),
+                policy_id: row.policy_id,
+                policy_version: row.policy_version,
+                contributing_policy_ids: row.contributing_policy_ids,
+                data_revision: row.data_revision,
+                policy_set_revision: row.policy_set_revision,
+                degraded_cause: row.degraded_cause.as_deref().and_then(DegradedCause::parse),
+                evaluated_at: row.created_at,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((decisions, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decision_filter_query() {
+        let uri: axum::http::Uri =
+            "/api/v1/decisions?decision=DENY&action_type=WITHDRAWAL&reason_code=KYC_REQUIRED&from=2024-01-01T00:00:00Z&page=2"
+                .parse()
+                .unwrap();
+        let Query(filter) = Query::<DecisionFilter>::try_from_uri(&uri).unwrap();
+        assert_eq!(filter.decision, Some(Decision::Deny));
+        assert_eq!(filter.stored_action_type().as_deref(), Some("Withdrawal"));
+        assert_eq!(filter.reason_code.as_deref(), Some("KYC_REQUIRED"));
+        assert!(filter.validate().is_ok());
+        let Query(page) = Query::<PageQuery>::try_from_uri(&uri).unwrap();
+        assert_eq!(page.page, Some(2));
+
+        let backwards = DecisionFilter {
+            from: filter.from,
+            until: filter.from,
+            ..Default::default()
+        };
+        assert!(matches!(backwards.validate(), Err(GuardRailError::Validation(_))));
+    }
+}
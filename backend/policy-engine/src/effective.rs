@@ -0,0 +1,523 @@
+//! Policy effectiveness windows
+//!
+//! A policy may carry `effective_from` and `effective_until`. The scheduler sleeps
+//! until the next boundary, a schedule change on this instance, or at most
+//! `POLICY_SCHEDULE_POLL_SECS` (to pick up schedules set through other instances).
+//! It then activates or deactivates the policies whose boundary has passed,
+//! reloads the engine and records each transition in the ledger. A boundary only
+//! applies to policies last changed before it, so a manual activate or deactivate
+//! after the boundary stands.
+//!
+//! A scheduled activation goes through the same checks as a manual one: the
+//! policy's libraries must be active and its tests must pass. A policy that fails
+//! them stays inactive, is marked as changed so the boundary is not retried, and
+//! the refusal is logged and recorded in the ledger.
+//!
+//! Each reload also records the enforced policy versions as a policy set revision,
+//! which decisions store next to their data revision.
+
+use crate::ledger::SYSTEM_ACTOR_ID;
+use crate::{activate_policy_impl, ensure_activatable, get_policy_impl, libraries, reload_policies, AppState};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use guardrail_shared::{
+    sha256_hex, ApiResponse, EventType, GuardRailError, Policy, PolicyKind, PolicyTargets, Result, SchedulePolicyRequest,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+#[derive(Debug, Clone)]
+pub struct ScheduleConfig {
+    /// Longest the scheduler sleeps between looking for due boundaries
+    pub poll_interval: Duration,
+}
+
+impl ScheduleConfig {
+    pub fn from_env() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(
+                std::env::var("POLICY_SCHEDULE_POLL_SECS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(30),
+            ),
+        }
+    }
+}
+
+// ============================================================================
+// Request/Response Types
+// ============================================================================
+
+/// An enforced policy version in a policy set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicySetEntry {
+    pub policy_id: Uuid,
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PolicySet {
+    pub revision: String,
+    pub policies: Vec<PolicySetEntry>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A scheduled activation or deactivation that has been applied
+#[derive(Debug)]
+pub struct Transition {
+    pub policy_id: Uuid,
+    pub name: String,
+    pub version: String,
+    pub active: bool,
+    /// The boundary that was reached
+    pub scheduled_at: DateTime<Utc>,
+    /// Why a due activation was refused; the policy stays inactive
+    pub error: Option<String>,
+}
+
+// ============================================================================
+// Windows
+// ============================================================================
+
+/// Check a requested window; a start in the past is allowed and means "now"
+pub fn validate_window(from: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>, now: DateTime<Utc>) -> Result<()> {
+    if let (Some(from), Some(until)) = (from, until) {
+        if until <= from {
+            return Err(GuardRailError::Validation(
+                "effective_until must be after effective_from".to_string(),
+            ));
+        }
+    }
+    if until.is_some_and(|until| until <= now) {
+        return Err(GuardRailError::Validation("effective_until must be in the future".to_string()));
+    }
+    Ok(())
+}
+
+/// Activate and deactivate policies whose boundary is due
+async fn apply_due(state: &AppState, now: DateTime<Utc>) -> Result<Vec<Transition>> {
+    let mut transitions = activate_due(state, now).await?;
+
+    let deactivated = sqlx::query!(
+        r#"
+        UPDATE policies
+        SET is_active = false, updated_at = $1
+        WHERE is_active = true AND effective_until <= $1 AND updated_at < effective_until
+        RETURNING id, name, version, effective_until as "scheduled_at!"
+        "#,
+        now,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    transitions.extend(deactivated.into_iter().map(|r| Transition {
+        policy_id: r.id,
+        name: r.name,
+        version: r.version,
+        active: false,
+        scheduled_at: r.scheduled_at,
+        error: None,
+    }));
+    transitions.sort_by_key(|t| t.scheduled_at);
+    Ok(transitions)
+}
+
+/// Activate the policies whose window has started, if they pass the activation checks
+async fn activate_due(state: &AppState, now: DateTime<Utc>) -> Result<Vec<Transition>> {
+    // Libraries first, so decision policies due at the same time can import them
+    let due = sqlx::query_as!(
+        Policy,
+        r#"
+        SELECT id, name, description, version, kind as "kind: PolicyKind", rego_source, test_source, is_active as "is_active!", is_shadow, effective_from, effective_until, targets as "targets: PolicyTargets", created_by, created_at as "created_at!", updated_at as "updated_at!"
+        FROM policies
+        WHERE is_active IS NOT TRUE AND effective_from <= $1 AND (effective_until IS NULL OR effective_until > $1)
+            AND updated_at < effective_from
+        ORDER BY kind = 'DECISION', effective_from ASC
+        "#,
+        now,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut transitions = Vec::with_capacity(due.len());
+    let mut libraries_activated = false;
+    for policy in due {
+        if policy.kind == PolicyKind::Decision && libraries_activated {
+            reload_policies(state).await?;
+            libraries_activated = false;
+        }
+
+        let error = match ensure_activatable(state, &policy).await {
+            Ok(()) => None,
+            Err(e @ GuardRailError::Database(_)) => return Err(e),
+            Err(e) => Some(e.to_string()),
+        };
+
+        // A refused policy is marked as changed so the boundary no longer applies to it
+        let applied = sqlx::query_scalar!(
+            r#"
+            UPDATE policies
+            SET is_active = $2, is_shadow = is_shadow AND NOT $2,
+                shadow_until = CASE WHEN $2 AND is_shadow THEN effective_from ELSE shadow_until END, updated_at = $3
+            WHERE id = $1 AND is_active IS NOT TRUE AND updated_at < effective_from
+            RETURNING effective_from as "scheduled_at!"
+            "#,
+            policy.id,
+            error.is_none(),
+            now,
+        )
+        .fetch_optional(&state.db)
+        .await?;
+
+        // Another instance got there first
+        let Some(scheduled_at) = applied else {
+            continue;
+        };
+        libraries_activated |= policy.kind == PolicyKind::Library && error.is_none();
+        transitions.push(Transition {
+            policy_id: policy.id,
+            name: policy.name,
+            version: policy.version,
+            active: true,
+            scheduled_at,
+            error,
+        });
+    }
+
+    Ok(transitions)
+}
+
+/// Whether any policy's window starts or ends in `(since, now]`
+async fn boundary_passed(db: &PgPool, since: DateTime<Utc>, now: DateTime<Utc>) -> Result<bool> {
+    let passed = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM policies
+            WHERE (effective_from > $1 AND effective_from <= $2) OR (effective_until > $1 AND effective_until <= $2)
+        ) as "passed!"
+        "#,
+        since,
+        now,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(passed)
+}
+
+/// The next window start or end after `after`
+async fn next_boundary(db: &PgPool, after: DateTime<Utc>) -> Result<Option<DateTime<Utc>>> {
+    let next = sqlx::query_scalar!(
+        r#"
+        SELECT MIN(boundary) FROM (
+            SELECT effective_from as boundary FROM policies WHERE effective_from > $1
+            UNION ALL
+            SELECT effective_until FROM policies WHERE effective_until > $1
+        ) b
+        "#,
+        after,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(next)
+}
+
+/// Apply due transitions and reload when the enforced set may have changed.
+///
+/// With `since`, nothing happens unless a boundary passed after it; another
+/// instance may already have applied the transition, so this one still reloads.
+pub async fn tick(state: &AppState, since: Option<DateTime<Utc>>, now: DateTime<Utc>) -> Result<Vec<Transition>> {
+    if let Some(since) = since {
+        if !boundary_passed(&state.db, since, now).await? {
+            return Ok(Vec::new());
+        }
+    }
+
+    let transitions = apply_due(state, now).await?;
+    if since.is_none() && transitions.is_empty() {
+        return Ok(transitions);
+    }
+    reload_policies(state).await?;
+
+    let revision = state.engine.load().policy_set_revision().map(str::to_string);
+    for transition in &transitions {
+        let event = match (transition.active, &transition.error) {
+            (_, Some(error)) => {
+                tracing::warn!(
+                    "Scheduled activation of policy {} v{} (due {}) refused: {}",
+                    transition.name,
+                    transition.version,
+                    transition.scheduled_at,
+                    error
+                );
+                "POLICY_ACTIVATION_FAILED"
+            }
+            (true, None) => "POLICY_ACTIVATED",
+            (false, None) => "POLICY_DEACTIVATED",
+        };
+        if transition.error.is_none() {
+            tracing::info!(
+                "Scheduled {} of policy {} v{} (due {})",
+                if transition.active { "activation" } else { "deactivation" },
+                transition.name,
+                transition.version,
+                transition.scheduled_at
+            );
+        }
+        state
+            .ledger
+            .record_event_logged(
+                EventType::SystemEvent,
+                SYSTEM_ACTOR_ID,
+                None,
+                serde_json::json!({
+                    "event": event,
+                    "policy_id": transition.policy_id,
+                    "name": transition.name,
+                    "version": transition.version,
+                    "scheduled_at": transition.scheduled_at,
+                    "policy_set_revision": revision,
+                    "error": transition.error,
+                }),
+            )
+            .await;
+    }
+
+    Ok(transitions)
+}
+
+/// Run the scheduler, starting from boundaries after `since`
+pub fn spawn_scheduler(state: Arc<AppState>, since: DateTime<Utc>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let poll_interval = state.schedule_config.poll_interval;
+        let mut since = since;
+        loop {
+            let wait = match next_boundary(&state.db, since).await {
+                Ok(Some(at)) => (at - Utc::now()).to_std().unwrap_or_default().min(poll_interval),
+                Ok(None) => poll_interval,
+                Err(e) => {
+                    tracing::error!("Policy schedule lookup failed: {}", e);
+                    poll_interval
+                }
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = state.schedule_changed.notified() => {}
+            }
+
+            let now = Utc::now();
+            match tick(&state, Some(since), now).await {
+                Ok(_) => since = now,
+                // Keep `since` so the missed boundaries are retried
+                Err(e) => tracing::error!("Policy schedule run failed: {}", e),
+            }
+        }
+    })
+}
+
+// ============================================================================
+// Policy sets
+// ============================================================================
+
+/// Revision identifying a set of enforced policy versions, independent of order
+pub fn policy_set_revision(entries: &[PolicySetEntry]) -> String {
+    let mut lines: Vec<String> = entries
+        .iter()
+        .map(|e| format!("{}@{}", e.policy_id, e.version))
+        .collect();
+    lines.sort();
+    sha256_hex(lines.join("\n").as_bytes())
+}
+
+/// Store the policy set behind a revision and return the revision
+pub async fn record_policy_set(db: &PgPool, entries: &[PolicySetEntry]) -> Result<String> {
+    let revision = policy_set_revision(entries);
+    sqlx::query!(
+        r#"
+        INSERT INTO policy_set_revisions (revision, policies, created_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (revision) DO NOTHING
+        "#,
+        revision,
+        serde_json::to_value(entries)?,
+        Utc::now(),
+    )
+    .execute(db)
+    .await?;
+
+    Ok(revision)
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+pub async fn schedule_policy(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<SchedulePolicyRequest>,
+) -> impl IntoResponse {
+    match schedule_policy_impl(&state, id, req).await {
+        Ok(policy) => (StatusCode::OK, Json(ApiResponse::success(policy))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Policy>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn schedule_policy_impl(state: &AppState, id: Uuid, req: SchedulePolicyRequest) -> Result<Policy> {
+    let now = Utc::now();
+    validate_window(req.effective_from, req.effective_until, now)?;
+    let current = get_policy_impl(&state.db, id).await?;
+    libraries::validate(current.kind, &current.targets, false, req.effective_from, req.effective_until)?;
+
+    // A window starting later takes the policy out of force until then
+    let active = req.effective_from.map_or(current.is_active, |from| from <= now);
+    if active != current.is_active {
+        activate_policy_impl(state, id, active).await?;
+    }
+
+    let policy = sqlx::query_as!(
+        Policy,
+        r#"
+        UPDATE policies
+        SET effective_from = $2, effective_until = $3, updated_at = $4
+        WHERE id = $1
+        RETURNING id, name, description, version, kind as "kind: PolicyKind", rego_source, test_source, is_active as "is_active!", is_shadow, effective_from, effective_until, targets as "targets: PolicyTargets", created_by, created_at as "created_at!", updated_at as "updated_at!"
+        "#,
+        id,
+        req.effective_from,
+        req.effective_until,
+        now,
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| GuardRailError::PolicyNotFound(id.to_string()))?;
+
+    state
+        .ledger
+        .record_event_logged(
+            EventType::SystemEvent,
+            req.created_by.unwrap_or(SYSTEM_ACTOR_ID),
+            None,
+            serde_json::json!({
+                "event": "POLICY_SCHEDULED",
+                "policy_id": policy.id,
+                "name": policy.name,
+                "version": policy.version,
+                "effective_from": policy.effective_from,
+                "effective_until": policy.effective_until,
+            }),
+        )
+        .await;
+
+    state.schedule_changed.notify_one();
+    Ok(policy)
+}
+
+pub async fn get_policy_set(
+    State(state): State<Arc<AppState>>,
+    Path(revision): Path<String>,
+) -> impl IntoResponse {
+    match get_policy_set_impl(&state.db, &revision).await {
+        Ok(set) => (StatusCode::OK, Json(ApiResponse::success(set))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<PolicySet>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn get_policy_set_impl(db: &PgPool, revision: &str) -> Result<PolicySet> {
+    let row = sqlx::query!(
+        r#"
+        SELECT revision, policies, created_at as "created_at!"
+        FROM policy_set_revisions
+        WHERE revision = $1
+        "#,
+        revision,
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| GuardRailError::NotFound(format!("Policy set revision {}", revision)))?;
+
+    Ok(PolicySet {
+        revision: row.revision,
+        policies: serde_json::from_value(row.policies)?,
+        created_at: row.created_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_window() {
+        let now = Utc::now();
+        let hour = chrono::Duration::hours(1);
+        assert!(validate_window(None, None, now).is_ok());
+        assert!(validate_window(Some(now - hour), None, now).is_ok());
+        assert!(validate_window(Some(now + hour), Some(now + hour * 2), now).is_ok());
+        assert!(validate_window(Some(now + hour), Some(now + hour), now).is_err());
+        assert!(validate_window(None, Some(now - hour), now).is_err());
+    }
+
+    #[test]
+    fn test_policy_set_revision() {
+        let entry = |id: u128, version: &str| PolicySetEntry {
+            policy_id: Uuid::from_u128(id),
+            name: format!("p{}", id),
+            version: version.to_string(),
+        };
+        let a = policy_set_revision(&[entry(1, "1.0.0"), entry(2, "1.0.0")]);
+        assert_eq!(a, policy_set_revision(&[entry(2, "1.0.0"), entry(1, "1.0.0")]));
+        assert_ne!(a, policy_set_revision(&[entry(1, "1.0.1"), entry(2, "1.0.0")]));
+        assert_ne!(a, policy_set_revision(&[entry(1, "1.0.0")]));
+    }
+
+    #[test]
+    fn test_scheduled_activation_requires_passing_tests() {
+        let now = Utc::now();
+        let policy = Policy {
+            id: Uuid::new_v4(),
+            name: "scheduled".to_string(),
+            description: None,
+            version: "1.0.0".to_string(),
+            kind: PolicyKind::Decision,
+            rego_source: "package guardrail.scheduled\n\nallow := true\n".to_string(),
+            test_source: Some(
+                "package guardrail.scheduled_test\n\nimport data.guardrail.scheduled\n\ntest_denies { not scheduled.allow }\n"
+                    .to_string(),
+            ),
+            is_active: false,
+            is_shadow: false,
+            effective_from: Some(now),
+            effective_until: None,
+            targets: PolicyTargets::default(),
+            created_by: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        let report = crate::activation_tests(&crate::PolicyEngine::new(), &policy).unwrap();
+        assert!(!report.passed);
+        assert!(crate::rego_tests::ensure_passed(&report).is_err());
+    }
+}
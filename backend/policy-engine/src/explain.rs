@@ -0,0 +1,258 @@
+//! Decision explanations
+//!
+//! Builds a [`DecisionExplanation`] from a regorus coverage report: which lines of
+//! each policy were evaluated, which `input` paths those lines read (and their
+//! values), and the rules in `data.guardrail` that produced a value.
+
+use guardrail_shared::{DecisionExplanation, InputRead, PolicyTrace, RuleEvaluation, RuleLocation, TracedLine};
+use uuid::Uuid;
+
+/// A loaded policy as seen by the coverage report
+pub struct TracedPolicy<'a> {
+    pub id: Uuid,
+    pub name: &'a str,
+    /// Module path passed to `Engine::add_policy`
+    pub module: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+fn render_path(segments: &[Segment]) -> String {
+    let mut path = "input".to_string();
+    for segment in segments {
+        match segment {
+            Segment::Key(key) if is_identifier(key) => {
+                path.push('.');
+                path.push_str(key);
+            }
+            Segment::Key(key) => path.push_str(&format!("[{:?}]", key)),
+            Segment::Index(i) => path.push_str(&format!("[{}]", i)),
+        }
+    }
+    path
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Parse the reference segments following `input`, stopping at the first
+/// segment that is not a literal (e.g. `input.credentials[i]`).
+fn parse_segments(chars: &[char], mut pos: usize) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    while pos < chars.len() {
+        match chars[pos] {
+            '.' => {
+                let start = pos + 1;
+                let mut end = start;
+                while end < chars.len() && is_ident_char(chars[end]) {
+                    end += 1;
+                }
+                if end == start {
+                    break;
+                }
+                segments.push(Segment::Key(chars[start..end].iter().collect()));
+                pos = end;
+            }
+            '[' => {
+                let Some(close) = chars[pos..].iter().position(|&c| c == ']') else {
+                    break;
+                };
+                let inner: String = chars[pos + 1..pos + close].iter().collect();
+                let inner = inner.trim();
+                if let Some(key) = inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+                    segments.push(Segment::Key(key.to_string()));
+                } else if let Ok(index) = inner.parse::<usize>() {
+                    segments.push(Segment::Index(index));
+                } else {
+                    break;
+                }
+                pos += close + 1;
+            }
+            _ => break,
+        }
+    }
+    segments
+}
+
+/// Find the `input` references on a line of Rego, ignoring strings and comments
+fn input_references(line: &str) -> Vec<Vec<Segment>> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut refs = Vec::new();
+    let mut in_string = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if in_string {
+            if c == '\\' {
+                i += 1;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == '#' {
+            break;
+        } else if chars[i..].starts_with(&['i', 'n', 'p', 'u', 't'])
+            && (i == 0 || !(is_ident_char(chars[i - 1]) || chars[i - 1] == '.'))
+            && chars.get(i + 5).is_none_or(|&c| !is_ident_char(c))
+        {
+            let segments = parse_segments(&chars, i + 5);
+            if !segments.is_empty() {
+                refs.push(segments);
+            }
+            i += 5;
+            continue;
+        }
+        i += 1;
+    }
+
+    refs
+}
+
+fn resolve<'a>(input: &'a serde_json::Value, segments: &[Segment]) -> Option<&'a serde_json::Value> {
+    segments.iter().try_fold(input, |value, segment| match segment {
+        Segment::Key(key) => value.get(key),
+        Segment::Index(i) => value.get(i),
+    })
+}
+
+/// Whether a rule value counts as having "fired"
+fn is_truthy(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null | serde_json::Value::Bool(false) => false,
+        serde_json::Value::Array(a) => !a.is_empty(),
+        serde_json::Value::Object(o) => !o.is_empty(),
+        _ => true,
+    }
+}
+
+/// Whether `line` starts a definition of `rule` (`rule[...]`, `rule = ...`, `rule { ...`)
+fn defines_rule(line: &str, rule: &str) -> bool {
+    let line = line.trim_start();
+    let line = line.strip_prefix("default ").unwrap_or(line);
+    line.strip_prefix(rule)
+        .and_then(|rest| rest.chars().next())
+        .is_some_and(|c| !is_ident_char(c) && c != '.')
+}
+
+/// Assemble an explanation for one evaluation
+pub fn build_explanation(
+    package: &serde_json::Value,
+    report: &regorus::coverage::Report,
+    policies: &[TracedPolicy<'_>],
+    input: &serde_json::Value,
+    prints: Vec<String>,
+) -> DecisionExplanation {
+    let mut traces = Vec::new();
+    // (policy id, line number, line text) for every evaluated line
+    let mut evaluated = Vec::new();
+
+    for policy in policies {
+        let Some(file) = report.files.iter().find(|f| f.path == policy.module) else {
+            continue;
+        };
+        let lines: Vec<&str> = file.code.lines().collect();
+
+        let mut evaluated_lines = Vec::new();
+        let mut input_reads: Vec<InputRead> = Vec::new();
+        for &line in &file.covered {
+            let Some(text) = lines.get((line as usize).saturating_sub(1)) else {
+                continue;
+            };
+            for segments in input_references(text) {
+                let path = render_path(&segments);
+                if !input_reads.iter().any(|r| r.path == path) {
+                    input_reads.push(InputRead {
+                        path,
+                        value: resolve(input, &segments).cloned(),
+                    });
+                }
+            }
+            evaluated.push((policy.id, line, *text));
+            evaluated_lines.push(TracedLine {
+                line,
+                text: text.trim().to_string(),
+            });
+        }
+
+        traces.push(PolicyTrace {
+            policy_id: policy.id,
+            policy_name: policy.name.to_string(),
+            evaluated_lines,
+            input_reads,
+        });
+    }
+
+    let rules = package
+        .as_object()
+        .map(|obj| {
+            obj.iter()
+                .filter(|(_, value)| is_truthy(value))
+                .map(|(rule, value)| RuleEvaluation {
+                    rule: rule.clone(),
+                    value: value.clone(),
+                    locations: evaluated
+                        .iter()
+                        .filter(|(_, _, text)| defines_rule(text, rule))
+                        .map(|&(policy_id, line, _)| RuleLocation { policy_id, line })
+                        .collect(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    DecisionExplanation {
+        rules,
+        policies: traces,
+        prints,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn paths(line: &str) -> Vec<String> {
+        input_references(line).iter().map(|s| render_path(s)).collect()
+    }
+
+    #[test]
+    fn test_input_references() {
+        assert_eq!(
+            paths(r#"to_number(input.action.amount) > data.limits["max"]"#),
+            vec!["input.action.amount"]
+        );
+        assert_eq!(
+            paths(r#"input.identity.credentials[0].type == input["context"].ip_address"#),
+            vec!["input.identity.credentials[0].type", "input.context.ip_address"]
+        );
+        // Non-literal index stops the path; strings and comments are ignored
+        assert_eq!(paths("c := input.identity.credentials[i]"), vec!["input.identity.credentials"]);
+        assert!(paths(r#"deny["input.amount too high"] # input.amount"#).is_empty());
+        assert!(paths("my_input.amount").is_empty());
+    }
+
+    #[test]
+    fn test_resolve_and_rule_definitions() {
+        let input = json!({"identity": {"credentials": [{"type": "KYC"}]}});
+        let refs = input_references("input.identity.credentials[0].type");
+        assert_eq!(resolve(&input, &refs[0]), Some(&json!("KYC")));
+
+        assert!(defines_rule(r#"  deny["too big"] {"#, "deny"));
+        assert!(defines_rule("default decision = \"ALLOW\"", "decision"));
+        assert!(!defines_rule("deny_all { true }", "deny"));
+    }
+}
@@ -0,0 +1,385 @@
+//! Failure handling for checks
+//!
+//! A check that cannot be decided normally gets the fallback decision configured
+//! for its action type instead of an error. Checks fall back when the policies
+//! fail to evaluate, when evaluation runs past the deadline, or when the database
+//! fails. Client errors such as an unknown identity are still returned as errors.
+//!
+//! Fallback decisions carry `degraded: true` and the cause. They are stored like
+//! any other decision when the database is reachable, so `REQUIRE_APPROVAL`
+//! fallbacks open approvals. They are also counted per cause and decision for
+//! `GET /api/v1/decisions/fallbacks`.
+//!
+//! Evaluation runs on a blocking thread. regorus cannot be interrupted, so a
+//! check that times out returns at the deadline while its evaluation finishes in
+//! the background.
+
+use crate::{record_decision, AppState, PolicyEngine, PolicyEvalResult};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use guardrail_shared::{
+    ActionType, ApiResponse, CheckActionRequest, Decision, DegradedCause, DenyReason, GuardRailError, PolicyDecision,
+    ReasonSeverity, Result,
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Code on the deny reason of a `DENY` fallback
+pub const UNAVAILABLE_CODE: &str = "POLICY_UNAVAILABLE";
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+#[derive(Debug, Clone)]
+pub struct FallbackConfig {
+    /// Decision for action types without an override
+    pub default_decision: Decision,
+    /// Per-action-type overrides
+    pub by_action_type: HashMap<ActionType, Decision>,
+    /// Roles asked to approve `REQUIRE_APPROVAL` fallbacks
+    pub approvers: Vec<String>,
+    /// Longest a single policy evaluation may run
+    pub deadline: Duration,
+}
+
+impl FallbackConfig {
+    pub fn from_env() -> Self {
+        Self {
+            default_decision: std::env::var("FAILURE_MODE")
+                .ok()
+                .and_then(|s| parse_decision(&s))
+                .unwrap_or(Decision::Deny),
+            by_action_type: std::env::var("FAILURE_MODES")
+                .map(|s| parse_modes(&s))
+                .unwrap_or_default(),
+            approvers: std::env::var("FAILURE_APPROVERS")
+                .unwrap_or_else(|_| "risk_officer".to_string())
+                .split(',')
+                .map(|role| role.trim().to_string())
+                .filter(|role| !role.is_empty())
+                .collect(),
+            deadline: Duration::from_millis(
+                std::env::var("EVALUATION_DEADLINE_MS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .filter(|ms| *ms > 0)
+                    .unwrap_or(500),
+            ),
+        }
+    }
+
+    /// Fallback decision for an action type
+    pub fn decision_for(&self, action_type: ActionType) -> Decision {
+        self.by_action_type
+            .get(&action_type)
+            .copied()
+            .unwrap_or(self.default_decision)
+    }
+}
+
+/// Parse `DENY`, `ALLOW` or `REQUIRE_APPROVAL`, ignoring case
+fn parse_decision(s: &str) -> Option<Decision> {
+    serde_json::from_value(serde_json::Value::String(s.trim().to_uppercase())).ok()
+}
+
+/// Parse a failure mode spec like `TRADE=ALLOW,WITHDRAWAL=REQUIRE_APPROVAL`
+pub fn parse_modes(spec: &str) -> HashMap<ActionType, Decision> {
+    spec.split(',')
+        .filter_map(|entry| {
+            let (action_type, decision) = entry.split_once('=')?;
+            let action_type =
+                serde_json::from_value(serde_json::Value::String(action_type.trim().to_uppercase())).ok()?;
+            Some((action_type, parse_decision(decision)?))
+        })
+        .collect()
+}
+
+// ============================================================================
+// Counters
+// ============================================================================
+
+/// Fallback decisions made by this process
+#[derive(Debug, Default)]
+pub struct FallbackCounters {
+    counts: Mutex<BTreeMap<(DegradedCause, Decision), u64>>,
+    /// Fallbacks that could not be stored in `policy_decisions`
+    unrecorded: AtomicU64,
+    since: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FallbackCount {
+    pub cause: DegradedCause,
+    pub decision: Decision,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FallbackStats {
+    /// When this process started counting
+    pub since: DateTime<Utc>,
+    pub total: u64,
+    pub unrecorded: u64,
+    pub counts: Vec<FallbackCount>,
+}
+
+impl FallbackCounters {
+    pub fn new() -> Self {
+        Self {
+            since: Utc::now(),
+            ..Self::default()
+        }
+    }
+
+    fn record(&self, cause: DegradedCause, decision: Decision, recorded: bool) {
+        *self
+            .counts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry((cause, decision))
+            .or_default() += 1;
+        if !recorded {
+            self.unrecorded.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> FallbackStats {
+        let counts: Vec<FallbackCount> = self
+            .counts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(&(cause, decision), &count)| FallbackCount { cause, decision, count })
+            .collect();
+        FallbackStats {
+            since: self.since,
+            total: counts.iter().map(|c| c.count).sum(),
+            unrecorded: self.unrecorded.load(Ordering::Relaxed),
+            counts,
+        }
+    }
+}
+
+// ============================================================================
+// Evaluation and fallback
+// ============================================================================
+
+/// Evaluate on a blocking thread, giving up once `deadline` has passed
+pub async fn evaluate(
+    engine: Arc<PolicyEngine>,
+    input: serde_json::Value,
+    explain: bool,
+    deadline: Duration,
+) -> Result<PolicyEvalResult> {
+    let task = tokio::task::spawn_blocking(move || {
+        if explain {
+            engine.explain(&input)
+        } else {
+            engine.evaluate(&input)
+        }
+    });
+
+    match tokio::time::timeout(deadline, task).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(GuardRailError::PolicyEvaluation(format!("Evaluation task failed: {}", e))),
+        Err(_) => Err(GuardRailError::EvaluationDeadlineExceeded(deadline.as_millis() as u64)),
+    }
+}
+
+/// Whether a failed check falls back, and why
+pub fn cause(e: &GuardRailError) -> Option<DegradedCause> {
+    match e {
+        GuardRailError::PolicyEvaluation(_) => Some(DegradedCause::EvaluationError),
+        GuardRailError::EvaluationDeadlineExceeded(_) => Some(DegradedCause::DeadlineExceeded),
+        // Only a database that can't be reached; query and data errors are real failures
+        GuardRailError::Database(
+            sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed,
+        ) => Some(DegradedCause::DatabaseUnavailable),
+        _ => None,
+    }
+}
+
+/// The evaluation result standing in for policies that could not be evaluated
+pub fn fallback_result(config: &FallbackConfig, action_type: ActionType, cause: DegradedCause) -> PolicyEvalResult {
+    let decision = config.decision_for(action_type);
+    let mut result = PolicyEvalResult {
+        decision,
+        reasons: Vec::new(),
+        reason_details: Vec::new(),
+        required_approvers: Vec::new(),
+        obligations: Vec::new(),
+        policies: Vec::new(),
+        evaluated_policies: Vec::new(),
+        explanation: None,
+        data_revision: None,
+        policy_set_revision: None,
+    };
+
+    match decision {
+        Decision::Deny => {
+            let message = "Policy evaluation unavailable".to_string();
+            result.reasons.push(message.clone());
+            result.reason_details.push(DenyReason {
+                code: Some(UNAVAILABLE_CODE.to_string()),
+                message,
+                severity: Some(ReasonSeverity::High),
+                params: serde_json::json!({ "cause": cause }),
+            });
+        }
+        Decision::RequireApproval => result.required_approvers = config.approvers.clone(),
+        Decision::Allow => {}
+    }
+    result
+}
+
+/// Decide a check that failed with `error` using its configured fallback
+pub async fn fall_back(
+    state: &AppState,
+    req: &CheckActionRequest,
+    cause: DegradedCause,
+    error: &GuardRailError,
+) -> PolicyDecision {
+    let eval_result = fallback_result(&state.fallback_config, req.action.action_type, cause);
+    let decision_id = Uuid::new_v4();
+    let now = Utc::now();
+
+    // A database that just failed is unlikely to take the fallback either, and
+    // retrying would hold the response until the pool gives up
+    let recorded = cause != DegradedCause::DatabaseUnavailable
+        && match record(state, decision_id, req, &eval_result, cause, now).await {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::error!("Failed to record fallback decision {}: {}", decision_id, e);
+                false
+            }
+        };
+    warn(req, &eval_result, cause, error);
+    degraded_response(state, decision_id, eval_result, cause, recorded, now)
+}
+
+pub fn warn(req: &CheckActionRequest, eval_result: &PolicyEvalResult, cause: DegradedCause, error: &GuardRailError) {
+    tracing::warn!(
+        "Check for identity {} fell back to {:?} ({}): {}",
+        req.identity_id,
+        eval_result.decision,
+        cause.as_str(),
+        error
+    );
+}
+
+/// Response for a fallback decision, counted as it is handed out
+pub fn degraded_response(
+    state: &AppState,
+    decision_id: Uuid,
+    eval_result: PolicyEvalResult,
+    cause: DegradedCause,
+    recorded: bool,
+    now: DateTime<Utc>,
+) -> PolicyDecision {
+    state.fallback_counters.record(cause, eval_result.decision, recorded);
+    let mut decision = crate::decision_response(decision_id, eval_result, now);
+    decision.degraded = true;
+    decision.degraded_cause = Some(cause);
+    decision
+}
+
+async fn record(
+    state: &AppState,
+    decision_id: Uuid,
+    req: &CheckActionRequest,
+    eval_result: &PolicyEvalResult,
+    cause: DegradedCause,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    let mut tx = state.db.begin().await?;
+    record_decision(&mut tx, state, decision_id, req, eval_result, Some(cause), now).await?;
+    tx.commit().await?;
+    state.outbox_ready.notify_one();
+    Ok(())
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+pub async fn fallback_stats(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (StatusCode::OK, Json(ApiResponse::success(state.fallback_counters.stats())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_modes() {
+        let modes = parse_modes("trade=allow, WITHDRAWAL=require_approval,bogus=DENY,SWAP=maybe");
+        assert_eq!(modes.len(), 2);
+        assert_eq!(modes[&ActionType::Trade], Decision::Allow);
+
+        let config = FallbackConfig {
+            default_decision: Decision::Deny,
+            by_action_type: modes,
+            approvers: vec!["risk_officer".to_string()],
+            deadline: Duration::from_millis(500),
+        };
+        assert_eq!(config.decision_for(ActionType::Swap), Decision::Deny);
+
+        let denied = fallback_result(&config, ActionType::Transfer, DegradedCause::DeadlineExceeded);
+        assert_eq!(denied.reason_details[0].code.as_deref(), Some(UNAVAILABLE_CODE));
+        assert_eq!(denied.reason_details[0].params["cause"], "DEADLINE_EXCEEDED");
+        let held = fallback_result(&config, ActionType::Withdrawal, DegradedCause::EvaluationError);
+        assert_eq!(held.decision, Decision::RequireApproval);
+        assert_eq!(held.required_approvers, vec!["risk_officer"]);
+        assert!(fallback_result(&config, ActionType::Trade, DegradedCause::EvaluationError).reasons.is_empty());
+
+        assert_eq!(cause(&GuardRailError::IdentityNotFound("x".into())), None);
+        assert_eq!(cause(&GuardRailError::Database(sqlx::Error::RowNotFound)), None);
+        assert_eq!(
+            cause(&GuardRailError::Database(sqlx::Error::PoolTimedOut)),
+            Some(DegradedCause::DatabaseUnavailable)
+        );
+        assert_eq!(
+            cause(&GuardRailError::EvaluationDeadlineExceeded(500)),
+            Some(DegradedCause::DeadlineExceeded)
+        );
+
+        let counters = FallbackCounters::new();
+        counters.record(DegradedCause::DatabaseUnavailable, Decision::Deny, false);
+        counters.record(DegradedCause::DatabaseUnavailable, Decision::Deny, false);
+        counters.record(DegradedCause::EvaluationError, Decision::Allow, true);
+        let stats = counters.stats();
+        assert_eq!((stats.total, stats.unrecorded, stats.counts.len()), (3, 2, 2));
+    }
+
+    #[tokio::test]
+    async fn test_evaluation_deadline() {
+        let mut engine = PolicyEngine::new();
+        engine
+            .load_policy(
+                Uuid::new_v4(),
+                "slow",
+                "1.0.0",
+                r#"
+                package guardrail
+                import future.keywords.in
+                deny[msg] {
+                    count([x | some x in numbers.range(1, 30000); x % 7 == 0]) > 0
+                    msg := "slow"
+                }
+                "#,
+            )
+            .unwrap();
+        let engine = Arc::new(engine);
+        let input = serde_json::json!({});
+
+        let result = evaluate(engine.clone(), input.clone(), false, Duration::from_millis(1)).await;
+        assert!(matches!(result, Err(GuardRailError::EvaluationDeadlineExceeded(1))));
+        let result = evaluate(engine, input, false, Duration::from_secs(60)).await.unwrap();
+        assert_eq!(result.decision, Decision::Deny);
+    }
+}
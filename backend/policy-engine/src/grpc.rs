@@ -0,0 +1,484 @@
+//! gRPC check service
+//!
+//! Serves `guardrail.v1.PolicyCheck` (see `proto/guardrail/v1/check.proto`) on
+//! `GRPC_PORT`, next to the HTTP API, so order flow can check actions without
+//! going through the gateway proxy. Checks run through the same code as
+//! `POST /api/v1/check` and `POST /api/v1/check/batch`.
+//!
+//! Callers authenticate with `x-api-key` or `authorization: Bearer <jwt>`
+//! metadata, checked as the gateway checks them. A stream is authenticated once,
+//! when it opens, and its checks are decided one at a time in arrival order.
+
+use crate::batch::{api_error, check_batch_impl};
+use crate::{check_action_impl, AppState};
+use guardrail_shared::{
+    ActionType, BatchCheckRequest, BatchCheckResponse, BatchCheckResult, CheckActionRequest, Decision,
+    DegradedCause, DenyReason, GuardRailError, Obligation, PolicyAttribution, PolicyDecision, ReasonSeverity, Result,
+};
+use proto::policy_check_server::{PolicyCheck, PolicyCheckServer};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::{Request, Response, Status, Streaming};
+use uuid::Uuid;
+
+/// Stream results buffered for a slow reader before checks pause
+const STREAM_BUFFER: usize = 32;
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+#[derive(Debug, Clone)]
+pub struct GrpcConfig {
+    pub addr: SocketAddr,
+    /// Secret for bearer tokens issued by the gateway; without it only API keys are accepted
+    pub jwt_secret: Option<String>,
+}
+
+impl GrpcConfig {
+    pub fn from_env() -> Self {
+        let port = std::env::var("GRPC_PORT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(50051);
+        Self {
+            addr: SocketAddr::from(([0, 0, 0, 0], port)),
+            jwt_secret: std::env::var("JWT_SECRET").ok().filter(|s| !s.is_empty()),
+        }
+    }
+}
+
+/// Serve the check service until `shutdown` resolves
+pub async fn serve(
+    state: Arc<AppState>,
+    config: GrpcConfig,
+    shutdown: impl Future<Output = ()>,
+) -> anyhow::Result<()> {
+    tracing::info!("Policy Engine gRPC listening on {}", config.addr);
+
+    let service = CheckService {
+        state,
+        jwt_secret: config.jwt_secret,
+    };
+    tonic::transport::Server::builder()
+        .add_service(PolicyCheckServer::new(service))
+        .serve_with_shutdown(config.addr, shutdown)
+        .await?;
+    Ok(())
+}
+
+// ============================================================================
+// Service
+// ============================================================================
+
+struct CheckService {
+    state: Arc<AppState>,
+    jwt_secret: Option<String>,
+}
+
+impl CheckService {
+    async fn authenticate(&self, metadata: &MetadataMap) -> std::result::Result<(), Status> {
+        let header = |name: &str| metadata.get(name).and_then(|v| v.to_str().ok());
+        guardrail_shared::authenticate(
+            &self.state.db,
+            self.jwt_secret.as_deref(),
+            header("x-api-key"),
+            header("authorization"),
+        )
+        .await
+        .map_err(status)?;
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl PolicyCheck for CheckService {
+    async fn check_action(
+        &self,
+        request: Request<proto::CheckActionRequest>,
+    ) -> std::result::Result<Response<proto::PolicyDecision>, Status> {
+        self.authenticate(request.metadata()).await?;
+        let req = check_request(request.into_inner()).map_err(status)?;
+        let decision = check_action_impl(&self.state, req).await.map_err(status)?;
+        Ok(Response::new(decision.into()))
+    }
+
+    async fn check_action_batch(
+        &self,
+        request: Request<proto::BatchCheckRequest>,
+    ) -> std::result::Result<Response<proto::BatchCheckResponse>, Status> {
+        self.authenticate(request.metadata()).await?;
+        let checks = request
+            .into_inner()
+            .checks
+            .into_iter()
+            .enumerate()
+            .map(|(index, check)| {
+                check_request(check).map_err(|e| match e {
+                    GuardRailError::Validation(message) => {
+                        GuardRailError::Validation(format!("checks[{}]: {}", index, message))
+                    }
+                    e => e,
+                })
+            })
+            .collect::<Result<Vec<_>>>()
+            .map_err(status)?;
+        let response = check_batch_impl(&self.state, BatchCheckRequest { checks })
+            .await
+            .map_err(status)?;
+        Ok(Response::new(response.into()))
+    }
+
+    type CheckActionStreamStream = ReceiverStream<std::result::Result<proto::BatchCheckResult, Status>>;
+
+    async fn check_action_stream(
+        &self,
+        request: Request<Streaming<proto::CheckActionRequest>>,
+    ) -> std::result::Result<Response<Self::CheckActionStreamStream>, Status> {
+        self.authenticate(request.metadata()).await?;
+
+        let state = self.state.clone();
+        let mut checks = request.into_inner();
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        tokio::spawn(async move {
+            let mut index = 0;
+            loop {
+                let check = match checks.message().await {
+                    Ok(Some(check)) => check,
+                    Ok(None) => break,
+                    Err(e) => {
+                        let _ = tx.send(Err(e)).await;
+                        break;
+                    }
+                };
+                let decided = match check_request(check) {
+                    Ok(req) => check_action_impl(&state, req).await,
+                    Err(e) => Err(e),
+                };
+                let result = BatchCheckResult {
+                    index,
+                    decision: decided.as_ref().ok().cloned(),
+                    error: decided.as_ref().err().map(api_error),
+                };
+                if tx.send(Ok(result.into())).await.is_err() {
+                    // The caller hung up
+                    break;
+                }
+                index += 1;
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// gRPC status for an error, carrying the API error code in `x-error-code`
+fn status(e: GuardRailError) -> Status {
+    let code = match e.status_code() {
+        400 => tonic::Code::InvalidArgument,
+        401 => tonic::Code::Unauthenticated,
+        403 => tonic::Code::PermissionDenied,
+        404 => tonic::Code::NotFound,
+        409 => tonic::Code::AlreadyExists,
+        410 | 412 => tonic::Code::FailedPrecondition,
+        429 => tonic::Code::ResourceExhausted,
+        501 => tonic::Code::Unimplemented,
+        502 | 503 => tonic::Code::Unavailable,
+        504 => tonic::Code::DeadlineExceeded,
+        _ => tonic::Code::Internal,
+    };
+    let mut status = Status::new(code, e.to_string());
+    status
+        .metadata_mut()
+        .insert("x-error-code", MetadataValue::from_static(e.error_code()));
+    status
+}
+
+// ============================================================================
+// Conversions
+// ============================================================================
+
+fn invalid(message: &str) -> GuardRailError {
+    GuardRailError::Validation(message.to_string())
+}
+
+/// Decode a check, holding it to the same requirements as the JSON API
+fn check_request(req: proto::CheckActionRequest) -> Result<CheckActionRequest> {
+    let identity_id = Uuid::parse_str(&req.identity_id).map_err(|_| invalid("identity_id must be a UUID"))?;
+    let action = req.action.ok_or_else(|| invalid("action is required"))?;
+    let context = req.context.ok_or_else(|| invalid("context is required"))?;
+
+    let action_type = match proto::ActionType::try_from(action.action_type) {
+        Ok(proto::ActionType::Withdrawal) => ActionType::Withdrawal,
+        Ok(proto::ActionType::Deposit) => ActionType::Deposit,
+        Ok(proto::ActionType::Transfer) => ActionType::Transfer,
+        Ok(proto::ActionType::Swap) => ActionType::Swap,
+        Ok(proto::ActionType::Trade) => ActionType::Trade,
+        Ok(proto::ActionType::ApiCall) => ActionType::ApiCall,
+        Ok(proto::ActionType::ConfigChange) => ActionType::ConfigChange,
+        Ok(proto::ActionType::Custom) => ActionType::Custom,
+        Ok(proto::ActionType::Unspecified) | Err(_) => return Err(invalid("action.action_type is required")),
+    };
+    let timestamp = context
+        .timestamp
+        .and_then(|t| chrono::DateTime::from_timestamp(t.seconds, t.nanos.try_into().ok()?))
+        .ok_or_else(|| invalid("context.timestamp must be a valid timestamp"))?;
+
+    Ok(CheckActionRequest {
+        identity_id,
+        action: guardrail_shared::Action {
+            action_type,
+            amount: action.amount,
+            asset: action.asset,
+            source_address: action.source_address,
+            target_address: action.target_address,
+            metadata: metadata(action.metadata),
+        },
+        context: guardrail_shared::ActionContext {
+            ip_address: context.ip_address,
+            device_id: context.device_id,
+            user_agent: context.user_agent,
+            geo_location: context.geo_location,
+            timestamp,
+            session_id: context.session_id,
+            metadata: metadata(context.metadata),
+        },
+        explain: req.explain,
+    })
+}
+
+/// Absent metadata reads as an empty object, as SDK callers send it
+fn metadata(value: Option<prost_types::Value>) -> serde_json::Value {
+    value.map(from_value).unwrap_or_else(|| serde_json::json!({}))
+}
+
+fn from_value(value: prost_types::Value) -> serde_json::Value {
+    use prost_types::value::Kind;
+    match value.kind {
+        None | Some(Kind::NullValue(_)) => serde_json::Value::Null,
+        Some(Kind::BoolValue(b)) => serde_json::Value::Bool(b),
+        // Whole numbers come back as integers so they compare and print as JSON integers do
+        Some(Kind::NumberValue(n)) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => (n as i64).into(),
+        Some(Kind::NumberValue(n)) => serde_json::Number::from_f64(n)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        Some(Kind::StringValue(s)) => serde_json::Value::String(s),
+        Some(Kind::ListValue(list)) => list.values.into_iter().map(from_value).collect(),
+        Some(Kind::StructValue(object)) => object
+            .fields
+            .into_iter()
+            .map(|(key, value)| (key, from_value(value)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+    }
+}
+
+fn to_value(value: serde_json::Value) -> prost_types::Value {
+    use prost_types::value::Kind;
+    let kind = match value {
+        serde_json::Value::Null => Kind::NullValue(prost_types::NullValue::NullValue as i32),
+        serde_json::Value::Bool(b) => Kind::BoolValue(b),
+        serde_json::Value::Number(n) => Kind::NumberValue(n.as_f64().unwrap_or_default()),
+        serde_json::Value::String(s) => Kind::StringValue(s),
+        serde_json::Value::Array(values) => Kind::ListValue(prost_types::ListValue {
+            values: values.into_iter().map(to_value).collect(),
+        }),
+        serde_json::Value::Object(object) => Kind::StructValue(prost_types::Struct {
+            fields: object.into_iter().map(|(key, value)| (key, to_value(value))).collect(),
+        }),
+    };
+    prost_types::Value { kind: Some(kind) }
+}
+
+fn timestamp(at: chrono::DateTime<chrono::Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: at.timestamp(),
+        nanos: at.timestamp_subsec_nanos() as i32,
+    }
+}
+
+impl From<Obligation> for proto::Obligation {
+    fn from(obligation: Obligation) -> Self {
+        Self {
+            r#type: obligation.obligation_type,
+            params: Some(to_value(obligation.params)),
+        }
+    }
+}
+
+impl From<DenyReason> for proto::DenyReason {
+    fn from(reason: DenyReason) -> Self {
+        let severity = match reason.severity {
+            None => proto::ReasonSeverity::Unspecified,
+            Some(ReasonSeverity::Low) => proto::ReasonSeverity::Low,
+            Some(ReasonSeverity::Medium) => proto::ReasonSeverity::Medium,
+            Some(ReasonSeverity::High) => proto::ReasonSeverity::High,
+            Some(ReasonSeverity::Critical) => proto::ReasonSeverity::Critical,
+        };
+        Self {
+            code: reason.code,
+            message: reason.message,
+            severity: severity.into(),
+            params: Some(to_value(reason.params)),
+        }
+    }
+}
+
+impl From<PolicyAttribution> for proto::PolicyAttribution {
+    fn from(attribution: PolicyAttribution) -> Self {
+        Self {
+            policy_id: attribution.policy_id.to_string(),
+            policy_name: attribution.policy_name,
+            policy_version: attribution.policy_version,
+            reasons: attribution.reasons,
+            required_approvers: attribution.required_approvers,
+            obligations: attribution.obligations.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<PolicyDecision> for proto::PolicyDecision {
+    fn from(decision: PolicyDecision) -> Self {
+        let outcome = match decision.decision {
+            Decision::Allow => proto::Decision::Allow,
+            Decision::Deny => proto::Decision::Deny,
+            Decision::RequireApproval => proto::Decision::RequireApproval,
+        };
+        let degraded_cause = match decision.degraded_cause {
+            None => proto::DegradedCause::Unspecified,
+            Some(DegradedCause::EvaluationError) => proto::DegradedCause::EvaluationError,
+            Some(DegradedCause::DeadlineExceeded) => proto::DegradedCause::DeadlineExceeded,
+            Some(DegradedCause::DatabaseUnavailable) => proto::DegradedCause::DatabaseUnavailable,
+        };
+        Self {
+            decision_id: decision.decision_id.to_string(),
+            decision: outcome.into(),
+            reasons: decision.reasons,
+            reason_details: decision.reason_details.into_iter().map(Into::into).collect(),
+            required_approvers: decision.required_approvers,
+            policy_id: decision.policy_id.map(|id| id.to_string()),
+            policy_version: decision.policy_version,
+            contributing_policies: decision.contributing_policies.into_iter().map(Into::into).collect(),
+            obligations: decision.obligations.into_iter().map(Into::into).collect(),
+            explanation: decision
+                .explanation
+                .and_then(|explanation| serde_json::to_value(explanation).ok())
+                .map(to_value),
+            data_revision: decision.data_revision,
+            policy_set_revision: decision.policy_set_revision,
+            degraded: decision.degraded,
+            degraded_cause: degraded_cause.into(),
+            evaluated_at: Some(timestamp(decision.evaluated_at)),
+        }
+    }
+}
+
+impl From<BatchCheckResult> for proto::BatchCheckResult {
+    fn from(result: BatchCheckResult) -> Self {
+        let outcome = match (result.decision, result.error) {
+            (Some(decision), _) => Some(proto::batch_check_result::Outcome::Decision(Box::new(decision.into()))),
+            (None, Some(error)) => Some(proto::batch_check_result::Outcome::Error(proto::Error {
+                code: error.code,
+                message: error.message,
+            })),
+            (None, None) => None,
+        };
+        Self {
+            index: result.index as u32,
+            outcome,
+        }
+    }
+}
+
+impl From<BatchCheckResponse> for proto::BatchCheckResponse {
+    fn from(response: BatchCheckResponse) -> Self {
+        Self {
+            results: response.results.into_iter().map(Into::into).collect(),
+            succeeded: response.succeeded as u32,
+            failed: response.failed as u32,
+        }
+    }
+}
+
+// ============================================================================
+// Messages
+// ============================================================================
+
+/// Messages of `proto/guardrail/v1/check.proto`, and the generated server
+pub mod proto {
+    tonic::include_proto!("guardrail.v1");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+    use serde_json::json;
+
+    fn check() -> proto::CheckActionRequest {
+        proto::CheckActionRequest {
+            identity_id: Uuid::nil().to_string(),
+            action: Some(proto::Action {
+                action_type: proto::ActionType::Withdrawal.into(),
+                amount: Some("250.5".to_string()),
+                asset: Some("BTC".to_string()),
+                metadata: Some(to_value(json!({"desk": "otc", "legs": 2, "fee": 0.25, "tags": ["a", null]}))),
+                ..Default::default()
+            }),
+            context: Some(proto::ActionContext {
+                timestamp: Some(prost_types::Timestamp { seconds: 1_704_067_200, nanos: 0 }),
+                ..Default::default()
+            }),
+            explain: true,
+        }
+    }
+
+    #[test]
+    fn test_check_request_decoding() {
+        // Round-trip through the wire format, as a client would send it
+        let wire = proto::CheckActionRequest::decode(check().encode_to_vec().as_slice()).unwrap();
+        let req = check_request(wire).unwrap();
+        assert_eq!(req.identity_id, Uuid::nil());
+        assert_eq!(req.action.action_type, ActionType::Withdrawal);
+        assert_eq!(req.action.metadata, json!({"desk": "otc", "legs": 2, "fee": 0.25, "tags": ["a", null]}));
+        assert_eq!(req.context.metadata, json!({}));
+        assert_eq!(req.context.timestamp.to_rfc3339(), "2024-01-01T00:00:00+00:00");
+        assert!(req.explain);
+
+        let mut unspecified = check();
+        unspecified.action.as_mut().unwrap().action_type = proto::ActionType::Unspecified.into();
+        assert!(matches!(check_request(unspecified), Err(GuardRailError::Validation(_))));
+
+        let mut bad_id = check();
+        bad_id.identity_id = "alice".to_string();
+        assert!(matches!(check_request(bad_id), Err(GuardRailError::Validation(_))));
+
+        let mut no_timestamp = check();
+        no_timestamp.context.as_mut().unwrap().timestamp = None;
+        assert!(matches!(check_request(no_timestamp), Err(GuardRailError::Validation(_))));
+    }
+
+    #[test]
+    fn test_error_status() {
+        let not_found = status(GuardRailError::IdentityNotFound(Uuid::nil().to_string()));
+        assert_eq!(not_found.code(), tonic::Code::NotFound);
+        assert_eq!(not_found.metadata().get("x-error-code").unwrap(), "IDENTITY_NOT_FOUND");
+        assert_eq!(
+            status(GuardRailError::Unauthorized("Invalid API key".to_string())).code(),
+            tonic::Code::Unauthenticated
+        );
+
+        let result: proto::BatchCheckResult = BatchCheckResult {
+            index: 3,
+            decision: None,
+            error: Some(api_error(&GuardRailError::IdentityNotFound(Uuid::nil().to_string()))),
+        }
+        .into();
+        assert_eq!(result.index, 3);
+        assert!(matches!(result.outcome, Some(proto::batch_check_result::Outcome::Error(e)) if e.code == "IDENTITY_NOT_FOUND"));
+    }
+}
@@ -0,0 +1,488 @@
+//! Rolling-window history for velocity rules
+//!
+//! Each check adds `input.history`: per configured window, counts of the identity's
+//! earlier decisions for all actions, the same action type, the same asset, and the
+//! same asset and action type. Amounts are only comparable within one asset, so
+//! `sum` appears at the `asset` and `asset_action_type` levels only. The figures come from
+//! `action_counters`, one-minute buckets updated in the same transaction as the
+//! decision, so a check reads a handful of rows instead of rescanning
+//! `policy_decisions`. Window edges are therefore accurate to the minute.
+
+use crate::AppState;
+use chrono::{DateTime, DurationRound, Utc};
+use guardrail_shared::{Action, Decision, Result};
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryWindow {
+    /// Key under `input.history`, e.g. `24h`
+    pub label: String,
+    pub duration: chrono::Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct HistoryConfig {
+    pub windows: Vec<HistoryWindow>,
+    /// How often buckets older than the longest window are pruned
+    pub sweep_interval: Duration,
+}
+
+impl HistoryConfig {
+    pub fn from_env() -> Self {
+        Self {
+            windows: parse_windows(&std::env::var("HISTORY_WINDOWS").unwrap_or_else(|_| "1h,24h,7d".to_string())),
+            sweep_interval: Duration::from_secs(
+                std::env::var("HISTORY_SWEEP_INTERVAL_SECS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(3600),
+            ),
+        }
+    }
+
+    /// The longest configured window, if any
+    fn retention(&self) -> Option<chrono::Duration> {
+        self.windows.iter().map(|w| w.duration).max()
+    }
+}
+
+/// Parse a window spec like `15m,1h,24h,7d`
+pub fn parse_windows(spec: &str) -> Vec<HistoryWindow> {
+    spec.split(',')
+        .filter_map(|entry| {
+            let label = entry.trim();
+            let unit = label.chars().last()?;
+            let amount: i64 = label[..label.len() - 1].parse().ok().filter(|n| *n > 0)?;
+            let duration = match unit {
+                'm' => chrono::Duration::minutes(amount),
+                'h' => chrono::Duration::hours(amount),
+                'd' => chrono::Duration::days(amount),
+                _ => return None,
+            };
+            Some(HistoryWindow {
+                label: label.to_string(),
+                duration,
+            })
+        })
+        .collect()
+}
+
+// ============================================================================
+// Aggregates
+// ============================================================================
+
+/// Decisions within one window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Aggregate {
+    /// Actions that were allowed or sent for approval
+    pub count: i64,
+    /// Sum of `amount` over those actions; `None` where they span several assets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sum: Option<f64>,
+    /// Actions that were denied
+    pub denied: i64,
+}
+
+/// `input.history.<window>`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WindowAggregates {
+    pub all: Aggregate,
+    pub action_type: Aggregate,
+    pub asset: Aggregate,
+    pub asset_action_type: Aggregate,
+}
+
+impl Default for WindowAggregates {
+    fn default() -> Self {
+        let summed = Aggregate {
+            sum: Some(0.0),
+            ..Aggregate::default()
+        };
+        Self {
+            all: Aggregate::default(),
+            action_type: Aggregate::default(),
+            asset: summed,
+            asset_action_type: summed,
+        }
+    }
+}
+
+/// One minute of activity for an identity, action type and asset
+struct CounterRow {
+    action_type: String,
+    asset: String,
+    bucket_start: DateTime<Utc>,
+    allowed_count: i64,
+    denied_count: i64,
+    amount_sum: f64,
+}
+
+impl Aggregate {
+    fn add(&mut self, row: &CounterRow) {
+        self.count += row.allowed_count;
+        self.denied += row.denied_count;
+        if let Some(sum) = &mut self.sum {
+            *sum += row.amount_sum;
+        }
+    }
+}
+
+/// Counter key for an action; `action_type` matches `policy_decisions.action_type`
+fn counter_key(action: &Action) -> (String, String) {
+    (
+        format!("{:?}", action.action_type),
+        action.asset.clone().unwrap_or_default(),
+    )
+}
+
+/// Parse an action amount, treating anything unparseable as zero
+fn parse_amount(amount: Option<&str>) -> f64 {
+    amount
+        .and_then(|a| a.trim().parse::<f64>().ok())
+        .filter(|a| a.is_finite())
+        .unwrap_or(0.0)
+}
+
+fn aggregate(
+    rows: &[CounterRow],
+    action_type: &str,
+    asset: &str,
+    now: DateTime<Utc>,
+    windows: &[HistoryWindow],
+) -> BTreeMap<String, WindowAggregates> {
+    windows
+        .iter()
+        .map(|window| {
+            let since = now - window.duration;
+            let mut totals = WindowAggregates::default();
+            for row in rows.iter().filter(|r| r.bucket_start >= since) {
+                let same_type = row.action_type == action_type;
+                let same_asset = row.asset == asset;
+                totals.all.add(row);
+                if same_type {
+                    totals.action_type.add(row);
+                }
+                if same_asset {
+                    totals.asset.add(row);
+                }
+                if same_type && same_asset {
+                    totals.asset_action_type.add(row);
+                }
+            }
+            (window.label.clone(), totals)
+        })
+        .collect()
+}
+
+// ============================================================================
+// Counter store
+// ============================================================================
+
+/// Serialise checks for one identity so concurrent actions can't both fit under a limit
+pub async fn lock_identity(tx: &mut Transaction<'_, Postgres>, identity_id: Uuid) -> Result<()> {
+    sqlx::query!("SELECT pg_advisory_xact_lock(hashtextextended($1::text, 0))", identity_id.to_string())
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Build `input.history` for an identity's next action
+pub async fn load(
+    tx: &mut Transaction<'_, Postgres>,
+    config: &HistoryConfig,
+    identity_id: Uuid,
+    action: &Action,
+    now: DateTime<Utc>,
+) -> Result<serde_json::Value> {
+    let Some(retention) = config.retention() else {
+        return Ok(serde_json::json!({}));
+    };
+
+    let rows = sqlx::query_as!(
+        CounterRow,
+        r#"
+        SELECT action_type, asset, bucket_start, allowed_count, denied_count, amount_sum
+        FROM action_counters
+        WHERE identity_id = $1 AND bucket_start >= $2
+        "#,
+        identity_id,
+        now - retention,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let (action_type, asset) = counter_key(action);
+    Ok(serde_json::to_value(aggregate(&rows, &action_type, &asset, now, &config.windows))?)
+}
+
+/// Count a decision in its minute bucket
+pub async fn record(
+    tx: &mut Transaction<'_, Postgres>,
+    identity_id: Uuid,
+    action: &Action,
+    decision: Decision,
+    at: DateTime<Utc>,
+) -> Result<()> {
+    let (action_type, asset) = counter_key(action);
+    let denied = decision == Decision::Deny;
+    let bucket_start = at.duration_trunc(chrono::Duration::minutes(1)).unwrap_or(at);
+
+    sqlx::query!(
+        r#"
+        INSERT INTO action_counters (identity_id, action_type, asset, bucket_start, allowed_count, denied_count, amount_sum)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (identity_id, bucket_start, action_type, asset) DO UPDATE
+        SET allowed_count = action_counters.allowed_count + EXCLUDED.allowed_count,
+            denied_count = action_counters.denied_count + EXCLUDED.denied_count,
+            amount_sum = action_counters.amount_sum + EXCLUDED.amount_sum
+        "#,
+        identity_id,
+        action_type,
+        asset,
+        bucket_start,
+        if denied { 0 } else { 1 },
+        if denied { 1 } else { 0 },
+        if denied { 0.0 } else { parse_amount(action.amount.as_deref()) },
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Seed an empty counter store from `policy_decisions` within the longest window
+pub async fn backfill(db: &PgPool, config: &HistoryConfig) -> Result<u64> {
+    let Some(retention) = config.retention() else {
+        return Ok(0);
+    };
+
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO action_counters (identity_id, action_type, asset, bucket_start, allowed_count, denied_count, amount_sum)
+        SELECT identity_id, action_type, COALESCE(action_payload->>'asset', ''), date_trunc('minute', created_at),
+               COUNT(*) FILTER (WHERE decision <> 'DENY'),
+               COUNT(*) FILTER (WHERE decision = 'DENY'),
+               COALESCE(SUM(CASE WHEN decision <> 'DENY' AND action_payload->>'amount' ~ '^\s*[0-9]+(\.[0-9]+)?\s*$'
+                                 THEN (action_payload->>'amount')::double precision END), 0)
+        FROM policy_decisions
+        WHERE created_at >= $1 AND NOT EXISTS (SELECT 1 FROM action_counters)
+        GROUP BY 1, 2, 3, 4
+        "#,
+        Utc::now() - retention,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Delete buckets that have fallen out of every window
+async fn prune(db: &PgPool, config: &HistoryConfig) -> Result<u64> {
+    let Some(retention) = config.retention() else {
+        return Ok(0);
+    };
+
+    let result = sqlx::query!(
+        "DELETE FROM action_counters WHERE bucket_start < $1",
+        Utc::now() - retention - chrono::Duration::minutes(1),
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// ============================================================================
+// Replay
+// ============================================================================
+
+/// `input.history` for recorded decisions, rebuilt from `policy_decisions`
+///
+/// Backtests use this so a replayed check sees the same figures `load` would
+/// have returned when the decision was made, including buckets that have
+/// since been pruned from `action_counters`.
+pub(crate) struct Replay {
+    windows: Vec<HistoryWindow>,
+    retention: Option<chrono::Duration>,
+    /// Each identity's decisions in time order, one row per decision
+    rows: HashMap<Uuid, (Vec<DateTime<Utc>>, Vec<CounterRow>)>,
+}
+
+impl Replay {
+    /// `input.history` for a decision the identity made at `at`
+    pub(crate) fn at(&self, identity_id: Uuid, action_type: &str, asset: &str, at: DateTime<Utc>) -> serde_json::Value {
+        let Some(retention) = self.retention else {
+            return serde_json::json!({});
+        };
+        let (times, rows) = self
+            .rows
+            .get(&identity_id)
+            .map(|(times, rows)| (times.as_slice(), rows.as_slice()))
+            .unwrap_or_default();
+
+        // Buckets are minute-aligned, so a decision up to a minute older than the window can still count
+        let start = times.partition_point(|t| *t < at - retention - chrono::Duration::minutes(1));
+        let end = times.partition_point(|t| *t < at);
+        serde_json::to_value(aggregate(&rows[start..end], action_type, asset, at, &self.windows))
+            .unwrap_or_else(|_| serde_json::json!({}))
+    }
+}
+
+/// Load what `Replay` needs for decisions made by `identities` between `from` and `to`
+pub(crate) async fn replay(
+    db: &PgPool,
+    config: &HistoryConfig,
+    identities: &[Uuid],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Replay> {
+    let mut replay = Replay {
+        windows: config.windows.clone(),
+        retention: config.retention(),
+        rows: HashMap::new(),
+    };
+    let Some(retention) = replay.retention else {
+        return Ok(replay);
+    };
+
+    let decisions = sqlx::query!(
+        r#"
+        SELECT identity_id, action_type, COALESCE(action_payload->>'asset', '') as "asset!",
+               action_payload->>'amount' as amount, decision as "decision: Decision", created_at as "created_at!"
+        FROM policy_decisions
+        WHERE identity_id = ANY($1) AND created_at >= $2 AND created_at < $3
+        ORDER BY created_at ASC
+        "#,
+        identities,
+        from - retention - chrono::Duration::minutes(1),
+        to,
+    )
+    .fetch_all(db)
+    .await?;
+
+    for decision in decisions {
+        let denied = decision.decision == Decision::Deny;
+        let (times, rows) = replay.rows.entry(decision.identity_id).or_default();
+        times.push(decision.created_at);
+        rows.push(CounterRow {
+            action_type: decision.action_type,
+            asset: decision.asset,
+            bucket_start: decision
+                .created_at
+                .duration_trunc(chrono::Duration::minutes(1))
+                .unwrap_or(decision.created_at),
+            allowed_count: if denied { 0 } else { 1 },
+            denied_count: if denied { 1 } else { 0 },
+            amount_sum: if denied { 0.0 } else { parse_amount(decision.amount.as_deref()) },
+        });
+    }
+
+    Ok(replay)
+}
+
+pub fn spawn_sweeper(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(state.history_config.sweep_interval);
+        loop {
+            interval.tick().await;
+            match prune(&state.db, &state.history_config).await {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("Pruned {} expired history buckets", n),
+                Err(e) => tracing::error!("History prune failed: {}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_windows() {
+        let windows = parse_windows("15m, 24h,7d,bogus,0h,3w");
+        let labels: Vec<&str> = windows.iter().map(|w| w.label.as_str()).collect();
+        assert_eq!(labels, vec!["15m", "24h", "7d"]);
+        assert_eq!(windows[1].duration, chrono::Duration::hours(24));
+        assert_eq!(parse_amount(Some(" 12.5 ")), 12.5);
+        assert_eq!(parse_amount(Some("NaN")), 0.0);
+        assert_eq!(parse_amount(None), 0.0);
+    }
+
+    #[test]
+    fn test_aggregate_windows() {
+        let now = Utc::now();
+        let row = |action_type: &str, asset: &str, minutes_ago: i64, allowed, denied, sum| CounterRow {
+            action_type: action_type.to_string(),
+            asset: asset.to_string(),
+            bucket_start: now - chrono::Duration::minutes(minutes_ago),
+            allowed_count: allowed,
+            denied_count: denied,
+            amount_sum: sum,
+        };
+        let rows = vec![
+            row("Withdrawal", "BTC", 5, 2, 1, 3.0),
+            row("Withdrawal", "ETH", 30, 1, 0, 10.0),
+            row("Transfer", "BTC", 90, 1, 0, 1.0),
+            row("Withdrawal", "BTC", 600, 4, 0, 8.0),
+        ];
+        let windows = parse_windows("1h,24h");
+
+        let history = aggregate(&rows, "Withdrawal", "BTC", now, &windows);
+        let hour = &history["1h"];
+        assert_eq!(hour.all, Aggregate { count: 3, sum: None, denied: 1 });
+        assert_eq!(hour.action_type.count, 3);
+        assert_eq!(hour.asset, Aggregate { count: 2, sum: Some(3.0), denied: 1 });
+        assert_eq!(hour.asset_action_type.sum, Some(3.0));
+
+        let day = &history["24h"];
+        assert_eq!(day.all.count, 8);
+        assert_eq!(day.asset.count, 7);
+        assert_eq!(day.asset_action_type, Aggregate { count: 6, sum: Some(11.0), denied: 1 });
+
+        let json = serde_json::to_value(day).unwrap();
+        assert!(json["all"].get("sum").is_none());
+        assert!(json["action_type"].get("sum").is_none());
+        assert_eq!(json["asset"]["sum"], 12.0);
+    }
+
+    #[test]
+    fn test_replay_sees_earlier_decisions_only() {
+        let start = Utc::now().duration_trunc(chrono::Duration::minutes(1)).unwrap();
+        let at = |minutes| start + chrono::Duration::minutes(minutes);
+        let row = |minutes, denied: bool| CounterRow {
+            action_type: "Withdrawal".to_string(),
+            asset: "BTC".to_string(),
+            bucket_start: at(minutes),
+            allowed_count: if denied { 0 } else { 1 },
+            denied_count: if denied { 1 } else { 0 },
+            amount_sum: if denied { 0.0 } else { 5.0 },
+        };
+        let identity_id = Uuid::new_v4();
+        let replay = Replay {
+            windows: parse_windows("1h"),
+            retention: Some(chrono::Duration::hours(1)),
+            rows: HashMap::from([(identity_id, (vec![at(0), at(30), at(45)], vec![row(0, false), row(30, true), row(45, false)]))]),
+        };
+
+        let history = replay.at(identity_id, "Withdrawal", "BTC", at(45));
+        assert_eq!(history["1h"]["asset"]["count"], 1);
+        assert_eq!(history["1h"]["asset"]["denied"], 1);
+        assert_eq!(history["1h"]["asset"]["sum"], 5.0);
+
+        // An hour on, the first decision has left the window
+        let history = replay.at(identity_id, "Withdrawal", "BTC", at(61));
+        assert_eq!(history["1h"]["asset"]["count"], 1);
+        assert_eq!(history["1h"]["asset"]["denied"], 1);
+        assert_eq!(replay.at(Uuid::new_v4(), "Withdrawal", "BTC", at(61))["1h"]["all"]["count"], 0);
+    }
+}
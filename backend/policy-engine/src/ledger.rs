@@ -0,0 +1,70 @@
+//! Movement ledger client
+//!
+//! Appends policy-engine events (approvals, decisions) to the hash-chained ledger.
+
+use guardrail_shared::{EventType, GuardRailError, Result};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Seeded `System` identity used as the actor for unattributed changes
+pub const SYSTEM_ACTOR_ID: Uuid = Uuid::from_u128(1);
+
+#[derive(Clone)]
+pub struct LedgerClient {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl LedgerClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into(),
+        }
+    }
+
+    /// Append an event to the movement ledger
+    pub async fn record_event(
+        &self,
+        event_type: EventType,
+        actor_id: Uuid,
+        policy_decision_id: Option<Uuid>,
+        payload: serde_json::Value,
+    ) -> Result<()> {
+        let response = self
+            .http
+            .post(format!("{}/api/v1/events", self.base_url))
+            .timeout(Duration::from_secs(5))
+            .json(&serde_json::json!({
+                "event_type": event_type,
+                "actor_id": actor_id,
+                "policy_decision_id": policy_decision_id,
+                "payload": payload,
+            }))
+            .send()
+            .await
+            .map_err(|e| GuardRailError::ExternalService(format!("Movement ledger unreachable: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(GuardRailError::ExternalService(format!(
+                "Movement ledger rejected event: {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Append an event, logging instead of failing if the ledger is unavailable
+    pub async fn record_event_logged(
+        &self,
+        event_type: EventType,
+        actor_id: Uuid,
+        policy_decision_id: Option<Uuid>,
+        payload: serde_json::Value,
+    ) {
+        if let Err(e) = self.record_event(event_type, actor_id, policy_decision_id, payload).await {
+            tracing::warn!("Failed to record {:?} event in ledger: {}", event_type, e);
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! Library policies
+//!
+//! A library provides a `guardrail.lib.<name>` package of helper rules and
+//! functions, which decision policies import (`import data.guardrail.lib.limits as
+//! limits`) or refer to inline. regorus only resolves functions by their full path,
+//! so library functions are called as `data.guardrail.lib.limits.max_for(...)`.
+//! Libraries decide nothing themselves: `PolicyEngine` loads them into every engine
+//! ahead of the decision policies, and decisions are read from `data.guardrail` alone.
+//!
+//! A policy depends on every library whose package it refers to. A library can't
+//! be deactivated, or lose its package in an update, while an active or shadow
+//! policy depends on it, and a policy can't be activated before its libraries.
+
+use crate::{get_policy_impl, lint, AppState};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use guardrail_shared::{ApiResponse, GuardRailError, Policy, PolicyKind, PolicyTargets, Result};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DependentPolicy {
+    pub policy_id: Uuid,
+    pub name: String,
+    pub version: String,
+    pub kind: PolicyKind,
+    pub is_active: bool,
+    pub is_shadow: bool,
+}
+
+/// A library path a policy refers to, and the libraries providing it
+#[derive(Debug, Clone, Serialize)]
+pub struct LibraryImport {
+    /// Path without the `data.` prefix, e.g. `guardrail.lib.limits`
+    pub path: String,
+    /// Empty when no library provides the path
+    pub provided_by: Vec<DependentPolicy>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PolicyDependencies {
+    pub policy_id: Uuid,
+    /// `None` when the current source doesn't parse
+    pub package: Option<String>,
+    pub imports: Vec<LibraryImport>,
+    /// Policies importing this library's package
+    pub imported_by: Vec<DependentPolicy>,
+}
+
+/// A stored policy with the package it declares and the library paths it uses
+struct Module {
+    policy: DependentPolicy,
+    package: String,
+    imports: BTreeSet<String>,
+}
+
+impl Module {
+    fn is_live(&self) -> bool {
+        self.policy.is_active || self.policy.is_shadow
+    }
+
+    fn imports_from(&self, package: &str) -> bool {
+        self.imports.iter().any(|path| lint::provides(package, path))
+    }
+}
+
+// ============================================================================
+// Checks
+// ============================================================================
+
+/// Reject settings that only make sense for decision policies
+pub fn validate(
+    kind: PolicyKind,
+    targets: &PolicyTargets,
+    shadow: bool,
+    effective_from: Option<DateTime<Utc>>,
+    effective_until: Option<DateTime<Utc>>,
+) -> Result<()> {
+    if kind != PolicyKind::Library {
+        return Ok(());
+    }
+    if !targets.is_empty() {
+        return Err(GuardRailError::Validation(
+            "Libraries are loaded for every check and can't have targets".to_string(),
+        ));
+    }
+    if shadow {
+        return Err(GuardRailError::Validation("Libraries can't be shadowed".to_string()));
+    }
+    if effective_from.is_some() || effective_until.is_some() {
+        return Err(GuardRailError::Validation(
+            "Libraries can't be scheduled; schedule the policies that import them".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Kind of a module judged by its package, for modules that arrive without one
+pub fn kind_of(rego_source: &str) -> PolicyKind {
+    match lint::module_imports(rego_source) {
+        Some((package, _)) if lint::is_library_package(&package) => PolicyKind::Library,
+        _ => PolicyKind::Decision,
+    }
+}
+
+async fn load_modules(db: &PgPool) -> Result<Vec<Module>> {
+    let policies = sqlx::query!(
+        r#"
+        SELECT id, name, version, kind as "kind: PolicyKind", rego_source, is_active as "is_active!", is_shadow
+        FROM policies
+        ORDER BY created_at ASC
+        "#
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(policies
+        .into_iter()
+        .filter_map(|p| {
+            let (package, imports) = lint::module_imports(&p.rego_source)?;
+            Some(Module {
+                policy: DependentPolicy {
+                    policy_id: p.id,
+                    name: p.name,
+                    version: p.version,
+                    kind: p.kind,
+                    is_active: p.is_active,
+                    is_shadow: p.is_shadow,
+                },
+                package,
+                imports,
+            })
+        })
+        .collect())
+}
+
+/// Live policies other than `library` that import from `package` but not from `replacement`
+fn broken_by<'a>(modules: &'a [Module], library: Uuid, package: &str, replacement: Option<&str>) -> Vec<&'a Module> {
+    modules
+        .iter()
+        .filter(|m| m.policy.policy_id != library && m.is_live())
+        .filter(|m| {
+            m.imports.iter().any(|path| {
+                lint::provides(package, path) && !replacement.is_some_and(|package| lint::provides(package, path))
+            })
+        })
+        .collect()
+}
+
+fn names(modules: &[&Module]) -> String {
+    modules.iter().map(|m| m.policy.name.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+/// Reject taking a library's package away from the policies importing it.
+/// `replacement` is the library's new source when it stays loaded.
+pub async fn ensure_not_imported(db: &PgPool, library: &Policy, replacement: Option<&str>) -> Result<()> {
+    if library.kind != PolicyKind::Library {
+        return Ok(());
+    }
+    let Some((package, _)) = lint::module_imports(&library.rego_source) else {
+        return Ok(());
+    };
+    let replacement = replacement.and_then(lint::module_imports).map(|(package, _)| package);
+
+    let modules = load_modules(db).await?;
+    let dependents = broken_by(&modules, library.id, &package, replacement.as_deref());
+    if dependents.is_empty() {
+        return Ok(());
+    }
+    Err(GuardRailError::Conflict(format!(
+        "Library {} provides `{}`, which is imported by {}",
+        library.name,
+        package,
+        names(&dependents)
+    )))
+}
+
+/// Reject activating a policy before the libraries it imports
+pub async fn ensure_imports_active(db: &PgPool, name: &str, rego_source: &str) -> Result<()> {
+    let Some((_, imports)) = lint::module_imports(rego_source) else {
+        return Ok(());
+    };
+    if imports.is_empty() {
+        return Ok(());
+    }
+
+    let modules = load_modules(db).await?;
+    let missing: Vec<&str> = imports
+        .iter()
+        .filter(|path| {
+            !modules.iter().any(|m| {
+                m.policy.kind == PolicyKind::Library && m.policy.is_active && lint::provides(&m.package, path)
+            })
+        })
+        .map(String::as_str)
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    Err(GuardRailError::Conflict(format!(
+        "Policy {} imports `data.{}`, which no active library provides",
+        name,
+        missing.join("`, `data.")
+    )))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn policy_dependencies_impl(state: &AppState, id: Uuid) -> Result<PolicyDependencies> {
+    let policy = get_policy_impl(&state.db, id).await?;
+    let modules = load_modules(&state.db).await?;
+    let (package, imports) = match lint::module_imports(&policy.rego_source) {
+        Some((package, imports)) => (Some(package), imports),
+        None => (None, BTreeSet::new()),
+    };
+
+    let imports = imports
+        .into_iter()
+        .map(|path| LibraryImport {
+            provided_by: modules
+                .iter()
+                .filter(|m| m.policy.kind == PolicyKind::Library && lint::provides(&m.package, &path))
+                .map(|m| m.policy.clone())
+                .collect(),
+            path,
+        })
+        .collect();
+    let imported_by = match (&package, policy.kind) {
+        (Some(package), PolicyKind::Library) => modules
+            .iter()
+            .filter(|m| m.policy.policy_id != id && m.imports_from(package))
+            .map(|m| m.policy.clone())
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Ok(PolicyDependencies {
+        policy_id: id,
+        package,
+        imports,
+        imported_by,
+    })
+}
+
+pub async fn policy_dependencies(State(state): State<Arc<AppState>>, Path(id): Path<Uuid>) -> impl IntoResponse {
+    match policy_dependencies_impl(&state, id).await {
+        Ok(dependencies) => (StatusCode::OK, Json(ApiResponse::success(dependencies))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<PolicyDependencies>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(name: &str, source: &str, is_active: bool) -> Module {
+        let (package, imports) = lint::module_imports(source).unwrap();
+        Module {
+            policy: DependentPolicy {
+                policy_id: Uuid::new_v4(),
+                name: name.to_string(),
+                version: "1.0.0".to_string(),
+                kind: PolicyKind::Decision,
+                is_active,
+                is_shadow: false,
+            },
+            package,
+            imports,
+        }
+    }
+
+    #[test]
+    fn test_broken_by() {
+        let library = module("limits", "package guardrail.lib.limits\nmax := 100", true);
+        let modules = vec![
+            module(
+                "withdrawals",
+                "package guardrail\nimport data.guardrail.lib.limits as limits\ndeny[\"big\"] { input.action.amount > limits.max }",
+                true,
+            ),
+            module("inline", "package guardrail\ndeny[\"big\"] { input.action.amount > data.guardrail.lib.limits.max }", true),
+            module("retired", "package guardrail\nimport data.guardrail.lib.limits", false),
+            module("other", "package guardrail\nimport data.guardrail.lib.velocity", true),
+        ];
+        assert_eq!(library.package, "guardrail.lib.limits");
+        assert_eq!(modules[1].imports, BTreeSet::from(["guardrail.lib.limits.max".to_string()]));
+
+        let id = library.policy.policy_id;
+        let dependents: Vec<&str> = broken_by(&modules, id, &library.package, None)
+            .iter()
+            .map(|m| m.policy.name.as_str())
+            .collect();
+        assert_eq!(dependents, vec!["withdrawals", "inline"]);
+
+        // An update keeping the package breaks nothing; renaming it does
+        assert!(broken_by(&modules, id, &library.package, Some("guardrail.lib.limits")).is_empty());
+        assert_eq!(broken_by(&modules, id, &library.package, Some("guardrail.lib.caps")).len(), 2);
+    }
+}
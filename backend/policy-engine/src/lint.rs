@@ -0,0 +1,956 @@
+//! Static checks for policies
+//!
+//! Walks the regorus AST of a policy and reports mistakes that would otherwise only
+//! show up as wrong decisions, since `parse_decision` skips values it doesn't read:
+//!
+//! | Code | Severity | Finding |
+//! |------|----------|---------|
+//! | `SYNTAX` | error | The module does not parse |
+//! | `PACKAGE` | error | The package is not `guardrail`, or `guardrail.lib.<name>` for a library, so the policy is never evaluated |
+//! | `MISSING_IMPORT` | error | A keyword such as `if` used without its import, which turns it into a rule of its own and drops the conditions of the rule before it |
+//! | `OUTPUT_TYPE` | error | `deny`, `required_approvers` or `obligations` is not a set of values `parse_decision` reads |
+//! | `UNKNOWN_IMPORT` | error | A `data.guardrail.lib.*` reference no active library provides |
+//! | `IMPORT_ALIAS` | error | A library import without `as`, whose name regorus leaves undefined in rule bodies |
+//! | `IMPORTED_CALL` | error | A library function called through its import's name, which regorus can't resolve |
+//! | `RULE_CONFLICT` | error, warning | A rule defined differently by another enforced or shadow policy in the same package |
+//! | `UNKNOWN_INPUT` | warning | An `input.*` path that checks never provide |
+//! | `UNUSED_RULE` | warning | A rule no policy refers to and `parse_decision` doesn't read |
+//!
+//! Libraries are exempt from `OUTPUT_TYPE` and `UNUSED_RULE`: they exist to be
+//! imported, and nothing reads their rules as decisions.
+//!
+//! Policies are only created or updated when lint reports no errors.
+
+use crate::AppState;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use guardrail_shared::{ApiResponse, GuardRailError, LintDiagnostic, LintReport, LintSeverity, PolicyKind, Result};
+use regorus::Engine;
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ops::Range;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Package `parse_decision` reads
+const PACKAGE: &str = "guardrail";
+
+/// Parent of every library package
+const LIBRARY_PACKAGE: &str = "guardrail.lib";
+
+/// Rules `parse_decision` reads
+const OUTPUTS: &[&str] = &["deny", "required_approvers", "obligations"];
+
+/// Keywords that parse as rule names when used without their import
+const KEYWORDS: &[&str] = &["if", "contains", "in", "every"];
+
+/// File name given to the module while parsing, as it appears in syntax errors
+const SOURCE_PATH: &str = "policy.rego";
+
+#[derive(Debug, Deserialize)]
+pub struct LintPolicyRequest {
+    pub rego_source: String,
+    #[serde(default)]
+    pub kind: PolicyKind,
+    /// Existing policy the draft would replace, left out of conflict checks
+    pub replaces_policy_id: Option<Uuid>,
+}
+
+// ============================================================================
+// Input shape
+// ============================================================================
+
+/// What lint knows about part of the input document
+enum Shape {
+    /// A string, number or boolean
+    Scalar,
+    /// Free-form JSON, such as `metadata`
+    Any,
+    Object(&'static [(&'static str, Shape)]),
+    /// An object with caller-chosen keys
+    Map(&'static Shape),
+    List(&'static Shape),
+}
+
+/// History counts across assets, which carry no amount sum
+const COUNTS: Shape = Shape::Object(&[("count", Shape::Scalar), ("denied", Shape::Scalar)]);
+
+const AGGREGATE: Shape = Shape::Object(&[
+    ("count", Shape::Scalar),
+    ("sum", Shape::Scalar),
+    ("denied", Shape::Scalar),
+]);
+
+/// `input` as built by `check_action`; `test_input_shape_matches_check_input` keeps the two in step
+const INPUT: Shape = Shape::Object(&[
+    (
+        "identity",
+        Shape::Object(&[
+            ("id", Shape::Scalar),
+            ("type", Shape::Scalar),
+            ("display_name", Shape::Scalar),
+            ("metadata", Shape::Any),
+            ("organization_id", Shape::Scalar),
+            (
+                "credentials",
+                Shape::List(&Shape::Object(&[
+                    ("type", Shape::Scalar),
+                    ("provider", Shape::Scalar),
+                    ("value", Shape::Any),
+                ])),
+            ),
+        ]),
+    ),
+    (
+        "action",
+        Shape::Object(&[
+            ("action_type", Shape::Scalar),
+            ("amount", Shape::Scalar),
+            ("asset", Shape::Scalar),
+            ("source_address", Shape::Scalar),
+            ("target_address", Shape::Scalar),
+            ("metadata", Shape::Any),
+        ]),
+    ),
+    (
+        "context",
+        Shape::Object(&[
+            ("ip_address", Shape::Scalar),
+            ("device_id", Shape::Scalar),
+            ("user_agent", Shape::Scalar),
+            ("geo_location", Shape::Scalar),
+            ("timestamp", Shape::Scalar),
+            ("session_id", Shape::Scalar),
+            ("metadata", Shape::Any),
+        ]),
+    ),
+    (
+        "history",
+        Shape::Map(&Shape::Object(&[
+            ("all", COUNTS),
+            ("action_type", COUNTS),
+            ("asset", AGGREGATE),
+            ("asset_action_type", AGGREGATE),
+        ])),
+    ),
+]);
+
+// ============================================================================
+// AST access
+// ============================================================================
+
+/// One step of a reference: a field with its span, or a computed index
+enum Step<'a> {
+    Field(&'a str, &'a Value),
+    Index,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RuleKind {
+    Value,
+    Set,
+    Object,
+    Function,
+}
+
+impl RuleKind {
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Value => "single-value rule",
+            Self::Set => "set rule",
+            Self::Object => "object rule",
+            Self::Function => "function",
+        }
+    }
+}
+
+/// A rule definition in a module
+struct RuleDef<'a> {
+    name: &'a str,
+    kind: RuleKind,
+    /// `default` rules only supply a fallback and don't decide the rule's kind
+    is_default: bool,
+    /// The variable naming the rule in its head
+    head_var: &'a Value,
+    /// Set member for set rules, e.g. the message in `deny contains "..."`
+    key: Option<&'a Value>,
+    position: (u32, u32),
+    /// Byte range of the whole rule in the source
+    span: Range<usize>,
+}
+
+/// Line and column of a span
+fn position(span: &Value) -> (u32, u32) {
+    let field = |name: &str| span[name].as_u64().unwrap_or(0) as u32;
+    (field("line"), field("col"))
+}
+
+/// Name and span of a `{"Var": [span, name]}` node
+fn var(expr: &Value) -> Option<(&str, &Value)> {
+    let [span, name] = expr.get("Var")?.as_array()?.as_slice() else {
+        return None;
+    };
+    Some((name.as_str()?, span))
+}
+
+fn string_literal(expr: &Value) -> Option<(&str, &Value)> {
+    let [span, text] = expr.get("String").or_else(|| expr.get("RawString"))?.as_array()?.as_slice() else {
+        return None;
+    };
+    Some((text.as_str()?, span))
+}
+
+/// Root variable of a reference like `input.action["amount"]`, and the steps after it
+fn reference(expr: &Value) -> Option<(&str, &Value, Vec<Step<'_>>)> {
+    if let Some((name, span)) = var(expr) {
+        return Some((name, span, Vec::new()));
+    }
+    if let Some(dot) = expr.get("RefDot") {
+        let (root, span, mut steps) = reference(&dot["refr"])?;
+        let [field_span, field] = dot["field"].as_array()?.as_slice() else {
+            return None;
+        };
+        steps.push(Step::Field(field.as_str()?, field_span));
+        return Some((root, span, steps));
+    }
+    let brack = expr.get("RefBrack")?;
+    let (root, span, mut steps) = reference(&brack["refr"])?;
+    steps.push(match string_literal(&brack["index"]) {
+        Some((field, field_span)) => Step::Field(field, field_span),
+        None => Step::Index,
+    });
+    Some((root, span, steps))
+}
+
+/// The `Var` node at the root of a reference
+fn root_var(expr: &Value) -> Option<&Value> {
+    if expr.get("Var").is_some() {
+        return Some(expr);
+    }
+    root_var(&expr.get("RefDot").or_else(|| expr.get("RefBrack"))?["refr"])
+}
+
+/// Visit every object node under `value`
+fn walk<'a>(value: &'a Value, visit: &mut impl FnMut(&'a Value)) {
+    match value {
+        Value::Object(map) => {
+            visit(value);
+            for child in map.values() {
+                walk(child, visit);
+            }
+        }
+        Value::Array(items) => {
+            for child in items {
+                walk(child, visit);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn rule_defs(ast: &Value) -> Vec<RuleDef<'_>> {
+    let mut defs = Vec::new();
+    for rule in ast["rules"].as_array().into_iter().flatten() {
+        let (refr, kind, key, is_default) = if let Some(default) = rule.get("Default") {
+            let has_args = default["args"].as_array().is_some_and(|args| !args.is_empty());
+            let kind = if has_args { RuleKind::Function } else { RuleKind::Value };
+            (&default["refr"], kind, None, true)
+        } else if let Some(head) = rule.get("Spec").map(|spec| &spec["head"]) {
+            if let Some(compr) = head.get("Compr") {
+                let kind = if compr["refr"].get("Var").is_some() { RuleKind::Value } else { RuleKind::Object };
+                (&compr["refr"], kind, None, false)
+            } else if let Some(set) = head.get("Set") {
+                (&set["refr"], RuleKind::Set, Some(&set["key"]), false)
+            } else if let Some(func) = head.get("Func") {
+                (&func["refr"], RuleKind::Function, None, false)
+            } else {
+                continue;
+            }
+        } else {
+            continue;
+        };
+
+        let Some((head_var, (name, span))) = root_var(refr).and_then(|v| Some((v, var(v)?))) else {
+            continue;
+        };
+        let rule_span = &rule.get("Default").or_else(|| rule.get("Spec")).unwrap_or(rule)["span"];
+        let offset = |name: &str| rule_span[name].as_u64().unwrap_or(0) as usize;
+        defs.push(RuleDef {
+            name,
+            kind,
+            is_default,
+            head_var,
+            key,
+            position: position(span),
+            span: offset("start")..offset("end"),
+        });
+    }
+    defs
+}
+
+/// Dotted name of a reference's leading fields, e.g. `data.guardrail.lib.limits`
+fn dotted(root: &str, steps: &[Step]) -> String {
+    steps
+        .iter()
+        .map_while(|step| match step {
+            Step::Field(field, _) => Some(*field),
+            Step::Index => None,
+        })
+        .fold(root.to_string(), |name, field| format!("{}.{}", name, field))
+}
+
+fn package_name(ast: &Value) -> Option<String> {
+    reference(&ast["package"]["refr"]).map(|(root, _, steps)| dotted(root, &steps))
+}
+
+/// `data.guardrail.lib.*` paths a module imports or refers to inline, without
+/// the `data.` prefix, each at the position it is first used
+fn library_references(ast: &Value) -> BTreeMap<(u32, u32), String> {
+    let mut references = BTreeMap::new();
+    for part in [&ast["imports"], &ast["rules"]] {
+        walk(part, &mut |node| {
+            let Some(("data", span, steps)) = reference(node) else {
+                return;
+            };
+            let path = dotted("data", &steps);
+            let Some(path) = path.strip_prefix("data.") else {
+                return;
+            };
+            // Nested references repeat a prefix of the outermost one, which is visited first
+            if is_library_package(path) {
+                references.entry(position(span)).or_insert_with(|| path.to_string());
+            }
+        });
+    }
+    references
+}
+
+/// Names imports give to library paths, e.g. `limits` for `import data.guardrail.lib.limits`
+fn library_aliases(ast: &Value, source: &str) -> HashMap<String, String> {
+    let mut aliases = HashMap::new();
+    for import in ast["imports"].as_array().into_iter().flatten() {
+        let Some(("data", _, steps)) = reference(&import["refr"]) else {
+            continue;
+        };
+        let path = dotted("data", &steps);
+        let Some(path) = path.strip_prefix("data.").filter(|path| is_library_package(path)) else {
+            continue;
+        };
+        let alias = match import.get("as").filter(|span| span.is_object()) {
+            Some(span) => {
+                let offset = |name: &str| span[name].as_u64().unwrap_or(0) as usize;
+                source.get(offset("start")..offset("end")).map(str::to_string)
+            }
+            None => path.rsplit('.').next().map(str::to_string),
+        };
+        aliases.extend(alias.map(|alias| (alias, path.to_string())));
+    }
+    aliases
+}
+
+/// Whether `name` is, or is inside, a `guardrail.lib.<name>` package
+pub(crate) fn is_library_package(name: &str) -> bool {
+    name.strip_prefix(LIBRARY_PACKAGE)
+        .and_then(|rest| rest.strip_prefix('.'))
+        .is_some_and(|rest| !rest.is_empty())
+}
+
+/// Whether a module in `package` provides the value at `path`: the path leads
+/// into the package, or to a parent package containing it
+pub(crate) fn provides(package: &str, path: &str) -> bool {
+    let within = |outer: &str, inner: &str| {
+        inner.strip_prefix(outer).is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    };
+    within(package, path) || within(path, package)
+}
+
+/// Package of a module and the library paths it refers to, if it parses
+pub(crate) fn module_imports(source: &str) -> Option<(String, BTreeSet<String>)> {
+    let ast = parse(source).ok()?;
+    Some((package_name(&ast)?, library_references(&ast).into_values().collect()))
+}
+
+/// Top-level rules of a module other than functions, with their byte ranges in
+/// source order; none if it doesn't parse
+pub(crate) fn value_rules(source: &str) -> Vec<(String, Range<usize>)> {
+    let Ok(ast) = parse(source) else {
+        return Vec::new();
+    };
+    rule_defs(&ast)
+        .into_iter()
+        .filter(|def| def.kind != RuleKind::Function)
+        .map(|def| (def.name.to_string(), def.span))
+        .collect()
+}
+
+/// Rule names a module refers to, directly or as `data.guardrail.<name>`
+fn referenced_names<'a>(ast: &'a Value, defs: &[RuleDef<'a>]) -> HashSet<&'a str> {
+    let mut used = HashSet::new();
+    walk(&ast["rules"], &mut |node| {
+        if defs.iter().any(|d| std::ptr::eq(d.head_var, node)) {
+            return;
+        }
+        if let Some((name, _)) = var(node) {
+            used.insert(name);
+        } else if let Some(("data", _, steps)) = reference(node) {
+            if let [Step::Field(PACKAGE, _), Step::Field(name, _), ..] = steps.as_slice() {
+                used.insert(*name);
+            }
+        }
+    });
+    used
+}
+
+/// Parse a module, or describe why it doesn't parse
+fn parse(source: &str) -> std::result::Result<Value, LintDiagnostic> {
+    let mut engine = Engine::new();
+    engine
+        .add_policy(SOURCE_PATH.to_string(), source.to_string())
+        .map_err(|e| syntax_error(&e.to_string()))?;
+    let modules: Value = engine
+        .get_ast_as_json()
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .ok_or_else(|| syntax_error("could not read the module"))?;
+    Ok(modules[0]["ast"].clone())
+}
+
+/// Diagnostic for a regorus parse error, which reads `--> policy.rego:<line>:<col>` ... `error: <message>`
+fn syntax_error(error: &str) -> LintDiagnostic {
+    let location = error
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("--> "))
+        .and_then(|location| {
+            let mut parts = location.rsplitn(3, ':');
+            let column = parts.next()?.parse().ok()?;
+            let line = parts.next()?.parse().ok()?;
+            Some((line, column))
+        })
+        .unwrap_or((1, 1));
+    let message = error.lines().rev().map(str::trim).find(|l| !l.is_empty()).unwrap_or(error);
+
+    diagnostic(
+        "SYNTAX",
+        LintSeverity::Error,
+        location,
+        message.strip_prefix("error: ").unwrap_or(message).to_string(),
+    )
+}
+
+fn diagnostic(code: &str, severity: LintSeverity, (line, column): (u32, u32), message: String) -> LintDiagnostic {
+    LintDiagnostic {
+        code: code.to_string(),
+        severity,
+        message,
+        line,
+        column,
+    }
+}
+
+// ============================================================================
+// Checks
+// ============================================================================
+
+fn check_package(ast: &Value, kind: PolicyKind, diagnostics: &mut Vec<LintDiagnostic>) {
+    let name = package_name(ast).unwrap_or_default();
+    let message = match kind {
+        PolicyKind::Decision if name != PACKAGE => {
+            format!("Package is `{}`; only `package {}` is evaluated", name, PACKAGE)
+        }
+        PolicyKind::Library if !is_library_package(&name) => {
+            format!("Package is `{}`; libraries must use `package {}.<name>`", name, LIBRARY_PACKAGE)
+        }
+        _ => return,
+    };
+    diagnostics.push(diagnostic("PACKAGE", LintSeverity::Error, position(&ast["package"]["span"]), message));
+}
+
+fn check_keywords(defs: &[RuleDef], diagnostics: &mut Vec<LintDiagnostic>) {
+    for def in defs.iter().filter(|d| KEYWORDS.contains(&d.name)) {
+        diagnostics.push(diagnostic(
+            "MISSING_IMPORT",
+            LintSeverity::Error,
+            def.position,
+            format!(
+                "`{0}` is parsed as a rule name, so the rule before it has no conditions; add `import future.keywords.{0}` or `import rego.v1`",
+                def.name
+            ),
+        ));
+    }
+}
+
+/// Why a literal set member can't be read by `parse_decision`, if it can't
+fn member_error(rule: &str, key: &Value) -> Option<&'static str> {
+    const LITERALS: &[&str] = &["String", "RawString", "Object", "Number", "True", "False", "Null", "Array", "Set"];
+    let literal = LITERALS.iter().find(|l| key.get(**l).is_some())?;
+    let object_has = |names: &[&str]| {
+        key["Object"]["fields"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|field| string_literal(field.get(1)?))
+            .any(|(name, _)| names.contains(&name))
+    };
+
+    match (rule, *literal) {
+        (_, "String" | "RawString") => None,
+        ("deny", "Object") => (!object_has(&["code", "message"])).then_some("deny objects need a `code` or `message`"),
+        ("obligations", "Object") => (!object_has(&["type"])).then_some("obligation objects need a `type`"),
+        ("deny", _) => Some("deny entries must be strings or objects with a `code` or `message`"),
+        ("obligations", _) => Some("obligations must be strings or objects with a `type`"),
+        _ => Some("required_approvers entries must be strings; other values are ignored"),
+    }
+}
+
+fn check_outputs(defs: &[RuleDef], diagnostics: &mut Vec<LintDiagnostic>) {
+    for def in defs.iter().filter(|d| OUTPUTS.contains(&d.name) && !d.is_default) {
+        let message = if def.kind != RuleKind::Set {
+            format!(
+                "`{0}` must be a set rule (`{0} contains ... if {{ ... }}`), not a {1}",
+                def.name,
+                def.kind.describe()
+            )
+        } else if let Some(error) = def.key.and_then(|key| member_error(def.name, key)) {
+            error.to_string()
+        } else {
+            continue;
+        };
+        diagnostics.push(diagnostic("OUTPUT_TYPE", LintSeverity::Error, def.position, message));
+    }
+}
+
+/// The first part of an `input` reference outside the known input, with its span
+/// and the fields that would have been valid there
+fn unknown_input<'a>(steps: &[Step<'a>]) -> Option<(String, &'a Value, Vec<&'static str>)> {
+    let mut shape: &Shape = &INPUT;
+    let mut path = "input".to_string();
+    for step in steps {
+        match step {
+            Step::Field(field, _) => path = format!("{}.{}", path, field),
+            Step::Index => path.push_str("[_]"),
+        }
+        shape = match (shape, step) {
+            (Shape::Any, _) | (Shape::Object(_) | Shape::Scalar, Step::Index) => return None,
+            (Shape::Map(inner) | Shape::List(inner), _) => inner,
+            (Shape::Object(fields), Step::Field(field, span)) => match fields.iter().find(|(f, _)| f == field) {
+                Some((_, inner)) => inner,
+                None => return Some((path, span, fields.iter().map(|(f, _)| *f).collect())),
+            },
+            (Shape::Scalar, Step::Field(_, span)) => return Some((path, span, Vec::new())),
+        };
+    }
+    None
+}
+
+fn check_input_paths(ast: &Value, diagnostics: &mut Vec<LintDiagnostic>) {
+    let mut reported = BTreeSet::new();
+    walk(&ast["rules"], &mut |node| {
+        let Some(("input", _, steps)) = reference(node) else {
+            return;
+        };
+        let Some((path, span, known)) = unknown_input(&steps) else {
+            return;
+        };
+        // Nested references repeat the same prefix; report each spot once
+        if !reported.insert(position(span)) {
+            return;
+        }
+        let hint = match known.is_empty() {
+            true => String::new(),
+            false => format!("; expected one of {}", known.join(", ")),
+        };
+        diagnostics.push(diagnostic(
+            "UNKNOWN_INPUT",
+            LintSeverity::Warning,
+            position(span),
+            format!("`{}` is not part of the check input{}", path, hint),
+        ));
+    });
+}
+
+fn check_imports(ast: &Value, libraries: &[String], diagnostics: &mut Vec<LintDiagnostic>) {
+    for (position, path) in library_references(ast) {
+        if libraries.iter().any(|package| provides(package, &path)) {
+            continue;
+        }
+        diagnostics.push(diagnostic(
+            "UNKNOWN_IMPORT",
+            LintSeverity::Error,
+            position,
+            format!("`data.{}` is not provided by any active library policy", path),
+        ));
+    }
+}
+
+fn check_import_aliases(ast: &Value, diagnostics: &mut Vec<LintDiagnostic>) {
+    for import in ast["imports"].as_array().into_iter().flatten() {
+        if import.get("as").is_some_and(Value::is_object) {
+            continue;
+        }
+        let Some(("data", _, steps)) = reference(&import["refr"]) else {
+            continue;
+        };
+        let path = dotted("data", &steps);
+        let Some(name) = path.rsplit('.').next().filter(|_| is_library_package(&path["data.".len()..])) else {
+            continue;
+        };
+        diagnostics.push(diagnostic(
+            "IMPORT_ALIAS",
+            LintSeverity::Error,
+            position(&import["span"]),
+            format!("`{0}` is undefined in rule bodies unless the import names it: `import {1} as {0}`", name, path),
+        ));
+    }
+}
+
+fn check_imported_calls(ast: &Value, source: &str, diagnostics: &mut Vec<LintDiagnostic>) {
+    let aliases = library_aliases(ast, source);
+    if aliases.is_empty() {
+        return;
+    }
+    walk(&ast["rules"], &mut |node| {
+        let Some((root, span, steps)) = node.get("Call").and_then(|call| reference(&call["fcn"])) else {
+            return;
+        };
+        let Some(path) = aliases.get(root) else {
+            return;
+        };
+        let function = dotted(root, &steps);
+        diagnostics.push(diagnostic(
+            "IMPORTED_CALL",
+            LintSeverity::Error,
+            position(span),
+            format!(
+                "`{}` calls a library function through its import, which fails at evaluation; call `data.{}{}` instead",
+                function,
+                path,
+                &function[root.len()..]
+            ),
+        ));
+    });
+}
+
+fn check_conflicts(defs: &[RuleDef], others: &[(&str, Vec<RuleDef>)], diagnostics: &mut Vec<LintDiagnostic>) {
+    for (policy, other_defs) in others {
+        let mut seen = HashSet::new();
+        for def in defs.iter().filter(|d| !d.is_default && !KEYWORDS.contains(&d.name)) {
+            let Some(other) = other_defs.iter().find(|o| o.name == def.name && !o.is_default) else {
+                continue;
+            };
+            if !seen.insert(def.name) {
+                continue;
+            }
+
+            if other.kind != def.kind {
+                diagnostics.push(diagnostic(
+                    "RULE_CONFLICT",
+                    LintSeverity::Error,
+                    def.position,
+                    format!(
+                        "`{}` is a {} here but a {} in policy {} (line {}); the two cannot be evaluated together",
+                        def.name,
+                        def.kind.describe(),
+                        other.kind.describe(),
+                        policy,
+                        other.position.0
+                    ),
+                ));
+            } else if matches!(def.kind, RuleKind::Value | RuleKind::Function) {
+                diagnostics.push(diagnostic(
+                    "RULE_CONFLICT",
+                    LintSeverity::Warning,
+                    def.position,
+                    format!(
+                        "`{}` is also defined by policy {} (line {}); evaluation fails whenever they produce different values",
+                        def.name, policy, other.position.0
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+fn check_unused(defs: &[RuleDef], used: &HashSet<&str>, diagnostics: &mut Vec<LintDiagnostic>) {
+    let mut reported = HashSet::new();
+    for def in defs {
+        if OUTPUTS.contains(&def.name)
+            || KEYWORDS.contains(&def.name)
+            || def.name.starts_with("test_")
+            || used.contains(def.name)
+            || !reported.insert(def.name)
+        {
+            continue;
+        }
+        diagnostics.push(diagnostic(
+            "UNUSED_RULE",
+            LintSeverity::Warning,
+            def.position,
+            format!(
+                "`{}` is not used by any policy; only {} affect decisions",
+                def.name,
+                OUTPUTS.join(", ")
+            ),
+        ));
+    }
+}
+
+/// Lint a policy as it would be loaded alongside `others` (policy name and source)
+pub fn lint(rego_source: &str, kind: PolicyKind, others: &[(String, String)]) -> LintReport {
+    let ast = match parse(rego_source) {
+        Ok(ast) => ast,
+        Err(syntax) => return LintReport::new(vec![syntax]),
+    };
+    let package = package_name(&ast).unwrap_or_default();
+    let other_asts: Vec<(&str, String, Value)> = others
+        .iter()
+        .filter_map(|(name, source)| {
+            let ast = parse(source).ok()?;
+            Some((name.as_str(), package_name(&ast)?, ast))
+        })
+        .collect();
+
+    let defs = rule_defs(&ast);
+    // Rules only meet rules of the same package
+    let other_defs: Vec<(&str, Vec<RuleDef>)> = other_asts
+        .iter()
+        .filter(|(_, other_package, _)| *other_package == package)
+        .map(|(name, _, ast)| (*name, rule_defs(ast)))
+        .collect();
+    let mut used = referenced_names(&ast, &defs);
+    for (_, _, ast) in &other_asts {
+        used.extend(referenced_names(ast, &rule_defs(ast)));
+    }
+    let libraries: Vec<String> = other_asts
+        .iter()
+        .map(|(_, package, _)| package.clone())
+        .chain(std::iter::once(package))
+        .filter(|package| is_library_package(package))
+        .collect();
+
+    let mut diagnostics = Vec::new();
+    check_package(&ast, kind, &mut diagnostics);
+    check_keywords(&defs, &mut diagnostics);
+    if kind == PolicyKind::Decision {
+        check_outputs(&defs, &mut diagnostics);
+    }
+    check_imports(&ast, &libraries, &mut diagnostics);
+    check_import_aliases(&ast, &mut diagnostics);
+    check_imported_calls(&ast, rego_source, &mut diagnostics);
+    check_conflicts(&defs, &other_defs, &mut diagnostics);
+    check_input_paths(&ast, &mut diagnostics);
+    if kind == PolicyKind::Decision {
+        check_unused(&defs, &used, &mut diagnostics);
+    }
+
+    LintReport::new(diagnostics)
+}
+
+/// Lint a policy against the enforced and shadow policies it would be loaded with
+pub async fn lint_policy(
+    db: &PgPool,
+    rego_source: &str,
+    kind: PolicyKind,
+    replaces_policy_id: Option<Uuid>,
+) -> Result<LintReport> {
+    let loaded = sqlx::query!(
+        r#"
+        SELECT name, rego_source
+        FROM policies
+        WHERE (is_active = true OR is_shadow = true) AND ($1::uuid IS NULL OR id <> $1)
+        ORDER BY created_at ASC
+        "#,
+        replaces_policy_id,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let others: Vec<(String, String)> = loaded.into_iter().map(|p| (p.name, p.rego_source)).collect();
+    Ok(lint(rego_source, kind, &others))
+}
+
+/// Turn lint errors into an `InvalidRego` error; warnings are only logged
+pub fn ensure_clean(policy_name: &str, report: &LintReport) -> Result<()> {
+    for warning in report.diagnostics.iter().filter(|d| d.severity == LintSeverity::Warning) {
+        tracing::warn!("Policy {} line {}: {}", policy_name, warning.line, warning.message);
+    }
+    if report.passed {
+        return Ok(());
+    }
+
+    let errors: Vec<String> = report
+        .diagnostics
+        .iter()
+        .filter(|d| d.severity == LintSeverity::Error)
+        .map(|d| format!("line {}: {}", d.line, d.message))
+        .collect();
+    Err(GuardRailError::InvalidRego(format!(
+        "{} lint error(s): {}",
+        report.errors,
+        errors.join("; ")
+    )))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+pub async fn lint_draft(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<LintPolicyRequest>,
+) -> impl IntoResponse {
+    match lint_policy(&state.db, &req.rego_source, req.kind, req.replaces_policy_id).await {
+        Ok(report) => (StatusCode::OK, Json(ApiResponse::success(report))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<LintReport>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(report: &LintReport) -> Vec<(&str, u32)> {
+        report.diagnostics.iter().map(|d| (d.code.as_str(), d.line)).collect()
+    }
+
+    #[test]
+    fn test_reports_common_mistakes() {
+        let clean = r#"package guardrail
+import future.keywords.if
+import future.keywords.contains
+
+deny contains {"code": "LIMIT", "message": "Over limit"} if {
+    over_limit
+}
+required_approvers contains "treasury" if {
+    input.history["24h"].asset.sum > 1000
+}
+over_limit if to_number(input.action.amount) > input.identity.credentials[0].value.limit
+"#;
+        let report = lint(clean, PolicyKind::Decision, &[]);
+        assert!(report.diagnostics.is_empty(), "{:?}", report.diagnostics);
+
+        let sloppy = r#"package guardrails
+deny := true if { input.action.amout > 1 }
+required_approvers contains 5 if { input.context.geo_location.country == "US" }
+obligations contains {"hours": 24} if { true }
+helper := 1
+"#;
+        let report = lint(sloppy, PolicyKind::Decision, &[]);
+        assert!(!report.passed);
+        assert_eq!(
+            codes(&report),
+            vec![
+                ("PACKAGE", 1),
+                ("OUTPUT_TYPE", 2),
+                ("MISSING_IMPORT", 2),
+                ("UNKNOWN_INPUT", 2),
+                ("OUTPUT_TYPE", 3),
+                ("MISSING_IMPORT", 3),
+                ("UNKNOWN_INPUT", 3),
+                ("OUTPUT_TYPE", 4),
+                ("MISSING_IMPORT", 4),
+                ("UNUSED_RULE", 5),
+            ]
+        );
+        assert!(report.diagnostics[3].message.contains("input.action.amout"));
+
+        let broken = lint("package guardrail\ndeny[msg] {\n  msg := \n}", PolicyKind::Decision, &[]);
+        assert_eq!(broken.diagnostics[0].code, "SYNTAX");
+        assert_eq!((broken.diagnostics[0].line, broken.diagnostics[0].column), (2, 11));
+        assert_eq!(broken.diagnostics[0].message, "expecting }");
+    }
+
+    #[test]
+    fn test_conflicts_with_loaded_policies() {
+        let loaded = vec![(
+            "limits".to_string(),
+            "package guardrail\nmax_amount := 100\ndeny[\"too big\"] { to_number(input.action.amount) > max_amount }\nrisky[x] { x := 1 }"
+                .to_string(),
+        )];
+        let draft = "package guardrail\n\nmax_amount := 500\nrisky := true\ndeny[\"risky\"] { risky }";
+
+        let report = lint(draft, PolicyKind::Decision, &loaded);
+        assert_eq!(codes(&report), vec![("RULE_CONFLICT", 3), ("RULE_CONFLICT", 4)]);
+        assert_eq!(report.warnings, 1);
+        assert_eq!(report.errors, 1);
+
+        // A helper used only by another policy is not unused
+        let helper = lint("package guardrail\nmax_amount := 100", PolicyKind::Decision, &[]);
+        assert_eq!(codes(&helper), vec![("UNUSED_RULE", 2)]);
+        let other = ("other".to_string(), "package guardrail\nx := data.guardrail.limit".to_string());
+        let shared = lint("package guardrail\nlimit := 1", PolicyKind::Decision, &[other]);
+        assert!(shared.diagnostics.is_empty(), "{:?}", shared.diagnostics);
+    }
+
+    #[test]
+    fn test_libraries() {
+        let library = "package guardrail.lib.limits\nmax := 100\ndeny[\"x\"] { true }";
+        assert!(lint(library, PolicyKind::Library, &[]).diagnostics.is_empty());
+        assert_eq!(codes(&lint("package guardrail\nmax := 100", PolicyKind::Library, &[])), vec![("PACKAGE", 1)]);
+
+        // Rules only conflict within a package; imports must resolve to a loaded library
+        let loaded = vec![("limits".to_string(), library.to_string())];
+        let policy = "package guardrail\nimport data.guardrail.lib.limits as limits\nimport data.guardrail.lib.velocity as velocity\nmax := 5\ndeny[\"big\"] { input.action.amount > limits.max + max }\ndeny[\"fast\"] { data.guardrail.lib.kyc.level < 2 }";
+        let report = lint(policy, PolicyKind::Decision, &loaded);
+        assert_eq!(codes(&report), vec![("UNKNOWN_IMPORT", 3), ("UNKNOWN_IMPORT", 6)]);
+        assert!(report.diagnostics[1].message.contains("data.guardrail.lib.kyc.level"));
+
+        // regorus resolves rules through an aliased import, but not functions
+        let call = "package guardrail\nimport data.guardrail.lib.limits\nimport data.guardrail.lib.limits as l\ndeny[\"big\"] { input.action.amount > l.max_for(input.action.asset) }";
+        let report = lint(call, PolicyKind::Decision, &loaded);
+        assert_eq!(codes(&report), vec![("IMPORT_ALIAS", 2), ("IMPORTED_CALL", 4)]);
+        assert!(report.diagnostics[1].message.contains("call `data.guardrail.lib.limits.max_for` instead"));
+
+        assert_eq!(
+            module_imports(policy).unwrap(),
+            (
+                "guardrail".to_string(),
+                BTreeSet::from([
+                    "guardrail.lib.kyc.level".to_string(),
+                    "guardrail.lib.limits".to_string(),
+                    "guardrail.lib.velocity".to_string()
+                ])
+            )
+        );
+    }
+
+    #[test]
+    fn test_input_shape_matches_check_input() {
+        let action: guardrail_shared::Action = serde_json::from_value(serde_json::json!({
+            "action_type": "WITHDRAWAL", "amount": "1", "asset": "BTC",
+            "source_address": "a", "target_address": "b", "metadata": {},
+        }))
+        .unwrap();
+        let context: guardrail_shared::ActionContext = serde_json::from_value(serde_json::json!({
+            "ip_address": null, "device_id": null, "user_agent": null, "geo_location": null,
+            "timestamp": "2024-01-01T00:00:00Z", "session_id": null, "metadata": {},
+        }))
+        .unwrap();
+        let identity = serde_json::json!({
+            "id": "x", "type": "HUMAN", "display_name": "x", "metadata": {}, "organization_id": null,
+            "credentials": [{"type": "KYC", "provider": "p", "value": {}}],
+        });
+        let mut input = crate::build_input(identity, &action, &context);
+        input["history"] = serde_json::json!({"24h": crate::history::WindowAggregates::default()});
+
+        fn covers(shape: &Shape, value: &Value, path: &str) {
+            match (shape, value) {
+                (Shape::Object(fields), Value::Object(map)) => {
+                    for (key, child) in map {
+                        let path = format!("{}.{}", path, key);
+                        let inner = fields.iter().find(|(f, _)| f == key).map(|(_, s)| s);
+                        covers(inner.unwrap_or_else(|| panic!("{} missing from INPUT", path)), child, &path);
+                    }
+                }
+                (Shape::Map(inner), Value::Object(map)) => map.values().for_each(|v| covers(inner, v, path)),
+                (Shape::List(inner), Value::Array(items)) => items.iter().for_each(|v| covers(inner, v, path)),
+                _ => {}
+            }
+        }
+        covers(&INPUT, &input, "input");
+    }
+}
@@ -2,23 +2,62 @@
 //!
 //! Evaluates actions against Rego policies using the regorus engine.
 
+mod analytics;
+mod approvals;
+mod backtest;
+mod batch;
+mod builtins;
+mod bundles;
+mod controls;
+mod data_documents;
+mod decisions;
+mod effective;
+mod explain;
+mod fallback;
+mod grpc;
+mod history;
+mod ledger;
+mod libraries;
+mod lint;
+mod outbox;
+mod rego_tests;
+mod shadow;
+mod snapshot;
+mod targeting;
+mod templates;
+mod versions;
+
+#[cfg(test)]
+mod bench;
+
+use approvals::ApprovalConfig;
+use bundles::BundleConfig;
+use controls::ControlConfig;
+use effective::ScheduleConfig;
+use fallback::{FallbackConfig, FallbackCounters};
+use grpc::GrpcConfig;
+use history::HistoryConfig;
+use outbox::OutboxConfig;
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
-    routing::{get, post},
+    routing::{get, post, put},
     Json, Router,
 };
 use guardrail_shared::{
     Action, ActionContext, ApiResponse, CheckActionRequest, CreatePolicyRequest,
-    Decision, GuardRailError, PaginatedResponse, Policy, PolicyDecision, Result,
+    Decision, DecisionExplanation, DegradedCause, DenyReason, EventType, GuardRailError, Obligation, PaginatedResponse, Policy, PolicyAttribution,
+    PolicyDecision, PolicyKind, PolicyTargets, PolicyTestReport, Result,
 };
+use ledger::{LedgerClient, SYSTEM_ACTOR_ID};
 use regorus::Engine;
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
+use snapshot::Snapshot;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, Notify};
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -31,60 +70,469 @@ use uuid::Uuid;
 #[derive(Clone)]
 pub struct AppState {
     pub db: PgPool,
-    pub engine: Arc<RwLock<PolicyEngine>>,
+    /// Enforced policies; checks evaluate against the current snapshot
+    pub engine: Arc<Snapshot<PolicyEngine>>,
+    /// Enforced plus shadow policies, evaluated after each check
+    pub shadow: Arc<Snapshot<shadow::ShadowEngine>>,
+    pub shadow_queue: Arc<shadow::ShadowQueue>,
+    /// Serialises reloads so a slow reload cannot overwrite a newer one
+    pub reload_lock: Arc<Mutex<()>>,
+    pub ledger: LedgerClient,
+    pub approval_config: ApprovalConfig,
+    pub history_config: HistoryConfig,
+    pub bundle_config: BundleConfig,
+    pub fallback_config: FallbackConfig,
+    pub fallback_counters: Arc<FallbackCounters>,
+    pub schedule_config: ScheduleConfig,
+    /// Wakes the scheduler when a policy's window changes
+    pub schedule_changed: Arc<Notify>,
+    pub outbox_config: OutboxConfig,
+    /// Wakes the ledger relay when decisions are committed
+    pub outbox_ready: Arc<Notify>,
+    pub control_config: ControlConfig,
+    /// Wakes the control sweeper when a control is created or changed
+    pub controls_changed: Arc<Notify>,
 }
 
 /// Policy engine wrapper around regorus
+///
+/// Loading policies needs `&mut self`; evaluation works on a clone of the compiled
+/// engine, so a loaded `PolicyEngine` can be shared and evaluated concurrently.
 pub struct PolicyEngine {
     engine: Engine,
-    loaded_policies: Vec<Uuid>,
+    /// Library packages, loaded into every engine ahead of the decision policies
+    libraries: Vec<LoadedLibrary>,
+    loaded_policies: Vec<LoadedPolicy>,
+    /// Data documents visible to every policy as `data.*`
+    data: Option<Arc<data_documents::DataSet>>,
+    /// Revision of the enforced policy set, recorded on decisions
+    policy_set_revision: Option<String>,
+    /// Lists behind `guardrail.in_list`, shared with every engine's builtins
+    lists: builtins::SharedLists,
+    /// Engines for checks that only some targeted policies apply to
+    subsets: targeting::SubsetEngines,
+}
+
+/// A policy loaded into the engine, with its own engine for attribution
+struct LoadedPolicy {
+    id: Uuid,
+    name: String,
+    version: String,
+    rego_source: String,
+    targets: PolicyTargets,
+    engine: Engine,
+}
+
+/// A library policy: a module other policies import, which decides nothing itself
+struct LoadedLibrary {
+    id: Uuid,
+    name: String,
+    version: String,
+    rego_source: String,
+}
+
+impl Default for PolicyEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PolicyEngine {
     pub fn new() -> Self {
+        let lists = builtins::SharedLists::default();
         Self {
-            engine: Engine::new(),
+            engine: Self::new_engine(&lists),
+            libraries: Vec::new(),
             loaded_policies: Vec::new(),
+            data: None,
+            policy_set_revision: None,
+            lists,
+            subsets: targeting::SubsetEngines::default(),
+        }
+    }
+
+    /// A regorus engine with the GuardRail builtins registered
+    fn new_engine(lists: &builtins::SharedLists) -> Engine {
+        let mut engine = Engine::new();
+        builtins::register(&mut engine, lists).expect("builtins are registered once on a fresh engine");
+        engine
+    }
+
+    /// A regorus engine with the builtins and the loaded libraries
+    fn library_engine(&self) -> Result<Engine> {
+        let mut engine = Self::new_engine(&self.lists);
+        for library in &self.libraries {
+            engine
+                .add_policy(Self::module_name(library.id, &library.name), library.rego_source.clone())
+                .map_err(|e| GuardRailError::PolicyEvaluation(format!("Failed to load library {}: {}", library.name, e)))?;
+        }
+        Ok(engine)
+    }
+
+    /// An empty engine sharing this one's data documents and libraries, for
+    /// evaluating policies outside the enforced set
+    pub fn scratch(&self) -> Result<PolicyEngine> {
+        let mut engine = PolicyEngine::new();
+        if let Some(data) = self.data.clone() {
+            engine.set_data(data)?;
+        }
+        for library in &self.libraries {
+            engine.load_library(library.id, &library.name, &library.version, &library.rego_source)?;
+        }
+        Ok(engine)
+    }
+
+    /// Module paths and sources of the loaded libraries, leaving out `except`
+    pub(crate) fn library_modules(&self, except: Option<Uuid>) -> Vec<(String, String)> {
+        self.libraries
+            .iter()
+            .filter(|l| Some(l.id) != except)
+            .map(|l| (Self::module_name(l.id, &l.name), l.rego_source.clone()))
+            .collect()
+    }
+
+    /// A regorus engine with this one's libraries and data documents plus one more
+    /// module, for Rego evaluated outside the decision set
+    pub(crate) fn engine_with(&self, module_name: &str, rego_source: &str) -> Result<Engine> {
+        let mut engine = self.library_engine()?;
+        if let Some(data) = &self.data {
+            Self::add_data(&mut engine, data)?;
+        }
+        engine
+            .add_policy(module_name.to_string(), rego_source.to_string())
+            .map_err(|e| GuardRailError::PolicyEvaluation(format!("Failed to load {}: {}", module_name, e)))?;
+        engine
+            .eval_query("true".to_string(), false)
+            .map_err(|e| GuardRailError::PolicyEvaluation(format!("Failed to prepare engine: {}", e)))?;
+        Ok(engine)
+    }
+
+    /// Make a data set visible to the policies in this engine
+    pub fn set_data(&mut self, data: Arc<data_documents::DataSet>) -> Result<()> {
+        let engines = std::iter::once(&mut self.engine).chain(self.loaded_policies.iter_mut().map(|p| &mut p.engine));
+        for engine in engines {
+            Self::add_data(engine, &data)?;
+        }
+        *self.lists.write().unwrap_or_else(|e| e.into_inner()) = data.lists.clone();
+        self.data = Some(data);
+        Ok(())
+    }
+
+    /// Revision of the data set in use, if any
+    pub fn data_revision(&self) -> Option<&str> {
+        self.data.as_ref().map(|d| d.revision.as_str())
+    }
+
+    /// Revision of the loaded policy set, once recorded by a reload
+    pub fn policy_set_revision(&self) -> Option<&str> {
+        self.policy_set_revision.as_deref()
+    }
+
+    pub(crate) fn add_data(engine: &mut Engine, data: &data_documents::DataSet) -> Result<()> {
+        engine
+            .add_data(data.document.clone().into())
+            .map_err(|e| GuardRailError::PolicyEvaluation(format!("Failed to load data documents: {}", e)))
+    }
+
+    /// Load a library, making its package importable by every policy in the engine
+    pub fn load_library(&mut self, policy_id: Uuid, name: &str, version: &str, rego_source: &str) -> Result<()> {
+        let module_name = Self::module_name(policy_id, name);
+        let rego_source = &*rego_tests::without_tests(rego_source);
+
+        // Compile alongside the other libraries first so a bad one leaves the engines untouched
+        let mut isolated = self.library_engine()?;
+        isolated
+            .add_policy(module_name.clone(), rego_source.to_string())
+            .map_err(|e| GuardRailError::PolicyEvaluation(format!("Failed to load library: {}", e)))?;
+
+        let engines = std::iter::once(&mut self.engine).chain(self.loaded_policies.iter_mut().map(|p| &mut p.engine));
+        for engine in engines {
+            engine
+                .add_policy(module_name.clone(), rego_source.to_string())
+                .map_err(|e| GuardRailError::PolicyEvaluation(format!("Failed to load library: {}", e)))?;
         }
+
+        self.libraries.push(LoadedLibrary {
+            id: policy_id,
+            name: name.to_string(),
+            version: version.to_string(),
+            rego_source: rego_source.to_string(),
+        });
+        Ok(())
     }
 
     /// Load a policy into the engine
-    pub fn load_policy(&mut self, policy_id: Uuid, name: &str, rego_source: &str) -> Result<()> {
-        // Create a unique module name for this policy
-        let module_name = format!("policy/{}", name);
-        
+    pub fn load_policy(
+        &mut self,
+        policy_id: Uuid,
+        name: &str,
+        version: &str,
+        rego_source: &str,
+    ) -> Result<()> {
+        self.load_targeted_policy(policy_id, name, version, rego_source, &PolicyTargets::default())
+    }
+
+    /// Load a policy that is only evaluated for actions matching `targets`
+    pub fn load_targeted_policy(
+        &mut self,
+        policy_id: Uuid,
+        name: &str,
+        version: &str,
+        rego_source: &str,
+        targets: &PolicyTargets,
+    ) -> Result<()> {
+        // Create a unique module name for this policy, and leave its inline tests out of decisions
+        let module_name = Self::module_name(policy_id, name);
+        let rego_source = &*rego_tests::without_tests(rego_source);
+
+        // Compile in isolation first so a bad policy leaves the merged engine untouched
+        let mut isolated = self.library_engine()?;
+        isolated
+            .add_policy(module_name.clone(), rego_source.to_string())
+            .map_err(|e| GuardRailError::PolicyEvaluation(format!("Failed to load policy: {}", e)))?;
+        if let Some(data) = &self.data {
+            Self::add_data(&mut isolated, data)?;
+        }
+
         self.engine
             .add_policy(module_name, rego_source.to_string())
             .map_err(|e| GuardRailError::PolicyEvaluation(format!("Failed to load policy: {}", e)))?;
-        
-        self.loaded_policies.push(policy_id);
+
+        self.loaded_policies.push(LoadedPolicy {
+            id: policy_id,
+            name: name.to_string(),
+            version: version.to_string(),
+            rego_source: rego_source.to_string(),
+            targets: targets.clone(),
+            engine: isolated,
+        });
+        Ok(())
+    }
+
+    /// Run regorus' one-off analysis so evaluation clones start prepared.
+    ///
+    /// Optional, but without it every evaluation repeats the analysis.
+    pub fn prepare(&mut self) -> Result<()> {
+        let engines = std::iter::once(&mut self.engine).chain(self.loaded_policies.iter_mut().map(|p| &mut p.engine));
+        for engine in engines {
+            engine
+                .eval_query("true".to_string(), false)
+                .map_err(|e| GuardRailError::PolicyEvaluation(format!("Failed to prepare engine: {}", e)))?;
+        }
         Ok(())
     }
 
     /// Evaluate an action against loaded policies
-    pub fn evaluate(&mut self, input: &serde_json::Value) -> Result<PolicyEvalResult> {
+    pub fn evaluate(&self, input: &serde_json::Value) -> Result<PolicyEvalResult> {
+        self.evaluate_with(input, false)
+    }
+
+    /// Evaluate an action and capture an explanation of how the decision was reached
+    pub fn explain(&self, input: &serde_json::Value) -> Result<PolicyEvalResult> {
+        self.evaluate_with(input, true)
+    }
+
+    /// Loaded policies whose targets match `input`, in load order
+    fn select(&self, input: &serde_json::Value) -> Vec<usize> {
+        self.loaded_policies
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| targeting::matches(&p.targets, input))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// A private copy of the merged engine for the `selected` policies
+    fn engine_for(&self, selected: &[usize]) -> Result<Engine> {
+        if selected.len() == self.loaded_policies.len() {
+            return Ok(self.engine.clone());
+        }
+
+        self.subsets.get_or_build(selected, || {
+            let mut engine = self.library_engine()?;
+            for policy in selected.iter().map(|&i| &self.loaded_policies[i]) {
+                engine
+                    .add_policy(Self::module_name(policy.id, &policy.name), policy.rego_source.clone())
+                    .map_err(|e| GuardRailError::PolicyEvaluation(format!("Failed to load policy: {}", e)))?;
+            }
+            if let Some(data) = &self.data {
+                Self::add_data(&mut engine, data)?;
+            }
+            engine
+                .eval_query("true".to_string(), false)
+                .map_err(|e| GuardRailError::PolicyEvaluation(format!("Failed to prepare engine: {}", e)))?;
+            Ok(engine)
+        })
+    }
+
+    fn evaluate_with(&self, input: &serde_json::Value, explain: bool) -> Result<PolicyEvalResult> {
+        // Evaluate on a private copy so concurrent evaluations don't share input or state
+        let indices = self.select(input);
+        let mut engine = self.engine_for(&indices)?;
+        let selected: Vec<&LoadedPolicy> = indices.iter().map(|&i| &self.loaded_policies[i]).collect();
+
         // Set the input for evaluation - convert serde_json::Value to regorus::Value
-        self.engine.set_input(input.clone().into());
+        engine.set_input(input.clone().into());
+
+        if explain {
+            engine.set_enable_coverage(true);
+            engine.set_gather_prints(true);
+        }
 
         // Query for the decision
         // Capture the package value in variable 'x'
         let query = "x = data.guardrail";
         
-        let results = self.engine
+        let results = engine
             .eval_query(query.to_string(), false)
             .map_err(|e| GuardRailError::PolicyEvaluation(format!("Failed to evaluate: {}", e)))?;
 
+        let explanation = if explain {
+            Some(Self::collect_trace(&mut engine, &selected, input, &results)?)
+        } else {
+            None
+        };
+
         // Parse the results
-        let decision = self.parse_decision(&results)?;
-        
+        let mut decision = Self::parse_decision(&results)?;
+        decision.policies = Self::attribute(&selected, input, &decision);
+        decision.evaluated_policies = selected.iter().map(|p| p.id).collect();
+        decision.explanation = explanation;
+        decision.data_revision = self.data_revision().map(str::to_string);
+        decision.policy_set_revision = self.policy_set_revision.clone();
+
         Ok(decision)
     }
 
-    fn parse_decision(&self, results: &regorus::QueryResults) -> Result<PolicyEvalResult> {
+    fn collect_trace(
+        engine: &mut Engine,
+        selected: &[&LoadedPolicy],
+        input: &serde_json::Value,
+        results: &regorus::QueryResults,
+    ) -> Result<DecisionExplanation> {
+        let report = engine
+            .get_coverage_report()
+            .map_err(|e| GuardRailError::PolicyEvaluation(format!("Failed to collect coverage: {}", e)))?;
+        let prints = engine
+            .take_prints()
+            .map_err(|e| GuardRailError::PolicyEvaluation(format!("Failed to collect prints: {}", e)))?;
+        let package = Self::package_value(results);
+
+        let modules: Vec<String> = selected.iter().map(|p| Self::module_name(p.id, &p.name)).collect();
+        let policies: Vec<explain::TracedPolicy> = selected
+            .iter()
+            .zip(&modules)
+            .map(|(p, module)| explain::TracedPolicy {
+                id: p.id,
+                name: &p.name,
+                module,
+            })
+            .collect();
+
+        Ok(explain::build_explanation(&package, &report, &policies, input, prints))
+    }
+
+    /// Module path of a policy; the ID keeps policies that share a name apart
+    fn module_name(policy_id: Uuid, policy_name: &str) -> String {
+        format!("policy/{}/{}", policy_id, policy_name)
+    }
+
+    /// The `data.guardrail` package value bound to `x`, as JSON
+    fn package_value(results: &regorus::QueryResults) -> serde_json::Value {
+        results
+            .result
+            .iter()
+            .filter_map(|result| result.bindings.as_object().ok())
+            .filter_map(|bindings| bindings.get(&"x".into()))
+            .find_map(|pkg_val| serde_json::to_value(pkg_val).ok())
+            .map(|mut package| {
+                // Libraries are subpackages of `guardrail`, not rules of the decision
+                if let Some(rules) = package.as_object_mut() {
+                    rules.remove("lib");
+                }
+                package
+            })
+            .unwrap_or_default()
+    }
+
+    /// Work out which of the selected policies produced the reasons, approvers and obligations in `merged`.
+    ///
+    /// All policies share the `guardrail` package, so the merged result cannot say
+    /// where a value came from. Each policy is re-evaluated on its own engine and
+    /// credited with the values it produces that made it into the merged result.
+    fn attribute(selected: &[&LoadedPolicy], input: &serde_json::Value, merged: &PolicyEvalResult) -> Vec<PolicyAttribution> {
+        if merged.decision == Decision::Allow && merged.obligations.is_empty() {
+            return Vec::new();
+        }
+
+        // A single policy produced everything; skip the extra evaluation
+        if let [policy] = selected {
+            return vec![PolicyAttribution {
+                policy_id: policy.id,
+                policy_name: policy.name.clone(),
+                policy_version: policy.version.clone(),
+                reasons: merged.reasons.clone(),
+                required_approvers: merged.required_approvers.clone(),
+                obligations: merged.obligations.clone(),
+            }];
+        }
+
+        let mut attributions = Vec::new();
+        for policy in selected {
+            let mut engine = policy.engine.clone();
+            engine.set_input(input.clone().into());
+            let isolated = match engine
+                .eval_query("x = data.guardrail".to_string(), false)
+                .map_err(|e| GuardRailError::PolicyEvaluation(e.to_string()))
+                .and_then(|results| Self::parse_decision(&results))
+            {
+                Ok(isolated) => isolated,
+                Err(e) => {
+                    tracing::warn!("Failed to attribute policy {}: {}", policy.name, e);
+                    continue;
+                }
+            };
+
+            let reasons: Vec<String> = isolated
+                .reasons
+                .into_iter()
+                .filter(|r| merged.reasons.contains(r))
+                .collect();
+            let required_approvers: Vec<String> = isolated
+                .required_approvers
+                .into_iter()
+                .filter(|a| merged.required_approvers.contains(a))
+                .collect();
+            let obligations: Vec<Obligation> = isolated
+                .obligations
+                .into_iter()
+                .filter(|o| merged.obligations.contains(o))
+                .collect();
+            // A bare `deny = true` contributes a DENY without any reason
+            let bare_deny = merged.decision == Decision::Deny && isolated.decision == Decision::Deny;
+
+            if !reasons.is_empty() || !required_approvers.is_empty() || !obligations.is_empty() || bare_deny {
+                attributions.push(PolicyAttribution {
+                    policy_id: policy.id,
+                    policy_name: policy.name.clone(),
+                    policy_version: policy.version.clone(),
+                    reasons,
+                    required_approvers,
+                    obligations,
+                });
+            }
+        }
+
+        attributions
+    }
+
+    fn parse_decision(results: &regorus::QueryResults) -> Result<PolicyEvalResult> {
         // Default to ALLOW if no policies match
         let mut decision = Decision::Allow;
         let mut reasons: Vec<String> = Vec::new();
+        let mut reason_details: Vec<DenyReason> = Vec::new();
         let mut required_approvers: Vec<String> = Vec::new();
+        let mut obligations: Vec<Obligation> = Vec::new();
 
         for result in results.result.iter() {
             // Get bindings as object if possible
@@ -100,9 +548,9 @@ impl PolicyEngine {
                                     if !arr.is_empty() {
                                         decision = Decision::Deny;
                                         for reason in arr {
-                                            if let Some(s) = reason.as_str() {
-                                                reasons.push(s.to_string());
-                                            }
+                                            let detail = Self::parse_reason(reason)?;
+                                            reasons.push(detail.message.clone());
+                                            reason_details.push(detail);
                                         }
                                     }
                                 } else if deny.as_bool() == Some(true) {
@@ -123,6 +571,13 @@ impl PolicyEngine {
                                     }
                                 }
                             }
+
+                            // Obligations apply whatever the decision
+                            if let Some(items) = obj.get("obligations").and_then(|o| o.as_array()) {
+                                for item in items {
+                                    obligations.push(Self::parse_obligation(item)?);
+                                }
+                            }
                         }
                     }
                 }
@@ -132,14 +587,67 @@ impl PolicyEngine {
         Ok(PolicyEvalResult {
             decision,
             reasons,
+            reason_details,
             required_approvers,
+            obligations,
+            policies: Vec::new(),
+            evaluated_policies: Vec::new(),
+            explanation: None,
+            data_revision: None,
+            policy_set_revision: None,
+        })
+    }
+
+    /// Parse a `deny` entry: `{"code", "message", "severity", "params"}`, or a bare message string
+    fn parse_reason(item: &serde_json::Value) -> Result<DenyReason> {
+        if let Some(message) = item.as_str() {
+            return Ok(DenyReason {
+                code: None,
+                message: message.to_string(),
+                severity: None,
+                params: serde_json::json!({}),
+            });
+        }
+
+        let invalid = |what: &str| GuardRailError::PolicyEvaluation(format!("Deny reason {}: {}", what, item));
+        let field = |name: &str| item.get(name).and_then(|v| v.as_str()).map(str::to_string);
+        let code = field("code");
+        let message = field("message")
+            .or_else(|| code.clone())
+            .ok_or_else(|| invalid("needs a code or message"))?;
+        let severity = field("severity")
+            .map(|s| serde_json::from_value(serde_json::Value::String(s.to_uppercase())))
+            .transpose()
+            .map_err(|_| invalid("has an unknown severity"))?;
+
+        Ok(DenyReason {
+            code,
+            message,
+            severity,
+            params: item.get("params").cloned().unwrap_or_else(|| serde_json::json!({})),
         })
     }
 
-    /// Reload all policies from database
-    pub fn clear(&mut self) {
-        self.engine = Engine::new();
-        self.loaded_policies.clear();
+    /// Parse `{"type": ..., "params": {...}}`, or a bare type string.
+    ///
+    /// A malformed obligation fails the evaluation rather than being dropped, since
+    /// the caller would otherwise skip something the policy asked for.
+    fn parse_obligation(item: &serde_json::Value) -> Result<Obligation> {
+        if let Some(obligation_type) = item.as_str() {
+            return Ok(Obligation {
+                obligation_type: obligation_type.to_string(),
+                params: serde_json::json!({}),
+            });
+        }
+
+        let obligation_type = item
+            .get("type")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| GuardRailError::PolicyEvaluation(format!("Obligation without a type: {}", item)))?;
+        Ok(Obligation {
+            obligation_type: obligation_type.to_string(),
+            params: item.get("params").cloned().unwrap_or_else(|| serde_json::json!({})),
+        })
     }
 }
 
@@ -147,7 +655,25 @@ impl PolicyEngine {
 pub struct PolicyEvalResult {
     pub decision: Decision,
     pub reasons: Vec<String>,
+    #[serde(default)]
+    pub reason_details: Vec<DenyReason>,
     pub required_approvers: Vec<String>,
+    #[serde(default)]
+    pub obligations: Vec<Obligation>,
+    /// Policies that contributed to the decision
+    #[serde(default)]
+    pub policies: Vec<PolicyAttribution>,
+    /// Policies whose targets matched the action; libraries aren't included
+    #[serde(skip)]
+    pub evaluated_policies: Vec<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<DecisionExplanation>,
+    /// Revision of the data documents the policies saw
+    #[serde(default)]
+    pub data_revision: Option<String>,
+    /// Revision of the enforced policy set the action was evaluated against
+    #[serde(default)]
+    pub policy_set_revision: Option<String>,
 }
 
 // ============================================================================
@@ -166,6 +692,9 @@ pub struct SimulateRequest {
     pub identity: serde_json::Value,
     pub action: Action,
     pub context: ActionContext,
+    /// Include an evaluation trace in the result
+    #[serde(default)]
+    pub explain: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -174,6 +703,9 @@ pub struct HealthResponse {
     pub service: String,
     pub version: String,
     pub loaded_policies: usize,
+    pub shadow_policies: usize,
+    /// Checks that skipped shadow evaluation because too many were in flight
+    pub shadow_dropped: u64,
 }
 
 // ============================================================================
@@ -181,12 +713,15 @@ pub struct HealthResponse {
 // ============================================================================
 
 async fn health(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let engine = state.engine.read().await;
+    let engine = state.engine.load();
+    let shadow = state.shadow.load();
     Json(HealthResponse {
         status: "healthy".to_string(),
         service: "policy-engine".to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
         loaded_policies: engine.loaded_policies.len(),
+        shadow_policies: shadow.shadow_policies.len(),
+        shadow_dropped: state.shadow_queue.dropped(),
     })
 }
 
@@ -194,7 +729,7 @@ async fn create_policy(
     State(state): State<Arc<AppState>>,
     Json(req): Json<CreatePolicyRequest>,
 ) -> impl IntoResponse {
-    match create_policy_impl(&state, req).await {
+    match create_policy_impl(&state, req, None).await {
         Ok(policy) => (StatusCode::CREATED, Json(ApiResponse::success(policy))),
         Err(e) => {
             let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
@@ -203,43 +738,115 @@ async fn create_policy(
     }
 }
 
-async fn create_policy_impl(state: &AppState, req: CreatePolicyRequest) -> Result<Policy> {
+/// Validate Rego syntax by loading it into a scratch engine
+pub(crate) fn validate_rego(name: &str, rego_source: &str) -> Result<()> {
+    let mut test_engine = PolicyEngine::new();
+    test_engine
+        .load_policy(Uuid::nil(), name, "", rego_source)
+        .map_err(|e| match e {
+            GuardRailError::PolicyEvaluation(msg) => GuardRailError::InvalidRego(msg),
+            other => other,
+        })
+}
+
+pub(crate) async fn create_policy_impl(
+    state: &AppState,
+    req: CreatePolicyRequest,
+    template: Option<&templates::TemplateSource>,
+) -> Result<Policy> {
     let id = Uuid::new_v4();
     let now = chrono::Utc::now();
-    let version = "1.0.0".to_string();
 
-    // Validate Rego syntax by trying to load it
-    {
-        let mut test_engine = PolicyEngine::new();
-        test_engine.load_policy(id, &req.name, &req.rego_source)?;
+    validate_rego(&req.name, &req.rego_source)?;
+    effective::validate_window(req.effective_from, req.effective_until, now)?;
+    targeting::validate(&req.targets)?;
+    libraries::validate(req.kind, &req.targets, req.shadow, req.effective_from, req.effective_until)?;
+    let lint_report = lint::lint_policy(&state.db, &req.rego_source, req.kind, None).await?;
+    lint::ensure_clean(&req.name, &lint_report)?;
+    let test_source = req.test_source.filter(|t| !t.trim().is_empty());
+    let test_report = rego_tests::run_tests(
+        &state.engine.load(),
+        None,
+        &req.name,
+        &req.rego_source,
+        test_source.as_deref(),
+    )?;
+    rego_tests::ensure_passed(&test_report)?;
+
+    let req = CreatePolicyRequest { test_source, ..req };
+
+    let mut tx = state.db.begin().await?;
+    let policy = insert_policy(&mut tx, id, &req, template, now).await?;
+    rego_tests::record_run(&mut *tx, policy.id, &policy.version, rego_tests::TestTrigger::Create, &test_report).await?;
+    tx.commit().await?;
+
+    // Load into the enforced or shadow engine
+    reload_policies(state).await?;
+    if policy.effective_from.is_some() || policy.effective_until.is_some() {
+        state.schedule_changed.notify_one();
     }
 
+    record_policy_created(state, &policy).await;
+
+    Ok(policy)
+}
+
+/// Insert a checked policy and its initial version
+pub(crate) async fn insert_policy(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    id: Uuid,
+    req: &CreatePolicyRequest,
+    template: Option<&templates::TemplateSource>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Policy> {
+    let in_effect = req.effective_from.is_none_or(|from| from <= now);
     let policy = sqlx::query_as!(
         Policy,
         r#"
-        INSERT INTO policies (id, name, description, version, rego_source, is_active, created_at, updated_at)
-        VALUES ($1, $2, $3, $4, $5, true, $6, $6)
-        RETURNING id, name, description, version, rego_source, is_active as "is_active!", created_by as "created_by!", created_at as "created_at!", updated_at as "updated_at!"
+        INSERT INTO policies (id, name, description, version, kind, rego_source, test_source, is_active, is_shadow, shadow_since, effective_from, effective_until, targets, created_by, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $14, $5, $6, NOT $7 AND $10, $7, CASE WHEN $7 THEN $9::timestamptz END, $11, $12, $13, $8, $9, $9)
+        RETURNING id, name, description, version, kind as "kind: PolicyKind", rego_source, test_source, is_active as "is_active!", is_shadow, effective_from, effective_until, targets as "targets: PolicyTargets", created_by, created_at as "created_at!", updated_at as "updated_at!"
         "#,
         id,
         req.name,
         req.description,
-        version,
+        versions::INITIAL_VERSION,
         req.rego_source,
+        req.test_source,
+        req.shadow,
+        req.created_by,
         now,
+        in_effect,
+        req.effective_from,
+        req.effective_until,
+        serde_json::to_value(&req.targets)?,
+        req.kind as PolicyKind,
     )
-    .fetch_one(&state.db)
+    .fetch_one(&mut **tx)
     .await?;
 
-    // Load into active engine
-    {
-        let mut engine = state.engine.write().await;
-        engine.load_policy(id, &policy.name, &policy.rego_source)?;
-    }
+    versions::record_version(tx, &policy, Some("Initial version"), template, policy.created_by).await?;
 
     Ok(policy)
 }
 
+pub(crate) async fn record_policy_created(state: &AppState, policy: &Policy) {
+    state
+        .ledger
+        .record_event_logged(
+            EventType::PolicyCreated,
+            policy.created_by.unwrap_or(SYSTEM_ACTOR_ID),
+            None,
+            serde_json::json!({
+                "policy_id": policy.id,
+                "name": policy.name,
+                "version": policy.version,
+                "rego_sha256": guardrail_shared::sha256_hex(policy.rego_source.as_bytes()),
+            }),
+        )
+        .await;
+}
+
 async fn list_policies(
     State(state): State<Arc<AppState>>,
     Query(query): Query<ListQuery>,
@@ -270,7 +877,7 @@ async fn list_policies_impl(
     let policies = sqlx::query_as!(
         Policy,
         r#"
-        SELECT id, name, description, version, rego_source, is_active as "is_active!", created_by as "created_by!", created_at as "created_at!", updated_at as "updated_at!"
+        SELECT id, name, description, version, kind as "kind: PolicyKind", rego_source, test_source, is_active as "is_active!", is_shadow, effective_from, effective_until, targets as "targets: PolicyTargets", created_by, created_at as "created_at!", updated_at as "updated_at!"
         FROM policies
         WHERE ($3::boolean = false OR is_active = true)
         ORDER BY created_at DESC
@@ -310,11 +917,11 @@ async fn get_policy(
     }
 }
 
-async fn get_policy_impl(db: &PgPool, id: Uuid) -> Result<Policy> {
+pub(crate) async fn get_policy_impl(db: &PgPool, id: Uuid) -> Result<Policy> {
     let policy = sqlx::query_as!(
         Policy,
         r#"
-        SELECT id, name, description, version, rego_source, is_active as "is_active!", created_by as "created_by!", created_at as "created_at!", updated_at as "updated_at!"
+        SELECT id, name, description, version, kind as "kind: PolicyKind", rego_source, test_source, is_active as "is_active!", is_shadow, effective_from, effective_until, targets as "targets: PolicyTargets", created_by, created_at as "created_at!", updated_at as "updated_at!"
         FROM policies
         WHERE id = $1
         "#,
@@ -340,19 +947,25 @@ async fn check_action(
     }
 }
 
-async fn check_action_impl(state: &AppState, req: CheckActionRequest) -> Result<PolicyDecision> {
+/// Load an identity and its credentials in the shape policies see as `input.identity`
+pub(crate) async fn load_identity_input(
+    db: &PgPool,
+    identity_id: Uuid,
+    include_inactive: bool,
+) -> Result<serde_json::Value> {
     // Get identity with credentials
     let identity = sqlx::query!(
         r#"
-        SELECT id, identity_type as "identity_type: String", display_name, metadata
+        SELECT id, identity_type as "identity_type: String", display_name, metadata, organization_id
         FROM identities
-        WHERE id = $1 AND is_active = true
+        WHERE id = $1 AND ($2 OR is_active = true)
         "#,
-        req.identity_id,
+        identity_id,
+        include_inactive,
     )
-    .fetch_optional(&state.db)
+    .fetch_optional(db)
     .await?
-    .ok_or_else(|| GuardRailError::IdentityNotFound(req.identity_id.to_string()))?;
+    .ok_or_else(|| GuardRailError::IdentityNotFound(identity_id.to_string()))?;
 
     // Get credentials for identity
     let credentials = sqlx::query!(
@@ -361,82 +974,195 @@ async fn check_action_impl(state: &AppState, req: CheckActionRequest) -> Result<
         FROM credentials
         WHERE identity_id = $1
         "#,
-        req.identity_id,
+        identity_id,
     )
-    .fetch_all(&state.db)
+    .fetch_all(db)
     .await?;
 
-    // Build input for policy evaluation
-    let input = serde_json::json!({
-        "identity": {
-            "id": identity.id.to_string(),
-            "type": identity.identity_type,
-            "display_name": identity.display_name,
-            "metadata": identity.metadata,
-            "credentials": credentials.iter().map(|c| serde_json::json!({
-                "type": c.credential_type,
-                "provider": c.provider,
-                "value": c.value,
-            })).collect::<Vec<_>>(),
+    Ok(serde_json::json!({
+        "id": identity.id.to_string(),
+        "type": identity.identity_type,
+        "display_name": identity.display_name,
+        "metadata": identity.metadata,
+        "organization_id": identity.organization_id,
+        "credentials": credentials.iter().map(|c| serde_json::json!({
+            "type": c.credential_type,
+            "provider": c.provider,
+            "value": c.value,
+        })).collect::<Vec<_>>(),
+    }))
+}
+
+/// Build the policy input document for an action
+pub(crate) fn build_input(
+    identity: serde_json::Value,
+    action: &impl Serialize,
+    context: &impl Serialize,
+) -> serde_json::Value {
+    serde_json::json!({
+        "identity": identity,
+        "action": action,
+        "context": context,
+    })
+}
+
+/// Decide a check, falling back to the configured decision when it can't be evaluated
+pub(crate) async fn check_action_impl(state: &AppState, req: CheckActionRequest) -> Result<PolicyDecision> {
+    match decide_check(state, &req).await {
+        Err(e) => match fallback::cause(&e) {
+            Some(cause) => Ok(fallback::fall_back(state, &req, cause, &e).await),
+            None => Err(e),
         },
-        "action": req.action,
-        "context": req.context,
-    });
+        decided => decided,
+    }
+}
+
+async fn decide_check(state: &AppState, req: &CheckActionRequest) -> Result<PolicyDecision> {
+    // Build input for policy evaluation
+    let identity = load_identity_input(&state.db, req.identity_id, false).await?;
+    let mut input = build_input(identity, &req.action, &req.context);
+
+    let decision_id = Uuid::new_v4();
+    let now = chrono::Utc::now();
+
+    // Record the decision and its attributions, opening approvals in the same transaction.
+    // History is read under a per-identity lock so concurrent checks see each other's counts.
+    let mut tx = state.db.begin().await?;
+    if !state.history_config.windows.is_empty() {
+        history::lock_identity(&mut tx, req.identity_id).await?;
+    }
+    input["history"] = history::load(&mut tx, &state.history_config, req.identity_id, &req.action, now).await?;
 
     // Evaluate policies
-    let eval_result = {
-        let mut engine = state.engine.write().await;
-        engine.evaluate(&input)?
-    };
+    let eval_result = fallback::evaluate(
+        state.engine.load(),
+        input.clone(),
+        req.explain,
+        state.fallback_config.deadline,
+    )
+    .await?;
 
-    // Get first active policy for recording (simplified - should aggregate in production)
-    let policy = sqlx::query!(
+    record_decision(&mut tx, state, decision_id, req, &eval_result, None, now).await?;
+    tx.commit().await?;
+
+    after_commit(state, decision_id, input, &eval_result).await;
+
+    Ok(decision_response(decision_id, eval_result, now))
+}
+
+/// Store a decision with its attributions and history counters, opening any approvals it needs
+pub(crate) async fn record_decision(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    state: &AppState,
+    decision_id: Uuid,
+    req: &CheckActionRequest,
+    eval_result: &PolicyEvalResult,
+    degraded: Option<DegradedCause>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<()> {
+    let action_payload = serde_json::to_value(&req.action)?;
+    let primary = eval_result.policies.first();
+
+    sqlx::query!(
         r#"
-        SELECT id, version
-        FROM policies
-        WHERE is_active = true
-        ORDER BY created_at DESC
-        LIMIT 1
+        INSERT INTO policy_decisions (id, identity_id, policy_id, policy_version, action_type, action_payload, context, decision, reasons, reason_details, required_approvers, obligations, explanation, data_revision, policy_set_revision, evaluated_policy_ids, degraded_cause, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
         "#,
+        decision_id,
+        req.identity_id,
+        primary.map(|p| p.policy_id),
+        primary.map(|p| p.policy_version.clone()),
+        format!("{:?}", req.action.action_type),
+        action_payload,
+        serde_json::to_value(&req.context)?,
+        eval_result.decision as Decision,
+        &eval_result.reasons,
+        serde_json::to_value(&eval_result.reason_details)?,
+        &eval_result.required_approvers,
+        serde_json::to_value(&eval_result.obligations)?,
+        eval_result.explanation.as_ref().map(serde_json::to_value).transpose()?,
+        eval_result.data_revision,
+        eval_result.policy_set_revision,
+        &eval_result.evaluated_policies,
+        degraded.map(DegradedCause::as_str),
+        now,
     )
-    .fetch_optional(&state.db)
+    .execute(&mut **tx)
     .await?;
 
-    let decision_id = Uuid::new_v4();
-    let now = chrono::Utc::now();
-
-    // Record decision in database
-    if let Some(p) = &policy {
+    for attribution in &eval_result.policies {
         sqlx::query!(
             r#"
-            INSERT INTO policy_decisions (id, identity_id, policy_id, policy_version, action_type, action_payload, context, decision, reasons, required_approvers, created_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            INSERT INTO policy_decision_attributions (decision_id, policy_id, policy_version, reasons, required_approvers, obligations)
+            VALUES ($1, $2, $3, $4, $5, $6)
             "#,
             decision_id,
+            attribution.policy_id,
+            attribution.policy_version,
+            &attribution.reasons,
+            &attribution.required_approvers,
+            serde_json::to_value(&attribution.obligations)?,
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    history::record(tx, req.identity_id, &req.action, eval_result.decision, now).await?;
+    outbox::enqueue(tx, decision_id, req, eval_result, degraded, now).await?;
+
+    if eval_result.decision == Decision::RequireApproval {
+        approvals::create_approvals(
+            tx,
+            &state.approval_config,
+            decision_id,
             req.identity_id,
-            p.id,
-            p.version,
-            format!("{:?}", req.action.action_type),
-            serde_json::to_value(&req.action)?,
-            serde_json::to_value(&req.context)?,
-            eval_result.decision as Decision,
-            &eval_result.reasons,
+            &action_payload,
             &eval_result.required_approvers,
-            now,
         )
-        .execute(&state.db)
         .await?;
     }
 
-    Ok(PolicyDecision {
+    Ok(())
+}
+
+/// Follow-up for a committed decision: wake the ledger relay and evaluate shadow policies
+pub(crate) async fn after_commit(
+    state: &AppState,
+    decision_id: Uuid,
+    input: serde_json::Value,
+    eval_result: &PolicyEvalResult,
+) {
+    state.outbox_ready.notify_one();
+
+    // Shadow policies never affect the response; evaluate them off the request path
+    if !state.shadow.load().shadow_policies.is_empty() {
+        shadow::spawn_evaluation(state, decision_id, input, eval_result.clone());
+    }
+}
+
+pub(crate) fn decision_response(
+    decision_id: Uuid,
+    eval_result: PolicyEvalResult,
+    now: chrono::DateTime<chrono::Utc>,
+) -> PolicyDecision {
+    let primary = eval_result.policies.first();
+    PolicyDecision {
         decision_id,
         decision: eval_result.decision,
+        policy_id: primary.map(|p| p.policy_id),
+        policy_version: primary.map(|p| p.policy_version.clone()),
         reasons: eval_result.reasons,
+        reason_details: eval_result.reason_details,
         required_approvers: eval_result.required_approvers,
-        policy_id: policy.as_ref().map(|p| p.id).unwrap_or(Uuid::nil()),
-        policy_version: policy.as_ref().map(|p| p.version.clone()).unwrap_or_default(),
+        contributing_policies: eval_result.policies,
+        obligations: eval_result.obligations,
+        explanation: eval_result.explanation,
+        data_revision: eval_result.data_revision,
+        policy_set_revision: eval_result.policy_set_revision,
+        degraded: false,
+        degraded_cause: None,
         evaluated_at: now,
-    })
+    }
 }
 
 async fn simulate_policy(
@@ -461,9 +1187,16 @@ async fn simulate_policy_impl(
     // Get the policy
     let policy = get_policy_impl(&state.db, policy_id).await?;
 
-    // Create a fresh engine with just this policy
-    let mut engine = PolicyEngine::new();
-    engine.load_policy(policy.id, &policy.name, &policy.rego_source)?;
+    if policy.kind == PolicyKind::Library {
+        return Err(GuardRailError::Validation(format!(
+            "Policy {} is a library and makes no decisions to simulate",
+            policy.name
+        )));
+    }
+
+    // Create a fresh engine with just this policy and the current data documents and libraries
+    let mut engine = state.engine.load().scratch()?;
+    engine.load_targeted_policy(policy.id, &policy.name, &policy.version, &policy.rego_source, &policy.targets)?;
 
     // Build input
     let input = serde_json::json!({
@@ -473,7 +1206,11 @@ async fn simulate_policy_impl(
     });
 
     // Evaluate
-    let result = engine.evaluate(&input)?;
+    let result = if req.explain {
+        engine.explain(&input)?
+    } else {
+        engine.evaluate(&input)?
+    };
 
     Ok(result)
 }
@@ -504,50 +1241,133 @@ async fn deactivate_policy(
     }
 }
 
-async fn activate_policy_impl(state: &AppState, id: Uuid, active: bool) -> Result<Policy> {
+pub(crate) async fn activate_policy_impl(state: &AppState, id: Uuid, active: bool) -> Result<Policy> {
     let now = chrono::Utc::now();
 
-    let policy = sqlx::query_as!(
+    let current = get_policy_impl(&state.db, id).await?;
+    if active {
+        ensure_activatable(state, &current).await?;
+    } else {
+        libraries::ensure_not_imported(&state.db, &current, None).await?;
+    }
+
+    let policy = set_active(&state.db, id, active, now).await?;
+
+    // Reload policies in engine
+    reload_policies(state).await?;
+
+    Ok(policy)
+}
+
+/// Run a policy's tests against the data and libraries of `engine`, as before it goes live
+pub(crate) fn activation_tests(engine: &PolicyEngine, policy: &Policy) -> Result<PolicyTestReport> {
+    rego_tests::run_tests(engine, Some(policy.id), &policy.name, &policy.rego_source, policy.test_source.as_deref())
+}
+
+/// Policies only go live with passing tests and their libraries loaded; the test
+/// run is recorded either way
+pub(crate) async fn ensure_activatable(state: &AppState, policy: &Policy) -> Result<()> {
+    libraries::ensure_imports_active(&state.db, &policy.name, &policy.rego_source).await?;
+    let report = activation_tests(&state.engine.load(), policy)?;
+    rego_tests::record_run(&state.db, policy.id, &policy.version, rego_tests::TestTrigger::Activate, &report).await?;
+    rego_tests::ensure_passed(&report)
+}
+
+/// Activate or deactivate a policy, taking it out of shadow mode
+pub(crate) async fn set_active<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    id: Uuid,
+    active: bool,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Policy> {
+    sqlx::query_as!(
         Policy,
         r#"
         UPDATE policies
-        SET is_active = $2, updated_at = $3
+        SET is_active = $2, is_shadow = false,
+            shadow_until = CASE WHEN is_shadow THEN $3 ELSE shadow_until END, updated_at = $3
         WHERE id = $1
-        RETURNING id, name, description, version, rego_source, is_active as "is_active!", created_by as "created_by!", created_at as "created_at!", updated_at as "updated_at!"
+        RETURNING id, name, description, version, kind as "kind: PolicyKind", rego_source, test_source, is_active as "is_active!", is_shadow, effective_from, effective_until, targets as "targets: PolicyTargets", created_by, created_at as "created_at!", updated_at as "updated_at!"
         "#,
         id,
         active,
         now,
     )
-    .fetch_optional(&state.db)
+    .fetch_optional(executor)
     .await?
-    .ok_or_else(|| GuardRailError::PolicyNotFound(id.to_string()))?;
-
-    // Reload policies in engine
-    reload_policies(state).await?;
-
-    Ok(policy)
+    .ok_or_else(|| GuardRailError::PolicyNotFound(id.to_string()))
 }
 
-async fn reload_policies(state: &AppState) -> Result<()> {
+/// Rebuild the enforced and shadow engines from the database and swap them in
+pub(crate) async fn reload_policies(state: &AppState) -> Result<()> {
+    let _reload = state.reload_lock.lock().await;
+
     let policies = sqlx::query!(
         r#"
-        SELECT id, name, rego_source
+        SELECT id, name, version, kind as "kind: PolicyKind", rego_source, targets as "targets: PolicyTargets", is_active as "is_active!", is_shadow
         FROM policies
-        WHERE is_active = true
+        WHERE is_active = true OR is_shadow = true
+        ORDER BY kind = 'DECISION', created_at ASC
         "#,
     )
     .fetch_all(&state.db)
     .await?;
 
-    let mut engine = state.engine.write().await;
-    engine.clear();
+    let data = data_documents::load_active(&state.db).await?.map(Arc::new);
+
+    // Build new snapshots off to the side; in-flight checks keep using the old ones
+    let mut engine = PolicyEngine::new();
+    let mut shadow = shadow::ShadowEngine::default();
+    if let Some(data) = data {
+        engine.set_data(data.clone())?;
+        shadow.engine.set_data(data)?;
+    }
 
+    let mut enforced = Vec::new();
     for policy in policies {
-        if let Err(e) = engine.load_policy(policy.id, &policy.name, &policy.rego_source) {
-            tracing::error!("Failed to load policy {}: {}", policy.name, e);
+        // Libraries come first, so every engine holds them before the policies importing them
+        if policy.kind == PolicyKind::Library {
+            if let Err(e) = shadow.engine.load_library(policy.id, &policy.name, &policy.version, &policy.rego_source) {
+                tracing::error!("Failed to load library {} into shadow engine: {}", policy.name, e);
+            }
+            match engine.load_library(policy.id, &policy.name, &policy.version, &policy.rego_source) {
+                Ok(()) => enforced.push(effective::PolicySetEntry {
+                    policy_id: policy.id,
+                    name: policy.name,
+                    version: policy.version,
+                }),
+                Err(e) => tracing::error!("Failed to load library {}: {}", policy.name, e),
+            }
+            continue;
+        }
+
+        // The shadow engine sees the enforced set too, so it predicts the post-activation outcome.
+        // It only observes, so a failure there never keeps a policy out of enforcement.
+        match shadow.engine.load_targeted_policy(policy.id, &policy.name, &policy.version, &policy.rego_source, &policy.targets) {
+            Ok(()) if policy.is_shadow => {
+                shadow.shadow_policies.insert(policy.id);
+            }
+            Ok(()) => {}
+            Err(e) => tracing::error!("Failed to load policy {} into shadow engine: {}", policy.name, e),
+        }
+        if policy.is_shadow {
+            continue;
+        }
+        match engine.load_targeted_policy(policy.id, &policy.name, &policy.version, &policy.rego_source, &policy.targets) {
+            Ok(()) => enforced.push(effective::PolicySetEntry {
+                policy_id: policy.id,
+                name: policy.name,
+                version: policy.version,
+            }),
+            Err(e) => tracing::error!("Failed to load policy {}: {}", policy.name, e),
         }
     }
+    engine.policy_set_revision = Some(effective::record_policy_set(&state.db, &enforced).await?);
+
+    engine.prepare()?;
+    shadow.engine.prepare()?;
+    state.engine.store(engine);
+    state.shadow.store(shadow);
 
     Ok(())
 }
@@ -563,12 +1383,59 @@ fn create_router(state: Arc<AppState>) -> Router {
         // Policy CRUD
         .route("/api/v1/policies", post(create_policy))
         .route("/api/v1/policies", get(list_policies))
-        .route("/api/v1/policies/:id", get(get_policy))
+        .route("/api/v1/policies/backtest", post(backtest::backtest_policy))
+        .route("/api/v1/policies/test", post(rego_tests::run_draft_tests))
+        .route("/api/v1/policies/lint", post(lint::lint_draft))
+        .route("/api/v1/policies/shadow", get(shadow::shadow_summary))
+        .route("/api/v1/policies/templates", get(templates::list_templates))
+        .route("/api/v1/policies/templates/:template_id", get(templates::get_template))
+        .route("/api/v1/policies/templates/:template_id/render", post(templates::render_template))
+        .route("/api/v1/policies/from-template", post(templates::create_from_template))
+        .route("/api/v1/policies/sets/:revision", get(effective::get_policy_set))
+        .route("/api/v1/policies/:id", get(get_policy).put(versions::update_policy))
         .route("/api/v1/policies/:id/activate", post(activate_policy))
         .route("/api/v1/policies/:id/deactivate", post(deactivate_policy))
         .route("/api/v1/policies/:id/simulate", post(simulate_policy))
+        .route("/api/v1/policies/:id/versions", get(versions::list_policy_versions))
+        .route("/api/v1/policies/:id/versions/:version", get(versions::get_policy_version))
+        .route("/api/v1/policies/:id/diff", get(versions::diff_policy_versions))
+        .route("/api/v1/policies/:id/rollback", post(versions::rollback_policy))
+        .route("/api/v1/policies/:id/tests", get(rego_tests::list_test_runs))
+        .route("/api/v1/policies/:id/template", put(templates::update_from_template))
+        .route("/api/v1/policies/:id/schedule", put(effective::schedule_policy))
+        .route("/api/v1/policies/:id/dependencies", get(libraries::policy_dependencies))
+        .route("/api/v1/policies/:id/shadow", post(shadow::shadow_policy).get(shadow::policy_shadow_summary))
+        // Policy bundles
+        .route("/api/v1/bundles/export", get(bundles::export_bundle))
+        .route("/api/v1/bundles/import", post(bundles::import_bundle))
         // Action checking
         .route("/api/v1/check", post(check_action))
+        .route("/api/v1/check/batch", post(batch::check_batch))
+        .route("/api/v1/decisions", get(decisions::list_decisions))
+        .route("/api/v1/decisions/stats/timeseries", get(analytics::decision_timeseries))
+        .route("/api/v1/decisions/stats/policies", get(analytics::policy_stats))
+        .route("/api/v1/decisions/stats/identities", get(analytics::identity_stats))
+        .route("/api/v1/decisions/stats/reasons", get(analytics::reason_stats))
+        .route("/api/v1/decisions/fallbacks", get(fallback::fallback_stats))
+        .route("/api/v1/decisions/outbox", get(outbox::outbox_stats))
+        .route("/api/v1/decisions/:id", get(decisions::get_decision))
+        // Data documents
+        .route("/api/v1/data", post(data_documents::upload_document).get(data_documents::list_documents))
+        .route("/api/v1/data/revisions/:revision", get(data_documents::get_revision))
+        .route("/api/v1/data/:id", get(data_documents::get_document))
+        .route("/api/v1/data/:id/activate", post(data_documents::activate_document))
+        .route("/api/v1/data/:id/deactivate", post(data_documents::deactivate_document))
+        // Controls
+        .route("/api/v1/controls", post(controls::create_control).get(controls::list_controls))
+        .route("/api/v1/controls/findings", get(controls::list_findings))
+        .route("/api/v1/controls/:id", get(controls::get_control).put(controls::update_control))
+        .route("/api/v1/controls/:id/run", post(controls::run_control_now))
+        .route("/api/v1/controls/:id/runs", get(controls::list_control_runs))
+        // Approvals
+        .route("/api/v1/approvals", get(approvals::list_approvals))
+        .route("/api/v1/approvals/:id", get(approvals::get_approval))
+        .route("/api/v1/approvals/:id/approve", post(approvals::approve_approval))
+        .route("/api/v1/approvals/:id/reject", post(approvals::reject_approval))
         .with_state(state)
         .layer(TraceLayer::new_for_http())
         .layer(CorsLayer::permissive())
@@ -628,16 +1495,58 @@ async fn main() -> anyhow::Result<()> {
 
     tracing::info!("Connected to database");
 
-    // Create policy engine
-    let engine = Arc::new(RwLock::new(PolicyEngine::new()));
+    // Create policy engines
+    let engine = Arc::new(Snapshot::new(PolicyEngine::new()));
+    let shadow = Arc::new(Snapshot::new(shadow::ShadowEngine::default()));
+
+    // Movement ledger for approval events
+    let ledger_url = std::env::var("MOVEMENT_LEDGER_URL")
+        .unwrap_or_else(|_| "http://localhost:3003".to_string());
 
     // Create app state
-    let state = Arc::new(AppState { db: db.clone(), engine });
+    let state = Arc::new(AppState {
+        db: db.clone(),
+        engine,
+        shadow,
+        shadow_queue: Arc::new(shadow::ShadowQueue::new(&shadow::ShadowConfig::from_env())),
+        reload_lock: Arc::new(Mutex::new(())),
+        ledger: LedgerClient::new(ledger_url),
+        approval_config: ApprovalConfig::from_env(),
+        history_config: HistoryConfig::from_env(),
+        bundle_config: BundleConfig::from_env(),
+        fallback_config: FallbackConfig::from_env(),
+        fallback_counters: Arc::new(FallbackCounters::new()),
+        schedule_config: ScheduleConfig::from_env(),
+        schedule_changed: Arc::new(Notify::new()),
+        outbox_config: OutboxConfig::from_env(),
+        outbox_ready: Arc::new(Notify::new()),
+        control_config: ControlConfig::from_env(),
+        controls_changed: Arc::new(Notify::new()),
+    });
 
-    // Load active policies
+    // Load active policies, then apply schedule boundaries passed while stopped
     reload_policies(&state).await?;
+    let scheduled_at = chrono::Utc::now();
+    effective::tick(&state, None, scheduled_at).await?;
     tracing::info!("Loaded active policies");
 
+    // Seed velocity counters from recorded decisions on first start
+    let seeded = history::backfill(&state.db, &state.history_config).await?;
+    if seeded > 0 {
+        tracing::info!("Seeded {} history buckets from policy_decisions", seeded);
+    }
+
+    // Expire stale approvals, prune old history, apply policy schedules, relay
+    // decisions to the ledger and sweep controls in the background
+    approvals::spawn_expiry_sweeper(state.clone());
+    history::spawn_sweeper(state.clone());
+    effective::spawn_scheduler(state.clone(), scheduled_at);
+    outbox::spawn_relay(state.clone());
+    controls::spawn_sweeper(state.clone());
+
+    // Serve gRPC checks alongside the HTTP API
+    let grpc = grpc::serve(state.clone(), GrpcConfig::from_env(), shutdown_signal());
+
     // Create router
     let app = create_router(state);
 
@@ -648,9 +1557,13 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("Policy Engine listening on {}", addr);
     
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    let http = async {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown_signal())
+            .await?;
+        anyhow::Ok(())
+    };
+    tokio::try_join!(http, grpc)?;
 
     Ok(())
 }
@@ -658,6 +1571,7 @@ async fn main() -> anyhow::Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use guardrail_shared::ActionType;
     use serde_json::json;
 
     #[test]
@@ -667,7 +1581,7 @@ mod tests {
             package guardrail
             default decision = "ALLOW"
         "#;
-        engine.load_policy(Uuid::new_v4(), "test", rego).unwrap();
+        engine.load_policy(Uuid::new_v4(), "test", "1.0.0", rego).unwrap();
         
         let input = json!({});
         let result = engine.evaluate(&input).unwrap();
@@ -684,7 +1598,7 @@ mod tests {
                 input.amount > 1000
             }
         "#;
-        engine.load_policy(Uuid::new_v4(), "test", rego).unwrap();
+        engine.load_policy(Uuid::new_v4(), "test", "1.0.0", rego).unwrap();
         
         let input = json!({"amount": 1500});
         let result = engine.evaluate(&input).unwrap();
@@ -702,7 +1616,7 @@ mod tests {
                 input.amount > 1000
             }
         "#;
-        engine.load_policy(Uuid::new_v4(), "test", rego).unwrap();
+        engine.load_policy(Uuid::new_v4(), "test", "1.0.0", rego).unwrap();
         
         let input = json!({"amount": 500});
         let result = engine.evaluate(&input).unwrap();
@@ -722,8 +1636,8 @@ mod tests {
             deny["Rule 2"] { input.y > 10 }
         "#;
         
-        engine.load_policy(Uuid::new_v4(), "p1", rego1).unwrap();
-        engine.load_policy(Uuid::new_v4(), "p2", rego2).unwrap();
+        engine.load_policy(Uuid::new_v4(), "p1", "1.0.0", rego1).unwrap();
+        engine.load_policy(Uuid::new_v4(), "p2", "1.0.0", rego2).unwrap();
         
         let input = json!({"x": 15, "y": 5});
         let result = engine.evaluate(&input).unwrap();
@@ -744,6 +1658,281 @@ mod tests {
         assert!(result.reasons.contains(&"Rule 2".to_string()));
     }
 
+    #[test]
+    fn test_reasons_attributed_to_source_policy() {
+        let mut engine = PolicyEngine::new();
+        let (p1, p2, p3) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        engine.load_policy(p1, "p1", "1.0.0", r#"
+            package guardrail
+            deny["Rule 1"] { input.x > 10 }
+        "#).unwrap();
+        engine.load_policy(p2, "p2", "2.1.0", r#"
+            package guardrail
+            deny["Rule 2"] { input.y > 10 }
+            required_approvers["admin"] { input.y > 0 }
+        "#).unwrap();
+        engine.load_policy(p3, "p3", "1.0.0", r#"
+            package guardrail
+            deny["Rule 3"] { input.z > 10 }
+        "#).unwrap();
+
+        let result = engine.evaluate(&json!({"x": 15, "y": 15, "z": 0})).unwrap();
+        assert_eq!(result.decision, Decision::Deny);
+        assert_eq!(result.policies.len(), 2);
+        assert_eq!(result.policies[0].policy_id, p1);
+        assert_eq!(result.policies[0].reasons, vec!["Rule 1".to_string()]);
+        assert_eq!(result.policies[1].policy_id, p2);
+        assert_eq!(result.policies[1].policy_version, "2.1.0");
+        assert_eq!(result.policies[1].reasons, vec!["Rule 2".to_string()]);
+        // Approvers are dropped on DENY, so none are attributed
+        assert!(result.policies[1].required_approvers.is_empty());
+
+        let result = engine.evaluate(&json!({"x": 0, "y": 5, "z": 0})).unwrap();
+        assert_eq!(result.decision, Decision::RequireApproval);
+        assert_eq!(result.policies.len(), 1);
+        assert_eq!(result.policies[0].required_approvers, vec!["admin".to_string()]);
+
+        let result = engine.evaluate(&json!({"x": 0, "y": 0, "z": 0})).unwrap();
+        assert_eq!(result.decision, Decision::Allow);
+        assert!(result.policies.is_empty());
+    }
+
+    #[test]
+    fn test_targeted_policies_only_evaluated_for_matching_actions() {
+        let mut engine = PolicyEngine::new();
+        let (global, config, btc) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        engine.load_policy(global, "global", "1.0.0", r#"
+            package guardrail
+            deny["Too large"] { to_number(input.action.amount) > 1000 }
+        "#).unwrap();
+        engine.load_targeted_policy(config, "config", "1.0.0", r#"
+            package guardrail
+            required_approvers["admin"] { true }
+        "#, &PolicyTargets {
+            action_types: vec![ActionType::ConfigChange],
+            ..Default::default()
+        }).unwrap();
+        engine.load_targeted_policy(btc, "btc", "1.0.0", r#"
+            package guardrail
+            deny["BTC paused"] { true }
+        "#, &PolicyTargets {
+            action_types: vec![ActionType::Withdrawal],
+            assets: vec!["BTC".to_string()],
+            ..Default::default()
+        }).unwrap();
+        engine.prepare().unwrap();
+
+        let action = |action_type: &str, asset: &str| json!({"action": {"action_type": action_type, "asset": asset, "amount": "10"}});
+
+        let result = engine.evaluate(&action("WITHDRAWAL", "ETH")).unwrap();
+        assert_eq!(result.decision, Decision::Allow);
+        assert_eq!(result.evaluated_policies, vec![global]);
+
+        let result = engine.evaluate(&action("WITHDRAWAL", "btc")).unwrap();
+        assert_eq!(result.decision, Decision::Deny);
+        assert_eq!(result.reasons, vec!["BTC paused".to_string()]);
+        assert_eq!(result.policies.len(), 1);
+        assert_eq!(result.policies[0].policy_id, btc);
+        assert_eq!(result.evaluated_policies, vec![global, btc]);
+
+        let result = engine.explain(&action("CONFIG_CHANGE", "")).unwrap();
+        assert_eq!(result.decision, Decision::RequireApproval);
+        assert_eq!(result.policies[0].policy_id, config);
+        let traced: Vec<Uuid> = result.explanation.unwrap().policies.iter().map(|p| p.policy_id).collect();
+        assert!(!traced.contains(&btc));
+    }
+
+    #[test]
+    fn test_policies_import_libraries() {
+        let mut engine = PolicyEngine::new();
+        engine.load_library(Uuid::new_v4(), "limits", "1.0.0", r#"
+            package guardrail.lib.limits
+            btc_max := 10
+            max_for(asset) := btc_max { asset == "BTC" }
+            default max_for(_) := 1000
+            deny["never a decision"] { true }
+        "#).unwrap();
+        // Two policies sharing a name load as separate modules
+        let (btc, other) = (Uuid::new_v4(), Uuid::new_v4());
+        engine.load_targeted_policy(btc, "limits", "1.0.0", r#"
+            package guardrail
+            import data.guardrail.lib.limits as limits
+            deny["Over the asset limit"] { to_number(input.action.amount) > limits.btc_max }
+        "#, &PolicyTargets {
+            assets: vec!["BTC".to_string()],
+            ..Default::default()
+        }).unwrap();
+        engine.load_policy(other, "limits", "1.0.0", r#"
+            package guardrail
+            deny["Over the default limit"] { to_number(input.action.amount) > data.guardrail.lib.limits.max_for("") }
+        "#).unwrap();
+        engine.prepare().unwrap();
+
+        let action = |asset: &str, amount: &str| json!({"action": {"action_type": "WITHDRAWAL", "asset": asset, "amount": amount}});
+
+        assert_eq!(engine.evaluate(&action("ETH", "50")).unwrap().decision, Decision::Allow);
+
+        let result = engine.evaluate(&action("BTC", "50")).unwrap();
+        assert_eq!(result.reasons, vec!["Over the asset limit".to_string()]);
+        assert_eq!(result.policies.len(), 1);
+        assert_eq!(result.policies[0].policy_id, btc);
+
+        let result = engine.explain(&action("BTC", "5000")).unwrap();
+        assert_eq!(result.policies.len(), 2);
+        assert!(result.explanation.unwrap().rules.iter().all(|r| r.rule != "lib"));
+
+        // Scratch engines keep the libraries
+        let mut scratch = engine.scratch().unwrap();
+        scratch.load_policy(other, "limits", "1.0.0", &engine.loaded_policies[1].rego_source).unwrap();
+        assert_eq!(scratch.evaluate(&action("ETH", "5000")).unwrap().decision, Decision::Deny);
+    }
+
+    #[test]
+    fn test_explain_captures_trace() {
+        let mut engine = PolicyEngine::new();
+        let id = Uuid::new_v4();
+        let rego = r#"
+package guardrail
+deny["Amount too high"] {
+    input.action.amount > 1000
+}
+required_approvers["admin"] {
+    input.action.amount > 5000
+}
+"#;
+        engine.load_policy(id, "test", "1.0.0", rego).unwrap();
+
+        let input = json!({"action": {"amount": 1500}});
+        let plain = engine.evaluate(&input).unwrap();
+        assert!(plain.explanation.is_none());
+
+        let result = engine.explain(&input).unwrap();
+        assert_eq!(result.decision, Decision::Deny);
+        let explanation = result.explanation.unwrap();
+
+        let deny = explanation.rules.iter().find(|r| r.rule == "deny").unwrap();
+        assert_eq!(deny.value, json!(["Amount too high"]));
+        assert_eq!(deny.locations, vec![guardrail_shared::RuleLocation { policy_id: id, line: 3 }]);
+
+        let trace = &explanation.policies[0];
+        assert_eq!(trace.policy_id, id);
+        assert_eq!(trace.input_reads[0].path, "input.action.amount");
+        assert_eq!(trace.input_reads[0].value, Some(json!(1500)));
+
+        // Coverage is reset between evaluations
+        let result = engine.explain(&json!({"action": {"amount": 10}})).unwrap();
+        assert_eq!(result.decision, Decision::Allow);
+        assert!(result.explanation.unwrap().rules.iter().all(|r| r.rule != "deny"));
+    }
+
+    #[test]
+    fn test_policies_see_data_documents() {
+        let mut engine = PolicyEngine::new();
+        engine
+            .set_data(Arc::new(data_documents::DataSet {
+                revision: "r1".to_string(),
+                document: json!({"sanctions": {"addresses": ["0xbad"]}}),
+                lists: Default::default(),
+            }))
+            .unwrap();
+        // Loaded after the data, so the isolated attribution engines need it too
+        engine
+            .load_policy(Uuid::new_v4(), "sanctions", "1.0.0", r#"
+                package guardrail
+                deny["Sanctioned address"] {
+                    data.sanctions.addresses[_] == input.action.to
+                }
+            "#)
+            .unwrap();
+        engine.load_policy(Uuid::new_v4(), "other", "1.0.0", "package guardrail
+allow_all { true }").unwrap();
+        engine.prepare().unwrap();
+
+        let result = engine.evaluate(&json!({"action": {"to": "0xbad"}})).unwrap();
+        assert_eq!(result.decision, Decision::Deny);
+        assert_eq!(result.policies.len(), 1);
+        assert_eq!(result.data_revision.as_deref(), Some("r1"));
+
+        let result = engine.evaluate(&json!({"action": {"to": "0xok"}})).unwrap();
+        assert_eq!(result.decision, Decision::Allow);
+    }
+
+    #[test]
+    fn test_structured_deny_reasons() {
+        let mut engine = PolicyEngine::new();
+        let rego = r#"
+            package guardrail
+            deny[{"code": "LIMIT_EXCEEDED", "message": "Over limit", "severity": "high", "params": {"limit": 1000}}] {
+                input.amount > 1000
+            }
+            deny["Legacy reason"] {
+                input.amount > 1000
+            }
+        "#;
+        engine.load_policy(Uuid::new_v4(), "limits", "1.0.0", rego).unwrap();
+
+        let result = engine.evaluate(&json!({"amount": 5000})).unwrap();
+        assert_eq!(result.decision, Decision::Deny);
+        assert_eq!(result.reasons.len(), 2);
+        assert_eq!(result.reason_details.len(), 2);
+        assert!(result.reasons.contains(&"Over limit".to_string()));
+
+        let coded = result.reason_details.iter().find(|r| r.code.is_some()).unwrap();
+        assert_eq!(coded.code.as_deref(), Some("LIMIT_EXCEEDED"));
+        assert_eq!(coded.severity, Some(guardrail_shared::ReasonSeverity::High));
+        assert_eq!(coded.params, json!({"limit": 1000}));
+        let legacy = result.reason_details.iter().find(|r| r.code.is_none()).unwrap();
+        assert_eq!(legacy.message, "Legacy reason");
+
+        // Reasons with an unknown severity fail the evaluation
+        let mut engine = PolicyEngine::new();
+        engine
+            .load_policy(Uuid::new_v4(), "bad", "1.0.0", "package guardrail\ndeny[{\"code\": \"X\", \"severity\": \"meh\"}] { true }")
+            .unwrap();
+        assert!(engine.evaluate(&json!({})).is_err());
+    }
+
+    #[test]
+    fn test_obligations_on_allow() {
+        let mut engine = PolicyEngine::new();
+        let delay_id = Uuid::new_v4();
+        engine
+            .load_policy(delay_id, "delay", "1.0.0", r#"
+                package guardrail
+                obligations[{"type": "DELAY_SETTLEMENT", "params": {"hours": 24}}] {
+                    input.amount > 1000
+                }
+            "#)
+            .unwrap();
+        engine
+            .load_policy(Uuid::new_v4(), "mfa", "1.0.0", r#"
+                package guardrail
+                obligations["STEP_UP_MFA"] {
+                    input.new_device
+                }
+            "#)
+            .unwrap();
+
+        let result = engine.evaluate(&json!({"amount": 5000, "new_device": false})).unwrap();
+        assert_eq!(result.decision, Decision::Allow);
+        assert_eq!(result.obligations.len(), 1);
+        assert_eq!(result.obligations[0].obligation_type, "DELAY_SETTLEMENT");
+        assert_eq!(result.obligations[0].params, json!({"hours": 24}));
+        assert_eq!(result.policies.len(), 1);
+        assert_eq!(result.policies[0].policy_id, delay_id);
+
+        let result = engine.evaluate(&json!({"amount": 5000, "new_device": true})).unwrap();
+        assert_eq!(result.obligations.len(), 2);
+        assert!(result.obligations.iter().any(|o| o.obligation_type == "STEP_UP_MFA" && o.params == json!({})));
+
+        // Obligations without a type fail the evaluation instead of being dropped
+        let mut engine = PolicyEngine::new();
+        engine
+            .load_policy(Uuid::new_v4(), "bad", "1.0.0", "package guardrail\nobligations[{\"hours\": 1}] { true }")
+            .unwrap();
+        assert!(engine.evaluate(&json!({})).is_err());
+    }
+
     #[test]
     fn test_require_approval() {
         let mut engine = PolicyEngine::new();
@@ -754,7 +1943,7 @@ mod tests {
                 input.amount > 5000
             }
         "#;
-        engine.load_policy(Uuid::new_v4(), "test", rego).unwrap();
+        engine.load_policy(Uuid::new_v4(), "test", "1.0.0", rego).unwrap();
         
         let input = json!({"amount": 6000});
         let result = engine.evaluate(&input).unwrap();
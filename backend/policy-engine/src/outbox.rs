@@ -0,0 +1,322 @@
+//! Decision ledger outbox
+//!
+//! Every stored decision gets a `decision_outbox` row in the transaction that
+//! stores it, so no committed decision can miss the movement ledger. Approval
+//! events for the decision are queued the same way, in the transaction that opens,
+//! votes on or expires the approval, and control findings in the transaction that
+//! opens or resolves them. A relay delivers the rows oldest first, linked by
+//! `policy_decision_id` where they concern a decision, and deletes each row once
+//! the ledger accepts it. A row the ledger can't take stays queued and is retried with exponential
+//! backoff.
+//!
+//! Delivery is at least once: if the ledger records an event but the row isn't
+//! deleted, the row is sent again. The ledger answers a repeated `POLICY_DECISION`
+//! with the event it already holds for that decision; other events may repeat.
+
+use crate::{AppState, PolicyEvalResult};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use guardrail_shared::{ApiResponse, CheckActionRequest, DegradedCause, EventType, Result};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Longest a failed row waits before its next attempt
+const MAX_BACKOFF_SECS: i32 = 300;
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+#[derive(Debug, Clone)]
+pub struct OutboxConfig {
+    /// Longest the relay sleeps between looking for due rows
+    pub poll_interval: Duration,
+    /// Rows delivered per pass
+    pub batch_size: i64,
+}
+
+impl OutboxConfig {
+    pub fn from_env() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(
+                std::env::var("LEDGER_OUTBOX_POLL_SECS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(5),
+            ),
+            batch_size: std::env::var("LEDGER_OUTBOX_BATCH_SIZE")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(100),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct OutboxStats {
+    /// Decision, approval and finding events not yet in the ledger
+    pub pending: i64,
+    /// Pending events whose delivery has failed at least once
+    pub retrying: i64,
+    pub oldest_pending_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+// ============================================================================
+// Enqueue
+// ============================================================================
+
+/// Ledger payload for a decision
+fn payload(
+    decision_id: Uuid,
+    req: &CheckActionRequest,
+    eval_result: &PolicyEvalResult,
+    degraded: Option<DegradedCause>,
+    now: DateTime<Utc>,
+) -> serde_json::Value {
+    serde_json::json!({
+        "decision_id": decision_id,
+        "identity_id": req.identity_id,
+        "decision": eval_result.decision,
+        "action": req.action,
+        "reasons": eval_result.reasons,
+        "required_approvers": eval_result.required_approvers,
+        "obligations": eval_result.obligations,
+        "policies": eval_result.policies.iter().map(|p| serde_json::json!({
+            "policy_id": p.policy_id,
+            "policy_version": p.policy_version,
+        })).collect::<Vec<_>>(),
+        "data_revision": eval_result.data_revision,
+        "policy_set_revision": eval_result.policy_set_revision,
+        "degraded_cause": degraded.map(DegradedCause::as_str),
+        "evaluated_at": now,
+    })
+}
+
+/// Queue a decision for the ledger, in the transaction that stores it
+pub(crate) async fn enqueue(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    decision_id: Uuid,
+    req: &CheckActionRequest,
+    eval_result: &PolicyEvalResult,
+    degraded: Option<DegradedCause>,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    enqueue_event(
+        tx,
+        EventType::PolicyDecision,
+        req.identity_id,
+        Some(decision_id),
+        payload(decision_id, req, eval_result, degraded, now),
+    )
+    .await
+}
+
+/// Queue another event, such as an approval vote on a decision, in the
+/// transaction that makes the change it records
+pub(crate) async fn enqueue_event(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    event_type: EventType,
+    actor_id: Uuid,
+    decision_id: Option<Uuid>,
+    payload: serde_json::Value,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO decision_outbox (decision_id, event_type, actor_id, payload)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        decision_id,
+        event_type as EventType,
+        actor_id,
+        payload,
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+// ============================================================================
+// Relay
+// ============================================================================
+
+/// Wait before retrying a row that has failed `attempts` times
+fn backoff_secs(attempts: i32) -> i32 {
+    1_i32.checked_shl(attempts.clamp(0, 30) as u32).unwrap_or(MAX_BACKOFF_SECS).min(MAX_BACKOFF_SECS)
+}
+
+/// Deliver due rows, oldest first. Returns how many rows were delivered and whether
+/// the pass stopped early on a failure.
+pub async fn deliver_due(state: &AppState) -> Result<(usize, bool)> {
+    let mut tx = state.db.begin().await?;
+
+    // Skip rows another instance is delivering
+    let due = sqlx::query!(
+        r#"
+        SELECT id, decision_id, event_type as "event_type: EventType", actor_id, payload, attempts
+        FROM decision_outbox
+        WHERE next_attempt_at <= NOW()
+        ORDER BY id
+        LIMIT $1
+        FOR UPDATE SKIP LOCKED
+        "#,
+        state.outbox_config.batch_size,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut delivered = 0;
+    let mut failed = false;
+    for row in due {
+        let recorded = state
+            .ledger
+            .record_event(row.event_type, row.actor_id, row.decision_id, row.payload)
+            .await;
+        match recorded {
+            Ok(()) => {
+                sqlx::query!("DELETE FROM decision_outbox WHERE id = $1", row.id)
+                    .execute(&mut *tx)
+                    .await?;
+                delivered += 1;
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Ledger delivery of {:?} from outbox row {} failed (attempt {}): {}",
+                    row.event_type,
+                    row.id,
+                    row.attempts + 1,
+                    e
+                );
+                sqlx::query!(
+                    r#"
+                    UPDATE decision_outbox
+                    SET attempts = attempts + 1,
+                        last_error = $2,
+                        next_attempt_at = NOW() + $3 * INTERVAL '1 second'
+                    WHERE id = $1
+                    "#,
+                    row.id,
+                    e.to_string(),
+                    f64::from(backoff_secs(row.attempts)),
+                )
+                .execute(&mut *tx)
+                .await?;
+                // The ledger is probably down; leave the rest for the next pass
+                failed = true;
+                break;
+            }
+        }
+    }
+
+    tx.commit().await?;
+    Ok((delivered, failed))
+}
+
+/// Deliver queued events as they are committed, and retry failed ones
+pub fn spawn_relay(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match deliver_due(&state).await {
+                // A full batch went through; there may be more waiting
+                Ok((delivered, false)) if delivered as i64 == state.outbox_config.batch_size => continue,
+                Ok(_) => {}
+                Err(e) => tracing::error!("Decision outbox relay failed: {}", e),
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(state.outbox_config.poll_interval) => {}
+                _ = state.outbox_ready.notified() => {}
+            }
+        }
+    })
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn outbox_stats_impl(state: &AppState) -> Result<OutboxStats> {
+    let stats = sqlx::query_as!(
+        OutboxStats,
+        r#"
+        SELECT COUNT(*) as "pending!",
+               COUNT(*) FILTER (WHERE attempts > 0) as "retrying!",
+               MIN(created_at) as oldest_pending_at,
+               (SELECT last_error FROM decision_outbox WHERE last_error IS NOT NULL
+                ORDER BY next_attempt_at DESC LIMIT 1) as last_error
+        FROM decision_outbox
+        "#
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(stats)
+}
+
+pub async fn outbox_stats(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match outbox_stats_impl(&state).await {
+        Ok(stats) => (StatusCode::OK, Json(ApiResponse::success(stats))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<OutboxStats>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use guardrail_shared::{Decision, PolicyAttribution};
+    use serde_json::json;
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff_secs(0), 1);
+        assert_eq!(backoff_secs(3), 8);
+        assert_eq!(backoff_secs(8), 256);
+        assert_eq!(backoff_secs(9), MAX_BACKOFF_SECS);
+        assert_eq!(backoff_secs(64), MAX_BACKOFF_SECS);
+    }
+
+    #[test]
+    fn test_payload() {
+        let req: CheckActionRequest = serde_json::from_value(json!({
+            "identity_id": Uuid::nil(),
+            "action": {"action_type": "WITHDRAWAL", "amount": "50000", "asset": "BTC", "metadata": {}},
+            "context": {"timestamp": "2024-01-01T00:00:00Z", "metadata": {}},
+        }))
+        .unwrap();
+        let policy_id = Uuid::new_v4();
+        let eval_result = PolicyEvalResult {
+            decision: Decision::Deny,
+            reasons: vec!["Amount exceeds limit".to_string()],
+            reason_details: vec![],
+            required_approvers: vec![],
+            obligations: vec![],
+            policies: vec![PolicyAttribution {
+                policy_id,
+                policy_name: "limits".to_string(),
+                policy_version: "1.0.0".to_string(),
+                reasons: vec!["Amount exceeds limit".to_string()],
+                required_approvers: vec![],
+                obligations: vec![],
+            }],
+            evaluated_policies: vec![policy_id],
+            explanation: None,
+            data_revision: None,
+            policy_set_revision: Some("abc".to_string()),
+        };
+
+        let decision_id = Uuid::new_v4();
+        let payload = payload(decision_id, &req, &eval_result, Some(DegradedCause::DeadlineExceeded), Utc::now());
+        assert_eq!(payload["decision_id"], json!(decision_id));
+        assert_eq!(payload["decision"], "DENY");
+        assert_eq!(payload["action"]["amount"], "50000");
+        assert_eq!(payload["policies"], json!([{"policy_id": policy_id, "policy_version": "1.0.0"}]));
+        assert_eq!(payload["policy_set_revision"], "abc");
+        assert_eq!(payload["degraded_cause"], "DEADLINE_EXCEEDED");
+    }
+}
@@ -0,0 +1,351 @@
+//! Rego unit tests
+//!
+//! Rules named `test_*` in a policy module or in its attached test module are run
+//! before a policy is created, updated or activated. A test passes when its rule
+//! evaluates to `true`. Tests run on a scratch engine holding only the policy under
+//! test, so they see the policy's own rules rather than the merged active set, plus
+//! the active data documents and libraries so lookups and imports behave as they
+//! do in production. Policies are loaded for evaluation without their `test_*`
+//! rules, so inline tests never run as part of a decision.
+
+use crate::{builtins, get_policy_impl, lint, AppState, PolicyEngine};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use guardrail_shared::{ApiResponse, GuardRailError, PolicyTestReport, PolicyTestResult, PolicyTestRun, Result};
+use regorus::Engine;
+use serde::Deserialize;
+use sqlx::PgExecutor;
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// What caused a test run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestTrigger {
+    Create,
+    Update,
+    Activate,
+}
+
+impl TestTrigger {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Create => "CREATE",
+            Self::Update => "UPDATE",
+            Self::Activate => "ACTIVATE",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RunTestsRequest {
+    pub rego_source: String,
+    pub test_source: Option<String>,
+    /// Library the draft would replace, left out of the loaded libraries
+    #[serde(default)]
+    pub replaces_policy_id: Option<Uuid>,
+}
+
+// ============================================================================
+// Discovery and execution
+// ============================================================================
+
+/// Package declared by a module, e.g. `guardrail.limits`
+fn module_package(source: &str) -> Option<&str> {
+    source
+        .lines()
+        .map(str::trim)
+        .find_map(|line| line.strip_prefix("package "))
+        .map(str::trim)
+}
+
+/// Names of the top-level `test_*` rules defined in a module, in source order
+fn find_test_rules(source: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (name, _) in lint::value_rules(source) {
+        if name.starts_with("test_") && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// `source` with its top-level `test_*` rules blanked out, keeping every other
+/// rule at the same line and column
+pub fn without_tests(source: &str) -> Cow<'_, str> {
+    let tests: Vec<Range<usize>> = lint::value_rules(source)
+        .into_iter()
+        .filter(|(name, _)| name.starts_with("test_"))
+        .map(|(_, span)| span)
+        .collect();
+    if tests.is_empty() {
+        return Cow::Borrowed(source);
+    }
+
+    source
+        .char_indices()
+        .map(|(i, c)| if c != '\n' && tests.iter().any(|t| t.contains(&i)) { ' ' } else { c })
+        .collect()
+}
+
+/// Run the `test_*` rules of a policy and its optional test module, with the data
+/// documents and libraries of `loaded`; `replaces` is left out of the libraries
+pub fn run_tests(
+    loaded: &PolicyEngine,
+    replaces: Option<Uuid>,
+    name: &str,
+    rego_source: &str,
+    test_source: Option<&str>,
+) -> Result<PolicyTestReport> {
+    let mut engine = Engine::new();
+    let lists = builtins::SharedLists::default();
+    builtins::register(&mut engine, &lists).map_err(|e| GuardRailError::Internal(e.to_string()))?;
+    if let Some(data) = loaded.data.as_deref() {
+        PolicyEngine::add_data(&mut engine, data)?;
+        *lists.write().unwrap_or_else(|e| e.into_inner()) = data.lists.clone();
+    }
+    for (path, source) in loaded.library_modules(replaces) {
+        engine
+            .add_policy(path.clone(), source)
+            .map_err(|e| GuardRailError::InvalidRego(format!("{}: {}", path, e)))?;
+    }
+    let mut modules = vec![(format!("policy/{}", name), rego_source)];
+    if let Some(test_source) = test_source {
+        modules.push((format!("policy/{}/test", name), test_source));
+    }
+
+    for (path, source) in &modules {
+        engine
+            .add_policy(path.clone(), source.to_string())
+            .map_err(|e| GuardRailError::InvalidRego(format!("{}: {}", path, e)))?;
+    }
+
+    let mut results = Vec::new();
+    for (_, source) in &modules {
+        let Some(package) = module_package(source) else {
+            continue;
+        };
+        for test in find_test_rules(source) {
+            let outcome = engine.eval_rule(format!("data.{}.{}", package, test));
+            let (passed, error) = match outcome {
+                Ok(value) if value == regorus::Value::from(true) => (true, None),
+                Ok(regorus::Value::Undefined) => (false, None),
+                Ok(value) => (false, Some(format!("Expected true, got {}", value))),
+                Err(e) => (false, Some(e.to_string())),
+            };
+            results.push(PolicyTestResult {
+                name: test,
+                package: package.to_string(),
+                passed,
+                error,
+            });
+        }
+    }
+
+    Ok(PolicyTestReport::new(results))
+}
+
+/// Reject a policy whose tests failed
+pub fn ensure_passed(report: &PolicyTestReport) -> Result<()> {
+    if report.passed {
+        return Ok(());
+    }
+
+    let failing: Vec<&str> = report
+        .results
+        .iter()
+        .filter(|r| !r.passed)
+        .map(|r| r.name.as_str())
+        .collect();
+    Err(GuardRailError::PolicyTestFailed(format!(
+        "{} of {} tests failed: {}",
+        report.failed,
+        report.total,
+        failing.join(", ")
+    )))
+}
+
+// ============================================================================
+// Storage
+// ============================================================================
+
+/// Store the outcome of a test run against a policy version
+pub async fn record_run<'e>(
+    executor: impl PgExecutor<'e>,
+    policy_id: Uuid,
+    policy_version: &str,
+    trigger: TestTrigger,
+    report: &PolicyTestReport,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO policy_test_runs (id, policy_id, policy_version, trigger, passed, total, failed, results, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        "#,
+        Uuid::new_v4(),
+        policy_id,
+        policy_version,
+        trigger.as_str(),
+        report.passed,
+        report.total as i32,
+        report.failed as i32,
+        serde_json::to_value(&report.results)?,
+        chrono::Utc::now(),
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+pub async fn list_test_runs(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match list_test_runs_impl(&state, id).await {
+        Ok(runs) => (StatusCode::OK, Json(ApiResponse::success(runs))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Vec<PolicyTestRun>>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn list_test_runs_impl(state: &AppState, id: Uuid) -> Result<Vec<PolicyTestRun>> {
+    // Ensure the policy exists so an unknown id is a 404 rather than an empty list
+    let _ = get_policy_impl(&state.db, id).await?;
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT id, policy_id, policy_version, trigger, passed, total, failed, results, created_at as "created_at!"
+        FROM policy_test_runs
+        WHERE policy_id = $1
+        ORDER BY created_at DESC
+        LIMIT 50
+        "#,
+        id,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(PolicyTestRun {
+                id: row.id,
+                policy_id: row.policy_id,
+                policy_version: row.policy_version,
+                trigger: row.trigger,
+                passed: row.passed,
+                total: row.total,
+                failed: row.failed,
+                results: serde_json::from_value(row.results)?,
+                created_at: row.created_at,
+            })
+        })
+        .collect()
+}
+
+/// Run tests against a draft without storing anything
+pub async fn run_draft_tests(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<RunTestsRequest>,
+) -> impl IntoResponse {
+    match run_tests(&state.engine.load(), req.replaces_policy_id, "draft", &req.rego_source, req.test_source.as_deref()) {
+        Ok(report) => (StatusCode::OK, Json(ApiResponse::success(report))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<PolicyTestReport>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = r#"
+package guardrail
+
+deny["Amount too high"] {
+    input.amount > 1000
+}
+"#;
+
+    #[test]
+    fn test_find_test_rules() {
+        let source = "package x\ntest_a { true }\ntest_a { false }\n  test_b { true }\ntest_c = true\ntest_helper(x) = x\nnot_test_d { true }\n";
+        assert_eq!(find_test_rules(source), vec!["test_a", "test_b", "test_c"]);
+
+        // A local named like a test is not a rule
+        let source = "package x\nallow {\n    test_x := 5\n    test_x > 1\n}\ntest_y {\n    test_z := 1\n    test_z == 1\n}\n";
+        assert_eq!(find_test_rules(source), vec!["test_y"]);
+        assert_eq!(module_package(source), Some("x"));
+    }
+
+    #[test]
+    fn test_run_tests_in_attached_module() {
+        let tests = r#"
+package guardrail.policy_test
+
+test_denies_large {
+    data.guardrail.deny["Amount too high"] with input as {"amount": 5000}
+}
+
+test_allows_small {
+    count(data.guardrail.deny) == 0 with input as {"amount": 10}
+}
+
+test_wrong_expectation {
+    count(data.guardrail.deny) == 0 with input as {"amount": 5000}
+}
+"#;
+        let report = run_tests(&PolicyEngine::new(), None, "limits", POLICY, Some(tests)).unwrap();
+        assert_eq!(report.total, 3);
+        assert_eq!(report.failed, 1);
+        assert!(!report.passed);
+        assert!(report.results[0].passed);
+        assert!(report.results[1].passed);
+        assert_eq!(report.results[2].name, "test_wrong_expectation");
+        assert!(!report.results[2].passed);
+
+        let err = ensure_passed(&report).unwrap_err();
+        assert!(err.to_string().contains("test_wrong_expectation"));
+    }
+
+    #[test]
+    fn test_run_tests_in_policy_module() {
+        let source = format!("{}\ntest_inline {{\n    deny[\"Amount too high\"] with input as {{\"amount\": 2000}}\n}}\n", POLICY);
+        let report = run_tests(&PolicyEngine::new(), None, "limits", &source, None).unwrap();
+        assert_eq!(report.total, 1);
+        assert!(report.passed);
+        assert!(ensure_passed(&report).is_ok());
+
+        let failing = format!("{}\ntest_inline_fails {{\n    count(deny) == 0 with input as {{\"amount\": 2000}}\n}}\n", source);
+        let report = run_tests(&PolicyEngine::new(), None, "limits", &failing, None).unwrap();
+        assert_eq!(report.total, 2);
+        assert!(!report.results[1].passed);
+        assert!(ensure_passed(&report).is_err());
+
+        // Decisions are evaluated without the inline tests
+        let stripped = without_tests(&failing);
+        assert!(!stripped.contains("test_inline"));
+        assert_eq!(stripped.lines().count(), failing.lines().count());
+        let mut engine = PolicyEngine::new();
+        engine.load_policy(Uuid::nil(), "limits", "1.0.0", &failing).unwrap();
+        assert_eq!(engine.loaded_policies[0].rego_source, stripped);
+
+        // No tests at all is not a failure
+        let report = run_tests(&PolicyEngine::new(), None, "limits", POLICY, None).unwrap();
+        assert_eq!(report.total, 0);
+        assert!(report.passed);
+    }
+}
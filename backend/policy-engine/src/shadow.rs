@@ -0,0 +1,390 @@
+//! Shadow policies
+//!
+//! A policy in SHADOW state is evaluated on every check it targets alongside the
+//! enforced set but never changes the returned decision. The shadow engine holds the enforced
+//! policies plus every shadow policy, so its outcome is what `check_action` would
+//! return if the shadow policies were activated. The shadow policies that applied
+//! are noted on the decision, and outcomes that differ from the enforced decision
+//! are stored in `shadow_differences`.
+//!
+//! Shadow evaluation runs on blocking threads after the decision is committed. At
+//! most `SHADOW_MAX_IN_FLIGHT` evaluations run at once; a check arriving while all
+//! of them are busy skips shadow evaluation, and is counted in `/health`.
+
+use crate::{get_policy_impl, libraries, AppState, PolicyEngine, PolicyEvalResult};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use guardrail_shared::{ApiResponse, Decision, GuardRailError, Policy, PolicyKind, PolicyTargets, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+
+/// Enforced policies plus shadow policies
+#[derive(Default)]
+pub struct ShadowEngine {
+    pub engine: PolicyEngine,
+    pub shadow_policies: HashSet<Uuid>,
+}
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+#[derive(Debug, Clone)]
+pub struct ShadowConfig {
+    /// Most shadow evaluations running at once
+    pub max_in_flight: usize,
+}
+
+impl ShadowConfig {
+    pub fn from_env() -> Self {
+        Self {
+            max_in_flight: std::env::var("SHADOW_MAX_IN_FLIGHT")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|&n: &usize| n > 0)
+                .unwrap_or(16),
+        }
+    }
+}
+
+/// Bounds the shadow evaluations in flight and counts the ones skipped
+pub struct ShadowQueue {
+    slots: Arc<Semaphore>,
+    dropped: AtomicU64,
+}
+
+impl ShadowQueue {
+    pub fn new(config: &ShadowConfig) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(config.max_in_flight)),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Checks that skipped shadow evaluation because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+// ============================================================================
+// Response Types
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+pub struct ShadowSummary {
+    pub policy_id: Uuid,
+    pub name: String,
+    pub version: String,
+    pub shadow_since: Option<DateTime<Utc>>,
+    /// When the policy left shadow, if it has
+    pub shadow_until: Option<DateTime<Utc>>,
+    /// Checks the policy was evaluated on while in shadow
+    pub evaluated: i64,
+    /// Checks where the policy contributed to a different outcome
+    pub differences: i64,
+    /// Differences that changed the decision itself, not just reasons
+    pub decision_changes: i64,
+    /// Counts keyed by `ENFORCED->SHADOW`
+    pub transitions: BTreeMap<String, i64>,
+}
+
+// ============================================================================
+// Evaluation
+// ============================================================================
+
+fn same_set(a: &[String], b: &[String]) -> bool {
+    a.len() == b.len() && a.iter().all(|x| b.contains(x))
+}
+
+/// Whether the shadow outcome differs from the enforced one
+fn differs(enforced: &PolicyEvalResult, shadow: &PolicyEvalResult) -> bool {
+    enforced.decision != shadow.decision
+        || !same_set(&enforced.reasons, &shadow.reasons)
+        || !same_set(&enforced.required_approvers, &shadow.required_approvers)
+        || enforced.obligations.len() != shadow.obligations.len()
+        || enforced.obligations.iter().any(|o| !shadow.obligations.contains(o))
+}
+
+/// Evaluate the shadow set for a recorded decision in the background, unless the
+/// queue is full
+pub fn spawn_evaluation(state: &AppState, decision_id: Uuid, input: serde_json::Value, enforced: PolicyEvalResult) {
+    let Ok(permit) = state.shadow_queue.slots.clone().try_acquire_owned() else {
+        let dropped = state.shadow_queue.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+        if dropped.is_power_of_two() {
+            tracing::warn!("Shadow evaluation queue is full; {} checks skipped so far", dropped);
+        }
+        return;
+    };
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = evaluate_and_record(&state, decision_id, input, &enforced).await {
+            tracing::warn!("Shadow evaluation failed for decision {}: {}", decision_id, e);
+        }
+        drop(permit);
+    });
+}
+
+/// Evaluate the shadow set for a recorded decision, note which shadow policies
+/// applied to it and store any difference
+async fn evaluate_and_record(
+    state: &AppState,
+    decision_id: Uuid,
+    input: serde_json::Value,
+    enforced: &PolicyEvalResult,
+) -> Result<()> {
+    let shadow = state.shadow.load();
+    if shadow.shadow_policies.is_empty() {
+        return Ok(());
+    }
+    let (shadow, evaluated, shadow_policy_ids) = tokio::task::spawn_blocking(move || -> Result<_> {
+        let result = shadow.engine.evaluate(&input)?;
+        let is_shadow = |id: &Uuid| shadow.shadow_policies.contains(id);
+        let evaluated: Vec<Uuid> = result.evaluated_policies.iter().copied().filter(is_shadow).collect();
+        let ids: Vec<Uuid> = result.policies.iter().map(|p| p.policy_id).filter(is_shadow).collect();
+        Ok((result, evaluated, ids))
+    })
+    .await
+    .map_err(|e| GuardRailError::PolicyEvaluation(format!("Shadow evaluation task failed: {}", e)))??;
+
+    if !evaluated.is_empty() {
+        sqlx::query!(
+            "UPDATE policy_decisions SET shadow_policy_ids = $2 WHERE id = $1",
+            decision_id,
+            &evaluated,
+        )
+        .execute(&state.db)
+        .await?;
+    }
+
+    if !differs(enforced, &shadow) {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO shadow_differences (id, decision_id, enforced_decision, shadow_decision, enforced_reasons, shadow_reasons, shadow_required_approvers, shadow_policy_ids, decision_changed, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#,
+        Uuid::new_v4(),
+        decision_id,
+        enforced.decision as Decision,
+        shadow.decision as Decision,
+        &enforced.reasons,
+        &shadow.reasons,
+        &shadow.required_approvers,
+        &shadow_policy_ids,
+        enforced.decision != shadow.decision,
+        Utc::now(),
+    )
+    .execute(&state.db)
+    .await?;
+
+    Ok(())
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+pub async fn shadow_policy(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match shadow_policy_impl(&state, id).await {
+        Ok(policy) => (StatusCode::OK, Json(ApiResponse::success(policy))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Policy>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+/// Move a policy into SHADOW state, taking it out of enforcement if it was active
+async fn shadow_policy_impl(state: &AppState, id: Uuid) -> Result<Policy> {
+    let now = Utc::now();
+    let current = get_policy_impl(&state.db, id).await?;
+    libraries::validate(current.kind, &current.targets, true, None, None)?;
+
+    let policy = sqlx::query_as!(
+        Policy,
+        r#"
+        UPDATE policies
+        SET is_active = false, is_shadow = true,
+            shadow_since = CASE WHEN is_shadow THEN shadow_since ELSE $2 END, shadow_until = NULL, updated_at = $2
+        WHERE id = $1
+        RETURNING id, name, description, version, kind as "kind: PolicyKind", rego_source, test_source, is_active as "is_active!", is_shadow, effective_from, effective_until, targets as "targets: PolicyTargets", created_by, created_at as "created_at!", updated_at as "updated_at!"
+        "#,
+        id,
+        now,
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| GuardRailError::PolicyNotFound(id.to_string()))?;
+
+    crate::reload_policies(state).await?;
+
+    Ok(policy)
+}
+
+pub async fn shadow_summary(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match shadow_summary_impl(&state, None).await {
+        Ok(summaries) => (StatusCode::OK, Json(ApiResponse::success(summaries))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Vec<ShadowSummary>>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+pub async fn policy_shadow_summary(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match policy_shadow_summary_impl(&state, id).await {
+        Ok(summary) => (StatusCode::OK, Json(ApiResponse::success(summary))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<ShadowSummary>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn policy_shadow_summary_impl(state: &AppState, id: Uuid) -> Result<ShadowSummary> {
+    let _ = get_policy_impl(&state.db, id).await?;
+
+    shadow_summary_impl(state, Some(id))
+        .await?
+        .pop()
+        .ok_or_else(|| GuardRailError::Validation(format!("Policy {} has never been in shadow", id)))
+}
+
+/// Summaries for current shadow policies, or for one policy that has been in shadow
+async fn shadow_summary_impl(state: &AppState, policy_id: Option<Uuid>) -> Result<Vec<ShadowSummary>> {
+    let policies = sqlx::query!(
+        r#"
+        SELECT p.id, p.name, p.version, p.shadow_since, p.shadow_until,
+               (SELECT COUNT(*) FROM policy_decisions d
+                WHERE p.id = ANY(d.shadow_policy_ids)
+                AND d.created_at >= p.shadow_since AND d.created_at < COALESCE(p.shadow_until, NOW())) as "evaluated!"
+        FROM policies p
+        WHERE ($1::uuid IS NULL AND p.is_shadow) OR (p.id = $1 AND p.shadow_since IS NOT NULL)
+        ORDER BY p.shadow_since ASC
+        "#,
+        policy_id,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut summaries = Vec::with_capacity(policies.len());
+    for policy in policies {
+        let rows = sqlx::query!(
+            r#"
+            SELECT enforced_decision as "enforced_decision: Decision", shadow_decision as "shadow_decision: Decision",
+                   COUNT(*) as "count!"
+            FROM shadow_differences
+            WHERE $1 = ANY(shadow_policy_ids) AND created_at >= $2 AND created_at < COALESCE($3, NOW())
+            GROUP BY enforced_decision, shadow_decision
+            "#,
+            policy.id,
+            policy.shadow_since,
+            policy.shadow_until,
+        )
+        .fetch_all(&state.db)
+        .await?;
+
+        let mut summary = ShadowSummary {
+            policy_id: policy.id,
+            name: policy.name,
+            version: policy.version,
+            shadow_since: policy.shadow_since,
+            shadow_until: policy.shadow_until,
+            evaluated: policy.evaluated,
+            differences: 0,
+            decision_changes: 0,
+            transitions: BTreeMap::new(),
+        };
+        for row in rows {
+            summary.differences += row.count;
+            if row.enforced_decision != row.shadow_decision {
+                summary.decision_changes += row.count;
+                let key = format!(
+                    "{}->{}",
+                    decision_label(row.enforced_decision),
+                    decision_label(row.shadow_decision)
+                );
+                *summary.transitions.entry(key).or_default() += row.count;
+            }
+        }
+        summaries.push(summary);
+    }
+
+    Ok(summaries)
+}
+
+fn decision_label(decision: Decision) -> &'static str {
+    match decision {
+        Decision::Allow => "ALLOW",
+        Decision::Deny => "DENY",
+        Decision::RequireApproval => "REQUIRE_APPROVAL",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(decision: Decision, reasons: &[&str]) -> PolicyEvalResult {
+        PolicyEvalResult {
+            decision,
+            reasons: reasons.iter().map(|r| r.to_string()).collect(),
+            reason_details: Vec::new(),
+            required_approvers: Vec::new(),
+            obligations: Vec::new(),
+            policies: Vec::new(),
+            evaluated_policies: Vec::new(),
+            explanation: None,
+            data_revision: None,
+            policy_set_revision: None,
+        }
+    }
+
+    #[test]
+    fn test_differs() {
+        let enforced = result(Decision::Deny, &["a", "b"]);
+        assert!(!differs(&enforced, &result(Decision::Deny, &["b", "a"])));
+        assert!(differs(&enforced, &result(Decision::Deny, &["a", "b", "c"])));
+        assert!(differs(&result(Decision::Allow, &[]), &result(Decision::Deny, &[])));
+    }
+
+    #[test]
+    fn test_shadow_engine_attributes_shadow_policy() {
+        let enforced_id = Uuid::new_v4();
+        let shadow_id = Uuid::new_v4();
+        let mut shadow = ShadowEngine::default();
+        shadow
+            .engine
+            .load_policy(enforced_id, "enforced", "1.0.0", "package guardrail\ndeny[\"over 1000\"] { input.amount > 1000 }")
+            .unwrap();
+        shadow
+            .engine
+            .load_policy(shadow_id, "stricter", "1.0.0", "package guardrail\ndeny[\"over 500\"] { input.amount > 500 }")
+            .unwrap();
+        shadow.shadow_policies.insert(shadow_id);
+
+        let result = shadow.engine.evaluate(&serde_json::json!({"amount": 700})).unwrap();
+        assert_eq!(result.decision, Decision::Deny);
+        assert_eq!(result.policies.len(), 1);
+        assert!(shadow.shadow_policies.contains(&result.policies[0].policy_id));
+    }
+}
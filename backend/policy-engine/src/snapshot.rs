@@ -0,0 +1,45 @@
+//! Atomically swappable snapshots
+//!
+//! Readers take an `Arc` to the current value and keep using it for as long as
+//! they need, while a writer builds a replacement and swaps it in. The lock is
+//! only held to clone or replace the `Arc`, never during evaluation.
+
+use std::sync::{Arc, RwLock};
+
+pub struct Snapshot<T> {
+    current: RwLock<Arc<T>>,
+}
+
+impl<T> Snapshot<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(value)),
+        }
+    }
+
+    /// The current value
+    pub fn load(&self) -> Arc<T> {
+        // A poisoned lock still holds a valid Arc; the swap itself cannot panic
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Replace the current value; existing readers keep the previous one
+    pub fn store(&self, value: T) {
+        let value = Arc::new(value);
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readers_keep_previous_snapshot() {
+        let snapshot = Snapshot::new(1);
+        let before = snapshot.load();
+        snapshot.store(2);
+        assert_eq!(*before, 1);
+        assert_eq!(*snapshot.load(), 2);
+    }
+}
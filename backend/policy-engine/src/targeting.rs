@@ -0,0 +1,129 @@
+//! Policy targeting
+//!
+//! A policy can declare the action types, assets, identity types and
+//! organisations it applies to. `PolicyEngine` evaluates a check against only
+//! the policies whose targets match its input, so a policy about config changes
+//! never runs, or interferes, on a withdrawal.
+//!
+//! All policies share the `guardrail` package, so a subset of them needs its own
+//! merged engine. Engines are built the first time a subset is selected and
+//! cached on the engine snapshot, which a reload replaces.
+
+use guardrail_shared::{GuardRailError, PolicyTargets, Result};
+use regorus::Engine;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Most subset engines kept per snapshot; the cache starts over once full
+const MAX_CACHED_SUBSETS: usize = 256;
+
+/// Whether a policy with `targets` applies to a check's input document
+pub fn matches(targets: &PolicyTargets, input: &Value) -> bool {
+    let field = |path: [&str; 2]| input.get(path[0]).and_then(|v| v.get(path[1])).and_then(Value::as_str);
+
+    let action_type = field(["action", "action_type"]);
+    let action_type_matches = targets.action_types.is_empty()
+        || targets
+            .action_types
+            .iter()
+            .any(|t| serde_json::to_value(t).ok().as_ref().and_then(Value::as_str) == action_type);
+
+    let asset = field(["action", "asset"]);
+    let asset_matches =
+        targets.assets.is_empty() || asset.is_some_and(|a| targets.assets.iter().any(|t| t.eq_ignore_ascii_case(a)));
+
+    let identity_type = field(["identity", "type"]);
+    let identity_type_matches = targets.identity_types.is_empty()
+        || targets
+            .identity_types
+            .iter()
+            .any(|t| serde_json::to_value(t).ok().as_ref().and_then(Value::as_str) == identity_type);
+
+    let organization = field(["identity", "organization_id"]);
+    let organization_matches = targets.organization_ids.is_empty()
+        || organization.is_some_and(|o| targets.organization_ids.iter().any(|t| t.to_string() == o));
+
+    action_type_matches && asset_matches && identity_type_matches && organization_matches
+}
+
+pub fn validate(targets: &PolicyTargets) -> Result<()> {
+    if targets.assets.iter().any(|a| a.trim().is_empty()) {
+        return Err(GuardRailError::Validation("Target assets must not be blank".to_string()));
+    }
+    Ok(())
+}
+
+/// Merged engines for subsets of the loaded policies, keyed by their load order
+#[derive(Default)]
+pub struct SubsetEngines {
+    engines: Mutex<HashMap<Vec<usize>, Engine>>,
+}
+
+impl SubsetEngines {
+    /// A copy of the engine for `subset`, building it with `build` on first use
+    pub fn get_or_build(&self, subset: &[usize], build: impl FnOnce() -> Result<Engine>) -> Result<Engine> {
+        if let Some(engine) = self.engines.lock().unwrap_or_else(|e| e.into_inner()).get(subset) {
+            return Ok(engine.clone());
+        }
+
+        // Build outside the lock; a concurrent build of the same subset is harmless
+        let engine = build()?;
+        let mut engines = self.engines.lock().unwrap_or_else(|e| e.into_inner());
+        if engines.len() >= MAX_CACHED_SUBSETS {
+            engines.clear();
+        }
+        engines.insert(subset.to_vec(), engine.clone());
+        Ok(engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use guardrail_shared::{ActionType, IdentityType};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_matches() {
+        let org = Uuid::new_v4();
+        let input = json!({
+            "identity": {"type": "AGENT", "organization_id": org},
+            "action": {"action_type": "WITHDRAWAL", "asset": "btc"},
+        });
+
+        assert!(matches(&PolicyTargets::default(), &input));
+        assert!(matches(
+            &PolicyTargets {
+                action_types: vec![ActionType::Transfer, ActionType::Withdrawal],
+                assets: vec!["BTC".to_string()],
+                identity_types: vec![IdentityType::Agent],
+                organization_ids: vec![org],
+            },
+            &input
+        ));
+        assert!(!matches(
+            &PolicyTargets {
+                action_types: vec![ActionType::ConfigChange],
+                ..Default::default()
+            },
+            &input
+        ));
+        assert!(!matches(
+            &PolicyTargets {
+                organization_ids: vec![Uuid::new_v4()],
+                ..Default::default()
+            },
+            &input
+        ));
+        // A targeted field missing from the input doesn't match
+        assert!(!matches(
+            &PolicyTargets {
+                assets: vec!["BTC".to_string()],
+                ..Default::default()
+            },
+            &json!({"action": {"action_type": "API_CALL"}})
+        ));
+    }
+}
@@ -0,0 +1,938 @@
+//! Parameterised policy templates
+//!
+//! The policy builder picks a template, fills in a parameter set described by the
+//! template's JSON schema, and gets a complete Rego module back:
+//!
+//! | Template | Produces |
+//! |----------|----------|
+//! | `withdrawal_limit_by_kyc` | `KYC_LIMIT_EXCEEDED` when the amount is above the limit for the identity's KYC level |
+//! | `jurisdiction_blocklist` | `JURISDICTION_BLOCKED` for identities or requests from a listed country |
+//! | `new_address_cooling_off` | `NEW_ADDRESS` / `ADDRESS_COOLING_OFF` for destinations missing from, or recently added to, an address book |
+//! | `agent_spend_cap` | `AGENT_SPEND_CAP` when an agent's spend over a history window would pass a cap |
+//!
+//! Every template can deny with a structured reason or send the action for
+//! approval instead. Rendered modules define no helper rules, so any number of
+//! them can share the `guardrail` package. They go through the same validation,
+//! lint and tests as hand-written policies, and each stored version records the
+//! template and parameters it was rendered from.
+
+use crate::history::HistoryConfig;
+use crate::versions::{self, Revision};
+use crate::{create_policy_impl, data_documents, get_policy_impl, lint, validate_rego, AppState};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use guardrail_shared::{
+    ActionType, ApiResponse, CreatePolicyRequest, GuardRailError, LintReport, Policy, PolicyKind, PolicyTargets, ReasonSeverity, Result,
+    VersionBump,
+};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use uuid::Uuid;
+
+// ============================================================================
+// Request/Response Types
+// ============================================================================
+
+/// A template as listed for the policy builder
+#[derive(Debug, Serialize)]
+pub struct TemplateInfo {
+    pub id: &'static str,
+    pub version: i32,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub parameter_schema: Value,
+    pub example_parameters: Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenderTemplateRequest {
+    pub parameters: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RenderedTemplate {
+    pub template_id: &'static str,
+    pub template_version: i32,
+    pub rego_source: String,
+    pub lint: LintReport,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateFromTemplateRequest {
+    pub template_id: String,
+    pub parameters: Value,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub test_source: Option<String>,
+    #[serde(default)]
+    pub shadow: bool,
+    #[serde(default)]
+    pub effective_from: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub effective_until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub targets: PolicyTargets,
+    #[serde(default)]
+    pub created_by: Option<Uuid>,
+}
+
+/// Re-render a template policy with new parameters
+#[derive(Debug, Deserialize)]
+pub struct UpdateFromTemplateRequest {
+    pub parameters: Value,
+    pub description: Option<String>,
+    pub change_summary: Option<String>,
+    #[serde(default)]
+    pub bump: VersionBump,
+    #[serde(default)]
+    pub created_by: Option<Uuid>,
+}
+
+/// The template and parameters a policy version was rendered from
+#[derive(Debug, Clone)]
+pub(crate) struct TemplateSource {
+    pub template_id: String,
+    pub template_version: i32,
+    pub parameters: Value,
+}
+
+// ============================================================================
+// Catalogue
+// ============================================================================
+
+pub struct PolicyTemplate {
+    pub id: &'static str,
+    /// Bumped whenever the rendered Rego changes for the same parameters
+    pub version: i32,
+    pub name: &'static str,
+    pub description: &'static str,
+    schema: fn() -> Value,
+    example: fn() -> Value,
+    render: fn(&Value, &HistoryConfig) -> Result<String>,
+}
+
+const TEMPLATES: &[PolicyTemplate] = &[
+    PolicyTemplate {
+        id: "withdrawal_limit_by_kyc",
+        version: 1,
+        name: "Withdrawal limit per KYC level",
+        description: "Caps single withdrawals at an amount that grows with the identity's KYC level",
+        schema: kyc_limit_schema,
+        example: kyc_limit_example,
+        render: render_kyc_limit,
+    },
+    PolicyTemplate {
+        id: "jurisdiction_blocklist",
+        version: 1,
+        name: "Jurisdiction blocklist",
+        description: "Blocks identities whose JURISDICTION credential, or requests whose geo location, is a listed country",
+        schema: jurisdiction_schema,
+        example: jurisdiction_example,
+        render: render_jurisdiction,
+    },
+    PolicyTemplate {
+        id: "new_address_cooling_off",
+        version: 1,
+        name: "New address cooling-off",
+        description: "Holds payments to destinations missing from the identity's address book or added too recently",
+        schema: cooling_off_schema,
+        example: cooling_off_example,
+        render: render_cooling_off,
+    },
+    PolicyTemplate {
+        id: "agent_spend_cap",
+        version: 1,
+        name: "Agent spend cap",
+        description: "Caps how much an AGENT identity can move over a rolling history window",
+        schema: spend_cap_schema,
+        example: spend_cap_example,
+        render: render_spend_cap,
+    },
+];
+
+pub fn find(template_id: &str) -> Result<&'static PolicyTemplate> {
+    TEMPLATES
+        .iter()
+        .find(|t| t.id == template_id)
+        .ok_or_else(|| GuardRailError::NotFound(format!("Policy template {} not found", template_id)))
+}
+
+impl PolicyTemplate {
+    pub fn info(&self) -> TemplateInfo {
+        TemplateInfo {
+            id: self.id,
+            version: self.version,
+            name: self.name,
+            description: self.description,
+            parameter_schema: (self.schema)(),
+            example_parameters: (self.example)(),
+        }
+    }
+
+    /// Check `parameters` against the schema and render them to Rego
+    pub fn render(&self, parameters: &Value, history: &HistoryConfig) -> Result<String> {
+        let schema = (self.schema)();
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|e| GuardRailError::Internal(format!("Invalid schema for template {}: {}", self.id, e)))?;
+        let errors: Vec<String> = validator
+            .iter_errors(parameters)
+            .map(|e| match e.instance_path.as_str() {
+                "" => e.to_string(),
+                path => format!("{}: {}", path, e),
+            })
+            .collect();
+        if !errors.is_empty() {
+            return Err(invalid_parameters(errors.join("; ")));
+        }
+        (self.render)(parameters, history)
+    }
+}
+
+fn invalid_parameters(message: impl Into<String>) -> GuardRailError {
+    GuardRailError::InvalidField {
+        field: "parameters".to_string(),
+        message: message.into(),
+    }
+}
+
+fn parse<T: DeserializeOwned>(parameters: &Value) -> Result<T> {
+    serde_json::from_value(parameters.clone()).map_err(|e| invalid_parameters(e.to_string()))
+}
+
+// ============================================================================
+// Shared parameters
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum Outcome {
+    #[default]
+    Deny,
+    RequireApproval,
+}
+
+/// What a template does when its condition holds
+#[derive(Debug, Deserialize)]
+struct OutcomeParams {
+    #[serde(default)]
+    outcome: Outcome,
+    #[serde(default = "default_approvers")]
+    approvers: Vec<String>,
+    #[serde(default = "default_severity")]
+    severity: ReasonSeverity,
+}
+
+fn default_approvers() -> Vec<String> {
+    vec!["risk_officer".to_string()]
+}
+
+fn default_severity() -> ReasonSeverity {
+    ReasonSeverity::High
+}
+
+/// Schema for a template's own properties plus the shared outcome properties
+fn object_schema(mut properties: Value, required: &[&str]) -> Value {
+    let shared = json!({
+        "outcome": {"enum": ["DENY", "REQUIRE_APPROVAL"], "default": "DENY"},
+        "approvers": {
+            "type": "array",
+            "items": {"type": "string", "minLength": 1},
+            "minItems": 1,
+            "uniqueItems": true,
+            "description": "Approvers required when outcome is REQUIRE_APPROVAL",
+        },
+        "severity": {"enum": ["LOW", "MEDIUM", "HIGH", "CRITICAL"], "default": "HIGH"},
+        "action_types": {
+            "type": "array",
+            "items": {"enum": ["WITHDRAWAL", "DEPOSIT", "TRANSFER", "SWAP", "TRADE", "API_CALL", "CONFIG_CHANGE", "CUSTOM"]},
+            "minItems": 1,
+            "uniqueItems": true,
+        },
+        "asset": {"type": "string", "minLength": 1, "description": "Only apply to this asset"},
+    });
+    if let (Some(properties), Value::Object(shared)) = (properties.as_object_mut(), shared) {
+        // A template may override parts of a shared property, e.g. its description
+        for (name, schema) in shared {
+            match (properties.get_mut(&name), schema) {
+                (Some(Value::Object(own)), Value::Object(shared)) => {
+                    for (key, value) in shared {
+                        own.entry(key).or_insert(value);
+                    }
+                }
+                (Some(_), _) => {}
+                (None, schema) => {
+                    properties.insert(name, schema);
+                }
+            }
+        }
+    }
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn amount_schema(description: &str) -> Value {
+    json!({"type": "string", "pattern": "^[0-9]+(\\.[0-9]+)?$", "description": description})
+}
+
+fn rego_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn rego_set(values: &[String]) -> String {
+    let items: Vec<String> = values.iter().map(|v| rego_string(v)).collect();
+    format!("{{{}}}", items.join(", "))
+}
+
+/// Conditions limiting a rule to some action types and an asset; `None` matches every type
+fn action_filter(action_types: Option<&[ActionType]>, asset: Option<&str>) -> Vec<String> {
+    let mut conditions = Vec::new();
+    if let Some(action_types) = action_types {
+        let names: Vec<String> = action_types
+            .iter()
+            .filter_map(|t| serde_json::to_value(t).ok())
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect();
+        conditions.push(format!("input.action.action_type in {}", rego_set(&names)));
+    }
+    if let Some(asset) = asset {
+        conditions.push(format!("input.action.asset == {}", rego_string(asset)));
+    }
+    conditions
+}
+
+/// A structured deny reason; `message` and `params` are Rego expressions
+struct Reason<'a> {
+    code: &'a str,
+    message: String,
+    params: String,
+}
+
+/// One rule producing `reason` (or the approvers) when every condition holds
+fn render_rule(outcome: &OutcomeParams, reason: Reason<'_>, conditions: &[String]) -> String {
+    let mut body: Vec<String> = conditions.to_vec();
+    let head = match outcome.outcome {
+        Outcome::Deny => {
+            let severity = serde_json::to_string(&outcome.severity).unwrap_or_default();
+            body.push(format!("message := {}", reason.message));
+            format!(
+                "deny contains {{\n    \"code\": {},\n    \"message\": message,\n    \"severity\": {},\n    \"params\": {},\n}}",
+                rego_string(reason.code),
+                severity,
+                reason.params
+            )
+        }
+        Outcome::RequireApproval => {
+            body.push(format!("some approver in {}", rego_set(&outcome.approvers)));
+            "required_approvers contains approver".to_string()
+        }
+    };
+    let body: Vec<String> = body.iter().map(|line| format!("    {}", line)).collect();
+    format!("{} if {{\n{}\n}}\n", head, body.join("\n"))
+}
+
+fn module(template: &str, rules: &[String]) -> String {
+    let version = find(template).map(|t| t.version).unwrap_or_default();
+    format!(
+        "# Rendered from template {} v{}; change its parameters rather than this source\n\
+         package guardrail\n\n\
+         import future.keywords.contains\n\
+         import future.keywords.if\n\
+         import future.keywords.in\n\n\
+         {}",
+        template,
+        version,
+        rules.join("\n")
+    )
+}
+
+// ============================================================================
+// withdrawal_limit_by_kyc
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+struct KycTier {
+    kyc_level: u32,
+    max_amount: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct KycLimitParams {
+    limits: Vec<KycTier>,
+    #[serde(default = "withdrawals")]
+    action_types: Vec<ActionType>,
+    asset: Option<String>,
+    #[serde(flatten)]
+    outcome: OutcomeParams,
+}
+
+fn withdrawals() -> Vec<ActionType> {
+    vec![ActionType::Withdrawal]
+}
+
+fn kyc_limit_schema() -> Value {
+    object_schema(
+        json!({
+            "limits": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "kyc_level": {"type": "integer", "minimum": 0},
+                        "max_amount": amount_schema("Largest amount allowed at this level and above"),
+                    },
+                    "required": ["kyc_level", "max_amount"],
+                    "additionalProperties": false,
+                },
+                "minItems": 1,
+                "description": "Limit per KYC level; must include level 0",
+            },
+        }),
+        &["limits"],
+    )
+}
+
+fn kyc_limit_example() -> Value {
+    json!({
+        "limits": [
+            {"kyc_level": 0, "max_amount": "1000"},
+            {"kyc_level": 1, "max_amount": "10000"},
+            {"kyc_level": 2, "max_amount": "100000"},
+        ],
+        "asset": "USDC",
+    })
+}
+
+fn render_kyc_limit(parameters: &Value, _history: &HistoryConfig) -> Result<String> {
+    let mut params: KycLimitParams = parse(parameters)?;
+    params.limits.sort_by_key(|tier| tier.kyc_level);
+    if params.limits[0].kyc_level != 0 {
+        return Err(invalid_parameters("limits must include kyc_level 0"));
+    }
+    if params.limits.windows(2).any(|w| w[0].kyc_level == w[1].kyc_level) {
+        return Err(invalid_parameters("limits has more than one entry for a kyc_level"));
+    }
+
+    let tiers: Vec<String> = params
+        .limits
+        .iter()
+        .map(|tier| format!("{{\"kyc_level\": {}, \"max_amount\": {}}}", tier.kyc_level, rego_string(&tier.max_amount)))
+        .collect();
+
+    let mut conditions = action_filter(Some(&params.action_types), params.asset.as_deref());
+    conditions.extend([
+        "input.action.amount != null".to_string(),
+        "levels := [c.value.level | some c in input.identity.credentials; upper(c.type) == \"KYC_LEVEL\"]".to_string(),
+        "level := max(array.concat([0], levels))".to_string(),
+        // Tiers are sorted by level, so the last one that applies is the highest
+        format!("limits := [t.max_amount | some t in [{}]; t.kyc_level <= level]", tiers.join(", ")),
+        "limit := limits[count(limits) - 1]".to_string(),
+        "guardrail.decimal_gt(input.action.amount, limit)".to_string(),
+    ]);
+
+    let rule = render_rule(
+        &params.outcome,
+        Reason {
+            code: "KYC_LIMIT_EXCEEDED",
+            // regorus' sprintf drops the space after a %s, so messages are concatenated
+            message: "concat(\"\", [\"Amount exceeds the \", limit, \" limit for KYC level \", format_int(level, 10)])"
+                .to_string(),
+            params: "{\"limit\": limit, \"kyc_level\": level}".to_string(),
+        },
+        &conditions,
+    );
+    Ok(module("withdrawal_limit_by_kyc", &[rule]))
+}
+
+// ============================================================================
+// jurisdiction_blocklist
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+struct JurisdictionParams {
+    jurisdictions: Vec<String>,
+    #[serde(default = "default_true")]
+    check_geo_location: bool,
+    action_types: Option<Vec<ActionType>>,
+    asset: Option<String>,
+    #[serde(flatten)]
+    outcome: OutcomeParams,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn jurisdiction_schema() -> Value {
+    object_schema(
+        json!({
+            "jurisdictions": {
+                "type": "array",
+                "items": {"type": "string", "pattern": "^[A-Za-z]{2}$"},
+                "minItems": 1,
+                "uniqueItems": true,
+                "description": "ISO 3166-1 alpha-2 country codes",
+            },
+            "check_geo_location": {
+                "type": "boolean",
+                "default": true,
+                "description": "Also match the request's context.geo_location",
+            },
+            "action_types": {"description": "Only apply to these action types; every action when omitted"},
+        }),
+        &["jurisdictions"],
+    )
+}
+
+fn jurisdiction_example() -> Value {
+    json!({"jurisdictions": ["KP", "IR", "SY", "CU"], "severity": "CRITICAL"})
+}
+
+fn render_jurisdiction(parameters: &Value, _history: &HistoryConfig) -> Result<String> {
+    let params: JurisdictionParams = parse(parameters)?;
+    let countries: Vec<String> = params.jurisdictions.iter().map(|c| c.to_ascii_uppercase()).collect();
+    let blocked = rego_set(&countries);
+
+    let filter = action_filter(params.action_types.as_deref(), params.asset.as_deref());
+    let reason = || Reason {
+        code: "JURISDICTION_BLOCKED",
+        message: "concat(\"\", [\"Jurisdiction \", country, \" is blocked\"])".to_string(),
+        params: "{\"country\": country}".to_string(),
+    };
+
+    let mut credential = filter.clone();
+    credential.extend([
+        "some c in input.identity.credentials".to_string(),
+        "upper(c.type) == \"JURISDICTION\"".to_string(),
+        "is_string(c.value.country)".to_string(),
+        "country := upper(c.value.country)".to_string(),
+        format!("country in {}", blocked),
+    ]);
+    let mut rules = vec![render_rule(&params.outcome, reason(), &credential)];
+
+    if params.check_geo_location {
+        let mut geo = filter;
+        geo.extend([
+            "is_string(input.context.geo_location)".to_string(),
+            "country := upper(input.context.geo_location)".to_string(),
+            format!("country in {}", blocked),
+        ]);
+        rules.push(render_rule(&params.outcome, reason(), &geo));
+    }
+
+    Ok(module("jurisdiction_blocklist", &rules))
+}
+
+// ============================================================================
+// new_address_cooling_off
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+struct CoolingOffParams {
+    address_book: String,
+    cooling_off_hours: u32,
+    #[serde(default = "payments")]
+    action_types: Vec<ActionType>,
+    asset: Option<String>,
+    #[serde(default = "require_approval")]
+    outcome: Outcome,
+    #[serde(flatten)]
+    rest: OutcomeParams,
+}
+
+fn payments() -> Vec<ActionType> {
+    vec![ActionType::Withdrawal, ActionType::Transfer]
+}
+
+fn require_approval() -> Outcome {
+    Outcome::RequireApproval
+}
+
+fn cooling_off_schema() -> Value {
+    object_schema(
+        json!({
+            "address_book": {
+                "type": "string",
+                "description": "Data document path holding {identity_id: {address: added_at}}, with RFC 3339 timestamps",
+            },
+            "cooling_off_hours": {"type": "integer", "minimum": 1, "maximum": 8760},
+            "outcome": {"default": "REQUIRE_APPROVAL"},
+        }),
+        &["address_book", "cooling_off_hours"],
+    )
+}
+
+fn cooling_off_example() -> Value {
+    json!({"address_book": "wallets.address_book", "cooling_off_hours": 24, "approvers": ["risk_officer"]})
+}
+
+fn render_cooling_off(parameters: &Value, _history: &HistoryConfig) -> Result<String> {
+    let params: CoolingOffParams = parse(parameters)?;
+    if let Err(GuardRailError::InvalidField { message, .. }) = data_documents::validate_path(&params.address_book) {
+        return Err(invalid_parameters(format!("address_book {}", message)));
+    }
+    let outcome = OutcomeParams {
+        outcome: params.outcome,
+        ..params.rest
+    };
+    let entries = format!("data.{}[input.identity.id]", params.address_book);
+    let hours = params.cooling_off_hours;
+
+    let mut filter = action_filter(Some(&params.action_types), params.asset.as_deref());
+    filter.extend([
+        "address := input.action.target_address".to_string(),
+        "is_string(address)".to_string(),
+    ]);
+
+    let mut missing = filter.clone();
+    missing.push(format!("not {}[address]", entries));
+    let new_address = render_rule(
+        &outcome,
+        Reason {
+            code: "NEW_ADDRESS",
+            message: rego_string("Destination address is not in the address book"),
+            params: "{\"address\": address}".to_string(),
+        },
+        &missing,
+    );
+
+    let mut recent = filter;
+    recent.extend([
+        format!("added := {}[address]", entries),
+        "elapsed := time.parse_rfc3339_ns(input.context.timestamp) - time.parse_rfc3339_ns(added)".to_string(),
+        format!("elapsed < {}", u64::from(hours) * 3_600_000_000_000),
+    ]);
+    let cooling_off = render_rule(
+        &outcome,
+        Reason {
+            code: "ADDRESS_COOLING_OFF",
+            message: rego_string(&format!("Destination address was added less than {} hours ago", hours)),
+            params: format!("{{\"address\": address, \"added_at\": added, \"cooling_off_hours\": {}}}", hours),
+        },
+        &recent,
+    );
+
+    Ok(module("new_address_cooling_off", &[new_address, cooling_off]))
+}
+
+// ============================================================================
+// agent_spend_cap
+// ============================================================================
+
+/// History levels that carry an amount sum; the others span several assets
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SpendScope {
+    #[default]
+    Asset,
+    AssetActionType,
+}
+
+impl SpendScope {
+    fn as_str(self) -> &'static str {
+        match self {
+            SpendScope::Asset => "asset",
+            SpendScope::AssetActionType => "asset_action_type",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SpendCapParams {
+    max_amount: String,
+    window: String,
+    #[serde(default)]
+    scope: SpendScope,
+    action_types: Option<Vec<ActionType>>,
+    asset: Option<String>,
+    #[serde(flatten)]
+    outcome: OutcomeParams,
+}
+
+fn spend_cap_schema() -> Value {
+    object_schema(
+        json!({
+            "max_amount": amount_schema("Most an agent may spend of the action's asset within the window, including this action"),
+            "window": {"type": "string", "description": "History window label, e.g. 24h; one of HISTORY_WINDOWS"},
+            "scope": {
+                "enum": ["asset", "asset_action_type"],
+                "default": "asset",
+                "description": "Which earlier actions in the same asset count towards the cap",
+            },
+            "action_types": {"description": "Only apply to these action types; every action when omitted"},
+        }),
+        &["max_amount", "window"],
+    )
+}
+
+fn spend_cap_example() -> Value {
+    json!({"max_amount": "5000", "window": "24h", "scope": "asset", "asset": "USDC"})
+}
+
+fn render_spend_cap(parameters: &Value, history: &HistoryConfig) -> Result<String> {
+    let params: SpendCapParams = parse(parameters)?;
+    if !history.windows.iter().any(|w| w.label == params.window) {
+        let labels: Vec<&str> = history.windows.iter().map(|w| w.label.as_str()).collect();
+        return Err(invalid_parameters(format!(
+            "window {} is not a configured history window ({})",
+            params.window,
+            labels.join(", ")
+        )));
+    }
+
+    let mut conditions = action_filter(params.action_types.as_deref(), params.asset.as_deref());
+    conditions.extend([
+        "input.identity.type == \"AGENT\"".to_string(),
+        "input.action.amount != null".to_string(),
+        format!(
+            "spent := input.history[{}].{}.sum + to_number(input.action.amount)",
+            rego_string(&params.window),
+            params.scope.as_str()
+        ),
+        // The amount pattern is also a valid Rego number literal
+        format!("spent > {}", params.max_amount),
+    ]);
+
+    let rule = render_rule(
+        &params.outcome,
+        Reason {
+            code: "AGENT_SPEND_CAP",
+            message: rego_string(&format!(
+                "Agent spend over {} would exceed {}",
+                params.window, params.max_amount
+            )),
+            params: format!(
+                "{{\"window\": {}, \"limit\": {}, \"spent\": spent}}",
+                rego_string(&params.window),
+                rego_string(&params.max_amount)
+            ),
+        },
+        &conditions,
+    );
+    Ok(module("agent_spend_cap", &[rule]))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+fn render_checked(state: &AppState, template: &PolicyTemplate, name: &str, parameters: &Value) -> Result<String> {
+    let rego_source = template.render(parameters, &state.history_config)?;
+    validate_rego(name, &rego_source)?;
+    Ok(rego_source)
+}
+
+pub async fn list_templates() -> impl IntoResponse {
+    let templates: Vec<TemplateInfo> = TEMPLATES.iter().map(PolicyTemplate::info).collect();
+    (StatusCode::OK, Json(ApiResponse::success(templates)))
+}
+
+pub async fn get_template(Path(template_id): Path<String>) -> impl IntoResponse {
+    match find(&template_id) {
+        Ok(template) => (StatusCode::OK, Json(ApiResponse::success(template.info()))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<TemplateInfo>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+pub async fn render_template(
+    State(state): State<Arc<AppState>>,
+    Path(template_id): Path<String>,
+    Json(req): Json<RenderTemplateRequest>,
+) -> impl IntoResponse {
+    match render_template_impl(&state, &template_id, req).await {
+        Ok(rendered) => (StatusCode::OK, Json(ApiResponse::success(rendered))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<RenderedTemplate>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn render_template_impl(state: &AppState, template_id: &str, req: RenderTemplateRequest) -> Result<RenderedTemplate> {
+    let template = find(template_id)?;
+    let rego_source = render_checked(state, template, template.id, &req.parameters)?;
+    let lint = lint::lint_policy(&state.db, &rego_source, PolicyKind::Decision, None).await?;
+    Ok(RenderedTemplate {
+        template_id: template.id,
+        template_version: template.version,
+        rego_source,
+        lint,
+    })
+}
+
+pub async fn create_from_template(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateFromTemplateRequest>,
+) -> impl IntoResponse {
+    match create_from_template_impl(&state, req).await {
+        Ok(policy) => (StatusCode::CREATED, Json(ApiResponse::success(policy))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Policy>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn create_from_template_impl(state: &AppState, req: CreateFromTemplateRequest) -> Result<Policy> {
+    let template = find(&req.template_id)?;
+    let rego_source = render_checked(state, template, &req.name, &req.parameters)?;
+    let source = TemplateSource {
+        template_id: template.id.to_string(),
+        template_version: template.version,
+        parameters: req.parameters,
+    };
+
+    create_policy_impl(
+        state,
+        CreatePolicyRequest {
+            name: req.name,
+            description: req.description.or_else(|| Some(template.description.to_string())),
+            kind: PolicyKind::Decision,
+            rego_source,
+            test_source: req.test_source,
+            shadow: req.shadow,
+            effective_from: req.effective_from,
+            effective_until: req.effective_until,
+            targets: req.targets,
+            created_by: req.created_by,
+        },
+        Some(&source),
+    )
+    .await
+}
+
+pub async fn update_from_template(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateFromTemplateRequest>,
+) -> impl IntoResponse {
+    match update_from_template_impl(&state, id, req).await {
+        Ok(policy) => (StatusCode::OK, Json(ApiResponse::success(policy))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Policy>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn update_from_template_impl(state: &AppState, id: Uuid, req: UpdateFromTemplateRequest) -> Result<Policy> {
+    let current = get_policy_impl(&state.db, id).await?;
+    let template_id = versions::current_template_id(state, &current).await?.ok_or_else(|| {
+        GuardRailError::Validation(format!("Policy {} was not rendered from a template", current.name))
+    })?;
+    let template = find(&template_id)?;
+    let rego_source = render_checked(state, template, &current.name, &req.parameters)?;
+    let source = TemplateSource {
+        template_id: template.id.to_string(),
+        template_version: template.version,
+        parameters: req.parameters,
+    };
+    let change_summary = req
+        .change_summary
+        .unwrap_or_else(|| format!("Re-rendered from template {} v{}", template.id, template.version));
+
+    versions::apply_revision(
+        state,
+        &current,
+        Revision {
+            description: req.description.or_else(|| current.description.clone()),
+            rego_source: &rego_source,
+            test_source: current.test_source.as_deref(),
+            targets: &current.targets,
+            bump: req.bump,
+            change_summary: Some(&change_summary),
+            created_by: req.created_by,
+            activate: false,
+            template: Some(&source),
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{history, PolicyEngine};
+    use guardrail_shared::Decision;
+
+    fn history_config() -> HistoryConfig {
+        HistoryConfig {
+            windows: history::parse_windows("1h,24h"),
+            sweep_interval: std::time::Duration::from_secs(3600),
+        }
+    }
+
+    #[test]
+    fn test_examples_render_load_and_lint() {
+        for template in TEMPLATES {
+            let info = template.info();
+            let rego_source = template.render(&info.example_parameters, &history_config()).unwrap();
+            PolicyEngine::new()
+                .load_policy(Uuid::nil(), template.id, "1.0.0", &rego_source)
+                .unwrap_or_else(|e| panic!("{} does not load: {}", template.id, e));
+            let report = lint::lint(&rego_source, PolicyKind::Decision, &[]);
+            assert!(report.diagnostics.is_empty(), "{}: {:?}", template.id, report.diagnostics);
+        }
+    }
+
+    #[test]
+    fn test_parameters_are_validated() {
+        let kyc = find("withdrawal_limit_by_kyc").unwrap();
+        let render = |parameters| kyc.render(&parameters, &history_config());
+        for parameters in [
+            json!({}),
+            json!({"limits": [{"kyc_level": 0, "max_amount": "ten"}]}),
+            json!({"limits": [{"kyc_level": 0, "max_amount": "10"}], "outcome": "ALLOW"}),
+            json!({"limits": [{"kyc_level": 1, "max_amount": "10"}]}),
+        ] {
+            assert!(matches!(render(parameters), Err(GuardRailError::InvalidField { .. })));
+        }
+
+        let cap = find("agent_spend_cap").unwrap();
+        let err = cap.render(&json!({"max_amount": "10", "window": "7d"}), &history_config()).unwrap_err();
+        assert!(err.to_string().contains("7d"));
+        let err = cap
+            .render(&json!({"max_amount": "10", "window": "24h", "scope": "all"}), &history_config())
+            .unwrap_err();
+        assert!(matches!(err, GuardRailError::InvalidField { .. }));
+        assert!(matches!(find("nope"), Err(GuardRailError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_kyc_limit_decisions() {
+        let kyc = find("withdrawal_limit_by_kyc").unwrap();
+        let rego_source = kyc.render(&kyc_limit_example(), &history_config()).unwrap();
+        let mut engine = PolicyEngine::new();
+        engine.load_policy(Uuid::nil(), kyc.id, "1.0.0", &rego_source).unwrap();
+
+        let check = |amount: &str, level: u32| {
+            engine
+                .evaluate(&json!({
+                    "identity": {"type": "HUMAN", "credentials": [{"type": "KYC_LEVEL", "value": {"level": level}}]},
+                    "action": {"action_type": "WITHDRAWAL", "amount": amount, "asset": "USDC"},
+                    "context": {},
+                }))
+                .unwrap()
+        };
+        assert_eq!(check("5000", 1).decision, Decision::Allow);
+        let denied = check("5000", 0);
+        assert_eq!(denied.decision, Decision::Deny);
+        assert_eq!(denied.reason_details[0].code.as_deref(), Some("KYC_LIMIT_EXCEEDED"));
+        assert_eq!(denied.reason_details[0].message, "Amount exceeds the 1000 limit for KYC level 0");
+        assert_eq!(denied.reason_details[0].params["limit"], "1000");
+        assert_eq!(check("500000", 5).decision, Decision::Deny);
+    }
+}
@@ -0,0 +1,502 @@
+//! Policy versioning, history and rollback
+//!
+//! Every change to a policy's Rego source or targets is stored in `policy_versions` with a
+//! semver bump and change summary. Rollback restores an older revision as a new
+//! version so the history stays append-only.
+
+use crate::libraries;
+use crate::lint;
+use crate::targeting;
+use crate::rego_tests::{self, TestTrigger};
+use crate::templates::TemplateSource;
+use crate::{get_policy_impl, ledger::SYSTEM_ACTOR_ID, reload_policies, validate_rego, AppState};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use guardrail_shared::{
+    ApiResponse, EventType, GuardRailError, Policy, PolicyKind, PolicyTargets, PolicyTestReport, PolicyVersion, Result,
+    RollbackPolicyRequest, UpdatePolicyRequest, VersionBump,
+};
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use sqlx::{Postgres, Transaction};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Version assigned to newly created policies
+pub const INITIAL_VERSION: &str = "1.0.0";
+
+// ============================================================================
+// Request/Response Types
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct DiffQuery {
+    pub from: String,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PolicyDiff {
+    pub policy_id: Uuid,
+    pub from_version: String,
+    pub to_version: String,
+    pub additions: usize,
+    pub deletions: usize,
+    pub unified: String,
+}
+
+// ============================================================================
+// Semver
+// ============================================================================
+
+/// Increment a `major.minor.patch` version string
+pub fn bump_version(current: &str, bump: VersionBump) -> Result<String> {
+    let parts: Vec<u64> = current
+        .split('.')
+        .map(|p| p.parse::<u64>())
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|_| GuardRailError::Validation(format!("Invalid policy version: {}", current)))?;
+
+    let [major, minor, patch] = parts[..] else {
+        return Err(GuardRailError::Validation(format!("Invalid policy version: {}", current)));
+    };
+
+    Ok(match bump {
+        VersionBump::Major => format!("{}.0.0", major + 1),
+        VersionBump::Minor => format!("{}.{}.0", major, minor + 1),
+        VersionBump::Patch => format!("{}.{}.{}", major, minor, patch + 1),
+    })
+}
+
+/// Build a unified diff between two revisions of Rego source
+pub fn diff_sources(from_version: &str, from: &str, to_version: &str, to: &str) -> (usize, usize, String) {
+    let diff = TextDiff::from_lines(from, to);
+
+    let mut additions = 0;
+    let mut deletions = 0;
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => additions += 1,
+            ChangeTag::Delete => deletions += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+
+    let unified = diff
+        .unified_diff()
+        .context_radius(3)
+        .header(from_version, to_version)
+        .to_string();
+
+    (additions, deletions, unified)
+}
+
+// ============================================================================
+// Storage
+// ============================================================================
+
+/// Append a policy's current source to `policy_versions`
+pub async fn record_version(
+    tx: &mut Transaction<'_, Postgres>,
+    policy: &Policy,
+    change_summary: Option<&str>,
+    template: Option<&TemplateSource>,
+    created_by: Option<Uuid>,
+) -> Result<PolicyVersion> {
+    let version = sqlx::query_as!(
+        PolicyVersion,
+        r#"
+        INSERT INTO policy_versions (id, policy_id, version, rego_source, test_source, targets, change_summary, template_id, template_version, template_params, created_by, created_at)
+        VALUES ($1, $2, $3, $4, $5, $12, $6, $7, $8, $9, $10, $11)
+        RETURNING id, policy_id, version, rego_source, test_source, targets as "targets: PolicyTargets", change_summary, template_id, template_version, template_params, created_by, created_at as "created_at!"
+        "#,
+        Uuid::new_v4(),
+        policy.id,
+        policy.version,
+        policy.rego_source,
+        policy.test_source,
+        change_summary,
+        template.map(|t| t.template_id.as_str()),
+        template.map(|t| t.template_version),
+        template.map(|t| &t.parameters),
+        created_by,
+        chrono::Utc::now(),
+        serde_json::to_value(&policy.targets)?,
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(version)
+}
+
+async fn get_version_impl(state: &AppState, policy_id: Uuid, version: &str) -> Result<PolicyVersion> {
+    sqlx::query_as!(
+        PolicyVersion,
+        r#"
+        SELECT id, policy_id, version, rego_source, test_source, targets as "targets: PolicyTargets", change_summary, template_id, template_version, template_params, created_by, created_at as "created_at!"
+        FROM policy_versions
+        WHERE policy_id = $1 AND version = $2
+        "#,
+        policy_id,
+        version,
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| GuardRailError::NotFound(format!("Policy {} version {} not found", policy_id, version)))
+}
+
+/// Template the policy's current version was rendered from, if any
+pub(crate) async fn current_template_id(state: &AppState, policy: &Policy) -> Result<Option<String>> {
+    Ok(get_version_impl(state, policy.id, &policy.version).await?.template_id)
+}
+
+/// A pending change to a policy's source
+pub(crate) struct Revision<'a> {
+    pub description: Option<String>,
+    pub rego_source: &'a str,
+    pub test_source: Option<&'a str>,
+    pub targets: &'a PolicyTargets,
+    pub bump: VersionBump,
+    pub change_summary: Option<&'a str>,
+    pub created_by: Option<Uuid>,
+    /// Also activate the policy if it is currently inactive
+    pub activate: bool,
+    /// Template the source was rendered from; hand-written sources have none
+    pub template: Option<&'a TemplateSource>,
+}
+
+/// Store a new revision and make it the policy's current source
+pub(crate) async fn apply_revision(state: &AppState, current: &Policy, revision: Revision<'_>) -> Result<Policy> {
+    let Revision {
+        rego_source,
+        test_source,
+        targets,
+        bump,
+        activate,
+        ..
+    } = revision;
+
+    validate_rego(&current.name, rego_source)?;
+    targeting::validate(targets)?;
+    libraries::validate(current.kind, targets, false, None, None)?;
+    if rego_source != current.rego_source {
+        let lint_report = lint::lint_policy(&state.db, rego_source, current.kind, Some(current.id)).await?;
+        lint::ensure_clean(&current.name, &lint_report)?;
+        libraries::ensure_not_imported(&state.db, current, Some(rego_source)).await?;
+    }
+    if activate && !current.is_active {
+        libraries::ensure_imports_active(&state.db, &current.name, rego_source).await?;
+    }
+
+    let next_version = bump_version(&current.version, bump)?;
+
+    // Record failing runs against the attempted version before rejecting it
+    let test_report = rego_tests::run_tests(&state.engine.load(), Some(current.id), &current.name, rego_source, test_source)?;
+    if !test_report.passed {
+        rego_tests::record_run(&state.db, current.id, &next_version, TestTrigger::Update, &test_report).await?;
+        rego_tests::ensure_passed(&test_report)?;
+    }
+
+    let mut tx = state.db.begin().await?;
+    let policy = store_revision(&mut tx, current, &revision, &next_version, &test_report, chrono::Utc::now()).await?;
+    tx.commit().await?;
+
+    if policy.is_active || policy.is_shadow || activate {
+        reload_policies(state).await?;
+    }
+
+    record_policy_updated(state, current, &policy, revision.change_summary, revision.created_by).await;
+
+    Ok(policy)
+}
+
+/// Write a checked revision and its passing test run
+pub(crate) async fn store_revision(
+    tx: &mut Transaction<'_, Postgres>,
+    current: &Policy,
+    revision: &Revision<'_>,
+    next_version: &str,
+    test_report: &PolicyTestReport,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Policy> {
+    let Revision {
+        description,
+        rego_source,
+        test_source,
+        targets,
+        change_summary,
+        created_by,
+        activate,
+        template,
+        ..
+    } = revision;
+
+    let policy = sqlx::query_as!(
+        Policy,
+        r#"
+        UPDATE policies
+        SET version = $2, rego_source = $3, test_source = $4, targets = $9, description = $5, is_active = (is_active OR $6),
+            is_shadow = (is_shadow AND NOT $6),
+            shadow_until = CASE WHEN is_shadow AND $6 THEN $7 ELSE shadow_until END, updated_at = $7
+        WHERE id = $1 AND version = $8
+        RETURNING id, name, description, version, kind as "kind: PolicyKind", rego_source, test_source, is_active as "is_active!", is_shadow, effective_from, effective_until, targets as "targets: PolicyTargets", created_by, created_at as "created_at!", updated_at as "updated_at!"
+        "#,
+        current.id,
+        next_version,
+        *rego_source,
+        *test_source,
+        description.as_deref(),
+        *activate,
+        now,
+        current.version,
+        serde_json::to_value(targets)?,
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| GuardRailError::Conflict(format!("Policy {} was modified concurrently", current.id)))?;
+
+    record_version(tx, &policy, *change_summary, *template, *created_by).await?;
+    rego_tests::record_run(&mut **tx, policy.id, &policy.version, TestTrigger::Update, test_report).await?;
+
+    Ok(policy)
+}
+
+pub(crate) async fn record_policy_updated(
+    state: &AppState,
+    previous: &Policy,
+    policy: &Policy,
+    change_summary: Option<&str>,
+    created_by: Option<Uuid>,
+) {
+    state
+        .ledger
+        .record_event_logged(
+            EventType::PolicyUpdated,
+            created_by.unwrap_or(SYSTEM_ACTOR_ID),
+            None,
+            serde_json::json!({
+                "policy_id": policy.id,
+                "name": policy.name,
+                "previous_version": previous.version,
+                "version": policy.version,
+                "change_summary": change_summary,
+                "rego_sha256": guardrail_shared::sha256_hex(policy.rego_source.as_bytes()),
+            }),
+        )
+        .await;
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+pub async fn update_policy(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdatePolicyRequest>,
+) -> impl IntoResponse {
+    match update_policy_impl(&state, id, req).await {
+        Ok(policy) => (StatusCode::OK, Json(ApiResponse::success(policy))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Policy>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn update_policy_impl(state: &AppState, id: Uuid, req: UpdatePolicyRequest) -> Result<Policy> {
+    let current = get_policy_impl(&state.db, id).await?;
+    let description = req.description.or_else(|| current.description.clone());
+    // An empty test module detaches the current one
+    let test_source = match req.test_source {
+        Some(t) if t.trim().is_empty() => None,
+        Some(t) => Some(t),
+        None => current.test_source.clone(),
+    };
+    let targets = req.targets.unwrap_or_else(|| current.targets.clone());
+
+    if req.rego_source == current.rego_source
+        && description == current.description
+        && test_source == current.test_source
+        && targets == current.targets
+    {
+        return Err(GuardRailError::Validation("Update does not change the policy".to_string()));
+    }
+
+    apply_revision(
+        state,
+        &current,
+        Revision {
+            description,
+            rego_source: &req.rego_source,
+            test_source: test_source.as_deref(),
+            targets: &targets,
+            bump: req.bump,
+            change_summary: req.change_summary.as_deref(),
+            created_by: req.created_by,
+            activate: false,
+            template: None,
+        },
+    )
+    .await
+}
+
+pub async fn list_policy_versions(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match list_policy_versions_impl(&state, id).await {
+        Ok(versions) => (StatusCode::OK, Json(ApiResponse::success(versions))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Vec<PolicyVersion>>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn list_policy_versions_impl(state: &AppState, id: Uuid) -> Result<Vec<PolicyVersion>> {
+    // Ensure the policy exists so an unknown id is a 404 rather than an empty list
+    let _ = get_policy_impl(&state.db, id).await?;
+
+    let versions = sqlx::query_as!(
+        PolicyVersion,
+        r#"
+        SELECT id, policy_id, version, rego_source, test_source, targets as "targets: PolicyTargets", change_summary, template_id, template_version, template_params, created_by, created_at as "created_at!"
+        FROM policy_versions
+        WHERE policy_id = $1
+        ORDER BY created_at DESC
+        "#,
+        id,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(versions)
+}
+
+pub async fn get_policy_version(
+    State(state): State<Arc<AppState>>,
+    Path((id, version)): Path<(Uuid, String)>,
+) -> impl IntoResponse {
+    match get_version_impl(&state, id, &version).await {
+        Ok(version) => (StatusCode::OK, Json(ApiResponse::success(version))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<PolicyVersion>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+pub async fn diff_policy_versions(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<DiffQuery>,
+) -> impl IntoResponse {
+    match diff_policy_versions_impl(&state, id, query).await {
+        Ok(diff) => (StatusCode::OK, Json(ApiResponse::success(diff))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<PolicyDiff>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn diff_policy_versions_impl(state: &AppState, id: Uuid, query: DiffQuery) -> Result<PolicyDiff> {
+    let from = get_version_impl(state, id, &query.from).await?;
+    let to = match query.to {
+        Some(version) => get_version_impl(state, id, &version).await?,
+        None => {
+            let current = get_policy_impl(&state.db, id).await?;
+            get_version_impl(state, id, &current.version).await?
+        }
+    };
+
+    let (additions, deletions, unified) =
+        diff_sources(&from.version, &from.rego_source, &to.version, &to.rego_source);
+
+    Ok(PolicyDiff {
+        policy_id: id,
+        from_version: from.version,
+        to_version: to.version,
+        additions,
+        deletions,
+        unified,
+    })
+}
+
+pub async fn rollback_policy(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<RollbackPolicyRequest>,
+) -> impl IntoResponse {
+    match rollback_policy_impl(&state, id, req).await {
+        Ok(policy) => (StatusCode::OK, Json(ApiResponse::success(policy))),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(ApiResponse::<Policy>::error(e.error_code(), e.to_string())))
+        }
+    }
+}
+
+async fn rollback_policy_impl(state: &AppState, id: Uuid, req: RollbackPolicyRequest) -> Result<Policy> {
+    let current = get_policy_impl(&state.db, id).await?;
+    let target = get_version_impl(state, id, &req.version).await?;
+
+    let change_summary = req
+        .change_summary
+        .unwrap_or_else(|| format!("Rollback to {}", target.version));
+    let template = match (&target.template_id, target.template_version) {
+        (Some(template_id), Some(template_version)) => Some(TemplateSource {
+            template_id: template_id.clone(),
+            template_version,
+            parameters: target.template_params.clone().unwrap_or_default(),
+        }),
+        _ => None,
+    };
+
+    apply_revision(
+        state,
+        &current,
+        Revision {
+            description: current.description.clone(),
+            rego_source: &target.rego_source,
+            test_source: target.test_source.as_deref(),
+            targets: &target.targets,
+            bump: VersionBump::Patch,
+            change_summary: Some(&change_summary),
+            created_by: req.created_by,
+            activate: true,
+            template: template.as_ref(),
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bump_version() {
+        assert_eq!(bump_version("1.2.3", VersionBump::Patch).unwrap(), "1.2.4");
+        assert_eq!(bump_version("1.2.3", VersionBump::Minor).unwrap(), "1.3.0");
+        assert_eq!(bump_version("1.2.3", VersionBump::Major).unwrap(), "2.0.0");
+        assert!(bump_version("1.2", VersionBump::Patch).is_err());
+        assert!(bump_version("1.x.3", VersionBump::Patch).is_err());
+    }
+
+    #[test]
+    fn test_diff_sources() {
+        let from = "package guardrail\ndeny[\"a\"] { input.x > 1 }\n";
+        let to = "package guardrail\ndeny[\"a\"] { input.x > 2 }\ndeny[\"b\"] { input.y }\n";
+        let (additions, deletions, unified) = diff_sources("1.0.0", from, "1.0.1", to);
+        assert_eq!(additions, 2);
+        assert_eq!(deletions, 1);
+        assert!(unified.contains("-deny[\"a\"] { input.x > 1 }"));
+        assert!(unified.contains("+deny[\"b\"] { input.y }"));
+    }
+}
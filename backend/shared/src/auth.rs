@@ -0,0 +1,158 @@
+//! Caller authentication
+//!
+//! API keys (`x-api-key`) and JWT bearer tokens, checked the same way by the API
+//! gateway and by services that accept connections directly, such as the policy
+//! engine's gRPC check service.
+
+use crate::crypto;
+use crate::errors::{GuardRailError, Result};
+use jsonwebtoken::{decode, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,        // User ID
+    pub email: String,
+    pub role: String,
+    pub org_id: Option<String>,
+    pub exp: usize,         // Expiry timestamp
+    pub iat: usize,         // Issued at
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub user_id: Uuid,
+    pub email: String,
+    pub role: String,
+    pub org_id: Option<Uuid>,
+    pub auth_method: AuthMethod,
+}
+
+#[derive(Debug, Clone)]
+pub enum AuthMethod {
+    Jwt,
+    ApiKey,
+}
+
+/// Authenticate a caller from its `x-api-key` and `Authorization` values.
+///
+/// An API key takes precedence over a bearer token. Bearer tokens are rejected
+/// when no JWT secret is configured.
+pub async fn authenticate(
+    db: &PgPool,
+    jwt_secret: Option<&str>,
+    api_key: Option<&str>,
+    authorization: Option<&str>,
+) -> Result<AuthenticatedUser> {
+    // Check for API key first
+    if let Some(api_key) = api_key {
+        return authenticate_api_key(db, api_key).await;
+    }
+
+    // Check for JWT
+    if let Some(token) = authorization.and_then(|h| h.strip_prefix("Bearer ")) {
+        let jwt_secret = jwt_secret
+            .ok_or_else(|| GuardRailError::Unauthorized("JWT authentication is not configured".to_string()))?;
+        return authenticate_jwt(jwt_secret, token);
+    }
+
+    Err(GuardRailError::Unauthorized("No valid authentication provided".to_string()))
+}
+
+pub fn authenticate_jwt(jwt_secret: &str, token: &str) -> Result<AuthenticatedUser> {
+    let token_data = decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(jwt_secret.as_bytes()),
+        &Validation::default(),
+    )
+    .map_err(|e| GuardRailError::Unauthorized(format!("Invalid token: {}", e)))?;
+
+    let claims = token_data.claims;
+
+    Ok(AuthenticatedUser {
+        user_id: Uuid::parse_str(&claims.sub)
+            .map_err(|_| GuardRailError::Unauthorized("Invalid user ID in token".to_string()))?,
+        email: claims.email,
+        role: claims.role,
+        org_id: claims.org_id.and_then(|s| Uuid::parse_str(&s).ok()),
+        auth_method: AuthMethod::Jwt,
+    })
+}
+
+pub async fn authenticate_api_key(db: &PgPool, api_key: &str) -> Result<AuthenticatedUser> {
+    let key_hash = crypto::sha256_hex(api_key.as_bytes());
+
+    let key_record = sqlx::query!(
+        r#"
+        SELECT ak.id, ak.scopes, ak.organization_id, ak.expires_at, u.id as user_id, u.email, u.role
+        FROM api_keys ak
+        JOIN users u ON ak.created_by = u.id
+        WHERE ak.key_hash = $1 AND ak.is_active = true
+        "#,
+        key_hash,
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| GuardRailError::Unauthorized("Invalid API key".to_string()))?;
+
+    // Check expiry
+    if let Some(expires_at) = key_record.expires_at {
+        if expires_at < chrono::Utc::now() {
+            return Err(GuardRailError::Unauthorized("API key expired".to_string()));
+        }
+    }
+
+    // Update last used
+    sqlx::query!(
+        "UPDATE api_keys SET last_used_at = NOW() WHERE id = $1",
+        key_record.id,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(AuthenticatedUser {
+        user_id: key_record.user_id,
+        email: key_record.email,
+        role: key_record.role,
+        org_id: key_record.organization_id,
+        auth_method: AuthMethod::ApiKey,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    fn token(secret: &str, sub: &str) -> String {
+        let now = chrono::Utc::now().timestamp() as usize;
+        let claims = Claims {
+            sub: sub.to_string(),
+            email: "ops@example.com".to_string(),
+            role: "admin".to_string(),
+            org_id: None,
+            exp: now + 3600,
+            iat: now,
+        };
+        encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn test_authenticate_jwt() {
+        let user_id = Uuid::new_v4();
+        let user = authenticate_jwt("secret", &token("secret", &user_id.to_string())).unwrap();
+        assert_eq!(user.user_id, user_id);
+        assert!(matches!(user.auth_method, AuthMethod::Jwt));
+
+        assert!(matches!(
+            authenticate_jwt("other", &token("secret", &user_id.to_string())),
+            Err(GuardRailError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            authenticate_jwt("secret", &token("secret", "not-a-uuid")),
+            Err(GuardRailError::Unauthorized(_))
+        ));
+    }
+}
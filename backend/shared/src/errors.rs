@@ -46,6 +46,12 @@ pub enum GuardRailError {
     #[error("Invalid Rego syntax: {0}")]
     InvalidRego(String),
 
+    #[error("Policy tests failed: {0}")]
+    PolicyTestFailed(String),
+
+    #[error("Policy evaluation exceeded its {0}ms deadline")]
+    EvaluationDeadlineExceeded(u64),
+
     // Event errors
     #[error("Event not found: {0}")]
     EventNotFound(String),
@@ -131,7 +137,7 @@ impl GuardRailError {
             | Self::ApprovalNotFound(_)
             | Self::AnchorNotFound(_)
             | Self::NotFound(_) => 404,
-            Self::Validation(_) | Self::InvalidField { .. } | Self::InvalidInput(_) | Self::InvalidRego(_) | Self::CryptoError(_) => 400,
+            Self::Validation(_) | Self::InvalidField { .. } | Self::InvalidInput(_) | Self::InvalidRego(_) | Self::PolicyTestFailed(_) | Self::CryptoError(_) => 400,
             Self::IdentityAlreadyExists(_)
             | Self::KeyAlreadyBound(_)
             | Self::ApprovalAlreadyProcessed
@@ -140,6 +146,7 @@ impl GuardRailError {
             Self::RateLimitExceeded => 429,
             Self::ExternalService(_) | Self::KycProvider(_) | Self::ServiceUnavailable(_) => 502,
             Self::NotImplemented(_) => 501,
+            Self::EvaluationDeadlineExceeded(_) => 504,
             _ => 500,
         }
     }
@@ -159,6 +166,8 @@ impl GuardRailError {
             Self::PolicyNotFound(_) => "POLICY_NOT_FOUND",
             Self::PolicyEvaluation(_) => "POLICY_EVALUATION_FAILED",
             Self::InvalidRego(_) => "INVALID_REGO",
+            Self::PolicyTestFailed(_) => "POLICY_TESTS_FAILED",
+            Self::EvaluationDeadlineExceeded(_) => "EVALUATION_DEADLINE_EXCEEDED",
             Self::EventNotFound(_) => "EVENT_NOT_FOUND",
             Self::HashChainViolation(_) => "HASH_CHAIN_VIOLATION",
             Self::ApprovalNotFound(_) => "APPROVAL_NOT_FOUND",
@@ -3,11 +3,13 @@
 //! This crate contains common types, errors, and utilities used across all GuardRail services.
 
 pub mod types;
+pub mod auth;
 pub mod errors;
 pub mod crypto;
 pub mod zk_credential;
 
 pub use types::*;
+pub use auth::*;
 pub use errors::*;
 pub use crypto::*;
 pub use zk_credential::*;
\ No newline at end of file
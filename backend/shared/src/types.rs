@@ -20,6 +20,13 @@ pub enum IdentityType {
     Organization,
 }
 
+// Controls store the identity types they sweep as an identity_type[] column
+impl sqlx::postgres::PgHasArrayType for IdentityType {
+    fn array_type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("_identity_type")
+    }
+}
+
 /// An identity represents a user, agent, or organization in the system
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Identity {
@@ -105,19 +112,296 @@ pub struct Policy {
     pub name: String,
     pub description: Option<String>,
     pub version: String,
+    /// Decision policies decide checks; libraries only provide packages for them to import
+    #[schema(value_type = String)]
+    pub kind: PolicyKind,
     pub rego_source: String,
+    /// Attached Rego test module
+    pub test_source: Option<String>,
     pub is_active: bool,
-    pub created_by: Uuid,
+    /// Evaluated on every check without affecting the decision
+    pub is_shadow: bool,
+    /// Scheduled activation; the policy is inactive until then
+    pub effective_from: Option<DateTime<Utc>>,
+    /// Scheduled deactivation
+    pub effective_until: Option<DateTime<Utc>>,
+    /// Actions the policy is evaluated for
+    #[schema(value_type = Object)]
+    pub targets: PolicyTargets,
+    pub created_by: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
-/// Request to create or update a policy
+/// What a policy contributes to evaluation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "policy_kind", rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PolicyKind {
+    /// Rules in `package guardrail` that decide checks
+    #[default]
+    Decision,
+    /// A `package guardrail.lib.<name>` of helpers for decision policies to import
+    Library,
+}
+
+/// Actions a policy applies to. Each non-empty list must match the action; a
+/// policy with no targets is evaluated for every action.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyTargets {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub action_types: Vec<ActionType>,
+    /// Asset symbols, matched case-insensitively
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assets: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub identity_types: Vec<IdentityType>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub organization_ids: Vec<Uuid>,
+}
+
+impl PolicyTargets {
+    pub fn is_empty(&self) -> bool {
+        self.action_types.is_empty()
+            && self.assets.is_empty()
+            && self.identity_types.is_empty()
+            && self.organization_ids.is_empty()
+    }
+}
+
+// Stored as a JSONB column
+impl sqlx::Type<sqlx::Postgres> for PolicyTargets {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <sqlx::types::Json<Self> as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+        <sqlx::types::Json<Self> as sqlx::Type<sqlx::Postgres>>::compatible(ty)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, sqlx::Postgres> for PolicyTargets {
+    fn decode(value: sqlx::postgres::PgValueRef<'r>) -> std::result::Result<Self, sqlx::error::BoxDynError> {
+        Ok(<sqlx::types::Json<Self> as sqlx::Decode<sqlx::Postgres>>::decode(value)?.0)
+    }
+}
+
+/// Request to create a policy
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreatePolicyRequest {
     pub name: String,
     pub description: Option<String>,
+    /// Fixed for the life of the policy
+    #[serde(default)]
+    pub kind: PolicyKind,
+    pub rego_source: String,
+    /// Rego module holding `test_*` rules for this policy
+    #[serde(default)]
+    pub test_source: Option<String>,
+    /// Create in SHADOW state instead of enforcing immediately
+    #[serde(default)]
+    pub shadow: bool,
+    /// Stay inactive until this time, then activate
+    #[serde(default)]
+    pub effective_from: Option<DateTime<Utc>>,
+    /// Deactivate at this time
+    #[serde(default)]
+    pub effective_until: Option<DateTime<Utc>>,
+    /// Evaluate only for matching actions
+    #[serde(default)]
+    pub targets: PolicyTargets,
+    #[serde(default)]
+    pub created_by: Option<Uuid>,
+}
+
+/// Request to update a policy, creating a new version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdatePolicyRequest {
+    pub description: Option<String>,
+    pub rego_source: String,
+    /// Replacement test module; the current one is kept when omitted
+    #[serde(default)]
+    pub test_source: Option<String>,
+    /// Replacement targets; the current ones are kept when omitted
+    #[serde(default)]
+    pub targets: Option<PolicyTargets>,
+    pub change_summary: Option<String>,
+    #[serde(default)]
+    pub bump: VersionBump,
+    #[serde(default)]
+    pub created_by: Option<Uuid>,
+}
+
+/// Request to roll a policy back to an earlier version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollbackPolicyRequest {
+    pub version: String,
+    pub change_summary: Option<String>,
+    #[serde(default)]
+    pub created_by: Option<Uuid>,
+}
+
+/// Request to set or clear a policy's effectiveness window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedulePolicyRequest {
+    /// Activate at this time; a past time activates the policy now
+    pub effective_from: Option<DateTime<Utc>>,
+    /// Deactivate at this time
+    pub effective_until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub created_by: Option<Uuid>,
+}
+
+/// Which semver component to increment when a policy changes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionBump {
+    Major,
+    Minor,
+    #[default]
+    Patch,
+}
+
+/// A stored revision of a policy's Rego source
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PolicyVersion {
+    pub id: Uuid,
+    pub policy_id: Uuid,
+    pub version: String,
     pub rego_source: String,
+    pub test_source: Option<String>,
+    pub targets: PolicyTargets,
+    pub change_summary: Option<String>,
+    /// Policy template the source was rendered from, if any
+    pub template_id: Option<String>,
+    pub template_version: Option<i32>,
+    pub template_params: Option<serde_json::Value>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A versioned JSON document exposed to policies under `data.<path>`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DataDocument {
+    pub id: Uuid,
+    /// Dotted path, e.g. `sanctions.addresses`
+    pub path: String,
+    pub version: i32,
+    pub description: Option<String>,
+    pub sha256: String,
+    pub size_bytes: i32,
+    pub is_active: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A data document together with its content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataDocumentWithContent {
+    #[serde(flatten)]
+    pub document: DataDocument,
+    pub content: serde_json::Value,
+}
+
+/// Request to upload a new version of a data document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadDataDocumentRequest {
+    pub path: String,
+    pub content: serde_json::Value,
+    pub description: Option<String>,
+    /// Activate the new version immediately
+    #[serde(default)]
+    pub activate: bool,
+    #[serde(default)]
+    pub created_by: Option<Uuid>,
+}
+
+/// Outcome of a single Rego `test_*` rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyTestResult {
+    pub name: String,
+    pub package: String,
+    pub passed: bool,
+    pub error: Option<String>,
+}
+
+/// Outcome of running a policy's tests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyTestReport {
+    pub passed: bool,
+    pub total: usize,
+    pub failed: usize,
+    pub results: Vec<PolicyTestResult>,
+}
+
+impl PolicyTestReport {
+    pub fn new(results: Vec<PolicyTestResult>) -> Self {
+        let failed = results.iter().filter(|r| !r.passed).count();
+        Self {
+            passed: failed == 0,
+            total: results.len(),
+            failed,
+            results,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum LintSeverity {
+    /// The policy is rejected on create and update
+    Error,
+    Warning,
+}
+
+/// A finding from static analysis of a policy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LintDiagnostic {
+    /// Stable identifier, e.g. `UNKNOWN_INPUT`
+    pub code: String,
+    pub severity: LintSeverity,
+    pub message: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// Outcome of linting a policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintReport {
+    /// No errors; warnings don't fail a policy
+    pub passed: bool,
+    pub errors: usize,
+    pub warnings: usize,
+    /// Ordered by position in the source
+    pub diagnostics: Vec<LintDiagnostic>,
+}
+
+impl LintReport {
+    pub fn new(mut diagnostics: Vec<LintDiagnostic>) -> Self {
+        diagnostics.sort_by_key(|d| (d.line, d.column));
+        let errors = diagnostics.iter().filter(|d| d.severity == LintSeverity::Error).count();
+        Self {
+            passed: errors == 0,
+            errors,
+            warnings: diagnostics.len() - errors,
+            diagnostics,
+        }
+    }
+}
+
+/// A stored test run against a policy version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyTestRun {
+    pub id: Uuid,
+    pub policy_id: Uuid,
+    pub policy_version: String,
+    /// CREATE, UPDATE or ACTIVATE
+    pub trigger: String,
+    pub passed: bool,
+    pub total: i32,
+    pub failed: i32,
+    pub results: Vec<PolicyTestResult>,
+    pub created_at: DateTime<Utc>,
 }
 
 /// An action to be checked against policies
@@ -131,7 +415,7 @@ pub struct Action {
     pub metadata: serde_json::Value,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ActionType {
     Withdrawal,
@@ -162,6 +446,9 @@ pub struct CheckActionRequest {
     pub identity_id: Uuid,
     pub action: Action,
     pub context: ActionContext,
+    /// Capture an evaluation trace and store it with the decision
+    #[serde(default)]
+    pub explain: bool,
 }
 
 /// Result of a policy check
@@ -169,14 +456,203 @@ pub struct CheckActionRequest {
 pub struct PolicyDecision {
     pub decision_id: Uuid,
     pub decision: Decision,
+    /// Deny reason messages
     pub reasons: Vec<String>,
+    /// Deny reasons with codes, severities and parameters, in the same order as `reasons`
+    #[serde(default)]
+    pub reason_details: Vec<DenyReason>,
     pub required_approvers: Vec<String>,
+    /// First contributing policy, if any policy contributed to the decision
+    pub policy_id: Option<Uuid>,
+    pub policy_version: Option<String>,
+    /// Every policy that produced a reason, approver or obligation, with what it produced
+    pub contributing_policies: Vec<PolicyAttribution>,
+    /// Obligations the caller must fulfil when acting on the decision
+    #[serde(default)]
+    pub obligations: Vec<Obligation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<DecisionExplanation>,
+    /// Revision of the data documents the policies saw
+    pub data_revision: Option<String>,
+    /// Revision of the enforced policy versions the action was evaluated against
+    #[serde(default)]
+    pub policy_set_revision: Option<String>,
+    /// The decision is the configured fallback because policies could not be evaluated
+    #[serde(default)]
+    pub degraded: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub degraded_cause: Option<DegradedCause>,
+    pub evaluated_at: DateTime<Utc>,
+}
+
+/// A stored decision with the action and context it was made for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionRecord {
+    pub decision_id: Uuid,
+    pub identity_id: Uuid,
+    pub decision: Decision,
+    pub action: Action,
+    pub context: ActionContext,
+    pub reasons: Vec<String>,
+    pub reason_details: Vec<DenyReason>,
+    pub required_approvers: Vec<String>,
+    pub policy_id: Option<Uuid>,
+    pub policy_version: Option<String>,
+    /// Every policy that produced a reason, approver or obligation
+    pub contributing_policy_ids: Vec<Uuid>,
+    pub data_revision: Option<String>,
+    pub policy_set_revision: Option<String>,
+    pub degraded_cause: Option<DegradedCause>,
+    pub evaluated_at: DateTime<Utc>,
+}
+
+/// Why a check fell back to its configured failure decision
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DegradedCause {
+    /// Policy evaluation returned an error
+    EvaluationError,
+    /// Policy evaluation ran past the deadline
+    DeadlineExceeded,
+    /// Identity, history or decision storage could not be reached
+    DatabaseUnavailable,
+}
+
+impl DegradedCause {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::EvaluationError => "EVALUATION_ERROR",
+            Self::DeadlineExceeded => "DEADLINE_EXCEEDED",
+            Self::DatabaseUnavailable => "DATABASE_UNAVAILABLE",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "EVALUATION_ERROR" => Some(Self::EvaluationError),
+            "DEADLINE_EXCEEDED" => Some(Self::DeadlineExceeded),
+            "DATABASE_UNAVAILABLE" => Some(Self::DatabaseUnavailable),
+            _ => None,
+        }
+    }
+}
+
+/// Request to check several actions at once
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchCheckRequest {
+    pub checks: Vec<CheckActionRequest>,
+}
+
+/// Outcome of one check in a batch; exactly one of `decision` and `error` is set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchCheckResult {
+    /// Position of the check in the request
+    pub index: usize,
+    pub decision: Option<PolicyDecision>,
+    pub error: Option<ApiError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchCheckResponse {
+    /// One entry per check, in request order
+    pub results: Vec<BatchCheckResult>,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+/// Trace of how a decision was reached, captured in `explain` mode
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DecisionExplanation {
+    /// Rules in `data.guardrail` that produced a value
+    pub rules: Vec<RuleEvaluation>,
+    /// Per-policy evaluated lines and the input they read
+    pub policies: Vec<PolicyTrace>,
+    /// Output of `print` calls made during evaluation
+    pub prints: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleEvaluation {
+    pub rule: String,
+    pub value: serde_json::Value,
+    /// Evaluated lines defining the rule
+    pub locations: Vec<RuleLocation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleLocation {
+    pub policy_id: Uuid,
+    pub line: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyTrace {
+    pub policy_id: Uuid,
+    pub policy_name: String,
+    pub evaluated_lines: Vec<TracedLine>,
+    pub input_reads: Vec<InputRead>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TracedLine {
+    pub line: u32,
+    pub text: String,
+}
+
+/// An `input` path referenced on an evaluated line, with the value it resolved to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputRead {
+    pub path: String,
+    pub value: Option<serde_json::Value>,
+}
+
+/// Reasons, approvers and obligations produced by a single policy during a check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyAttribution {
     pub policy_id: Uuid,
+    pub policy_name: String,
     pub policy_version: String,
-    pub evaluated_at: DateTime<Utc>,
+    pub reasons: Vec<String>,
+    pub required_approvers: Vec<String>,
+    #[serde(default)]
+    pub obligations: Vec<Obligation>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+/// How serious a deny reason is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReasonSeverity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+/// A deny reason with a stable code, for clients that localise or aggregate reasons
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DenyReason {
+    /// Stable machine-readable code, e.g. `KYC_REQUIRED`; absent for bare-string reasons
+    pub code: Option<String>,
+    /// Human-readable message, also listed in `reasons`
+    pub message: String,
+    pub severity: Option<ReasonSeverity>,
+    /// Values for message templates, e.g. `{"limit": "10000"}`
+    #[serde(default)]
+    pub params: serde_json::Value,
+}
+
+/// Something the caller must do alongside the decision, e.g. delay settlement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Obligation {
+    /// Obligation kind, e.g. `DELAY_SETTLEMENT`, `NOTIFY`, `STEP_UP_MFA`
+    #[serde(rename = "type")]
+    pub obligation_type: String,
+    /// Type-specific parameters, e.g. `{"hours": 24}`
+    #[serde(default)]
+    pub params: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "decision", rename_all = "SCREAMING_SNAKE_CASE")]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Decision {
@@ -218,6 +694,7 @@ pub enum EventType {
     ApprovalRequested,
     ApprovalGranted,
     ApprovalRejected,
+    ApprovalExpired,
     PolicyCreated,
     PolicyUpdated,
     AnchorBatchCreated,
@@ -236,6 +713,8 @@ pub struct Approval {
     pub identity_id: Uuid,
     pub action: serde_json::Value,
     pub required_role: String,
+    pub required_count: i32,
+    pub approval_count: i32,
     pub status: ApprovalStatus,
     pub approved_by: Option<Uuid>,
     pub approved_at: Option<DateTime<Utc>>,
@@ -254,6 +733,123 @@ pub enum ApprovalStatus {
     Expired,
 }
 
+/// A single approver's vote on an approval request
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ApprovalVote {
+    pub id: Uuid,
+    pub approval_id: Uuid,
+    pub approver_id: Uuid,
+    pub approved: bool,
+    pub comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Request to approve or reject a pending approval; the approver is the
+/// authenticated caller
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApprovalVoteRequest {
+    #[serde(alias = "reason")]
+    pub comment: Option<String>,
+}
+
+// ============================================================================
+// Control Types
+// ============================================================================
+
+/// A control: a Rego module swept periodically over identities, rather than
+/// evaluated on checks, recording what it finds as findings
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Control {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// Module in `package guardrail.control` producing a `violation` set
+    pub rego_source: String,
+    /// Identity types swept; empty sweeps every type
+    pub identity_types: Vec<IdentityType>,
+    /// Seconds between sweeps
+    pub interval_secs: i32,
+    /// Days of decisions summarised into `input.activity`
+    pub activity_days: i32,
+    pub is_active: bool,
+    pub next_run_at: DateTime<Utc>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Request to create a control
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateControlRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub rego_source: String,
+    #[serde(default)]
+    pub identity_types: Vec<IdentityType>,
+    /// Defaults to hourly
+    #[serde(default)]
+    pub interval_secs: Option<i32>,
+    /// Defaults to 30 days
+    #[serde(default)]
+    pub activity_days: Option<i32>,
+    #[serde(default)]
+    pub created_by: Option<Uuid>,
+}
+
+/// Request to change a control; omitted fields are kept
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateControlRequest {
+    pub description: Option<String>,
+    pub rego_source: Option<String>,
+    pub identity_types: Option<Vec<IdentityType>>,
+    pub interval_secs: Option<i32>,
+    pub activity_days: Option<i32>,
+    pub is_active: Option<bool>,
+}
+
+/// One sweep of a control over the identities in its scope
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ControlRun {
+    pub id: Uuid,
+    pub control_id: Uuid,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub identities_checked: i32,
+    pub violations: i32,
+    /// Findings opened by this sweep
+    pub opened: i32,
+    /// Findings this sweep no longer found
+    pub resolved: i32,
+    /// Set when the sweep failed, leaving findings as they were
+    pub error: Option<String>,
+}
+
+/// An identity found violating a control. It stays open, and is updated by each
+/// sweep that finds it again, until a sweep no longer does.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ControlFinding {
+    pub id: Uuid,
+    pub control_id: Uuid,
+    pub identity_id: Uuid,
+    pub code: Option<String>,
+    pub message: String,
+    pub severity: Option<String>,
+    pub params: serde_json::Value,
+    pub status: FindingStatus,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "finding_status", rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FindingStatus {
+    Open,
+    Resolved,
+}
+
 // ============================================================================
 // Anchor Types
 // ============================================================================
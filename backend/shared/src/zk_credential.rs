@@ -54,6 +54,15 @@ pub fn prove_age(
     age: u64,
     threshold: u64,
 ) -> Result<Proof<Bls12_381>, crate::errors::GuardRailError> {
+    // The circuit below does not constrain age >= threshold yet, so never
+    // produce a proof for an age under the threshold
+    if age < threshold {
+        return Err(crate::errors::GuardRailError::Validation(format!(
+            "Age {} is below the threshold {}",
+            age, threshold
+        )));
+    }
+
     let rng = &mut OsRng;
 
     // For POC: prove that we know 'age' such that age^2 = age^2
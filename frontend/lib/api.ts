@@ -3,10 +3,23 @@ import type {
   PaginatedResponse,
   Identity,
   Policy,
+  PolicyDependencies,
   MovementEvent,
   Approval,
+  Control,
+  ControlRun,
+  ControlFinding,
+  CreateControlRequest,
+  FindingStatus,
   AnchorBatch,
   PolicyDecision,
+  DecisionRecord,
+  DecisionFilter,
+  DecisionTimeSeries,
+  RankedStats,
+  PolicyDenyStats,
+  IdentityDenyStats,
+  DenyReasonStats,
   EventWithProof,
   LoginResponse,
   CreateIdentityRequest,
@@ -178,6 +191,10 @@ class ApiClient {
     });
   }
 
+  async getPolicyDependencies(id: string): Promise<ApiResponse<PolicyDependencies>> {
+    return this.request(`/api/v1/policies/${id}/dependencies`);
+  }
+
   async simulatePolicy(
     id: string,
     data: { identity: object; action: object; context: object }
@@ -196,6 +213,59 @@ class ApiClient {
     });
   }
 
+  // Decisions
+  private decisionParams(filters?: DecisionFilter, extra?: Record<string, string | number>) {
+    const params = new URLSearchParams();
+    Object.entries({ ...filters, ...extra }).forEach(([key, value]) => {
+      if (value !== undefined) {
+        params.set(key, value.toString());
+      }
+    });
+    return params;
+  }
+
+  async listDecisions(
+    page = 1,
+    perPage = 20,
+    filters?: DecisionFilter
+  ): Promise<ApiResponse<PaginatedResponse<DecisionRecord>>> {
+    const params = this.decisionParams(filters, { page, per_page: perPage });
+    return this.request(`/api/v1/decisions?${params}`);
+  }
+
+  async getDecisionTimeSeries(
+    bucket: 'hour' | 'day' = 'hour',
+    filters?: DecisionFilter
+  ): Promise<ApiResponse<DecisionTimeSeries>> {
+    const params = this.decisionParams(filters, { bucket });
+    return this.request(`/api/v1/decisions/stats/timeseries?${params}`);
+  }
+
+  async getPolicyDenyStats(
+    filters?: DecisionFilter,
+    limit = 20
+  ): Promise<ApiResponse<RankedStats<PolicyDenyStats>>> {
+    const params = this.decisionParams(filters, { limit });
+    return this.request(`/api/v1/decisions/stats/policies?${params}`);
+  }
+
+  async getIdentityDenyStats(
+    filters?: DecisionFilter,
+    limit = 20,
+    minDecisions = 1
+  ): Promise<ApiResponse<RankedStats<IdentityDenyStats>>> {
+    const params = this.decisionParams(filters, { limit, min_decisions: minDecisions });
+    return this.request(`/api/v1/decisions/stats/identities?${params}`);
+  }
+
+  async getDenyReasonStats(
+    filters?: DecisionFilter,
+    limit = 20
+  ): Promise<ApiResponse<RankedStats<DenyReasonStats>>> {
+    const params = this.decisionParams(filters, { limit });
+    return this.request(`/api/v1/decisions/stats/reasons?${params}`);
+  }
+
   // Events
   async listEvents(
     page = 1,
@@ -203,6 +273,7 @@ class ApiClient {
     filters?: {
       event_type?: string;
       actor_id?: string;
+      policy_decision_id?: string;
       from_date?: string;
       to_date?: string;
       anchored_only?: boolean;
@@ -265,6 +336,49 @@ class ApiClient {
     });
   }
 
+  // Controls
+  async listControls(page = 1, perPage = 20): Promise<ApiResponse<PaginatedResponse<Control>>> {
+    const params = new URLSearchParams({
+      page: page.toString(),
+      per_page: perPage.toString(),
+    });
+
+    return this.request(`/api/v1/controls?${params}`);
+  }
+
+  async createControl(data: CreateControlRequest): Promise<ApiResponse<Control>> {
+    return this.request('/api/v1/controls', {
+      method: 'POST',
+      body: JSON.stringify(data),
+    });
+  }
+
+  async runControl(id: string): Promise<ApiResponse<ControlRun>> {
+    return this.request(`/api/v1/controls/${id}/run`, {
+      method: 'POST',
+    });
+  }
+
+  async listControlRuns(id: string): Promise<ApiResponse<ControlRun[]>> {
+    return this.request(`/api/v1/controls/${id}/runs`);
+  }
+
+  async listControlFindings(
+    page = 1,
+    perPage = 20,
+    filter: { status?: FindingStatus; control_id?: string; identity_id?: string } = {}
+  ): Promise<ApiResponse<PaginatedResponse<ControlFinding>>> {
+    const params = new URLSearchParams({
+      page: page.toString(),
+      per_page: perPage.toString(),
+    });
+    if (filter.status) params.set('status', filter.status);
+    if (filter.control_id) params.set('control_id', filter.control_id);
+    if (filter.identity_id) params.set('identity_id', filter.identity_id);
+
+    return this.request(`/api/v1/controls/findings?${params}`);
+  }
+
   // Anchors
   async listAnchors(
     page = 1,
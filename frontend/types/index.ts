@@ -15,11 +15,13 @@ export type EventType =
   | 'APPROVAL_REQUESTED'
   | 'APPROVAL_GRANTED'
   | 'APPROVAL_REJECTED'
+  | 'APPROVAL_EXPIRED'
   | 'POLICY_CREATED'
   | 'POLICY_UPDATED'
   | 'ANCHOR_BATCH_CREATED'
   | 'SYSTEM_EVENT';
 export type ApprovalStatus = 'PENDING' | 'APPROVED' | 'REJECTED' | 'EXPIRED';
+export type FindingStatus = 'OPEN' | 'RESOLVED';
 export type AnchorStatus = 'PENDING' | 'ANCHORING' | 'CONFIRMED' | 'FAILED';
 
 // Identity Types
@@ -65,21 +67,196 @@ export interface Policy {
   name: string;
   description?: string;
   version: string;
+  kind: PolicyKind;
   rego_source: string;
+  test_source?: string;
   is_active: boolean;
+  is_shadow: boolean;
+  effective_from?: string;
+  effective_until?: string;
+  targets: PolicyTargets;
   created_by?: string;
   created_at: string;
   updated_at: string;
 }
 
+export type PolicyKind = 'DECISION' | 'LIBRARY';
+
+export interface DependentPolicy {
+  policy_id: string;
+  name: string;
+  version: string;
+  kind: PolicyKind;
+  is_active: boolean;
+  is_shadow: boolean;
+}
+
+export interface PolicyDependencies {
+  policy_id: string;
+  package?: string;
+  imports: { path: string; provided_by: DependentPolicy[] }[];
+  imported_by: DependentPolicy[];
+}
+
+export interface PolicyTargets {
+  action_types?: string[];
+  assets?: string[];
+  identity_types?: IdentityType[];
+  organization_ids?: string[];
+}
+
+export interface PolicyTestResult {
+  name: string;
+  package: string;
+  passed: boolean;
+  error?: string;
+}
+
+export interface PolicyTestRun {
+  id: string;
+  policy_id: string;
+  policy_version: string;
+  trigger: 'CREATE' | 'UPDATE' | 'ACTIVATE';
+  passed: boolean;
+  total: number;
+  failed: number;
+  results: PolicyTestResult[];
+  created_at: string;
+}
+
+export interface DataDocument {
+  id: string;
+  path: string;
+  version: number;
+  description?: string;
+  sha256: string;
+  size_bytes: number;
+  is_active: boolean;
+  created_by?: string;
+  created_at: string;
+  content?: unknown;
+}
+
 export interface PolicyDecision {
   decision_id: string;
   decision: Decision;
   reasons: string[];
   required_approvers: string[];
+  policy_id?: string;
+  policy_version?: string;
+  contributing_policies: PolicyAttribution[];
+  reason_details: DenyReason[];
+  obligations: Obligation[];
+  explanation?: DecisionExplanation;
+  data_revision?: string;
+  policy_set_revision?: string;
+  degraded: boolean;
+  degraded_cause?: 'EVALUATION_ERROR' | 'DEADLINE_EXCEEDED' | 'DATABASE_UNAVAILABLE';
+  evaluated_at: string;
+}
+
+export interface DecisionRecord {
+  decision_id: string;
+  identity_id: string;
+  decision: Decision;
+  action: CheckActionRequest['action'];
+  context: CheckActionRequest['context'];
+  reasons: string[];
+  reason_details: DenyReason[];
+  required_approvers: string[];
+  policy_id?: string;
+  policy_version?: string;
+  contributing_policy_ids: string[];
+  data_revision?: string;
+  policy_set_revision?: string;
+  degraded_cause?: PolicyDecision['degraded_cause'];
+  evaluated_at: string;
+}
+
+export interface DecisionFilter {
+  identity_id?: string;
+  policy_id?: string;
+  decision?: Decision;
+  action_type?: string;
+  reason_code?: string;
+  from?: string;
+  until?: string;
+}
+
+export interface DecisionTimeSeries {
+  from: string;
+  until: string;
+  buckets: {
+    bucket_start: string;
+    total: number;
+    allowed: number;
+    denied: number;
+    approval_required: number;
+  }[];
+}
+
+export interface RankedStats<T> {
+  from: string;
+  until: string;
+  items: T[];
+}
+
+export interface PolicyDenyStats {
   policy_id: string;
+  policy_name: string;
+  evaluated: number;
+  denied: number;
+  approval_required: number;
+  deny_rate?: number;
+}
+
+export interface IdentityDenyStats {
+  identity_id: string;
+  display_name: string;
+  total: number;
+  denied: number;
+  approval_required: number;
+  deny_rate?: number;
+}
+
+export interface DenyReasonStats {
+  code?: string;
+  message: string;
+  count: number;
+  identities: number;
+  last_seen: string;
+}
+
+export interface DecisionExplanation {
+  rules: { rule: string; value: unknown; locations: { policy_id: string; line: number }[] }[];
+  policies: {
+    policy_id: string;
+    policy_name: string;
+    evaluated_lines: { line: number; text: string }[];
+    input_reads: { path: string; value?: unknown }[];
+  }[];
+  prints: string[];
+}
+
+export interface PolicyAttribution {
+  policy_id: string;
+  policy_name: string;
   policy_version: string;
-  evaluated_at: string;
+  reasons: string[];
+  required_approvers: string[];
+  obligations: Obligation[];
+}
+
+export interface Obligation {
+  type: string;
+  params: Record<string, unknown>;
+}
+
+export interface DenyReason {
+  code?: string;
+  message: string;
+  severity?: 'LOW' | 'MEDIUM' | 'HIGH' | 'CRITICAL';
+  params: Record<string, unknown>;
 }
 
 // Event Types
@@ -122,6 +299,8 @@ export interface Approval {
   identity_id: string;
   action: Record<string, unknown>;
   required_role: string;
+  required_count: number;
+  approval_count: number;
   status: ApprovalStatus;
   approved_by?: string;
   approved_at?: string;
@@ -130,6 +309,58 @@ export interface Approval {
   created_at: string;
 }
 
+// Control Types
+export interface Control {
+  id: string;
+  name: string;
+  description?: string;
+  rego_source: string;
+  identity_types: IdentityType[];
+  interval_secs: number;
+  activity_days: number;
+  is_active: boolean;
+  next_run_at: string;
+  last_run_at?: string;
+  created_by?: string;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface CreateControlRequest {
+  name: string;
+  description?: string;
+  rego_source: string;
+  identity_types?: IdentityType[];
+  interval_secs?: number;
+  activity_days?: number;
+}
+
+export interface ControlRun {
+  id: string;
+  control_id: string;
+  started_at: string;
+  finished_at: string;
+  identities_checked: number;
+  violations: number;
+  opened: number;
+  resolved: number;
+  error?: string;
+}
+
+export interface ControlFinding {
+  id: string;
+  control_id: string;
+  identity_id: string;
+  code?: string;
+  message: string;
+  severity?: string;
+  params: Record<string, unknown>;
+  status: FindingStatus;
+  first_seen_at: string;
+  last_seen_at: string;
+  resolved_at?: string;
+}
+
 // Anchor Types
 export interface AnchorBatch {
   id: string;
@@ -215,7 +446,9 @@ export interface CreateIdentityRequest {
 export interface CreatePolicyRequest {
   name: string;
   description?: string;
+  kind?: PolicyKind;
   rego_source: string;
+  targets?: PolicyTargets;
 }
 
 export interface CheckActionRequest {
@@ -237,4 +470,5 @@ export interface CheckActionRequest {
     session_id?: string;
     metadata?: Record<string, unknown>;
   };
+  explain?: boolean;
 }
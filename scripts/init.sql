@@ -20,12 +20,15 @@ CREATE TYPE event_type AS ENUM (
     'APPROVAL_REQUESTED',
     'APPROVAL_GRANTED',
     'APPROVAL_REJECTED',
+    'APPROVAL_EXPIRED',
     'POLICY_CREATED',
     'POLICY_UPDATED',
     'ANCHOR_BATCH_CREATED',
     'SYSTEM_EVENT'
 );
 CREATE TYPE approval_status AS ENUM ('PENDING', 'APPROVED', 'REJECTED', 'EXPIRED');
+CREATE TYPE policy_kind AS ENUM ('DECISION', 'LIBRARY');
+CREATE TYPE finding_status AS ENUM ('OPEN', 'RESOLVED');
 CREATE TYPE anchor_status AS ENUM ('PENDING', 'ANCHORING', 'CONFIRMED', 'FAILED');
 
 -- ============================================================================
@@ -117,18 +120,33 @@ CREATE TABLE policies (
     name VARCHAR(255) NOT NULL,
     description TEXT,
     version VARCHAR(20) NOT NULL,
+    -- Libraries contribute guardrail.lib.* packages for decision policies to import
+    kind policy_kind NOT NULL DEFAULT 'DECISION',
     rego_source TEXT NOT NULL,
+    test_source TEXT,
     is_active BOOLEAN DEFAULT true,
+    is_shadow BOOLEAN NOT NULL DEFAULT false,
+    shadow_since TIMESTAMPTZ,
+    shadow_until TIMESTAMPTZ,
+    -- Scheduled window; the scheduler activates and deactivates the policy at these times
+    effective_from TIMESTAMPTZ,
+    effective_until TIMESTAMPTZ,
+    -- Action types, assets, identity types and organisations the policy is evaluated for
+    targets JSONB NOT NULL DEFAULT '{}',
     created_by UUID REFERENCES identities(id),
     organization_id UUID REFERENCES organizations(id),
     created_at TIMESTAMPTZ DEFAULT NOW(),
     updated_at TIMESTAMPTZ DEFAULT NOW(),
     
-    UNIQUE(name, version, organization_id)
+    UNIQUE(name, version, organization_id),
+    CHECK (effective_until > effective_from)
 );
 
 CREATE INDEX idx_policies_name ON policies(name);
 CREATE INDEX idx_policies_active ON policies(is_active) WHERE is_active = true;
+CREATE INDEX idx_policies_effective_from ON policies(effective_from) WHERE effective_from IS NOT NULL;
+CREATE INDEX idx_policies_effective_until ON policies(effective_until) WHERE effective_until IS NOT NULL;
+CREATE INDEX idx_policies_shadow ON policies(is_shadow) WHERE is_shadow = true;
 CREATE INDEX idx_policies_org ON policies(organization_id);
 
 -- Policy version history
@@ -137,13 +155,84 @@ CREATE TABLE policy_versions (
     policy_id UUID NOT NULL REFERENCES policies(id) ON DELETE CASCADE,
     version VARCHAR(20) NOT NULL,
     rego_source TEXT NOT NULL,
+    test_source TEXT,
+    targets JSONB NOT NULL DEFAULT '{}',
     change_summary TEXT,
+    -- Template and parameters the source was rendered from, if any
+    template_id VARCHAR(64),
+    template_version INTEGER,
+    template_params JSONB,
     created_by UUID REFERENCES identities(id),
-    created_at TIMESTAMPTZ DEFAULT NOW()
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+
+    UNIQUE(policy_id, version)
 );
 
 CREATE INDEX idx_policy_versions_policy ON policy_versions(policy_id);
 
+-- Enforced policy versions behind each policy set revision recorded on decisions
+CREATE TABLE policy_set_revisions (
+    revision VARCHAR(64) PRIMARY KEY,
+    policies JSONB NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+-- Rego unit test runs (CREATE, UPDATE, ACTIVATE)
+CREATE TABLE policy_test_runs (
+    id UUID PRIMARY KEY,
+    policy_id UUID NOT NULL REFERENCES policies(id) ON DELETE CASCADE,
+    policy_version VARCHAR(20) NOT NULL,
+    trigger VARCHAR(20) NOT NULL,
+    passed BOOLEAN NOT NULL,
+    total INTEGER NOT NULL,
+    failed INTEGER NOT NULL,
+    results JSONB NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX idx_policy_test_runs_policy ON policy_test_runs(policy_id, created_at DESC);
+
+-- Per-minute decision counters behind input.history velocity aggregates
+CREATE TABLE action_counters (
+    identity_id UUID NOT NULL REFERENCES identities(id),
+    action_type VARCHAR(50) NOT NULL,
+    asset TEXT NOT NULL DEFAULT '',
+    bucket_start TIMESTAMPTZ NOT NULL,
+    allowed_count BIGINT NOT NULL DEFAULT 0,
+    denied_count BIGINT NOT NULL DEFAULT 0,
+    amount_sum DOUBLE PRECISION NOT NULL DEFAULT 0,
+
+    PRIMARY KEY (identity_id, bucket_start, action_type, asset)
+);
+
+CREATE INDEX idx_action_counters_bucket ON action_counters(bucket_start);
+
+-- External data documents exposed to policies as data.<path>
+CREATE TABLE data_documents (
+    id UUID PRIMARY KEY,
+    path VARCHAR(255) NOT NULL,
+    version INTEGER NOT NULL,
+    description TEXT,
+    content JSONB NOT NULL,
+    sha256 VARCHAR(64) NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT false,
+    created_by UUID REFERENCES identities(id),
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+
+    UNIQUE(path, version)
+);
+
+-- At most one active version per path
+CREATE UNIQUE INDEX idx_data_documents_active_path ON data_documents(path) WHERE is_active = true;
+
+-- Set of active document versions behind each data revision recorded on decisions
+CREATE TABLE data_revisions (
+    revision VARCHAR(64) PRIMARY KEY,
+    documents JSONB NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
 -- ============================================================================
 -- Movement Events (append-only ledger)
 -- ============================================================================
@@ -170,6 +259,9 @@ CREATE INDEX idx_events_created ON movement_events(created_at);
 CREATE INDEX idx_events_anchor ON movement_events(anchor_batch_id) WHERE anchor_batch_id IS NOT NULL;
 CREATE INDEX idx_events_org ON movement_events(organization_id);
 CREATE INDEX idx_events_hash ON movement_events(event_hash);
+CREATE INDEX idx_events_decision ON movement_events(policy_decision_id) WHERE policy_decision_id IS NOT NULL;
+-- One POLICY_DECISION event per decision, however often it is delivered
+CREATE UNIQUE INDEX idx_events_decision_once ON movement_events(policy_decision_id) WHERE event_type = 'POLICY_DECISION';
 
 -- ============================================================================
 -- Policy Decisions
@@ -178,23 +270,87 @@ CREATE INDEX idx_events_hash ON movement_events(event_hash);
 CREATE TABLE policy_decisions (
     id UUID PRIMARY KEY,
     identity_id UUID NOT NULL REFERENCES identities(id),
-    policy_id UUID NOT NULL REFERENCES policies(id),
-    policy_version VARCHAR(20) NOT NULL,
+    policy_id UUID REFERENCES policies(id),
+    policy_version VARCHAR(20),
     action_type VARCHAR(50) NOT NULL,
     action_payload JSONB NOT NULL,
     context JSONB NOT NULL,
     decision decision NOT NULL,
     reasons TEXT[] DEFAULT '{}',
+    reason_details JSONB NOT NULL DEFAULT '[]',
     required_approvers TEXT[] DEFAULT '{}',
+    obligations JSONB NOT NULL DEFAULT '[]',
+    explanation JSONB,
+    data_revision VARCHAR(64),
+    policy_set_revision VARCHAR(64),
+    -- Policies whose targets matched the action, libraries excluded
+    evaluated_policy_ids UUID[] NOT NULL DEFAULT '{}',
+    -- Shadow policies whose targets matched, set once the shadow evaluation has run
+    shadow_policy_ids UUID[] NOT NULL DEFAULT '{}',
+    -- Set when the decision is the configured fallback, e.g. DEADLINE_EXCEEDED
+    degraded_cause VARCHAR(32),
     organization_id UUID REFERENCES organizations(id),
     created_at TIMESTAMPTZ DEFAULT NOW()
 );
 
-CREATE INDEX idx_decisions_identity ON policy_decisions(identity_id);
+CREATE INDEX idx_decisions_identity ON policy_decisions(identity_id, created_at);
 CREATE INDEX idx_decisions_policy ON policy_decisions(policy_id);
 CREATE INDEX idx_decisions_decision ON policy_decisions(decision);
 CREATE INDEX idx_decisions_created ON policy_decisions(created_at);
 CREATE INDEX idx_decisions_org ON policy_decisions(organization_id);
+-- Lookups by reason code, e.g. reason_details @> '[{"code": "KYC_REQUIRED"}]'
+CREATE INDEX idx_decisions_reason_details ON policy_decisions USING GIN (reason_details jsonb_path_ops);
+CREATE INDEX idx_decisions_degraded ON policy_decisions(created_at) WHERE degraded_cause IS NOT NULL;
+CREATE INDEX idx_decisions_shadow_policies ON policy_decisions USING GIN (shadow_policy_ids);
+
+-- Per-policy contributions to a decision
+CREATE TABLE policy_decision_attributions (
+    decision_id UUID NOT NULL REFERENCES policy_decisions(id) ON DELETE CASCADE,
+    policy_id UUID NOT NULL REFERENCES policies(id),
+    policy_version VARCHAR(20) NOT NULL,
+    reasons TEXT[] DEFAULT '{}',
+    required_approvers TEXT[] DEFAULT '{}',
+    obligations JSONB NOT NULL DEFAULT '[]',
+
+    PRIMARY KEY (decision_id, policy_id)
+);
+
+CREATE INDEX idx_decision_attributions_policy ON policy_decision_attributions(policy_id);
+
+-- Ledger events for decisions, their approvals and control findings not yet recorded
+-- in the movement ledger, written in the transaction that makes them and delivered
+-- in id order. Finding events aren't about a decision and have no decision_id.
+CREATE TABLE decision_outbox (
+    id BIGSERIAL PRIMARY KEY,
+    decision_id UUID REFERENCES policy_decisions(id) ON DELETE CASCADE,
+    event_type event_type NOT NULL,
+    actor_id UUID NOT NULL REFERENCES identities(id),
+    payload JSONB NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_decision_outbox_due ON decision_outbox(next_attempt_at, id);
+CREATE INDEX idx_decision_outbox_decision ON decision_outbox(decision_id);
+
+-- Checks where shadow policies would have changed the outcome
+CREATE TABLE shadow_differences (
+    id UUID PRIMARY KEY,
+    decision_id UUID NOT NULL REFERENCES policy_decisions(id) ON DELETE CASCADE,
+    enforced_decision decision NOT NULL,
+    shadow_decision decision NOT NULL,
+    enforced_reasons TEXT[] DEFAULT '{}',
+    shadow_reasons TEXT[] DEFAULT '{}',
+    shadow_required_approvers TEXT[] DEFAULT '{}',
+    shadow_policy_ids UUID[] NOT NULL DEFAULT '{}',
+    decision_changed BOOLEAN NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX idx_shadow_differences_policies ON shadow_differences USING GIN (shadow_policy_ids);
+CREATE INDEX idx_shadow_differences_created ON shadow_differences(created_at);
 
 -- ============================================================================
 -- Approvals
@@ -206,6 +362,8 @@ CREATE TABLE approvals (
     identity_id UUID NOT NULL REFERENCES identities(id),
     action JSONB NOT NULL,
     required_role VARCHAR(100) NOT NULL,
+    required_count INTEGER NOT NULL DEFAULT 1,
+    approval_count INTEGER NOT NULL DEFAULT 0,
     status approval_status DEFAULT 'PENDING',
     approved_by UUID REFERENCES identities(id),
     approved_at TIMESTAMPTZ,
@@ -219,6 +377,79 @@ CREATE INDEX idx_approvals_status ON approvals(status);
 CREATE INDEX idx_approvals_identity ON approvals(identity_id);
 CREATE INDEX idx_approvals_expires ON approvals(expires_at);
 CREATE INDEX idx_approvals_org ON approvals(organization_id);
+CREATE INDEX idx_approvals_decision ON approvals(decision_id);
+
+-- Individual approver votes (m-of-n quorum per role)
+CREATE TABLE approval_votes (
+    id UUID PRIMARY KEY,
+    approval_id UUID NOT NULL REFERENCES approvals(id) ON DELETE CASCADE,
+    approver_id UUID NOT NULL REFERENCES identities(id),
+    approved BOOLEAN NOT NULL,
+    comment TEXT,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+
+    UNIQUE(approval_id, approver_id)
+);
+
+CREATE INDEX idx_approval_votes_approval ON approval_votes(approval_id);
+
+-- ============================================================================
+-- Controls (policies swept periodically over identities)
+-- ============================================================================
+
+CREATE TABLE controls (
+    id UUID PRIMARY KEY,
+    name VARCHAR(255) NOT NULL UNIQUE,
+    description TEXT,
+    rego_source TEXT NOT NULL,
+    -- Identity types swept; empty sweeps every type
+    identity_types identity_type[] NOT NULL DEFAULT '{}',
+    interval_secs INTEGER NOT NULL CHECK (interval_secs > 0),
+    -- Days of decisions summarised into input.activity
+    activity_days INTEGER NOT NULL CHECK (activity_days > 0),
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    next_run_at TIMESTAMPTZ NOT NULL,
+    last_run_at TIMESTAMPTZ,
+    created_by UUID REFERENCES identities(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_controls_due ON controls(next_run_at) WHERE is_active = true;
+
+CREATE TABLE control_runs (
+    id UUID PRIMARY KEY,
+    control_id UUID NOT NULL REFERENCES controls(id) ON DELETE CASCADE,
+    started_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ NOT NULL,
+    identities_checked INTEGER NOT NULL,
+    violations INTEGER NOT NULL,
+    opened INTEGER NOT NULL,
+    resolved INTEGER NOT NULL,
+    -- Set when the sweep failed; findings are left as they were
+    error TEXT
+);
+
+CREATE INDEX idx_control_runs_control ON control_runs(control_id, started_at);
+
+-- An identity violating a control, open until a sweep no longer finds it
+CREATE TABLE control_findings (
+    id UUID PRIMARY KEY,
+    control_id UUID NOT NULL REFERENCES controls(id) ON DELETE CASCADE,
+    identity_id UUID NOT NULL REFERENCES identities(id) ON DELETE CASCADE,
+    code VARCHAR(100),
+    message TEXT NOT NULL,
+    severity VARCHAR(20),
+    params JSONB NOT NULL DEFAULT '{}',
+    status finding_status NOT NULL DEFAULT 'OPEN',
+    first_seen_at TIMESTAMPTZ NOT NULL,
+    last_seen_at TIMESTAMPTZ NOT NULL,
+    resolved_at TIMESTAMPTZ
+);
+
+-- One open finding per violation, identified by its code or else its message
+CREATE UNIQUE INDEX idx_control_findings_open ON control_findings(control_id, identity_id, COALESCE(code, message)) WHERE status = 'OPEN';
+CREATE INDEX idx_control_findings_identity ON control_findings(identity_id, status);
 
 -- ============================================================================
 -- Anchor Batches
@@ -324,6 +555,7 @@ CREATE TRIGGER update_identities_updated_at BEFORE UPDATE ON identities FOR EACH
 CREATE TRIGGER update_credentials_updated_at BEFORE UPDATE ON credentials FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
 CREATE TRIGGER update_policies_updated_at BEFORE UPDATE ON policies FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
 CREATE TRIGGER update_users_updated_at BEFORE UPDATE ON users FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+CREATE TRIGGER update_controls_updated_at BEFORE UPDATE ON controls FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
 CREATE TRIGGER update_webhooks_updated_at BEFORE UPDATE ON webhooks FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
 
 -- ============================================================================
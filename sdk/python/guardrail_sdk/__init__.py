@@ -4,7 +4,7 @@ GuardRail Python SDK
 Client library for integrating with the GuardRail compliance platform.
 """
 
-from dataclasses import dataclass
+from dataclasses import dataclass, field, fields
 from datetime import datetime
 from enum import Enum
 from typing import Any, Dict, List, Optional
@@ -51,15 +51,73 @@ class Identity:
     external_id: Optional[str] = None
 
 
+@dataclass
+class Obligation:
+    """An action the caller must take alongside a decision, e.g. DELAY_SETTLEMENT."""
+    type: str
+    params: Dict[str, Any] = field(default_factory=dict)
+
+
+@dataclass
+class DenyReason:
+    """A deny reason; match on `code`, which stays stable when messages are reworded."""
+    message: str
+    code: Optional[str] = None
+    severity: Optional[str] = None
+    params: Dict[str, Any] = field(default_factory=dict)
+
+
+@dataclass
+class PolicyAttribution:
+    """A policy that contributed reasons, approvers or obligations to a decision."""
+    policy_id: str
+    policy_name: str
+    policy_version: str
+    reasons: List[str] = field(default_factory=list)
+    required_approvers: List[str] = field(default_factory=list)
+    obligations: List[Obligation] = field(default_factory=list)
+
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> "PolicyAttribution":
+        known = {f.name for f in fields(cls)}
+        values = {k: v for k, v in data.items() if k in known}
+        values["obligations"] = [Obligation(**o) for o in data.get("obligations", [])]
+        return cls(**values)
+
+
 @dataclass
 class PolicyDecision:
     decision_id: str
     decision: Decision
     reasons: List[str]
     required_approvers: List[str]
-    policy_id: str
-    policy_version: str
     evaluated_at: str
+    policy_id: Optional[str] = None
+    policy_version: Optional[str] = None
+    reason_details: List[DenyReason] = field(default_factory=list)
+    obligations: List[Obligation] = field(default_factory=list)
+    # Policies the reasons and approvers came from; policy_id/policy_version name the
+    # first of them, and are None when no policy contributed
+    contributing_policies: List[PolicyAttribution] = field(default_factory=list)
+    explanation: Optional[Dict[str, Any]] = None
+    data_revision: Optional[str] = None
+    # Revision of the enforced policy versions the action was evaluated against
+    policy_set_revision: Optional[str] = None
+    # True when this is the configured fallback because policies could not be evaluated
+    degraded: bool = False
+    degraded_cause: Optional[str] = None
+
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> "PolicyDecision":
+        """Build from an API response, ignoring fields this SDK version doesn't know."""
+        known = {f.name for f in fields(cls)}
+        values = {k: v for k, v in data.items() if k in known}
+        values["reason_details"] = [DenyReason(**r) for r in data.get("reason_details", [])]
+        values["obligations"] = [Obligation(**o) for o in data.get("obligations", [])]
+        values["contributing_policies"] = [
+            PolicyAttribution.from_dict(p) for p in data.get("contributing_policies", [])
+        ]
+        return cls(**values)
 
 
 @dataclass
@@ -80,6 +138,38 @@ class GuardRailError(Exception):
         super().__init__(f"[{code}] {message}")
 
 
+@dataclass
+class BatchCheckResult:
+    """One check from `check_batch`; exactly one of `decision` and `error` is set."""
+    index: int
+    decision: Optional[PolicyDecision] = None
+    error: Optional[GuardRailError] = None
+
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> "BatchCheckResult":
+        error = data.get("error")
+        return cls(
+            index=data["index"],
+            decision=PolicyDecision.from_dict(data["decision"]) if data.get("decision") else None,
+            error=GuardRailError(error["code"], error["message"]) if error else None,
+        )
+
+
+def _batch_payload(checks: List[Dict[str, Any]]) -> Dict[str, Any]:
+    """Request body for `/api/v1/check/batch`, defaulting each check's timestamp."""
+    timestamp = datetime.utcnow().isoformat() + "Z"
+    return {
+        "checks": [
+            {
+                **check,
+                "action": {"metadata": {}, **check["action"]},
+                "context": {"timestamp": timestamp, "metadata": {}, **(check.get("context") or {})},
+            }
+            for check in checks
+        ],
+    }
+
+
 class GuardRailClient:
     """
     GuardRail API Client
@@ -236,7 +326,18 @@ class GuardRailClient:
                 **(context or {}),
             },
         })
-        return PolicyDecision(**data)
+        return PolicyDecision.from_dict(data)
+    
+    def check_batch(self, checks: List[Dict[str, Any]]) -> List[BatchCheckResult]:
+        """
+        Check up to 100 actions in one request.
+        
+        Each check is a dict with `identity_id`, `action` and optional `context`, as
+        for `/api/v1/check`. Decisions are recorded in order, so velocity rules see
+        earlier checks in the batch; a check that can't be decided gets an `error`.
+        """
+        data = self._request("POST", "/api/v1/check/batch", json=_batch_payload(checks))
+        return [BatchCheckResult.from_dict(r) for r in data["results"]]
     
     def check_withdrawal(
         self,
@@ -283,6 +384,7 @@ class GuardRailClient:
         per_page: int = 50,
         event_type: Optional[str] = None,
         actor_id: Optional[str] = None,
+        policy_decision_id: Optional[str] = None,
         from_date: Optional[str] = None,
         to_date: Optional[str] = None,
     ) -> Dict[str, Any]:
@@ -292,6 +394,8 @@ class GuardRailClient:
             params["event_type"] = event_type
         if actor_id:
             params["actor_id"] = actor_id
+        if policy_decision_id:
+            params["policy_decision_id"] = policy_decision_id
         if from_date:
             params["from_date"] = from_date
         if to_date:
@@ -393,7 +497,12 @@ class AsyncGuardRailClient:
                 **(context or {}),
             },
         })
-        return PolicyDecision(**data)
+        return PolicyDecision.from_dict(data)
+    
+    async def check_batch(self, checks: List[Dict[str, Any]]) -> List[BatchCheckResult]:
+        """Check up to 100 actions in one request."""
+        data = await self._request("POST", "/api/v1/check/batch", json=_batch_payload(checks))
+        return [BatchCheckResult.from_dict(r) for r in data["results"]]
     
     async def check_withdrawal(
         self,
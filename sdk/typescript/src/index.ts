@@ -21,13 +21,49 @@ export interface Identity {
   updated_at: string;
 }
 
+export interface Obligation {
+  /** e.g. DELAY_SETTLEMENT, NOTIFY, STEP_UP_MFA */
+  type: string;
+  params: Record<string, unknown>;
+}
+
+export interface DenyReason {
+  /** Stable code to match on; absent for reasons written as plain strings */
+  code?: string;
+  message: string;
+  severity?: 'LOW' | 'MEDIUM' | 'HIGH' | 'CRITICAL';
+  params: Record<string, unknown>;
+}
+
+export interface PolicyAttribution {
+  policy_id: string;
+  policy_name: string;
+  policy_version: string;
+  reasons: string[];
+  required_approvers: string[];
+  obligations: Obligation[];
+}
+
 export interface PolicyDecision {
   decision_id: string;
   decision: 'ALLOW' | 'DENY' | 'REQUIRE_APPROVAL';
   reasons: string[];
+  /** Structured form of `reasons`, in the same order */
+  reason_details: DenyReason[];
   required_approvers: string[];
-  policy_id: string;
-  policy_version: string;
+  /** Actions the caller must take alongside the decision */
+  obligations: Obligation[];
+  /** First of `contributing_policies`; null when no policy contributed */
+  policy_id?: string | null;
+  policy_version?: string | null;
+  /** Policies the reasons, approvers and obligations came from */
+  contributing_policies: PolicyAttribution[];
+  data_revision?: string;
+  /** Revision of the enforced policy versions the action was evaluated against */
+  policy_set_revision?: string;
+  /** The configured fallback decision, returned because policies could not be evaluated */
+  degraded: boolean;
+  degraded_cause?: 'EVALUATION_ERROR' | 'DEADLINE_EXCEEDED' | 'DATABASE_UNAVAILABLE';
   evaluated_at: string;
 }
 
@@ -50,6 +86,22 @@ export interface CheckActionRequest {
   };
 }
 
+/** Outcome of one check in a batch; exactly one of `decision` and `error` is set */
+export interface BatchCheckResult {
+  index: number;
+  decision?: PolicyDecision;
+  error?: {
+    code: string;
+    message: string;
+  };
+}
+
+export interface BatchCheckResponse {
+  results: BatchCheckResult[];
+  succeeded: number;
+  failed: number;
+}
+
 export interface ApiResponse<T> {
   success: boolean;
   data?: T;
@@ -204,6 +256,25 @@ export class GuardRailClient {
     });
   }
 
+  /**
+   * Check up to 100 actions in one request
+   *
+   * Decisions are recorded in order, so velocity rules see earlier checks in the
+   * batch. A check that can't be decided gets an `error` instead of failing the batch.
+   */
+  async checkBatch(requests: CheckActionRequest[]): Promise<BatchCheckResponse> {
+    const timestamp = new Date().toISOString();
+    return this.request('POST', '/api/v1/check/batch', {
+      checks: requests.map((request) => ({
+        ...request,
+        context: {
+          timestamp,
+          ...request.context,
+        },
+      })),
+    });
+  }
+
   /**
    * Convenience method for withdrawal checks
    */
@@ -262,6 +333,7 @@ export class GuardRailClient {
     perPage?: number;
     eventType?: string;
     actorId?: string;
+    policyDecisionId?: string;
     fromDate?: string;
     toDate?: string;
   }): Promise<{ items: any[]; total: number }> {
@@ -270,6 +342,7 @@ export class GuardRailClient {
     if (params?.perPage) query.set('per_page', params.perPage.toString());
     if (params?.eventType) query.set('event_type', params.eventType);
     if (params?.actorId) query.set('actor_id', params.actorId);
+    if (params?.policyDecisionId) query.set('policy_decision_id', params.policyDecisionId);
     if (params?.fromDate) query.set('from_date', params.fromDate);
     if (params?.toDate) query.set('to_date', params.toDate);
 
@@ -313,12 +386,15 @@ const decision = await client.checkWithdrawal({
 });
 
 if (decision.decision === 'ALLOW') {
-  // Proceed with withdrawal
+  // Proceed with withdrawal, honouring any obligations
+  for (const obligation of decision.obligations) {
+    console.log('Obligation:', obligation.type, obligation.params);
+  }
 } else if (decision.decision === 'REQUIRE_APPROVAL') {
   // Queue for approval
   console.log('Requires approval from:', decision.required_approvers);
 } else {
-  // Deny with reasons
-  console.log('Denied:', decision.reasons);
+  // Deny with reasons; match on codes rather than message text
+  console.log('Denied:', decision.reason_details.map((r) => r.code ?? r.message));
 }
 */